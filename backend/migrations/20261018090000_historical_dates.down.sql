@@ -0,0 +1,14 @@
+-- Add down migration script here
+
+DROP INDEX IF EXISTS documents_date_idx;
+
+ALTER TABLE documents
+    DROP CONSTRAINT IF EXISTS documents_date_bounds_check,
+    DROP COLUMN IF EXISTS date_uncertain,
+    DROP COLUMN IF EXISTS date_approximate,
+    DROP COLUMN IF EXISTS date_precision,
+    DROP COLUMN IF EXISTS date_latest;
+
+ALTER TABLE documents RENAME COLUMN date_earliest TO date;
+
+DROP TYPE IF EXISTS date_precision;
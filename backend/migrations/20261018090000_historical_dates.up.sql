@@ -0,0 +1,20 @@
+-- Add up migration script here
+-- Documents keep the bounds of a possibly partial or uncertain historical date
+CREATE TYPE date_precision AS ENUM ('year', 'month', 'day');
+
+ALTER TABLE documents RENAME COLUMN date TO date_earliest;
+
+ALTER TABLE documents
+    ADD COLUMN date_latest DATE,
+    ADD COLUMN date_precision date_precision NOT NULL DEFAULT 'day',
+    ADD COLUMN date_approximate BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN date_uncertain BOOLEAN NOT NULL DEFAULT FALSE;
+
+UPDATE documents SET date_latest = date_earliest;
+
+ALTER TABLE documents
+    ALTER COLUMN date_latest SET NOT NULL,
+    ALTER COLUMN date_precision DROP DEFAULT,
+    ADD CONSTRAINT documents_date_bounds_check CHECK (date_earliest <= date_latest);
+
+CREATE INDEX IF NOT EXISTS documents_date_idx ON documents (date_earliest, date_latest);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP INDEX IF EXISTS documents_place_id_idx;
+DROP INDEX IF EXISTS documents_institute_id_idx;
+DROP INDEX IF EXISTS documents_archive_id_idx;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- Indexes for filtering documents on their archive, institute and place
+CREATE INDEX IF NOT EXISTS documents_archive_id_idx ON documents (archive_id);
+CREATE INDEX IF NOT EXISTS documents_institute_id_idx ON documents (institute_id);
+CREATE INDEX IF NOT EXISTS documents_place_id_idx ON documents (place_id);
@@ -0,0 +1,14 @@
+-- Add down migration script here
+
+DROP TRIGGER IF EXISTS places_reindex_documents ON places;
+DROP TRIGGER IF EXISTS institutes_reindex_documents ON institutes;
+DROP TRIGGER IF EXISTS archives_reindex_documents ON archives;
+DROP FUNCTION IF EXISTS documents_reindex_names_trigger();
+
+DROP TRIGGER IF EXISTS documents_search_vector_update ON documents;
+DROP FUNCTION IF EXISTS documents_search_vector_trigger();
+
+DROP INDEX IF EXISTS documents_search_vector_idx;
+ALTER TABLE documents DROP COLUMN IF EXISTS search_vector;
+
+DROP FUNCTION IF EXISTS document_search_vector(TEXT, TEXT, TEXT, INT, INT, INT);
@@ -0,0 +1,77 @@
+-- Add up migration script here
+-- Full-text search over documents and the names of their archive, institute and place.
+-- Identifiers are indexed as-is, free text with both Dutch and English stemming.
+CREATE OR REPLACE FUNCTION document_search_vector(
+    inventory_number TEXT,
+    scan_number TEXT,
+    notes TEXT,
+    doc_archive_id INT,
+    doc_institute_id INT,
+    doc_place_id INT
+) RETURNS tsvector
+LANGUAGE sql STABLE AS $$
+    WITH names AS (
+        SELECT concat_ws(' ',
+            (SELECT name FROM archives WHERE id = doc_archive_id),
+            (SELECT name FROM institutes WHERE id = doc_institute_id),
+            (SELECT name FROM places WHERE id = doc_place_id)
+        ) AS text
+    )
+    SELECT
+        setweight(to_tsvector('simple', concat_ws(' ', inventory_number, scan_number)), 'A') ||
+        setweight(to_tsvector('simple', names.text), 'B') ||
+        setweight(to_tsvector('dutch', names.text), 'B') ||
+        setweight(to_tsvector('english', names.text), 'B') ||
+        setweight(to_tsvector('dutch', coalesce(notes, '')), 'C') ||
+        setweight(to_tsvector('english', coalesce(notes, '')), 'C')
+    FROM names
+$$;
+
+ALTER TABLE documents ADD COLUMN search_vector tsvector;
+
+UPDATE documents SET search_vector = document_search_vector(
+    inventory_number, scan_number, notes, archive_id, institute_id, place_id
+);
+
+ALTER TABLE documents ALTER COLUMN search_vector SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS documents_search_vector_idx ON documents USING GIN (search_vector);
+
+CREATE OR REPLACE FUNCTION documents_search_vector_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    NEW.search_vector := document_search_vector(
+        NEW.inventory_number, NEW.scan_number, NEW.notes,
+        NEW.archive_id, NEW.institute_id, NEW.place_id
+    );
+    RETURN NEW;
+END
+$$;
+
+CREATE TRIGGER documents_search_vector_update
+    BEFORE INSERT OR UPDATE ON documents
+    FOR EACH ROW EXECUTE FUNCTION documents_search_vector_trigger();
+
+-- Renaming an archive, institute or place re-indexes the documents that refer to it.
+-- The no-op update fires the trigger above for those documents.
+CREATE OR REPLACE FUNCTION documents_reindex_names_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF NEW.name IS DISTINCT FROM OLD.name THEN
+        EXECUTE format('UPDATE documents SET id = id WHERE %I = $1', TG_ARGV[0]) USING NEW.id;
+    END IF;
+    RETURN NEW;
+END
+$$;
+
+CREATE TRIGGER archives_reindex_documents
+    AFTER UPDATE OF name ON archives
+    FOR EACH ROW EXECUTE FUNCTION documents_reindex_names_trigger('archive_id');
+
+CREATE TRIGGER institutes_reindex_documents
+    AFTER UPDATE OF name ON institutes
+    FOR EACH ROW EXECUTE FUNCTION documents_reindex_names_trigger('institute_id');
+
+CREATE TRIGGER places_reindex_documents
+    AFTER UPDATE OF name ON places
+    FOR EACH ROW EXECUTE FUNCTION documents_reindex_names_trigger('place_id');
@@ -0,0 +1,5 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS document_persons;
+DROP TABLE IF EXISTS persons;
+DROP TYPE IF EXISTS person_role;
+DROP TYPE IF EXISTS sex;
@@ -0,0 +1,56 @@
+-- Add up migration script here
+-- People mentioned in documents, e.g. the baptized child, its parents and the witnesses
+CREATE TYPE sex AS ENUM ('male', 'female', 'unknown');
+
+CREATE TYPE person_role AS ENUM ('subject', 'father', 'mother', 'witness', 'official', 'spouse');
+
+-- Table: persons
+-- Birth and death dates are historical dates like `documents.date_*`, but optional
+CREATE TABLE IF NOT EXISTS persons (
+    id SERIAL PRIMARY KEY,
+    given_names TEXT,
+    patronymic TEXT,
+    surname_prefix TEXT,
+    surname TEXT,
+    sex sex NOT NULL DEFAULT 'unknown',
+    birth_date_earliest DATE,
+    birth_date_latest DATE,
+    birth_date_precision date_precision,
+    birth_date_approximate BOOLEAN NOT NULL DEFAULT FALSE,
+    birth_date_uncertain BOOLEAN NOT NULL DEFAULT FALSE,
+    birth_place_id INT REFERENCES places(id) ON DELETE RESTRICT,
+    death_date_earliest DATE,
+    death_date_latest DATE,
+    death_date_precision date_precision,
+    death_date_approximate BOOLEAN NOT NULL DEFAULT FALSE,
+    death_date_uncertain BOOLEAN NOT NULL DEFAULT FALSE,
+    death_place_id INT REFERENCES places(id) ON DELETE RESTRICT,
+    -- Surname prefixes such as "van der" are ignored when sorting, as in Dutch indexes
+    sort_name TEXT GENERATED ALWAYS AS (
+        lower(coalesce(surname, '') || ' ' || coalesce(given_names, '') || ' ' || coalesce(patronymic, ''))
+    ) STORED NOT NULL,
+    CONSTRAINT persons_birth_date_bounds_check CHECK (
+        (birth_date_earliest IS NULL AND birth_date_latest IS NULL AND birth_date_precision IS NULL)
+        OR birth_date_earliest <= birth_date_latest AND birth_date_precision IS NOT NULL
+    ),
+    CONSTRAINT persons_death_date_bounds_check CHECK (
+        (death_date_earliest IS NULL AND death_date_latest IS NULL AND death_date_precision IS NULL)
+        OR death_date_earliest <= death_date_latest AND death_date_precision IS NOT NULL
+    )
+);
+
+CREATE INDEX IF NOT EXISTS persons_sort_name_idx ON persons (sort_name, id);
+CREATE INDEX IF NOT EXISTS persons_birth_place_id_idx ON persons (birth_place_id);
+CREATE INDEX IF NOT EXISTS persons_death_place_id_idx ON persons (death_place_id);
+
+-- Table: document_persons
+-- A person is mentioned in a document in a role; the same person may have several roles
+CREATE TABLE IF NOT EXISTS document_persons (
+    id SERIAL PRIMARY KEY,
+    document_id INT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    person_id INT NOT NULL REFERENCES persons(id) ON DELETE RESTRICT,
+    role person_role NOT NULL,
+    CONSTRAINT document_persons_role_key UNIQUE (document_id, person_id, role)
+);
+
+CREATE INDEX IF NOT EXISTS document_persons_person_id_idx ON document_persons (person_id);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS relationship_documents;
+DROP TABLE IF EXISTS relationships;
+DROP TYPE IF EXISTS relationship_kind;
@@ -0,0 +1,28 @@
+-- Add up migration script here
+-- Family relationships between persons, each supported by one or more documents
+CREATE TYPE relationship_kind AS ENUM ('parent', 'spouse');
+
+-- Table: relationships
+-- `parent`: person_id is a parent of relative_id.
+-- `spouse`: person_id and relative_id are spouses, stored with the lower id first.
+CREATE TABLE IF NOT EXISTS relationships (
+    id SERIAL PRIMARY KEY,
+    kind relationship_kind NOT NULL,
+    person_id INT NOT NULL REFERENCES persons(id) ON DELETE CASCADE,
+    relative_id INT NOT NULL REFERENCES persons(id) ON DELETE CASCADE,
+    CONSTRAINT relationships_relative_check CHECK (person_id <> relative_id),
+    CONSTRAINT relationships_spouse_order_check CHECK (kind <> 'spouse' OR person_id < relative_id),
+    CONSTRAINT relationships_relationship_key UNIQUE (kind, person_id, relative_id)
+);
+
+-- Traversal follows parent relationships in both directions
+CREATE INDEX IF NOT EXISTS relationships_relative_id_idx ON relationships (relative_id, kind);
+
+-- Table: relationship_documents
+CREATE TABLE IF NOT EXISTS relationship_documents (
+    relationship_id INT NOT NULL REFERENCES relationships(id) ON DELETE CASCADE,
+    document_id INT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    PRIMARY KEY (relationship_id, document_id)
+);
+
+CREATE INDEX IF NOT EXISTS relationship_documents_document_id_idx ON relationship_documents (document_id);
@@ -0,0 +1,28 @@
+-- Add down migration script here
+-- Documents get back the numbers of their first scan, other scans and all files are dropped
+ALTER TABLE documents ADD COLUMN IF NOT EXISTS scan_number TEXT;
+ALTER TABLE documents ADD COLUMN IF NOT EXISTS page_number TEXT;
+
+DROP TRIGGER IF EXISTS document_scans_reindex_documents ON document_scans;
+DROP FUNCTION IF EXISTS document_scans_reindex_trigger();
+
+CREATE OR REPLACE FUNCTION documents_search_vector_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    NEW.search_vector := document_search_vector(
+        NEW.inventory_number, NEW.scan_number, NEW.notes,
+        NEW.archive_id, NEW.institute_id, NEW.place_id
+    );
+    RETURN NEW;
+END
+$$;
+
+UPDATE documents SET scan_number = first_scan.scan_number, page_number = first_scan.page_number
+FROM (
+    SELECT DISTINCT ON (document_id) document_id, scan_number, page_number
+    FROM document_scans
+    ORDER BY document_id, id
+) AS first_scan
+WHERE documents.id = first_scan.document_id;
+
+DROP TABLE IF EXISTS document_scans;
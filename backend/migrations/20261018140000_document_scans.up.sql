@@ -0,0 +1,66 @@
+-- Add up migration script here
+-- Table: document_scans
+-- The scans of a document, each with its own scan and page number.
+-- Uploaded files are stored on disk under their SHA-256; scans that were only catalogued
+-- by number, e.g. before uploads existed, have no file and no file metadata.
+CREATE TABLE IF NOT EXISTS document_scans (
+    id SERIAL PRIMARY KEY,
+    document_id INT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    scan_number TEXT,
+    page_number TEXT,
+    sha256 TEXT CHECK (sha256 ~ '^[0-9a-f]{64}$'),
+    file_name TEXT,
+    mime_type TEXT,
+    size_bytes BIGINT CHECK (size_bytes >= 0),
+    -- In pixels, NULL for PDFs
+    width INT,
+    height INT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT document_scans_file_key UNIQUE (document_id, sha256),
+    CONSTRAINT document_scans_file_check CHECK (
+        (sha256 IS NULL AND mime_type IS NULL AND size_bytes IS NULL)
+        OR (sha256 IS NOT NULL AND mime_type IS NOT NULL AND size_bytes IS NOT NULL)
+    )
+);
+
+CREATE INDEX IF NOT EXISTS document_scans_document_id_idx ON document_scans (document_id);
+CREATE INDEX IF NOT EXISTS document_scans_sha256_idx ON document_scans (sha256);
+
+INSERT INTO document_scans (document_id, scan_number, page_number)
+SELECT id, NULLIF(scan_number, ''), NULLIF(page_number, '')
+FROM documents
+WHERE COALESCE(scan_number, '') <> '' OR COALESCE(page_number, '') <> '';
+
+-- Scan numbers stay searchable, now through the scans of the document
+CREATE OR REPLACE FUNCTION documents_search_vector_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    NEW.search_vector := document_search_vector(
+        NEW.inventory_number,
+        (SELECT string_agg(scan_number, ' ') FROM document_scans WHERE document_id = NEW.id),
+        NEW.notes, NEW.archive_id, NEW.institute_id, NEW.place_id
+    );
+    RETURN NEW;
+END
+$$;
+
+-- Adding, renumbering or removing a scan re-indexes its document
+CREATE OR REPLACE FUNCTION document_scans_reindex_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        UPDATE documents SET id = id WHERE id = OLD.document_id;
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        UPDATE documents SET id = id WHERE id = NEW.document_id;
+    END IF;
+    RETURN NULL;
+END
+$$;
+
+CREATE TRIGGER document_scans_reindex_documents
+    AFTER INSERT OR DELETE OR UPDATE OF document_id, scan_number ON document_scans
+    FOR EACH ROW EXECUTE FUNCTION document_scans_reindex_trigger();
+
+ALTER TABLE documents DROP COLUMN IF EXISTS scan_number;
+ALTER TABLE documents DROP COLUMN IF EXISTS page_number;
@@ -0,0 +1,11 @@
+-- Add down migration script here
+-- The derivative files stay on disk, they are not referenced anymore
+DROP TABLE IF EXISTS scan_derivatives;
+DROP TYPE IF EXISTS derivative_format;
+
+DROP INDEX IF EXISTS document_scans_pending_derivatives_idx;
+ALTER TABLE document_scans
+    DROP COLUMN IF EXISTS derivatives_status,
+    DROP COLUMN IF EXISTS derivatives_error,
+    DROP COLUMN IF EXISTS derivatives_settings;
+DROP TYPE IF EXISTS derivatives_status;
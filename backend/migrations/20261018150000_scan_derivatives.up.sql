@@ -0,0 +1,32 @@
+-- Add up migration script here
+-- Thumbnails and web-size versions of scans, made by a background worker.
+-- `derivatives_settings` records the settings they were made with, so that changed
+-- settings can be detected and the derivatives made again.
+CREATE TYPE derivatives_status AS ENUM ('pending', 'processing', 'done', 'failed', 'skipped');
+
+ALTER TABLE document_scans
+    ADD COLUMN derivatives_status derivatives_status NOT NULL DEFAULT 'pending',
+    ADD COLUMN derivatives_error TEXT,
+    ADD COLUMN derivatives_settings TEXT;
+
+CREATE INDEX IF NOT EXISTS document_scans_pending_derivatives_idx
+    ON document_scans (id) WHERE derivatives_status = 'pending';
+
+CREATE TYPE derivative_format AS ENUM ('jpeg', 'webp');
+
+-- Table: scan_derivatives
+-- Derivative files are stored like scans, under their SHA-256
+CREATE TABLE IF NOT EXISTS scan_derivatives (
+    id SERIAL PRIMARY KEY,
+    scan_id INT NOT NULL REFERENCES document_scans(id) ON DELETE CASCADE,
+    size TEXT NOT NULL,
+    format derivative_format NOT NULL,
+    sha256 TEXT NOT NULL CHECK (sha256 ~ '^[0-9a-f]{64}$'),
+    size_bytes BIGINT NOT NULL CHECK (size_bytes >= 0),
+    width INT NOT NULL,
+    height INT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT scan_derivatives_variant_key UNIQUE (scan_id, size, format)
+);
+
+CREATE INDEX IF NOT EXISTS scan_derivatives_sha256_idx ON scan_derivatives (sha256);
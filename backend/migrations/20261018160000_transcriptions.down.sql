@@ -0,0 +1,51 @@
+-- Add down migration script here
+DROP TRIGGER IF EXISTS transcriptions_reindex_documents ON transcriptions;
+DROP FUNCTION IF EXISTS transcriptions_reindex_trigger();
+
+DROP TABLE IF EXISTS transcription_revisions;
+DROP TABLE IF EXISTS transcriptions;
+DROP TYPE IF EXISTS transcription_status;
+
+ALTER TABLE document_scans DROP CONSTRAINT IF EXISTS document_scans_document_key;
+
+DROP FUNCTION IF EXISTS document_search_vector(TEXT, TEXT, TEXT, TEXT, INT, INT, INT);
+
+CREATE OR REPLACE FUNCTION document_search_vector(
+    inventory_number TEXT,
+    scan_number TEXT,
+    notes TEXT,
+    doc_archive_id INT,
+    doc_institute_id INT,
+    doc_place_id INT
+) RETURNS tsvector
+LANGUAGE sql STABLE AS $$
+    WITH names AS (
+        SELECT concat_ws(' ',
+            (SELECT name FROM archives WHERE id = doc_archive_id),
+            (SELECT name FROM institutes WHERE id = doc_institute_id),
+            (SELECT name FROM places WHERE id = doc_place_id)
+        ) AS text
+    )
+    SELECT
+        setweight(to_tsvector('simple', concat_ws(' ', inventory_number, scan_number)), 'A') ||
+        setweight(to_tsvector('simple', names.text), 'B') ||
+        setweight(to_tsvector('dutch', names.text), 'B') ||
+        setweight(to_tsvector('english', names.text), 'B') ||
+        setweight(to_tsvector('dutch', coalesce(notes, '')), 'C') ||
+        setweight(to_tsvector('english', coalesce(notes, '')), 'C')
+    FROM names
+$$;
+
+CREATE OR REPLACE FUNCTION documents_search_vector_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    NEW.search_vector := document_search_vector(
+        NEW.inventory_number,
+        (SELECT string_agg(scan_number, ' ') FROM document_scans WHERE document_id = NEW.id),
+        NEW.notes, NEW.archive_id, NEW.institute_id, NEW.place_id
+    );
+    RETURN NEW;
+END
+$$;
+
+UPDATE documents SET id = id;
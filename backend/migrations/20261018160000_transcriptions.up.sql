@@ -0,0 +1,105 @@
+-- Add up migration script here
+CREATE TYPE transcription_status AS ENUM ('draft', 'reviewed', 'final');
+
+-- Scans are referred to together with their document, so that a transcription
+-- can only be tied to a scan of its own document
+ALTER TABLE document_scans
+    ADD CONSTRAINT document_scans_document_key UNIQUE (id, document_id);
+
+-- Table: transcriptions
+-- The text of a document, or of one of its scans, as transcribed by a researcher.
+-- `revision` is the number of the current revision in transcription_revisions.
+CREATE TABLE IF NOT EXISTS transcriptions (
+    id SERIAL PRIMARY KEY,
+    document_id INT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    scan_id INT,
+    transcriber TEXT NOT NULL,
+    -- BCP 47 language tag, e.g. `nl` or `la`
+    language TEXT NOT NULL,
+    status transcription_status NOT NULL DEFAULT 'draft',
+    text TEXT NOT NULL,
+    revision INT NOT NULL DEFAULT 1,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT transcriptions_scan_id_fkey FOREIGN KEY (scan_id, document_id)
+        REFERENCES document_scans (id, document_id) ON DELETE SET NULL (scan_id)
+);
+
+CREATE INDEX IF NOT EXISTS transcriptions_document_id_idx ON transcriptions (document_id);
+CREATE INDEX IF NOT EXISTS transcriptions_scan_id_idx ON transcriptions (scan_id);
+
+-- Table: transcription_revisions
+-- Every version of a transcription, the latest one equal to the transcription itself
+CREATE TABLE IF NOT EXISTS transcription_revisions (
+    id SERIAL PRIMARY KEY,
+    transcription_id INT NOT NULL REFERENCES transcriptions(id) ON DELETE CASCADE,
+    revision INT NOT NULL,
+    transcriber TEXT NOT NULL,
+    language TEXT NOT NULL,
+    status transcription_status NOT NULL,
+    text TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    CONSTRAINT transcription_revisions_revision_key UNIQUE (transcription_id, revision)
+);
+
+-- Transcribed text is searched like the notes of a document
+DROP FUNCTION IF EXISTS document_search_vector(TEXT, TEXT, TEXT, INT, INT, INT);
+
+CREATE OR REPLACE FUNCTION document_search_vector(
+    inventory_number TEXT,
+    scan_number TEXT,
+    notes TEXT,
+    transcriptions TEXT,
+    doc_archive_id INT,
+    doc_institute_id INT,
+    doc_place_id INT
+) RETURNS tsvector
+LANGUAGE sql STABLE AS $$
+    WITH names AS (
+        SELECT concat_ws(' ',
+            (SELECT name FROM archives WHERE id = doc_archive_id),
+            (SELECT name FROM institutes WHERE id = doc_institute_id),
+            (SELECT name FROM places WHERE id = doc_place_id)
+        ) AS text
+    )
+    SELECT
+        setweight(to_tsvector('simple', concat_ws(' ', inventory_number, scan_number)), 'A') ||
+        setweight(to_tsvector('simple', names.text), 'B') ||
+        setweight(to_tsvector('dutch', names.text), 'B') ||
+        setweight(to_tsvector('english', names.text), 'B') ||
+        setweight(to_tsvector('dutch', concat_ws(' ', notes, transcriptions)), 'C') ||
+        setweight(to_tsvector('english', concat_ws(' ', notes, transcriptions)), 'C')
+    FROM names
+$$;
+
+CREATE OR REPLACE FUNCTION documents_search_vector_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    NEW.search_vector := document_search_vector(
+        NEW.inventory_number,
+        (SELECT string_agg(scan_number, ' ') FROM document_scans WHERE document_id = NEW.id),
+        NEW.notes,
+        (SELECT string_agg(text, ' ') FROM transcriptions WHERE document_id = NEW.id),
+        NEW.archive_id, NEW.institute_id, NEW.place_id
+    );
+    RETURN NEW;
+END
+$$;
+
+-- Adding, editing or removing a transcription re-indexes its document
+CREATE OR REPLACE FUNCTION transcriptions_reindex_trigger() RETURNS trigger
+LANGUAGE plpgsql AS $$
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        UPDATE documents SET id = id WHERE id = OLD.document_id;
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        UPDATE documents SET id = id WHERE id = NEW.document_id;
+    END IF;
+    RETURN NULL;
+END
+$$;
+
+CREATE TRIGGER transcriptions_reindex_documents
+    AFTER INSERT OR DELETE OR UPDATE OF document_id, text ON transcriptions
+    FOR EACH ROW EXECUTE FUNCTION transcriptions_reindex_trigger();
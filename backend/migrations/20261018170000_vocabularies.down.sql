@@ -0,0 +1,4 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS document_terms;
+DROP TABLE IF EXISTS vocabulary_terms;
+DROP TABLE IF EXISTS vocabularies;
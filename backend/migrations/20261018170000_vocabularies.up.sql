@@ -0,0 +1,36 @@
+-- Add up migration script here
+-- Table: vocabularies
+-- Controlled vocabularies to classify documents with, e.g. document types and topics
+CREATE TABLE IF NOT EXISTS vocabularies (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    description TEXT,
+    CONSTRAINT vocabularies_name_key UNIQUE (name)
+);
+
+-- Table: vocabulary_terms
+-- The terms of a vocabulary, optionally nested under a broader term of the same vocabulary.
+-- `labels` maps language tags to the label in that language, e.g. {"en": "Baptism", "nl": "Doop"}.
+CREATE TABLE IF NOT EXISTS vocabulary_terms (
+    id SERIAL PRIMARY KEY,
+    vocabulary_id INT NOT NULL REFERENCES vocabularies(id) ON DELETE CASCADE,
+    parent_id INT,
+    code TEXT NOT NULL,
+    labels JSONB NOT NULL CHECK (jsonb_typeof(labels) = 'object'),
+    CONSTRAINT vocabulary_terms_code_key UNIQUE (vocabulary_id, code),
+    CONSTRAINT vocabulary_terms_vocabulary_key UNIQUE (id, vocabulary_id),
+    CONSTRAINT vocabulary_terms_parent_id_fkey FOREIGN KEY (parent_id, vocabulary_id)
+        REFERENCES vocabulary_terms (id, vocabulary_id)
+);
+
+CREATE INDEX IF NOT EXISTS vocabulary_terms_parent_id_idx ON vocabulary_terms (parent_id);
+
+-- Table: document_terms
+-- Terms assigned to documents. Terms in use cannot be deleted.
+CREATE TABLE IF NOT EXISTS document_terms (
+    document_id INT NOT NULL REFERENCES documents(id) ON DELETE CASCADE,
+    term_id INT NOT NULL REFERENCES vocabulary_terms(id),
+    CONSTRAINT document_terms_term_key PRIMARY KEY (document_id, term_id)
+);
+
+CREATE INDEX IF NOT EXISTS document_terms_term_id_idx ON document_terms (term_id);
@@ -0,0 +1,29 @@
+-- Add up migration script here
+-- Table: users
+-- People who sign in to the archive. `email` is stored in lower case, `password_hash`
+-- is an Argon2id hash in PHC string format. Inactive users cannot sign in.
+CREATE TABLE IF NOT EXISTS users (
+    id SERIAL PRIMARY KEY,
+    email TEXT NOT NULL CHECK (email = lower(email)),
+    name TEXT NOT NULL,
+    password_hash TEXT NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_login_at TIMESTAMPTZ,
+    CONSTRAINT users_email_key UNIQUE (email)
+);
+
+-- Table: refresh_tokens
+-- Long lived tokens to get a new access token with. Only the SHA-256 of a token is stored.
+-- A token is revoked when it is used, so every refresh hands out a new one.
+CREATE TABLE IF NOT EXISTS refresh_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    CONSTRAINT refresh_tokens_token_hash_key UNIQUE (token_hash)
+);
+
+CREATE INDEX IF NOT EXISTS refresh_tokens_user_id_idx ON refresh_tokens (user_id);
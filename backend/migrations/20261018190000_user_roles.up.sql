@@ -0,0 +1,15 @@
+-- Add up migration script here
+-- Roles of users. What each role may do is the permission matrix in `auth::permissions`.
+CREATE TYPE user_role AS ENUM ('admin', 'editor', 'volunteer', 'reader');
+
+-- Table: user_roles
+CREATE TABLE IF NOT EXISTS user_roles (
+    user_id INT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role user_role NOT NULL,
+    CONSTRAINT user_roles_role_key PRIMARY KEY (user_id, role)
+);
+
+-- Users from before roles could do everything
+INSERT INTO user_roles (user_id, role)
+SELECT id, 'admin' FROM users
+ON CONFLICT DO NOTHING;
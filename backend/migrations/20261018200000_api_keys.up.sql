@@ -0,0 +1,18 @@
+-- Add up migration script here
+-- Table: api_keys
+-- Keys for scripts, sent as `Authorization: Bearer glk_...`. Only the SHA-256 of a key is
+-- stored, `prefix` is its start to tell keys apart. `scopes` are permissions such as
+-- `documents:read`, see `auth::permissions`. Keys are revoked rather than deleted.
+CREATE TABLE IF NOT EXISTS api_keys (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL,
+    scopes TEXT[] NOT NULL,
+    created_by INT REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ,
+    CONSTRAINT api_keys_key_hash_key UNIQUE (key_hash)
+);
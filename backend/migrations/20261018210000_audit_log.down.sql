@@ -0,0 +1,9 @@
+-- Add down migration script here
+DROP TABLE IF EXISTS audit_log;
+DROP TYPE IF EXISTS audit_action;
+
+CREATE TABLE IF NOT EXISTS debug_log (
+    id SERIAL PRIMARY KEY,
+    message TEXT NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
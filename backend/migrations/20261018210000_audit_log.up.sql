@@ -0,0 +1,25 @@
+-- Add up migration script here
+-- The audit log replaces the debug log, which was never written to
+DROP TABLE IF EXISTS debug_log;
+
+CREATE TYPE audit_action AS ENUM ('create', 'update', 'delete');
+
+-- Table: audit_log
+-- Every change of archives, documents, institutes and places, written in the transaction
+-- of the change. `changes` maps each changed field to {"before": ..., "after": ...}.
+-- The actor is a user or an API key, kept as NULL once that is deleted.
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    actor_user_id INT REFERENCES users(id) ON DELETE SET NULL,
+    actor_api_key_id INT REFERENCES api_keys(id) ON DELETE SET NULL,
+    resource_type TEXT NOT NULL,
+    resource_id INT NOT NULL,
+    action audit_action NOT NULL,
+    changes JSONB NOT NULL CHECK (jsonb_typeof(changes) = 'object')
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_created_at_idx ON audit_log (created_at);
+CREATE INDEX IF NOT EXISTS audit_log_resource_idx ON audit_log (resource_type, resource_id);
+CREATE INDEX IF NOT EXISTS audit_log_actor_user_id_idx ON audit_log (actor_user_id);
+CREATE INDEX IF NOT EXISTS audit_log_actor_api_key_id_idx ON audit_log (actor_api_key_id);
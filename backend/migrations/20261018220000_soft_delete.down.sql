@@ -0,0 +1,19 @@
+-- Add down migration script here
+-- Items that are marked as deleted are kept, and show up again
+ALTER TABLE archives DROP COLUMN IF EXISTS deleted_at, DROP COLUMN IF EXISTS deleted_by;
+ALTER TABLE documents DROP COLUMN IF EXISTS deleted_at, DROP COLUMN IF EXISTS deleted_by;
+ALTER TABLE institutes DROP COLUMN IF EXISTS deleted_at, DROP COLUMN IF EXISTS deleted_by;
+ALTER TABLE places DROP COLUMN IF EXISTS deleted_at, DROP COLUMN IF EXISTS deleted_by;
+
+-- Enum values cannot be dropped, so the type is replaced, with restores and purges
+-- logged as updates and deletes
+ALTER TYPE audit_action RENAME TO audit_action_old;
+CREATE TYPE audit_action AS ENUM ('create', 'update', 'delete');
+ALTER TABLE audit_log ALTER COLUMN action TYPE audit_action USING (
+    CASE action::text
+        WHEN 'restore' THEN 'update'
+        WHEN 'purge' THEN 'delete'
+        ELSE action::text
+    END
+)::audit_action;
+DROP TYPE audit_action_old;
@@ -0,0 +1,18 @@
+-- Add up migration script here
+-- Deleting an archive, document, institute or place only marks it as deleted, so that it can
+-- be restored. Admins purge items to remove them for good.
+ALTER TABLE archives
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS deleted_by INT REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE documents
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS deleted_by INT REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE institutes
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS deleted_by INT REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE places
+    ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS deleted_by INT REFERENCES users(id) ON DELETE SET NULL;
+
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'restore';
+ALTER TYPE audit_action ADD VALUE IF NOT EXISTS 'purge';
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE document_scans DROP COLUMN IF EXISTS derivatives_claimed_at;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- When a worker claimed a scan to make its derivatives. Claims that are older than the
+-- worker's timeout are from a crashed worker and the scan is queued again.
+ALTER TABLE document_scans ADD COLUMN IF NOT EXISTS derivatives_claimed_at TIMESTAMPTZ;
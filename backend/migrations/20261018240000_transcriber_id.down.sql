@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE transcription_revisions DROP COLUMN IF EXISTS transcriber_id;
+ALTER TABLE transcriptions DROP COLUMN IF EXISTS transcriber_id;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- The user who made a transcription or revision. `transcriber` keeps their name as it was
+-- then, so it stays readable when the user is removed.
+ALTER TABLE transcriptions
+    ADD COLUMN IF NOT EXISTS transcriber_id INT REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE transcription_revisions
+    ADD COLUMN IF NOT EXISTS transcriber_id INT REFERENCES users(id) ON DELETE SET NULL;
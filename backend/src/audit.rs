@@ -0,0 +1,140 @@
+use serde_json::{Map, Value, json};
+use sqlx::PgConnection;
+
+use crate::auth::Actor;
+use crate::errors::AppError;
+use crate::models::audit::AuditAction;
+
+/**
+ * Record a change of the item `id` in `table` in the audit log, on the connection of the
+ * transaction that makes the change. `before` is `None` for a new item, `after` for a
+ * purged one. An update that changes nothing is not recorded.
+ */
+pub async fn record(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    table: &str,
+    id: i32,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<(), AppError> {
+    let action = match (&before, &after) {
+        (None, _) => AuditAction::Create,
+        (_, None) => AuditAction::Purge,
+        _ => AuditAction::Update,
+    };
+    record_as(conn, actor, action, table, id, before, after).await
+}
+
+/**
+ * Like `record`, for changes that are updates of the row but another action to the user:
+ * soft deletes and restores.
+ */
+pub async fn record_as(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    action: AuditAction,
+    table: &str,
+    id: i32,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<(), AppError> {
+    let changes = diff(before.as_ref(), after.as_ref());
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    record_changes(conn, actor, table, &[id], action, Value::Object(changes)).await
+}
+
+/**
+ * Record that `column` of the items `ids` in `table` changed from `from` to `to`,
+ * e.g. when documents are moved to another archive.
+ */
+pub async fn record_moves(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    table: &str,
+    ids: &[i32],
+    column: &str,
+    from: i32,
+    to: i32,
+) -> Result<(), AppError> {
+    let changes = json!({ column: {"before": from, "after": to} });
+
+    record_changes(conn, actor, table, ids, AuditAction::Update, changes).await
+}
+
+async fn record_changes(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    table: &str,
+    ids: &[i32],
+    action: AuditAction,
+    changes: Value,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_log
+            (actor_user_id, actor_api_key_id, resource_type, resource_id, action, changes)
+        SELECT $1, $2, $3, resource_id, $5, $6
+        FROM unnest($4::int[]) AS resource_id
+    "#,
+    )
+    .bind(actor.user_id())
+    .bind(actor.api_key_id())
+    .bind(table)
+    .bind(ids)
+    .bind(action)
+    .bind(changes)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/**
+ * `{"field": {"before": ..., "after": ...}}` for every top level field that differs.
+ * A missing side counts as `null` for all fields.
+ */
+fn diff(before: Option<&Value>, after: Option<&Value>) -> Map<String, Value> {
+    let fields = |value: Option<&Value>| match value {
+        Some(Value::Object(fields)) => fields.clone(),
+        _ => Map::new(),
+    };
+    let (before, after) = (fields(before), fields(after));
+
+    before
+        .keys()
+        .chain(after.keys().filter(|key| !before.contains_key(*key)))
+        .filter_map(|key| {
+            let old = before.get(key).unwrap_or(&Value::Null);
+            let new = after.get(key).unwrap_or(&Value::Null);
+            (old != new).then(|| (key.clone(), json!({"before": old, "after": new})))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_keeps_only_changed_fields() {
+        let before = json!({"id": 1, "name": "Leiden", "latitude": null});
+        let after = json!({"id": 1, "name": "Leyden", "latitude": 52.16});
+
+        assert_eq!(
+            Value::Object(diff(Some(&before), Some(&after))),
+            json!({
+                "name": {"before": "Leiden", "after": "Leyden"},
+                "latitude": {"before": null, "after": 52.16}
+            })
+        );
+        assert!(diff(Some(&before), Some(&before)).is_empty());
+        assert_eq!(
+            diff(None, Some(&after))["name"],
+            json!({"before": null, "after": "Leyden"})
+        );
+    }
+}
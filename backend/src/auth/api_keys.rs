@@ -0,0 +1,43 @@
+use sqlx::PgPool;
+
+use super::tokens::{hash_token, random_token};
+use crate::models::api_keys::ApiKey;
+
+/** Start of every API key, which tells them apart from access tokens */
+pub const KEY_PREFIX: &str = "glk_";
+
+/** Length of the start of a key that is kept to recognize it by */
+const DISPLAY_PREFIX_LENGTH: usize = 12;
+
+pub const API_KEY_COLUMNS: &str =
+    "id, name, prefix, scopes, created_by, created_at, expires_at, last_used_at, revoked_at";
+
+/**
+ * A new random API key, its prefix to show and the hash to store.
+ */
+pub fn generate() -> (String, String, String) {
+    let key = format!("{}{}", KEY_PREFIX, random_token());
+    let prefix = key[..DISPLAY_PREFIX_LENGTH].to_string();
+    let hash = hash_token(&key);
+
+    (key, prefix, hash)
+}
+
+/**
+ * The unexpired, unrevoked API key `key`, which is marked as used now.
+ */
+pub async fn authenticate(pool: &PgPool, key: &str) -> Result<Option<ApiKey>, sqlx::Error> {
+    let query = format!(
+        r#"
+        UPDATE api_keys SET last_used_at = now()
+        WHERE key_hash = $1 AND revoked_at IS NULL AND expires_at > now()
+        RETURNING {}
+    "#,
+        API_KEY_COLUMNS
+    );
+
+    sqlx::query_as::<_, ApiKey>(&query)
+        .bind(hash_token(key))
+        .fetch_optional(pool)
+        .await
+}
@@ -0,0 +1,340 @@
+pub mod api_keys;
+pub mod password;
+pub mod permissions;
+pub mod tokens;
+
+use std::sync::Arc;
+
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{HeaderMap, Method, header::AUTHORIZATION, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use axum_extra::extract::CookieJar;
+use rand_core::{OsRng, RngCore};
+use sqlx::{PgConnection, PgPool};
+
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::models::api_keys::ApiKey;
+use crate::models::users::{Role, User, UserWithRoles};
+use permissions::Permission;
+
+/** Cookie with the access token, for browsers */
+pub const ACCESS_COOKIE: &str = "access_token";
+/** Cookie with the refresh token, only sent to the `/api/v1/auth` routes */
+pub const REFRESH_COOKIE: &str = "refresh_token";
+
+const MIN_SECRET_LENGTH: usize = 32;
+
+/**
+ * How access and refresh tokens are signed and how long they last.
+ * Read from `JWT_SECRET`, `ACCESS_TOKEN_TTL` and `REFRESH_TOKEN_TTL` (in seconds, 15 minutes
+ * and 30 days by default) and `COOKIE_SECURE` (`false` or `0` allows the cookies over plain
+ * HTTP during development).
+ */
+#[derive(Clone)]
+pub struct AuthSettings {
+    secret: Vec<u8>,
+    access_token_ttl: i64,
+    refresh_token_ttl: i64,
+    secure_cookies: bool,
+}
+
+impl AuthSettings {
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self {
+            secret,
+            access_token_ttl: 15 * 60,
+            refresh_token_ttl: 30 * 24 * 60 * 60,
+            secure_cookies: true,
+        }
+    }
+
+    /**
+     * Without `JWT_SECRET` a random secret is used, so sessions end when the server restarts.
+     */
+    pub fn from_env() -> Result<Self, String> {
+        let secret = match std::env::var("JWT_SECRET") {
+            Ok(secret) if secret.len() >= MIN_SECRET_LENGTH => secret.into_bytes(),
+            Ok(_) => {
+                return Err(format!(
+                    "JWT_SECRET must be at least {} bytes long",
+                    MIN_SECRET_LENGTH
+                ));
+            }
+            Err(_) => {
+                eprintln!("⚠️ JWT_SECRET is not set, sessions end when the server restarts");
+                let mut secret = vec![0u8; MIN_SECRET_LENGTH];
+                OsRng.fill_bytes(&mut secret);
+                secret
+            }
+        };
+
+        let mut settings = Self::new(secret);
+        settings.access_token_ttl =
+            seconds_from_env("ACCESS_TOKEN_TTL", settings.access_token_ttl)?;
+        settings.refresh_token_ttl =
+            seconds_from_env("REFRESH_TOKEN_TTL", settings.refresh_token_ttl)?;
+        settings.secure_cookies = match std::env::var("COOKIE_SECURE") {
+            Ok(value) => !matches!(value.trim().to_lowercase().as_str(), "false" | "0"),
+            Err(_) => true,
+        };
+
+        Ok(settings)
+    }
+
+    pub fn secret(&self) -> &[u8] {
+        &self.secret
+    }
+
+    /** Lifetime of an access token in seconds */
+    pub fn access_token_ttl(&self) -> i64 {
+        self.access_token_ttl
+    }
+
+    /** Lifetime of a refresh token in seconds */
+    pub fn refresh_token_ttl(&self) -> i64 {
+        self.refresh_token_ttl
+    }
+
+    pub fn secure_cookies(&self) -> bool {
+        self.secure_cookies
+    }
+}
+
+fn seconds_from_env(name: &str, default: i64) -> Result<i64, String> {
+    match std::env::var(name) {
+        Err(_) => Ok(default),
+        Ok(value) => match value.trim().parse::<i64>() {
+            Ok(seconds) if seconds > 0 => Ok(seconds),
+            _ => Err(format!(
+                "Invalid {}: {}, expected a number of seconds",
+                name, value
+            )),
+        },
+    }
+}
+
+/**
+ * Who makes a request: a signed in user, with the access token in the `Authorization: Bearer`
+ * header or else the `access_token` cookie, or a script with an API key as bearer token.
+ * Rejects the request with a 401 when there is no valid token or key, or the user is no
+ * longer active.
+ */
+#[derive(Clone, Debug)]
+pub enum Actor {
+    User(UserWithRoles),
+    ApiKey(ApiKey),
+}
+
+impl Actor {
+    pub fn permits(&self, permission: Permission) -> bool {
+        match self {
+            Self::User(user) => user.roles.iter().any(|role| role.permits(permission)),
+            Self::ApiKey(key) => key.permits(permission),
+        }
+    }
+
+    pub fn user_id(&self) -> Option<i32> {
+        match self {
+            Self::User(user) => Some(user.user.id),
+            Self::ApiKey(_) => None,
+        }
+    }
+
+    pub fn api_key_id(&self) -> Option<i32> {
+        match self {
+            Self::User(_) => None,
+            Self::ApiKey(key) => Some(key.id),
+        }
+    }
+}
+
+impl FromRequestParts<Arc<AppState>> for Actor {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        // Already looked up by an earlier middleware
+        if let Some(actor) = parts.extensions.get::<Actor>() {
+            return Ok(actor.clone());
+        }
+
+        let token = access_token(&parts.headers)
+            .ok_or_else(|| AppError::Unauthorized("Authentication required".to_string()))?;
+
+        let actor = if token.starts_with(api_keys::KEY_PREFIX) {
+            api_keys::authenticate(state.pool(), &token)
+                .await?
+                .map(Actor::ApiKey)
+                .ok_or_else(|| {
+                    AppError::Unauthorized("Invalid, expired or revoked API key".to_string())
+                })?
+        } else {
+            let user_id = tokens::verify_access_token(state.auth(), &token)?;
+            fetch_user_with_roles(state.pool(), user_id)
+                .await?
+                .filter(|user| user.user.active)
+                .map(Actor::User)
+                .ok_or_else(|| AppError::Unauthorized("User account is not active".to_string()))?
+        };
+
+        parts.extensions.insert(actor.clone());
+        Ok(actor)
+    }
+}
+
+/**
+ * The signed in user of a request, for what only people may do. API keys get a 403.
+ */
+#[derive(Clone, Debug)]
+pub struct AuthUser(pub UserWithRoles);
+
+impl FromRequestParts<Arc<AppState>> for AuthUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        match Actor::from_request_parts(parts, state).await? {
+            Actor::User(user) => Ok(AuthUser(user)),
+            Actor::ApiKey(_) => Err(AppError::Forbidden(
+                "Only signed in users can do this, not API keys".to_string(),
+            )),
+        }
+    }
+}
+
+fn access_token(headers: &HeaderMap) -> Option<String> {
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+
+    bearer.or_else(|| {
+        CookieJar::from_headers(headers)
+            .get(ACCESS_COOKIE)
+            .map(|cookie| cookie.value().to_string())
+    })
+}
+
+/**
+ * Middleware that lets reads through and requires a signed in user or an API key for every
+ * other request.
+ */
+pub async fn require_user_for_mutations(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) {
+        return Ok(next.run(request).await);
+    }
+
+    let (mut parts, body) = request.into_parts();
+    Actor::from_request_parts(&mut parts, &state).await?;
+
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
+
+/**
+ * The user with `id` and their roles.
+ */
+pub async fn fetch_user_with_roles(
+    pool: &PgPool,
+    id: i32,
+) -> Result<Option<UserWithRoles>, sqlx::Error> {
+    sqlx::query_as::<_, UserWithRoles>(
+        r#"
+        SELECT users.id, email, name, active, created_at, last_login_at,
+            array_remove(array_agg(role ORDER BY role), NULL) AS roles
+        FROM users
+        LEFT JOIN user_roles ON user_roles.user_id = users.id
+        WHERE users.id = $1
+        GROUP BY users.id
+    "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+/**
+ * Add a user with the given password and roles, e.g. from `backend create-user`.
+ */
+pub async fn create_user(
+    pool: &PgPool,
+    email: &str,
+    name: &str,
+    password: &str,
+    roles: &[Role],
+) -> Result<UserWithRoles, AppError> {
+    let email = email.trim().to_lowercase();
+    if !email.contains('@') {
+        return Err(AppError::Validation(format!("Invalid email: {}", email)));
+    }
+    if name.trim().is_empty() {
+        return Err(AppError::Validation("name must not be empty".to_string()));
+    }
+
+    let password_hash = password::hash_password(password)?;
+
+    let mut tx = pool.begin().await?;
+
+    let user = sqlx::query_as::<_, User>(
+        r#"
+        INSERT INTO users (email, name, password_hash)
+        VALUES ($1, $2, $3)
+        RETURNING id, email, name, active, created_at, last_login_at
+    "#,
+    )
+    .bind(&email)
+    .bind(name.trim())
+    .bind(password_hash)
+    .fetch_one(&mut *tx)
+    .await?;
+    let roles = set_roles(&mut tx, user.id, roles).await?;
+
+    tx.commit().await?;
+
+    Ok(UserWithRoles { user, roles })
+}
+
+/**
+ * Replace the roles of a user and return the new ones in order.
+ */
+pub async fn set_roles(
+    conn: &mut PgConnection,
+    user_id: i32,
+    roles: &[Role],
+) -> Result<Vec<Role>, sqlx::Error> {
+    sqlx::query("DELETE FROM user_roles WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO user_roles (user_id, role)
+        SELECT DISTINCT $1, role FROM unnest($2::user_role[]) AS role
+        RETURNING role
+    "#,
+    )
+    .bind(user_id)
+    .bind(roles)
+    .fetch_all(&mut *conn)
+    .await
+    .map(|mut roles: Vec<Role>| {
+        roles.sort();
+        roles
+    })
+}
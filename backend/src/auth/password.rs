@@ -0,0 +1,63 @@
+use argon2::{
+    Argon2,
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+};
+use rand_core::OsRng;
+use std::sync::LazyLock;
+
+use crate::errors::AppError;
+
+pub const MIN_PASSWORD_LENGTH: usize = 10;
+
+/**
+ * Argon2id hash of `password` with a random salt, in PHC string format.
+ */
+pub fn hash_password(password: &str) -> Result<String, AppError> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(AppError::Validation(format!(
+            "A password needs at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|err| AppError::Internal(format!("Failed to hash a password: {}", err)))
+}
+
+/**
+ * Whether `password` matches the stored `hash`.
+ * Without a stored hash a dummy hash is checked, so unknown accounts take as long
+ * to reject as wrong passwords.
+ */
+pub fn verify_password(password: &str, hash: Option<&str>) -> bool {
+    static DUMMY_HASH: LazyLock<String> =
+        LazyLock::new(|| hash_password("not the password of anyone").unwrap_or_default());
+
+    let Ok(parsed) = PasswordHash::new(hash.unwrap_or(&DUMMY_HASH)) else {
+        return false;
+    };
+
+    Argon2::default()
+        .verify_password(password.as_bytes(), &parsed)
+        .is_ok()
+        && hash.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifies_only_the_hashed_password() {
+        let hash = hash_password("correct horse battery").unwrap();
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password("correct horse battery", Some(&hash)));
+        assert!(!verify_password("wrong horse battery", Some(&hash)));
+        assert!(!verify_password("correct horse battery", None));
+        assert!(hash_password("short").is_err());
+    }
+}
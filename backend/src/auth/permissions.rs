@@ -0,0 +1,392 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    extract::{FromRequestParts, Query, Request, State},
+    http::{Method, Uri},
+    middleware::{Next, from_fn_with_state},
+    response::Response,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::Actor;
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::export::ExportFormat;
+use crate::models::users::Role;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Archives,
+    Documents,
+    Institutes,
+    Persons,
+    Places,
+    Relationships,
+    Scans,
+    Transcriptions,
+    Vocabularies,
+    /** Accounts and their roles */
+    Users,
+    ApiKeys,
+    /** Who changed what, see `audit::record` */
+    Audit,
+}
+
+impl Resource {
+    pub const ALL: [Resource; 12] = [
+        Resource::Archives,
+        Resource::Documents,
+        Resource::Institutes,
+        Resource::Persons,
+        Resource::Places,
+        Resource::Relationships,
+        Resource::Scans,
+        Resource::Transcriptions,
+        Resource::Vocabularies,
+        Resource::Users,
+        Resource::ApiKeys,
+        Resource::Audit,
+    ];
+
+    /**
+     * Resources that manage who may do what, or show who did what.
+     * Only admins have permissions on them.
+     */
+    pub fn is_access_control(self) -> bool {
+        matches!(self, Self::Users | Self::ApiKeys | Self::Audit)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Archives => "archives",
+            Self::Documents => "documents",
+            Self::Institutes => "institutes",
+            Self::Persons => "persons",
+            Self::Places => "places",
+            Self::Relationships => "relationships",
+            Self::Scans => "scans",
+            Self::Transcriptions => "transcriptions",
+            Self::Vocabularies => "vocabularies",
+            Self::Users => "users",
+            Self::ApiKeys => "api_keys",
+            Self::Audit => "audit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Create,
+    Read,
+    Update,
+    Delete,
+    /** Bulk imports, e.g. a CSV of documents or a GEDCOM file */
+    Import,
+    /** Deleting an item after moving its documents to another item (`?reassign_to=`) */
+    Merge,
+    /** Whole lists as CSV, XLSX or JSON files, and GEDCOM exports */
+    Export,
+    /** Removing a deleted item for good (`?purge=true`) */
+    Purge,
+}
+
+impl Action {
+    pub const ALL: [Action; 8] = [
+        Action::Create,
+        Action::Read,
+        Action::Update,
+        Action::Delete,
+        Action::Import,
+        Action::Merge,
+        Action::Export,
+        Action::Purge,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Read => "read",
+            Self::Update => "update",
+            Self::Delete => "delete",
+            Self::Import => "import",
+            Self::Merge => "merge",
+            Self::Export => "export",
+            Self::Purge => "purge",
+        }
+    }
+}
+
+/**
+ * An action on a resource, written as `resource:action`, e.g. `places:merge`.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permission {
+    pub resource: Resource,
+    pub action: Action,
+}
+
+impl Permission {
+    pub const fn new(resource: Resource, action: Action) -> Self {
+        Self { resource, action }
+    }
+
+    /**
+     * Every combination of resource and action.
+     */
+    pub fn all() -> impl Iterator<Item = Permission> {
+        Resource::ALL.into_iter().flat_map(|resource| {
+            Action::ALL
+                .into_iter()
+                .map(move |action| Permission::new(resource, action))
+        })
+    }
+
+    /**
+     * What anyone may do without signing in: read everything but users, API keys
+     * and the audit log.
+     */
+    pub fn is_public(self) -> bool {
+        self.action == Action::Read && !self.resource.is_access_control()
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.resource.name(), self.action.name())
+    }
+}
+
+impl FromStr for Permission {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Permission::all()
+            .find(|permission| permission.to_string() == value)
+            .ok_or_else(|| {
+                format!(
+                    "Invalid permission: {}, expected resource:action such as documents:read",
+                    value
+                )
+            })
+    }
+}
+
+impl Serialize for Permission {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Permission {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl Role {
+    /**
+     * The permission matrix.
+     * - admin: everything, including managing users, their roles and API keys, reading
+     *   the audit log and purging deleted items
+     * - editor: everything else on the archive's data
+     * - volunteer: read everything and transcribe
+     * - reader: read and export everything
+     */
+    pub fn permits(self, permission: Permission) -> bool {
+        use Action::*;
+
+        let Permission { resource, action } = permission;
+        match self {
+            Role::Admin => true,
+            _ if resource.is_access_control() || action == Purge => false,
+            Role::Editor => true,
+            Role::Volunteer => match resource {
+                Resource::Transcriptions => matches!(action, Create | Read | Update),
+                _ => action == Read,
+            },
+            Role::Reader => matches!(action, Read | Export),
+        }
+    }
+
+    pub fn permissions(self) -> Vec<Permission> {
+        Permission::all()
+            .filter(|permission| self.permits(*permission))
+            .collect()
+    }
+}
+
+/**
+ * Which permission the requests to a group of routes need.
+ */
+#[derive(Debug, Clone, Copy)]
+pub enum Access {
+    /**
+     * From the method: `GET` reads (or exports, with `?format=` or an `Accept` header
+     * for CSV or XLSX), `POST` creates, `PATCH` and `PUT` update and `DELETE` deletes
+     * (and merges, with `?reassign_to=`, or purges, with `?purge=`).
+     */
+    Crud(Resource),
+    /** Reads as with `Crud`, any other method needs the given action */
+    Changes(Resource, Action),
+    /** Every request needs the given action */
+    Only(Resource, Action),
+}
+
+impl Access {
+    fn required(self, method: &Method, uri: &Uri, export: bool) -> Vec<Permission> {
+        let reads = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+        let (resource, mut actions) = match self {
+            Self::Only(resource, action) => (resource, vec![action]),
+            Self::Crud(resource) | Self::Changes(resource, _) if reads => {
+                if export {
+                    (resource, vec![Action::Read, Action::Export])
+                } else {
+                    (resource, vec![Action::Read])
+                }
+            }
+            Self::Changes(resource, action) => (resource, vec![action]),
+            Self::Crud(resource) => match *method {
+                Method::POST => (resource, vec![Action::Create]),
+                Method::DELETE => (resource, vec![Action::Delete]),
+                _ => (resource, vec![Action::Update]),
+            },
+        };
+        if *method == Method::DELETE && has_query_param(uri, "reassign_to") {
+            actions.push(Action::Merge);
+        }
+        if *method == Method::DELETE && has_query_param(uri, "purge") {
+            actions.push(Action::Purge);
+        }
+
+        actions
+            .into_iter()
+            .map(|action| Permission::new(resource, action))
+            .collect()
+    }
+}
+
+/**
+ * Whether the query has the parameter `name`, with the names percent-decoded as the `Query`
+ * extractors of the handlers decode them, so that `?purg%65=true` counts as `purge`.
+ * A query that cannot be decoded counts as having every parameter.
+ */
+fn has_query_param(uri: &Uri, name: &str) -> bool {
+    match Query::<Vec<(String, String)>>::try_from_uri(uri) {
+        Ok(Query(pairs)) => pairs.iter().any(|(key, _)| key == name),
+        Err(_) => true,
+    }
+}
+
+/**
+ * Check the permissions of the requests to the routes of a router, see `Access`.
+ */
+pub trait Authorize {
+    fn authorize(self, state: &Arc<AppState>, access: Access) -> Self;
+}
+
+impl<S: Clone + Send + Sync + 'static> Authorize for Router<S> {
+    fn authorize(self, state: &Arc<AppState>, access: Access) -> Self {
+        self.route_layer(from_fn_with_state((state.clone(), access), authorize))
+    }
+}
+
+/**
+ * Middleware that lets public reads through, rejects other requests without a signed in
+ * user or API key with a 401 and those that lack a permission with a 403.
+ */
+async fn authorize(
+    State((state, access)): State<(Arc<AppState>, Access)>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let export = has_query_param(request.uri(), "format")
+        || ExportFormat::negotiate(None, request.headers()).is_some();
+    let required = access.required(request.method(), request.uri(), export);
+    if required.iter().all(|permission| permission.is_public()) {
+        return Ok(next.run(request).await);
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let actor = Actor::from_request_parts(&mut parts, &state).await?;
+    if let Some(missing) = required
+        .iter()
+        .find(|permission| !actor.permits(**permission))
+    {
+        let message = match actor {
+            Actor::User(_) => format!("Your roles do not allow {}", missing),
+            Actor::ApiKey(_) => format!("The scopes of this API key do not include {}", missing),
+        };
+        return Err(AppError::Forbidden(message));
+    }
+
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn volunteers_transcribe_but_do_not_delete_or_merge() {
+        let permission = |value: &str| value.parse::<Permission>().unwrap();
+
+        assert!(Role::Volunteer.permits(permission("archives:read")));
+        assert!(Role::Volunteer.permits(permission("transcriptions:create")));
+        assert!(Role::Volunteer.permits(permission("transcriptions:update")));
+        assert!(!Role::Volunteer.permits(permission("transcriptions:delete")));
+        assert!(!Role::Volunteer.permits(permission("archives:delete")));
+        assert!(!Role::Volunteer.permits(permission("places:merge")));
+        assert!(Role::Editor.permits(permission("places:merge")));
+        assert!(!Role::Editor.permits(permission("places:purge")));
+        assert!(Role::Admin.permits(permission("places:purge")));
+        assert!(!Role::Editor.permits(permission("users:update")));
+        assert!(!Role::Editor.permits(permission("audit:read")));
+        assert!(!permission("audit:read").is_public());
+        assert!(Role::Reader.permits(permission("documents:export")));
+        assert!(!Role::Reader.permits(permission("documents:update")));
+        assert!(Role::Admin.permissions().len() == Permission::all().count());
+        assert!("places:fly".parse::<Permission>().is_err());
+    }
+
+    #[test]
+    fn access_follows_the_method() {
+        let uri: Uri = "/api/v1/places/1?reassign_to=2".parse().unwrap();
+        let required = Access::Crud(Resource::Places).required(&Method::DELETE, &uri, false);
+        assert_eq!(
+            required,
+            vec![
+                Permission::new(Resource::Places, Action::Delete),
+                Permission::new(Resource::Places, Action::Merge)
+            ]
+        );
+
+        let uri: Uri = "/api/v1/places/1?reassign%5Fto=2".parse().unwrap();
+        assert!(has_query_param(&uri, "reassign_to"));
+        let uri: Uri = "/api/v1/places?%66ormat=csv&q=a+b".parse().unwrap();
+        assert!(has_query_param(&uri, "format"));
+        assert!(!has_query_param(&uri, "purge"));
+
+        let uri: Uri = "/api/v1/documents?format=csv".parse().unwrap();
+        let required = Access::Crud(Resource::Documents).required(&Method::GET, &uri, true);
+        assert_eq!(
+            required[1],
+            Permission::new(Resource::Documents, Action::Export)
+        );
+
+        let uri: Uri = "/api/v1/documents/1/terms".parse().unwrap();
+        let required = Access::Changes(Resource::Documents, Action::Update).required(
+            &Method::POST,
+            &uri,
+            false,
+        );
+        assert_eq!(
+            required,
+            vec![Permission::new(Resource::Documents, Action::Update)]
+        );
+    }
+}
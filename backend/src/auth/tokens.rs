@@ -0,0 +1,89 @@
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::AuthSettings;
+use crate::errors::AppError;
+
+/**
+ * Claims of an access token. `sub` is the id of the user.
+ */
+#[derive(Serialize, Deserialize, Debug)]
+struct Claims {
+    sub: String,
+    iat: i64,
+    exp: i64,
+}
+
+/**
+ * A signed (HS256) access token for the user, valid for `settings.access_token_ttl()` seconds.
+ */
+pub fn issue_access_token(settings: &AuthSettings, user_id: i32) -> Result<String, AppError> {
+    let now = Utc::now().timestamp();
+    let claims = Claims {
+        sub: user_id.to_string(),
+        iat: now,
+        exp: now + settings.access_token_ttl(),
+    };
+
+    jsonwebtoken::encode(
+        &Header::new(Algorithm::HS256),
+        &claims,
+        &EncodingKey::from_secret(settings.secret()),
+    )
+    .map_err(|err| AppError::Internal(format!("Failed to sign an access token: {}", err)))
+}
+
+/**
+ * The id of the user of a valid, unexpired access token.
+ */
+pub fn verify_access_token(settings: &AuthSettings, token: &str) -> Result<i32, AppError> {
+    let invalid = || AppError::Unauthorized("Invalid or expired access token".to_string());
+
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.leeway = 0;
+    let data = jsonwebtoken::decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(settings.secret()),
+        &validation,
+    )
+    .map_err(|_| invalid())?;
+
+    data.claims.sub.parse().map_err(|_| invalid())
+}
+
+/**
+ * A new opaque token of 32 random bytes, for refresh tokens and API keys.
+ */
+pub fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/**
+ * The SHA-256 of a refresh token or API key, which is what the database stores.
+ */
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn access_tokens_only_verify_with_the_same_secret() {
+        let settings = AuthSettings::new(b"a secret of at least thirty-two bytes".to_vec());
+        let token = issue_access_token(&settings, 42).unwrap();
+
+        assert_eq!(verify_access_token(&settings, &token).unwrap(), 42);
+
+        let other = AuthSettings::new(b"another secret of thirty-two bytes".to_vec());
+        assert!(verify_access_token(&other, &token).is_err());
+        assert!(verify_access_token(&settings, "not.a.token").is_err());
+    }
+}
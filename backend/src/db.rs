@@ -1,24 +1,62 @@
 use sqlx::{Pool, Postgres, postgres::PgPoolOptions};
 
+use crate::auth::AuthSettings;
+use crate::iiif::IiifSettings;
+use crate::scans::ScanStore;
+use crate::scans::derivatives::{DerivativeSettings, DerivativeWorker};
+
 // #[derive(Clone)] allows to wrap AppState in Arc and clone it for routes.
 #[derive(Clone)]
 pub struct AppState {
     pool: Pool<Postgres>,
+    scans: ScanStore,
+    derivatives: DerivativeWorker,
+    auth: AuthSettings,
+    iiif: IiifSettings,
 }
 
 impl AppState {
-    pub fn new(pool: Pool<Postgres>) -> Self {
-        Self { pool }
+    pub fn new(
+        pool: Pool<Postgres>,
+        scans: ScanStore,
+        settings: DerivativeSettings,
+        auth: AuthSettings,
+        iiif: IiifSettings,
+    ) -> Self {
+        let derivatives = DerivativeWorker::new(pool.clone(), scans.clone(), settings);
+        Self {
+            pool,
+            scans,
+            derivatives,
+            auth,
+            iiif,
+        }
     }
 
     pub fn pool(&self) -> &Pool<Postgres> {
         &self.pool
     }
 
+    pub fn scans(&self) -> &ScanStore {
+        &self.scans
+    }
+
+    pub fn derivatives(&self) -> &DerivativeWorker {
+        &self.derivatives
+    }
+
+    pub fn auth(&self) -> &AuthSettings {
+        &self.auth
+    }
+
+    pub fn iiif(&self) -> &IiifSettings {
+        &self.iiif
+    }
+
     pub async fn init(database_url: &str) -> Self {
         let pool = PgPoolOptions::new()
             .max_connections(10)
-            .connect(&database_url)
+            .connect(database_url)
             .await
             .unwrap_or_else(|err| {
                 eprintln!("🔥 Failed to connect to the database: {:?}", err);
@@ -27,6 +65,21 @@ impl AppState {
 
         println!("✅ Connection to the database is successful!");
 
-        Self::new(pool)
+        let settings = DerivativeSettings::from_env().unwrap_or_else(|err| {
+            eprintln!("🔥 {}", err);
+            std::process::exit(1);
+        });
+
+        let auth = AuthSettings::from_env().unwrap_or_else(|err| {
+            eprintln!("🔥 {}", err);
+            std::process::exit(1);
+        });
+
+        let iiif = IiifSettings::from_env().unwrap_or_else(|err| {
+            eprintln!("🔥 {}", err);
+            std::process::exit(1);
+        });
+
+        Self::new(pool, ScanStore::from_env(), settings, auth, iiif)
     }
 }
@@ -0,0 +1,216 @@
+use axum::{
+    Json,
+    extract::{
+        multipart::{MultipartError, MultipartRejection},
+        rejection::{JsonRejection, PathRejection},
+    },
+    http::{StatusCode, header::WWW_AUTHENTICATE},
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::QueryRejection;
+use serde_json::json;
+
+/**
+ * Error returned by every handler.
+ * It renders as `{"status": "fail" | "error", "code": "...", "message": "..."}`,
+ * where `code` is a stable, machine-readable identifier for the kind of error.
+ */
+#[derive(Debug)]
+pub enum AppError {
+    NotFound(String),
+    Duplicate(String),
+    StillReferenced {
+        message: String,
+        documents: Option<i64>,
+    },
+    InvalidReference(String),
+    Validation(String),
+    InvalidRequest(StatusCode, String),
+    Unauthorized(String),
+    Forbidden(String),
+    Internal(String),
+}
+
+impl AppError {
+    pub fn not_found(id: i32) -> Self {
+        Self::NotFound(format!("Item with ID: {} not found", id))
+    }
+
+    /**
+     * Map the error of a `DELETE` statement.
+     * A foreign key violation there means other rows still reference the deleted item.
+     */
+    pub fn from_delete(err: sqlx::Error) -> Self {
+        match &err {
+            sqlx::Error::Database(e) if e.is_foreign_key_violation() => Self::still_referenced(),
+            _ => Self::from(err),
+        }
+    }
+
+    /**
+     * The item cannot be deleted because other items than documents refer to it.
+     */
+    pub fn still_referenced() -> Self {
+        Self::StillReferenced {
+            message: "Item is still referenced by other items and cannot be deleted".to_string(),
+            documents: None,
+        }
+    }
+
+    /**
+     * The item with `id` cannot be deleted because `documents` documents refer to it.
+     */
+    pub fn referenced_by_documents(id: i32, documents: i64) -> Self {
+        Self::StillReferenced {
+            message: format!(
+                "Item with ID: {} is still referenced by {} document(s)",
+                id, documents
+            ),
+            documents: Some(documents),
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Duplicate(_) | Self::StillReferenced { .. } => StatusCode::CONFLICT,
+            Self::InvalidReference(_) | Self::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::InvalidRequest(status, _) => *status,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound(_) => "not_found",
+            Self::Duplicate(_) => "duplicate",
+            Self::StillReferenced { .. } => "still_referenced",
+            Self::InvalidReference(_) => "invalid_reference",
+            Self::Validation(_) => "validation_failed",
+            Self::InvalidRequest(..) => "invalid_request",
+            Self::Unauthorized(_) => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::Internal(_) => "internal_error",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Self::NotFound(message)
+            | Self::Duplicate(message)
+            | Self::StillReferenced { message, .. }
+            | Self::InvalidReference(message)
+            | Self::Validation(message)
+            | Self::InvalidRequest(_, message)
+            | Self::Unauthorized(message)
+            | Self::Forbidden(message) => message,
+            // Internal details are logged, never sent to the client
+            Self::Internal(_) => "🔥 Something bad happened on our side",
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+
+        if let Self::Internal(details) = &self {
+            eprintln!("🔥 Internal error: {}", details);
+        }
+
+        let mut body = json!({
+            "status": if status.is_server_error() { "error" } else { "fail" },
+            "code": self.code(),
+            "message": self.message(),
+        });
+
+        if let Self::StillReferenced {
+            documents: Some(documents),
+            ..
+        } = &self
+        {
+            body["documents"] = json!(documents);
+        }
+
+        if let Self::Unauthorized(_) = &self {
+            return (status, [(WWW_AUTHENTICATE, "Bearer")], Json(body)).into_response();
+        }
+
+        (status, Json(body)).into_response()
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(err: sqlx::Error) -> Self {
+        match &err {
+            sqlx::Error::RowNotFound => Self::NotFound("Item not found".to_string()),
+            sqlx::Error::Database(e) => match e.code().as_deref() {
+                // unique_violation
+                Some("23505") => Self::Duplicate(match constraint_column(e.as_ref(), "_key") {
+                    Some(column) => format!("Item with that {} already exists", column),
+                    None => "Item already exists".to_string(),
+                }),
+                // foreign_key_violation
+                Some("23503") => {
+                    Self::InvalidReference(match constraint_column(e.as_ref(), "_id_fkey") {
+                        Some(column) => format!("Referenced {} does not exist", column),
+                        None => "Referenced item does not exist".to_string(),
+                    })
+                }
+                // not_null_violation, check_violation
+                Some("23502") | Some("23514") => Self::Validation(e.message().to_string()),
+                // data exceptions, e.g. a bound value that does not cast to its column type
+                Some(code) if code.starts_with("22") => Self::Validation(e.message().to_string()),
+                _ => Self::Internal(format!("{:?}", err)),
+            },
+            _ => Self::Internal(format!("{:?}", err)),
+        }
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        Self::InvalidRequest(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(rejection: PathRejection) -> Self {
+        Self::InvalidRequest(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<MultipartRejection> for AppError {
+    fn from(rejection: MultipartRejection) -> Self {
+        Self::InvalidRequest(rejection.status(), rejection.body_text())
+    }
+}
+
+impl From<MultipartError> for AppError {
+    fn from(err: MultipartError) -> Self {
+        Self::InvalidRequest(err.status(), err.body_text())
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        Self::InvalidRequest(rejection.status(), rejection.body_text())
+    }
+}
+
+/**
+ * Human readable column name from a default Postgres constraint name,
+ * e.g. `documents_inventory_number_key` becomes "inventory number".
+ */
+fn constraint_column(e: &dyn sqlx::error::DatabaseError, suffix: &str) -> Option<String> {
+    let constraint = e.constraint()?;
+    let table = e.table()?;
+    let column = constraint
+        .strip_prefix(table)?
+        .strip_prefix('_')?
+        .strip_suffix(suffix)?;
+
+    Some(column.replace('_', " "))
+}
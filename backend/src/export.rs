@@ -0,0 +1,374 @@
+use async_stream::try_stream;
+use axum::{
+    body::{Body, Bytes},
+    http::{
+        HeaderMap,
+        header::{ACCEPT, CONTENT_DISPOSITION, CONTENT_TYPE},
+    },
+    response::{IntoResponse, Response},
+};
+use futures::TryStreamExt;
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder, postgres::PgRow};
+use tokio_util::io::ReaderStream;
+
+use crate::errors::AppError;
+use crate::pagination::{Embed, Filter, Listing, embed_columns, embed_joins, order_by};
+
+const XLSX_CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+/** Rows are sent to the client in chunks of about this many bytes */
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/**
+ * A file format a list can be exported as, instead of returned as a page of JSON.
+ */
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Xlsx,
+}
+
+impl ExportFormat {
+    /**
+     * The format asked for with `?format=`, or else with the `Accept` header.
+     * `None` means the usual page of JSON, which is also what `Accept: application/json` gets.
+     */
+    pub fn negotiate(format: Option<Self>, headers: &HeaderMap) -> Option<Self> {
+        if format.is_some() {
+            return format;
+        }
+
+        let accept = headers.get(ACCEPT)?.to_str().ok()?;
+        accept
+            .split(',')
+            .map(|media_type| media_type.split(';').next().unwrap_or_default().trim())
+            .find_map(|media_type| match media_type {
+                "text/csv" => Some(Self::Csv),
+                XLSX_CONTENT_TYPE => Some(Self::Xlsx),
+                _ => None,
+            })
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Xlsx => XLSX_CONTENT_TYPE,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Xlsx => "xlsx",
+        }
+    }
+}
+
+/**
+ * A spreadsheet cell.
+ */
+pub enum Cell {
+    Empty,
+    Text(String),
+    Number(f64),
+}
+
+impl From<String> for Cell {
+    fn from(value: String) -> Self {
+        Self::Text(value)
+    }
+}
+
+impl From<i32> for Cell {
+    fn from(value: i32) -> Self {
+        Self::Number(value.into())
+    }
+}
+
+impl From<f64> for Cell {
+    fn from(value: f64) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl<T: Into<Cell>> From<Option<T>> for Cell {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Empty, Into::into)
+    }
+}
+
+impl Cell {
+    /**
+     * The cell as a CSV field. Text that a spreadsheet would read as a formula is
+     * prefixed with `'`, so opening an export cannot run a formula someone typed in a note.
+     */
+    fn to_csv_field(&self) -> String {
+        match self {
+            Self::Empty => String::new(),
+            Self::Text(text) if text.starts_with(['=', '+', '-', '@', '\t', '\r']) => {
+                format!("'{}", text)
+            }
+            Self::Text(text) => text.clone(),
+            Self::Number(number) => number.to_string(),
+        }
+    }
+}
+
+/**
+ * An item that can be written as a row of a CSV or XLSX export.
+ */
+pub trait Tabular {
+    /**
+     * The column names, given the embeds that were asked for with `?expand=`.
+     */
+    fn headers(embeds: &[&Embed]) -> Vec<&'static str>;
+
+    /**
+     * The cells of the row, in the order of `headers`.
+     */
+    fn cells(&self) -> Vec<Cell>;
+}
+
+/**
+ * Export every item of `listing` that passes `filter`, in the order of `sort`.
+ *
+ * Rows are streamed from the database with `fetch` and written to the response as they come,
+ * so an export never holds the whole list in memory. An XLSX file is a zip archive that can
+ * only be sent once it is complete, it is written to a temporary file that is then streamed.
+ */
+pub async fn export_list<T>(
+    pool: &PgPool,
+    listing: &Listing,
+    sort: Option<&str>,
+    filter: &impl Filter,
+    embeds: &[&Embed],
+    format: ExportFormat,
+) -> Result<Response, AppError>
+where
+    T: for<'r> FromRow<'r, PgRow> + Serialize + Tabular + Send + Unpin + 'static,
+{
+    let order = order_by(listing, sort)?;
+    let mut query = QueryBuilder::new(format!(
+        "SELECT {}.*{} FROM {}{} WHERE TRUE",
+        listing.table,
+        embed_columns(embeds),
+        listing.table,
+        embed_joins(listing.table, embeds)
+    ));
+    filter.push_conditions(&mut query);
+    query.push(order);
+
+    let headers = T::headers(embeds);
+    let body = match format {
+        ExportFormat::Xlsx => write_xlsx::<T>(pool, query, listing.table, &headers).await?,
+        _ => stream_rows::<T>(pool.clone(), query, headers, format),
+    };
+    let disposition = format!(
+        "attachment; filename=\"{}.{}\"",
+        listing.table,
+        format.extension()
+    );
+
+    Ok((
+        [
+            (CONTENT_TYPE, format.content_type().to_string()),
+            (CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/**
+ * A response body that streams the rows while the query runs.
+ * A failure halfway aborts the response, the client sees an incomplete body.
+ */
+fn stream_rows<T>(
+    pool: PgPool,
+    mut query: QueryBuilder<'static, Postgres>,
+    headers: Vec<&'static str>,
+    format: ExportFormat,
+) -> Body
+where
+    T: for<'r> FromRow<'r, PgRow> + Serialize + Tabular + Send + Unpin + 'static,
+{
+    let chunks = try_stream! {
+        let mut buffer = Vec::new();
+        match format {
+            ExportFormat::Csv => write_csv_record(&mut buffer, &headers)?,
+            _ => buffer.extend_from_slice(br#"{"status":"success","items":["#),
+        }
+
+        let mut rows = query.build().fetch(&pool);
+        let mut first = true;
+        while let Some(row) = rows.try_next().await? {
+            let item = T::from_row(&row)?;
+            match format {
+                ExportFormat::Csv => {
+                    let cells: Vec<String> = item.cells().iter().map(Cell::to_csv_field).collect();
+                    write_csv_record(&mut buffer, &cells)?;
+                }
+                _ => {
+                    if !first {
+                        buffer.push(b',');
+                    }
+                    serde_json::to_writer(&mut buffer, &item)
+                        .map_err(|error| AppError::Internal(error.to_string()))?;
+                }
+            }
+            first = false;
+
+            if buffer.len() >= CHUNK_SIZE {
+                yield Bytes::from(std::mem::take(&mut buffer));
+            }
+        }
+
+        if format == ExportFormat::Json {
+            buffer.extend_from_slice(b"]}");
+        }
+        yield Bytes::from(buffer);
+    };
+
+    Body::from_stream(chunks.map_err(|error: AppError| {
+        eprintln!("🔥 Export failed: {:?}", error);
+        std::io::Error::other(error.message().to_string())
+    }))
+}
+
+/**
+ * Append a CSV record to `buffer`, quoting the fields that need it.
+ */
+fn write_csv_record(buffer: &mut Vec<u8>, fields: &[impl AsRef<[u8]>]) -> Result<(), AppError> {
+    let mut writer = csv::WriterBuilder::new()
+        .buffer_capacity(256)
+        .from_writer(buffer);
+    writer
+        .write_record(fields)
+        .and_then(|_| writer.flush().map_err(csv::Error::from))
+        .map_err(|error| AppError::Internal(format!("{:?}", error)))
+}
+
+/**
+ * A response body with the rows as an XLSX file.
+ * The rows are kept in temporary files by the constant memory worksheet, the finished file
+ * is written to a temporary file as well, which is unlinked once it is opened for streaming.
+ */
+async fn write_xlsx<T>(
+    pool: &PgPool,
+    mut query: QueryBuilder<'static, Postgres>,
+    name: &str,
+    headers: &[&str],
+) -> Result<Body, AppError>
+where
+    T: for<'r> FromRow<'r, PgRow> + Tabular,
+{
+    let xlsx_error = |error: XlsxError| match error {
+        XlsxError::RowColumnLimitError => AppError::Validation(
+            "Too many rows for an XLSX export, export as CSV instead".to_string(),
+        ),
+        error => AppError::Internal(format!("{:?}", error)),
+    };
+
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet_with_constant_memory();
+    sheet.set_name(name).map_err(xlsx_error)?;
+    let bold = Format::new().set_bold();
+    for (column, header) in headers.iter().enumerate() {
+        sheet
+            .write_string_with_format(0, column as u16, *header, &bold)
+            .map_err(xlsx_error)?;
+    }
+    sheet.set_freeze_panes(1, 0).map_err(xlsx_error)?;
+
+    let mut rows = query.build().fetch(pool);
+    let mut row_number: u32 = 1;
+    while let Some(row) = rows.try_next().await? {
+        for (column, cell) in T::from_row(&row)?.cells().into_iter().enumerate() {
+            let column = column as u16;
+            match cell {
+                Cell::Empty => continue,
+                Cell::Text(text) => sheet.write_string(row_number, column, text),
+                Cell::Number(number) => sheet.write_number(row_number, column, number),
+            }
+            .map_err(xlsx_error)?;
+        }
+        row_number += 1;
+    }
+
+    drop(rows);
+
+    let path = std::env::temp_dir().join(format!("golijath-export-{}.xlsx", uuid::Uuid::new_v4()));
+    let saved = {
+        let path = path.clone();
+        tokio::task::spawn_blocking(move || workbook.save(path))
+            .await
+            .map_err(|error| AppError::Internal(error.to_string()))?
+            .map_err(xlsx_error)
+    };
+    let file = match saved {
+        Ok(()) => tokio::fs::File::open(&path).await,
+        Err(error) => {
+            let _ = tokio::fs::remove_file(&path).await;
+            return Err(error);
+        }
+    };
+    let _ = tokio::fs::remove_file(&path).await;
+    let file = file.map_err(|error| AppError::Internal(error.to_string()))?;
+
+    Ok(Body::from_stream(ReaderStream::new(file)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn format_parameter_wins_over_accept_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(ExportFormat::negotiate(None, &headers), None);
+
+        headers.insert(
+            ACCEPT,
+            HeaderValue::from_static("application/json;q=0.9, text/csv"),
+        );
+        assert_eq!(
+            ExportFormat::negotiate(None, &headers),
+            Some(ExportFormat::Csv)
+        );
+        assert_eq!(
+            ExportFormat::negotiate(Some(ExportFormat::Xlsx), &headers),
+            Some(ExportFormat::Xlsx)
+        );
+
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+        assert_eq!(ExportFormat::negotiate(None, &headers), None);
+    }
+
+    #[test]
+    fn csv_fields_that_start_a_formula_are_escaped() {
+        for (text, field) in [
+            (
+                "=HYPERLINK(\"http://example.com\")",
+                "'=HYPERLINK(\"http://example.com\")",
+            ),
+            ("+31 23 517 2700", "'+31 23 517 2700"),
+            ("-1+1", "'-1+1"),
+            ("@SUM(A1)", "'@SUM(A1)"),
+            ("\tcmd", "'\tcmd"),
+            ("\rcmd", "'\rcmd"),
+            ("Haarlem", "Haarlem"),
+            ("", ""),
+        ] {
+            assert_eq!(Cell::from(text.to_string()).to_csv_field(), field);
+        }
+        assert_eq!(Cell::from(-12).to_csv_field(), "-12");
+        assert_eq!(Cell::Empty.to_csv_field(), "");
+    }
+}
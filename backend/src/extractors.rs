@@ -0,0 +1,39 @@
+use axum::extract::{FromRequest, FromRequestParts, Multipart, Request};
+
+use crate::errors::AppError;
+
+/**
+ * `axum::Json` that rejects malformed bodies with an `AppError`.
+ */
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(AppError))]
+pub struct AppJson<T>(pub T);
+
+/**
+ * `axum::extract::Path` that rejects malformed path parameters with an `AppError`.
+ */
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(AppError))]
+pub struct AppPath<T>(pub T);
+
+/**
+ * `axum::extract::Multipart` that rejects requests that are not `multipart/form-data`
+ * with an `AppError`.
+ */
+pub struct AppMultipart(pub Multipart);
+
+impl<S: Send + Sync> FromRequest<S> for AppMultipart {
+    type Rejection = AppError;
+
+    async fn from_request(request: Request, state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(Multipart::from_request(request, state).await?))
+    }
+}
+
+/**
+ * `axum_extra::extract::Query` that rejects malformed query strings with an `AppError`.
+ * Repeated keys (`?id=1&id=2`) deserialize into a `Vec`.
+ */
+#[derive(FromRequestParts)]
+#[from_request(via(axum_extra::extract::Query), rejection(AppError))]
+pub struct AppQuery<T>(pub T);
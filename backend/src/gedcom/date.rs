@@ -0,0 +1,132 @@
+use chrono::Datelike;
+
+use crate::models::historical_date::{DatePrecision, HistoricalDate};
+
+const MONTHS: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+
+/**
+ * A historical date as a GEDCOM date, e.g. "12 MAY 1743", "ABT 1690" or "BET 1743 AND 1745".
+ * GEDCOM cannot qualify the ends of a range, so ranges lose their qualifiers.
+ */
+pub fn format_date(date: &HistoricalDate) -> String {
+    let earliest = format_at(date.earliest, date.precision);
+    let latest = format_at(date.latest, date.precision);
+    if earliest != latest {
+        return format!("BET {} AND {}", earliest, latest);
+    }
+
+    match (date.approximate, date.uncertain) {
+        (true, _) => format!("ABT {}", earliest),
+        (false, true) => format!("EST {}", earliest),
+        (false, false) => earliest,
+    }
+}
+
+fn format_at(date: chrono::NaiveDate, precision: DatePrecision) -> String {
+    let month = MONTHS[date.month0() as usize];
+    match precision {
+        DatePrecision::Year => format!("{}", date.year()),
+        DatePrecision::Month => format!("{} {}", month, date.year()),
+        DatePrecision::Day => format!("{} {} {}", date.day(), month, date.year()),
+    }
+}
+
+/**
+ * Read a GEDCOM date. Exact dates, `ABT`/`CAL`/`EST` and `BET ... AND ...` or `FROM ... TO ...`
+ * ranges are supported. Open ranges (`BEF`, `AFT`), other calendars and dual years
+ * are not, those return `None`.
+ */
+pub fn parse_date(value: &str) -> Option<HistoricalDate> {
+    let value = value.trim().to_uppercase();
+    let value = value.strip_prefix("@#DGREGORIAN@").unwrap_or(&value).trim();
+    let value = value.strip_prefix("GREGORIAN").unwrap_or(value).trim();
+    let words: Vec<&str> = value.split_whitespace().collect();
+
+    let edtf = match words.as_slice() {
+        ["BET", rest @ ..] | ["FROM", rest @ ..] => {
+            let separator = rest.iter().position(|word| matches!(*word, "AND" | "TO"))?;
+            format!(
+                "{}/{}",
+                to_edtf(&rest[..separator])?,
+                to_edtf(&rest[separator + 1..])?
+            )
+        }
+        ["ABT", rest @ ..] | ["CAL", rest @ ..] => format!("{}~", to_edtf(rest)?),
+        ["EST", rest @ ..] => format!("{}?", to_edtf(rest)?),
+        exact => to_edtf(exact)?,
+    };
+
+    edtf.parse().ok()
+}
+
+/**
+ * `[day] [month] year` as an EDTF date, e.g. "1743-05-12".
+ */
+fn to_edtf(words: &[&str]) -> Option<String> {
+    let month = |word: &str| {
+        MONTHS
+            .iter()
+            .position(|month| *month == word)
+            .map(|i| i + 1)
+    };
+    let year = |word: &str| -> Option<u32> {
+        (word.len() <= 4 && word.chars().all(|c| c.is_ascii_digit()))
+            .then(|| word.parse().ok())
+            .flatten()
+    };
+
+    match words {
+        [y] => Some(format!("{:04}", year(y)?)),
+        [m, y] => Some(format!("{:04}-{:02}", year(y)?, month(m)?)),
+        [d, m, y] => {
+            let day: u32 = d.parse().ok()?;
+            Some(format!("{:04}-{:02}-{:02}", year(y)?, month(m)?, day))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_between_gedcom_and_historical_dates() {
+        for (gedcom, edtf) in [
+            ("12 MAY 1743", "1743-05-12"),
+            ("MAY 1743", "1743-05"),
+            ("1743", "1743"),
+            ("ABT 1690", "1690~"),
+            ("EST 3 FEB 1701", "1701-02-03?"),
+            ("BET 1743 AND 1745", "1743/1745"),
+            ("BET MAY 1743 AND JUN 1743", "1743-05/1743-06"),
+        ] {
+            let date: HistoricalDate = edtf.parse().unwrap();
+            assert_eq!(format_date(&date), gedcom, "{}", edtf);
+            assert_eq!(parse_date(gedcom), Some(date), "{}", gedcom);
+        }
+
+        assert_eq!(
+            parse_date("from 1743 to 1745"),
+            parse_date("BET 1743 AND 1745")
+        );
+        assert_eq!(parse_date("CAL 1690"), parse_date("ABT 1690"));
+    }
+
+    #[test]
+    fn rejects_unsupported_dates() {
+        for value in [
+            "",
+            "BEF 1743",
+            "AFT 1743",
+            "@#DJULIAN@ 12 MAY 1743",
+            "12 MAY 1743/44",
+            "31 FEB 1743",
+            "INT 1743 (in the spring)",
+        ] {
+            assert_eq!(parse_date(value), None, "{}", value);
+        }
+    }
+}
@@ -0,0 +1,493 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use sqlx::PgPool;
+
+use super::date::format_date;
+use super::{Version, Writer};
+use crate::errors::AppError;
+use crate::handlers::lineage::{Direction, fetch_edges};
+use crate::handlers::relationships::SELECT_RELATIONSHIPS;
+use crate::models::archives::Archive;
+use crate::models::documents::Document;
+use crate::models::historical_date::HistoricalDate;
+use crate::models::institutes::Institute;
+use crate::models::persons::{DocumentPerson, Person, PersonRole, Sex};
+use crate::models::places::Place;
+use crate::models::relationships::{Relationship, RelationshipKind};
+
+/**
+ * The tree of a single person: their ancestors and descendants up to `generations` away,
+ * and the spouses of everyone in it.
+ */
+pub struct Tree {
+    pub person_id: i32,
+    pub generations: i32,
+}
+
+/**
+ * Export the whole database, or the documents and persons of a tree, as a GEDCOM file.
+ *
+ * Archives are `REPO` records, documents `SOUR` records with their inventory number as
+ * call number, persons `INDI` records citing the documents they are mentioned in.
+ * Parent and spouse relationships are grouped into `FAM` records by couple.
+ * Deleted documents and archives are left out, as are the citations of deleted documents.
+ */
+pub async fn export(
+    pool: &PgPool,
+    version: Version,
+    tree: Option<Tree>,
+) -> Result<String, AppError> {
+    let person_ids = match tree {
+        Some(tree) => Some(tree_persons(pool, tree).await?),
+        None => None,
+    };
+
+    let persons = sqlx::query_as::<_, Person>(
+        "SELECT * FROM persons WHERE $1::int[] IS NULL OR id = ANY($1) ORDER BY id",
+    )
+    .bind(&person_ids)
+    .fetch_all(pool)
+    .await?;
+
+    let query = format!(
+        "{} WHERE $1::int[] IS NULL OR (person_id = ANY($1) AND relative_id = ANY($1)) ORDER BY id",
+        SELECT_RELATIONSHIPS
+    );
+    let mut relationships = sqlx::query_as::<_, Relationship>(&query)
+        .bind(&person_ids)
+        .fetch_all(pool)
+        .await?;
+
+    let mentions = sqlx::query_as::<_, DocumentPerson>(
+        r#"
+        SELECT document_persons.* FROM document_persons
+        JOIN documents ON documents.id = document_persons.document_id
+        WHERE documents.deleted_at IS NULL AND ($1::int[] IS NULL OR person_id = ANY($1))
+        ORDER BY document_persons.id
+    "#,
+    )
+    .bind(&person_ids)
+    .fetch_all(pool)
+    .await?;
+
+    // A tree only has the documents its persons and relationships refer to
+    let document_ids: Option<Vec<i32>> = person_ids.as_ref().map(|_| {
+        let ids: BTreeSet<i32> = mentions
+            .iter()
+            .map(|mention| mention.document_id)
+            .chain(
+                relationships
+                    .iter()
+                    .flat_map(|r| r.document_ids.iter().copied()),
+            )
+            .collect();
+        ids.into_iter().collect()
+    });
+    let documents = sqlx::query_as::<_, Document>(
+        r#"
+        SELECT * FROM documents
+        WHERE deleted_at IS NULL AND ($1::int[] IS NULL OR id = ANY($1))
+        ORDER BY id
+    "#,
+    )
+    .bind(&document_ids)
+    .fetch_all(pool)
+    .await?;
+    let exported: BTreeSet<i32> = documents.iter().map(|document| document.id).collect();
+    for relationship in relationships.iter_mut() {
+        relationship.document_ids.retain(|id| exported.contains(id));
+    }
+
+    let archive_ids: Option<Vec<i32>> = document_ids.as_ref().map(|_| {
+        documents
+            .iter()
+            .map(|document| document.archive_id)
+            .collect()
+    });
+    let archives = sqlx::query_as::<_, Archive>(
+        r#"
+        SELECT * FROM archives
+        WHERE deleted_at IS NULL AND ($1::int[] IS NULL OR id = ANY($1))
+        ORDER BY id
+    "#,
+    )
+    .bind(&archive_ids)
+    .fetch_all(pool)
+    .await?;
+
+    let institute_ids: Vec<i32> = documents
+        .iter()
+        .map(|document| document.institute_id)
+        .collect();
+    let institutes: HashMap<i32, String> =
+        sqlx::query_as::<_, Institute>("SELECT * FROM institutes WHERE id = ANY($1)")
+            .bind(institute_ids)
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|institute| (institute.id, institute.name))
+            .collect();
+
+    let place_ids: Vec<i32> = documents
+        .iter()
+        .map(|document| document.place_id)
+        .chain(persons.iter().filter_map(|person| person.birth_place_id))
+        .chain(persons.iter().filter_map(|person| person.death_place_id))
+        .collect();
+    let places: HashMap<i32, Place> =
+        sqlx::query_as::<_, Place>("SELECT * FROM places WHERE id = ANY($1)")
+            .bind(place_ids)
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|place| (place.id, place))
+            .collect();
+
+    let sexes: HashMap<i32, Sex> = persons
+        .iter()
+        .map(|person| (person.id, person.sex))
+        .collect();
+    let families = families(&relationships, &sexes);
+
+    let mut writer = Writer::new(version);
+    for person in &persons {
+        let person_mentions = mentions
+            .iter()
+            .filter(|mention| mention.person_id == person.id);
+        write_person(&mut writer, person, &families, person_mentions, &places);
+    }
+    for (index, family) in families.iter().enumerate() {
+        write_family(&mut writer, index, family);
+    }
+    for document in &documents {
+        write_document(&mut writer, document, &institutes, &places);
+    }
+    for archive in &archives {
+        writer.line(0, Some(&archive_xref(archive.id)), "REPO", None);
+        writer.line(1, None, "NAME", Some(&archive.name));
+    }
+
+    Ok(writer.finish())
+}
+
+/**
+ * The ids of the persons in `tree`, an error when its person does not exist.
+ */
+async fn tree_persons(pool: &PgPool, tree: Tree) -> Result<Vec<i32>, AppError> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM persons WHERE id = $1)")
+        .bind(tree.person_id)
+        .fetch_one(pool)
+        .await?;
+    if !exists {
+        return Err(AppError::not_found(tree.person_id));
+    }
+
+    let mut ids = BTreeSet::from([tree.person_id]);
+    for direction in [Direction::Ancestors, Direction::Descendants] {
+        let edges = fetch_edges(pool, tree.person_id, tree.generations, direction).await?;
+        for edge in edges
+            .iter()
+            .filter(|edge| edge.generation <= tree.generations)
+        {
+            ids.insert(edge.parent_id);
+            ids.insert(edge.child_id);
+        }
+    }
+
+    let ids: Vec<i32> = ids.into_iter().collect();
+    let spouses: Vec<i32> = sqlx::query_scalar(
+        r#"
+        SELECT CASE WHEN person_id = ANY($1) THEN relative_id ELSE person_id END
+        FROM relationships
+        WHERE kind = 'spouse' AND (person_id = ANY($1) OR relative_id = ANY($1))
+    "#,
+    )
+    .bind(&ids)
+    .fetch_all(pool)
+    .await?;
+
+    let ids: BTreeSet<i32> = ids.into_iter().chain(spouses).collect();
+    Ok(ids.into_iter().collect())
+}
+
+/**
+ * A couple, or a single parent, with their children.
+ */
+#[derive(Default)]
+struct Family {
+    husband: Option<i32>,
+    wife: Option<i32>,
+    children: Vec<i32>,
+    /** Documents of the spouse relationship, cited for the marriage */
+    union_documents: Option<Vec<i32>>,
+    /** Documents of the parent relationships */
+    documents: BTreeSet<i32>,
+}
+
+impl Family {
+    fn partners(&self) -> impl Iterator<Item = i32> {
+        self.husband.into_iter().chain(self.wife)
+    }
+}
+
+/**
+ * Group the relationships into families: children with the same parents form one family,
+ * spouses form one together with their children.
+ */
+fn families(relationships: &[Relationship], sexes: &HashMap<i32, Sex>) -> Vec<Family> {
+    let mut parents: BTreeMap<i32, Vec<&Relationship>> = BTreeMap::new();
+    for relationship in relationships {
+        if relationship.kind == RelationshipKind::Parent {
+            parents
+                .entry(relationship.relative_id)
+                .or_default()
+                .push(relationship);
+        }
+    }
+
+    let mut families: BTreeMap<Vec<i32>, Family> = BTreeMap::new();
+    for relationship in relationships {
+        if relationship.kind == RelationshipKind::Spouse {
+            let partners = vec![relationship.person_id, relationship.relative_id];
+            family(&mut families, partners, sexes).union_documents =
+                Some(relationship.document_ids.clone());
+        }
+    }
+    for (child, relationships) in parents {
+        let mut partners: Vec<i32> = relationships.iter().map(|r| r.person_id).collect();
+        partners.sort_unstable();
+        // A family has at most two partners, more parents each get a family of their own
+        let groups = match partners.len() {
+            1 | 2 => vec![partners],
+            _ => partners.into_iter().map(|partner| vec![partner]).collect(),
+        };
+        for group in groups {
+            let family = family(&mut families, group.clone(), sexes);
+            family.children.push(child);
+            family.documents.extend(
+                relationships
+                    .iter()
+                    .filter(|r| group.contains(&r.person_id))
+                    .flat_map(|r| r.document_ids.iter().copied()),
+            );
+        }
+    }
+
+    families.into_values().collect()
+}
+
+fn family<'a>(
+    families: &'a mut BTreeMap<Vec<i32>, Family>,
+    partners: Vec<i32>,
+    sexes: &HashMap<i32, Sex>,
+) -> &'a mut Family {
+    families.entry(partners).or_insert_with_key(|partners| {
+        let is_female = |id: &i32| sexes.get(id) == Some(&Sex::Female);
+        // Women are wives, anyone else takes the place of the husband first
+        let mut partners = partners.clone();
+        partners.sort_by_key(is_female);
+        let (husband, wife) = match partners[..] {
+            [partner] if is_female(&partner) => (None, Some(partner)),
+            [partner] => (Some(partner), None),
+            [husband, wife] => (Some(husband), Some(wife)),
+            _ => (None, None),
+        };
+        Family {
+            husband,
+            wife,
+            ..Family::default()
+        }
+    })
+}
+
+fn person_xref(id: i32) -> String {
+    format!("@I{}@", id)
+}
+
+fn family_xref(index: usize) -> String {
+    format!("@F{}@", index + 1)
+}
+
+fn document_xref(id: i32) -> String {
+    format!("@S{}@", id)
+}
+
+fn archive_xref(id: i32) -> String {
+    format!("@R{}@", id)
+}
+
+fn write_person<'a>(
+    writer: &mut Writer,
+    person: &Person,
+    families: &[Family],
+    mentions: impl Iterator<Item = &'a DocumentPerson>,
+    places: &HashMap<i32, Place>,
+) {
+    writer.line(0, Some(&person_xref(person.id)), "INDI", None);
+
+    let given = [person.given_names.as_deref(), person.patronymic.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let surname = [person.surname_prefix.as_deref(), person.surname.as_deref()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let name = match surname.is_empty() {
+        true => given,
+        false => format!("{} /{}/", given, surname).trim().to_string(),
+    };
+    writer.line(1, None, "NAME", Some(&name));
+    for (tag, part) in [
+        ("GIVN", &person.given_names),
+        ("_PATR", &person.patronymic),
+        ("SPFX", &person.surname_prefix),
+        ("SURN", &person.surname),
+    ] {
+        if let Some(part) = part {
+            writer.line(2, None, tag, Some(part));
+        }
+    }
+
+    let sex = match person.sex {
+        Sex::Male => "M",
+        Sex::Female => "F",
+        Sex::Unknown => "U",
+    };
+    writer.line(1, None, "SEX", Some(sex));
+
+    for (tag, date, place_id) in [
+        ("BIRT", &person.birth_date, person.birth_place_id),
+        ("DEAT", &person.death_date, person.death_place_id),
+    ] {
+        if date.is_none() && place_id.is_none() {
+            continue;
+        }
+        writer.line(1, None, tag, None);
+        write_date_and_place(writer, 2, date.as_ref(), place_id, places);
+    }
+
+    for (index, family) in families.iter().enumerate() {
+        if family.children.contains(&person.id) {
+            writer.pointer(1, "FAMC", &family_xref(index));
+        }
+        if family.partners().any(|partner| partner == person.id) {
+            writer.pointer(1, "FAMS", &family_xref(index));
+        }
+    }
+
+    for mention in mentions {
+        writer.pointer(1, "SOUR", &document_xref(mention.document_id));
+        writer.line(2, None, "EVEN", Some("EVEN"));
+        write_role(writer, 3, mention.role);
+    }
+}
+
+/**
+ * The role of a person in a citation. GEDCOM 5.5.1 has no tag for witnesses, officials
+ * and subjects, those are written as a descriptor in parentheses.
+ */
+fn write_role(writer: &mut Writer, level: usize, role: PersonRole) {
+    let tag = match role {
+        PersonRole::Father => "FATH",
+        PersonRole::Mother => "MOTH",
+        PersonRole::Spouse => "SPOU",
+        PersonRole::Witness if writer.version() == Version::V70 => "WITN",
+        PersonRole::Official if writer.version() == Version::V70 => "OFFICIATOR",
+        PersonRole::Witness => "(Witness)",
+        PersonRole::Official => "(Official)",
+        PersonRole::Subject if writer.version() == Version::V70 => "OTHER",
+        PersonRole::Subject => "(Subject)",
+    };
+    writer.line(level, None, "ROLE", Some(tag));
+    if tag == "OTHER" {
+        writer.line(level + 1, None, "PHRASE", Some("Subject"));
+    }
+}
+
+fn write_date_and_place(
+    writer: &mut Writer,
+    level: usize,
+    date: Option<&HistoricalDate>,
+    place_id: Option<i32>,
+    places: &HashMap<i32, Place>,
+) {
+    if let Some(date) = date {
+        writer.line(level, None, "DATE", Some(&format_date(date)));
+    }
+    let Some(place) = place_id.and_then(|id| places.get(&id)) else {
+        return;
+    };
+    writer.line(level, None, "PLAC", Some(&place.name));
+    if let (Some(latitude), Some(longitude)) = (place.latitude, place.longitude) {
+        writer.line(level + 1, None, "MAP", None);
+        let latitude = format_coordinate(latitude, 'N', 'S');
+        let longitude = format_coordinate(longitude, 'E', 'W');
+        writer.line(level + 2, None, "LATI", Some(&latitude));
+        writer.line(level + 2, None, "LONG", Some(&longitude));
+    }
+}
+
+fn format_coordinate(value: f64, positive: char, negative: char) -> String {
+    match value < 0.0 {
+        true => format!("{}{}", negative, -value),
+        false => format!("{}{}", positive, value),
+    }
+}
+
+fn write_family(writer: &mut Writer, index: usize, family: &Family) {
+    writer.line(0, Some(&family_xref(index)), "FAM", None);
+    if let Some(husband) = family.husband {
+        writer.pointer(1, "HUSB", &person_xref(husband));
+    }
+    if let Some(wife) = family.wife {
+        writer.pointer(1, "WIFE", &person_xref(wife));
+    }
+    for child in &family.children {
+        writer.pointer(1, "CHIL", &person_xref(*child));
+    }
+    if let Some(documents) = &family.union_documents {
+        writer.line(1, None, "MARR", None);
+        for document in documents {
+            writer.pointer(2, "SOUR", &document_xref(*document));
+        }
+    }
+    for document in &family.documents {
+        writer.pointer(1, "SOUR", &document_xref(*document));
+    }
+}
+
+fn write_document(
+    writer: &mut Writer,
+    document: &Document,
+    institutes: &HashMap<i32, String>,
+    places: &HashMap<i32, Place>,
+) {
+    writer.line(0, Some(&document_xref(document.id)), "SOUR", None);
+    let title = document
+        .notes
+        .as_deref()
+        .and_then(|notes| notes.lines().next())
+        .filter(|line| !line.trim().is_empty())
+        .unwrap_or(&document.inventory_number);
+    writer.line(1, None, "TITL", Some(title));
+    if let Some(institute) = institutes.get(&document.institute_id) {
+        writer.line(1, None, "AUTH", Some(institute));
+    }
+    writer.line(1, None, "DATA", None);
+    writer.line(2, None, "EVEN", Some("EVEN"));
+    write_date_and_place(
+        writer,
+        3,
+        Some(&document.date),
+        Some(document.place_id),
+        places,
+    );
+    writer.pointer(1, "REPO", &archive_xref(document.archive_id));
+    writer.line(2, None, "CALN", Some(&document.inventory_number));
+    if let Some(notes) = &document.notes {
+        writer.line(1, None, "NOTE", Some(notes));
+    }
+}
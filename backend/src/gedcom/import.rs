@@ -0,0 +1,854 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
+
+use super::date::parse_date;
+use super::{Node, declared_version, parse};
+use crate::audit;
+use crate::auth::Actor;
+use crate::errors::AppError;
+use crate::handlers::persons::{PERSON_COLUMNS, push_person_values};
+use crate::models::documents::Document;
+use crate::models::persons::{PersonRole, Sex};
+use crate::models::places::Place;
+use crate::models::relationships::RelationshipKind;
+use crate::schemas::persons::CreatePerson;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Created,
+    /** An existing item matched, it is used as is */
+    Merged,
+    Skipped,
+}
+
+/**
+ * What happened to a record of the file, or to an item derived from one, e.g. a place.
+ */
+#[derive(Serialize, Debug)]
+pub struct ReportEntry {
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xref: Option<String>,
+    pub label: String,
+    pub action: Action,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub created: usize,
+    pub merged: usize,
+    pub skipped: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportReport {
+    pub version: String,
+    pub dry_run: bool,
+    pub summary: BTreeMap<&'static str, Counts>,
+    pub entries: Vec<ReportEntry>,
+}
+
+/**
+ * Import a GEDCOM file in one transaction, which is rolled back again for a `dry_run`.
+ *
+ * - `REPO` records become archives and `PLAC` values places, both matched on name.
+ * - `SOUR` records become documents, matched on their call number (`REPO.CALN`, or `REFN`).
+ *   The author (`AUTH`) is the institute, `DATA.EVEN` has the date and place.
+ * - `INDI` records become persons, matched on their name parts and birth date.
+ *   Their source citations become mentions, with the role from `SOUR.EVEN.ROLE`.
+ * - `FAM` records become parent relationships, supported by the sources cited by the family
+ *   and by the birth or baptism of the child. Partners become spouses when the family has
+ *   a marriage or other union event, or no children.
+ *
+ * The archives, institutes, places and documents it creates or changes are recorded in the
+ * audit log as changes by `actor`.
+ */
+pub async fn import(
+    pool: &PgPool,
+    actor: &Actor,
+    text: &str,
+    dry_run: bool,
+) -> Result<ImportReport, AppError> {
+    let records = parse(text).map_err(AppError::Validation)?;
+    if records.first().is_none_or(|record| record.tag != "HEAD") {
+        return Err(AppError::Validation(
+            "A GEDCOM file starts with a HEAD record".to_string(),
+        ));
+    }
+    let version = declared_version(&records).map_err(AppError::Validation)?;
+
+    let mut importer = Importer {
+        tx: pool.begin().await?,
+        actor: actor.clone(),
+        archives: HashMap::new(),
+        institutes: HashMap::new(),
+        places: HashMap::new(),
+        documents: HashMap::new(),
+        persons: HashMap::new(),
+        created_persons: Vec::new(),
+        entries: Vec::new(),
+    };
+    let with_tag = |tag: &'static str| records.iter().filter(move |record| record.tag == tag);
+
+    for record in with_tag("REPO") {
+        importer.repository(record).await?;
+    }
+    for record in with_tag("SOUR") {
+        importer.source(record).await?;
+    }
+    for record in with_tag("INDI") {
+        importer.individual(record).await?;
+    }
+    let individuals: HashMap<&str, &Node> = with_tag("INDI")
+        .filter_map(|record| Some((record.xref.as_deref()?, record)))
+        .collect();
+    for record in with_tag("FAM") {
+        importer.family(record, &individuals).await?;
+    }
+
+    if dry_run {
+        importer.tx.rollback().await?;
+    } else {
+        importer.tx.commit().await?;
+    }
+
+    let mut summary: BTreeMap<&'static str, Counts> = BTreeMap::new();
+    for entry in &importer.entries {
+        let counts = summary.entry(entry.kind).or_default();
+        match entry.action {
+            Action::Created => counts.created += 1,
+            Action::Merged => counts.merged += 1,
+            Action::Skipped => counts.skipped += 1,
+        }
+    }
+
+    Ok(ImportReport {
+        version: version.to_string(),
+        dry_run,
+        summary,
+        entries: importer.entries,
+    })
+}
+
+struct Importer {
+    tx: Transaction<'static, Postgres>,
+    actor: Actor,
+    /** Ids of the imported records and items by xref or name */
+    archives: HashMap<String, i32>,
+    institutes: HashMap<String, i32>,
+    places: HashMap<String, i32>,
+    documents: HashMap<String, i32>,
+    persons: HashMap<String, i32>,
+    /** Persons are not matched against persons from the same file */
+    created_persons: Vec<i32>,
+    entries: Vec<ReportEntry>,
+}
+
+impl Importer {
+    fn report(
+        &mut self,
+        kind: &'static str,
+        record: Option<&Node>,
+        label: &str,
+        action: Action,
+        id: Option<i32>,
+    ) {
+        self.entries.push(ReportEntry {
+            kind,
+            xref: record.and_then(|record| record.xref.clone()),
+            label: label.to_string(),
+            action,
+            id,
+            reason: None,
+        });
+    }
+
+    fn skip(&mut self, kind: &'static str, record: Option<&Node>, label: &str, reason: &str) {
+        self.report(kind, record, label, Action::Skipped, None);
+        if let Some(entry) = self.entries.last_mut() {
+            entry.reason = Some(reason.to_string());
+        }
+    }
+
+    /**
+     * The id of the archive or institute with `name`, created when there is none yet.
+     * A deleted one is not used, it must be restored first.
+     */
+    async fn named(
+        &mut self,
+        kind: &'static str,
+        record: Option<&Node>,
+        name: &str,
+    ) -> Result<i32, AppError> {
+        let cache = match kind {
+            "archives" => &self.archives,
+            _ => &self.institutes,
+        };
+        if let Some(id) = cache.get(name) {
+            return Ok(*id);
+        }
+
+        let query = format!(
+            "SELECT id, deleted_at IS NOT NULL FROM {} WHERE name = $1",
+            kind
+        );
+        let existing: Option<(i32, bool)> = sqlx::query_as(&query)
+            .bind(name)
+            .fetch_optional(&mut *self.tx)
+            .await?;
+        let (id, action) = match existing {
+            Some((_, true)) => return Err(deleted(name)),
+            Some((id, false)) => (id, Action::Merged),
+            None => {
+                let query = format!(
+                    "INSERT INTO {0} (name) VALUES ($1) RETURNING id, to_jsonb({0})",
+                    kind
+                );
+                let (id, item): (i32, Value) = sqlx::query_as(&query)
+                    .bind(name)
+                    .fetch_one(&mut *self.tx)
+                    .await?;
+                audit::record(&mut self.tx, &self.actor, kind, id, None, Some(item)).await?;
+                (id, Action::Created)
+            }
+        };
+
+        self.report(kind, record, name, action, Some(id));
+        match kind {
+            "archives" => self.archives.insert(name.to_string(), id),
+            _ => self.institutes.insert(name.to_string(), id),
+        };
+        Ok(id)
+    }
+
+    /**
+     * The id of the place of a `PLAC` node, created when there is none with its name yet.
+     * Only the first part of a place hierarchy like "Haarlem, Noord-Holland" is the name.
+     * An existing place gets the coordinates of `MAP` when it has none, a deleted one
+     * must be restored first.
+     */
+    async fn place(&mut self, node: &Node) -> Result<Option<i32>, AppError> {
+        let Some(name) = node
+            .value
+            .as_deref()
+            .and_then(|value| value.split(',').next())
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        else {
+            return Ok(None);
+        };
+        if let Some(id) = self.places.get(name) {
+            return Ok(Some(*id));
+        }
+
+        let map = node.child("MAP");
+        let latitude = map
+            .and_then(|map| map.value_of("LATI"))
+            .and_then(|value| parse_coordinate(value, 'N', 'S'))
+            .filter(|latitude| (-90.0..=90.0).contains(latitude));
+        let longitude = map
+            .and_then(|map| map.value_of("LONG"))
+            .and_then(|value| parse_coordinate(value, 'E', 'W'))
+            .filter(|longitude| (-180.0..=180.0).contains(longitude));
+
+        let existing = sqlx::query_as::<_, Place>("SELECT * FROM places WHERE name = $1")
+            .bind(name)
+            .fetch_optional(&mut *self.tx)
+            .await?;
+        let (id, action) = match existing {
+            Some(place) if place.deleted_at.is_some() => return Err(deleted(name)),
+            Some(before) => {
+                let after = sqlx::query_as::<_, Place>(
+                    r#"
+                    UPDATE places SET
+                        latitude = COALESCE(latitude, $2),
+                        longitude = COALESCE(longitude, $3)
+                    WHERE id = $1
+                    RETURNING *
+                "#,
+                )
+                .bind(before.id)
+                .bind(latitude)
+                .bind(longitude)
+                .fetch_one(&mut *self.tx)
+                .await?;
+                audit::record(
+                    &mut self.tx,
+                    &self.actor,
+                    "places",
+                    after.id,
+                    Some(json!(before)),
+                    Some(json!(after)),
+                )
+                .await?;
+                (after.id, Action::Merged)
+            }
+            None => {
+                let item = sqlx::query_as::<_, Place>(
+                    "INSERT INTO places (name, latitude, longitude) VALUES ($1, $2, $3) RETURNING *",
+                )
+                .bind(name)
+                .bind(latitude)
+                .bind(longitude)
+                .fetch_one(&mut *self.tx)
+                .await?;
+                audit::record(
+                    &mut self.tx,
+                    &self.actor,
+                    "places",
+                    item.id,
+                    None,
+                    Some(json!(item)),
+                )
+                .await?;
+                (item.id, Action::Created)
+            }
+        };
+
+        self.report("places", None, name, action, Some(id));
+        self.places.insert(name.to_string(), id);
+        Ok(Some(id))
+    }
+
+    async fn repository(&mut self, record: &Node) -> Result<(), AppError> {
+        let label = record.xref.clone().unwrap_or_default();
+        let (Some(xref), Some(name)) = (record.xref.as_deref(), record.value_of("NAME")) else {
+            self.skip("archives", Some(record), &label, "Repository has no NAME");
+            return Ok(());
+        };
+
+        let id = self.named("archives", Some(record), name).await?;
+        self.archives.insert(xref.to_string(), id);
+        Ok(())
+    }
+
+    async fn source(&mut self, record: &Node) -> Result<(), AppError> {
+        let label = record
+            .value_of("TITL")
+            .or(record.xref.as_deref())
+            .unwrap_or_default()
+            .to_string();
+        let Some(xref) = record.xref.as_deref() else {
+            self.skip("documents", Some(record), &label, "Source has no xref");
+            return Ok(());
+        };
+
+        let repository = record.child("REPO");
+        let Some(inventory_number) = repository
+            .and_then(|repository| repository.value_of("CALN"))
+            .or(record.value_of("REFN"))
+        else {
+            self.skip(
+                "documents",
+                Some(record),
+                &label,
+                "Source has no call number (REPO.CALN) or REFN to use as inventory number",
+            );
+            return Ok(());
+        };
+
+        let existing: Option<(i32, bool)> = sqlx::query_as(
+            "SELECT id, deleted_at IS NOT NULL FROM documents WHERE inventory_number = $1",
+        )
+        .bind(inventory_number)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        if let Some((_, true)) = existing {
+            return Err(deleted(inventory_number));
+        }
+        if let Some((id, _)) = existing {
+            self.report("documents", Some(record), &label, Action::Merged, Some(id));
+            self.documents.insert(xref.to_string(), id);
+            return Ok(());
+        }
+
+        let data = record.child("DATA");
+        let event = data.and_then(|data| data.child("EVEN"));
+        let date = event
+            .and_then(|event| event.value_of("DATE"))
+            .or(data.and_then(|data| data.value_of("DATE")));
+        let place = event
+            .and_then(|event| event.child("PLAC"))
+            .or(data.and_then(|data| data.child("PLAC")));
+        let archive_id = repository
+            .and_then(Node::pointer)
+            .and_then(|pointer| self.archives.get(pointer))
+            .copied();
+
+        let reason = match (archive_id, record.value_of("AUTH"), date, place) {
+            (None, ..) => Some("Source has no imported repository to use as archive".to_string()),
+            (_, None, ..) => Some("Source has no author (AUTH) to use as institute".to_string()),
+            (_, _, None, _) => Some("Source has no date (DATA.EVEN.DATE)".to_string()),
+            (_, _, Some(date), _) if parse_date(date).is_none() => {
+                Some(format!("Unsupported date: {}", date))
+            }
+            (.., None) => Some("Source has no place (DATA.EVEN.PLAC)".to_string()),
+            _ => None,
+        };
+        if let Some(reason) = reason {
+            self.skip("documents", Some(record), &label, &reason);
+            return Ok(());
+        }
+        let (Some(archive_id), Some(author), Some(date), Some(place)) = (
+            archive_id,
+            record.value_of("AUTH"),
+            date.and_then(parse_date),
+            place,
+        ) else {
+            return Ok(());
+        };
+
+        let institute_id = self.named("institutes", None, author).await?;
+        let Some(place_id) = self.place(place).await? else {
+            self.skip(
+                "documents",
+                Some(record),
+                &label,
+                "Source has no place name",
+            );
+            return Ok(());
+        };
+
+        // The title stands in for missing notes, unless it is just the inventory number
+        let notes: Vec<&str> = record
+            .children_with("NOTE")
+            .filter(|note| note.pointer().is_none())
+            .filter_map(|note| note.value.as_deref())
+            .collect();
+        let notes = match record.value_of("TITL") {
+            _ if !notes.is_empty() => Some(notes.join("\n")),
+            Some(title) if title != inventory_number => Some(title.to_string()),
+            _ => None,
+        };
+
+        let item = sqlx::query_as::<_, Document>(
+            r#"
+            INSERT INTO documents
+                (date_earliest, date_latest, date_precision, date_approximate, date_uncertain,
+                 inventory_number, notes, archive_id, institute_id, place_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING *
+        "#,
+        )
+        .bind(date.earliest)
+        .bind(date.latest)
+        .bind(date.precision)
+        .bind(date.approximate)
+        .bind(date.uncertain)
+        .bind(inventory_number)
+        .bind(notes)
+        .bind(archive_id)
+        .bind(institute_id)
+        .bind(place_id)
+        .fetch_one(&mut *self.tx)
+        .await?;
+        let id = item.id;
+        audit::record(
+            &mut self.tx,
+            &self.actor,
+            "documents",
+            id,
+            None,
+            Some(json!(item)),
+        )
+        .await?;
+
+        self.report("documents", Some(record), &label, Action::Created, Some(id));
+        self.documents.insert(xref.to_string(), id);
+        Ok(())
+    }
+
+    async fn individual(&mut self, record: &Node) -> Result<(), AppError> {
+        let name = record.child("NAME");
+        let label = name
+            .and_then(|name| name.value.as_deref())
+            .map(|name| name.replace('/', "").trim().to_string())
+            .filter(|name| !name.is_empty())
+            .or(record.xref.clone())
+            .unwrap_or_default();
+        let Some(xref) = record.xref.as_deref() else {
+            self.skip("persons", Some(record), &label, "Individual has no xref");
+            return Ok(());
+        };
+
+        let (given_names, surname) = name
+            .and_then(|name| name.value.as_deref())
+            .map(split_name)
+            .unwrap_or_default();
+        let part = |tag: &str| name.and_then(|name| name.value_of(tag)).map(str::to_string);
+        let birth = record.child("BIRT");
+        let death = record.child("DEAT");
+        let date_of = |event: Option<&Node>| {
+            event
+                .and_then(|event| event.value_of("DATE"))
+                .and_then(parse_date)
+        };
+
+        let mut person = CreatePerson {
+            given_names: part("GIVN").or(given_names),
+            patronymic: part("_PATR"),
+            surname_prefix: part("SPFX"),
+            surname: part("SURN").or(surname),
+            sex: Some(match record.value_of("SEX") {
+                Some("M") => Sex::Male,
+                Some("F") => Sex::Female,
+                _ => Sex::Unknown,
+            }),
+            birth_date: date_of(birth),
+            birth_place_id: None,
+            death_date: date_of(death),
+            death_place_id: None,
+        };
+        if let Err(reason) = person.validate() {
+            self.skip("persons", Some(record), &label, &reason);
+            return Ok(());
+        }
+
+        let existing: Option<i32> = sqlx::query_scalar(
+            r#"
+            SELECT id FROM persons
+            WHERE given_names IS NOT DISTINCT FROM $1
+                AND patronymic IS NOT DISTINCT FROM $2
+                AND surname_prefix IS NOT DISTINCT FROM $3
+                AND surname IS NOT DISTINCT FROM $4
+                AND birth_date_earliest IS NOT DISTINCT FROM $5
+                AND birth_date_latest IS NOT DISTINCT FROM $6
+                AND NOT id = ANY($7)
+            ORDER BY id
+            LIMIT 1
+        "#,
+        )
+        .bind(&person.given_names)
+        .bind(&person.patronymic)
+        .bind(&person.surname_prefix)
+        .bind(&person.surname)
+        .bind(person.birth_date.map(|date| date.earliest))
+        .bind(person.birth_date.map(|date| date.latest))
+        .bind(&self.created_persons)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+
+        let id = match existing {
+            Some(id) => {
+                self.report("persons", Some(record), &label, Action::Merged, Some(id));
+                id
+            }
+            None => {
+                if let Some(place) = birth.and_then(|birth| birth.child("PLAC")) {
+                    person.birth_place_id = self.place(place).await?;
+                }
+                if let Some(place) = death.and_then(|death| death.child("PLAC")) {
+                    person.death_place_id = self.place(place).await?;
+                }
+
+                let mut query =
+                    QueryBuilder::new(format!("INSERT INTO persons ({}) VALUES (", PERSON_COLUMNS));
+                push_person_values(&mut query, person);
+                query.push(") RETURNING id");
+                let id: i32 = query.build_query_scalar().fetch_one(&mut *self.tx).await?;
+
+                self.created_persons.push(id);
+                self.report("persons", Some(record), &label, Action::Created, Some(id));
+                id
+            }
+        };
+        self.persons.insert(xref.to_string(), id);
+
+        for citation in record.descendants_with("SOUR") {
+            let Some(source) = citation.pointer() else {
+                continue;
+            };
+            let mention = format!("{} in {}", label, source);
+            let Some(document_id) = self.documents.get(source).copied() else {
+                self.skip("mentions", None, &mention, "Source was not imported");
+                continue;
+            };
+            let role = citation
+                .child("EVEN")
+                .and_then(|event| event.value_of("ROLE"))
+                .map(parse_role)
+                .unwrap_or(PersonRole::Subject);
+
+            let created: Option<i32> = sqlx::query_scalar(
+                r#"
+                INSERT INTO document_persons (document_id, person_id, role) VALUES ($1, $2, $3)
+                ON CONFLICT ON CONSTRAINT document_persons_role_key DO NOTHING
+                RETURNING id
+            "#,
+            )
+            .bind(document_id)
+            .bind(id)
+            .bind(role)
+            .fetch_optional(&mut *self.tx)
+            .await?;
+            let action = match created {
+                Some(_) => Action::Created,
+                None => Action::Merged,
+            };
+            self.report("mentions", None, &mention, action, created);
+        }
+
+        Ok(())
+    }
+
+    async fn family(
+        &mut self,
+        record: &Node,
+        individuals: &HashMap<&str, &Node>,
+    ) -> Result<(), AppError> {
+        let label = record.xref.clone().unwrap_or_default();
+        // Sources cited for the family support both kinds of relationship,
+        // those cited for its events only the couple
+        let documents = self.cited_documents(record.children_with("SOUR"));
+        let couple_documents = self.cited_documents(record.descendants_with("SOUR"));
+
+        let mut partners = Vec::new();
+        for tag in ["HUSB", "WIFE"] {
+            let Some(pointer) = record.child(tag).and_then(Node::pointer) else {
+                continue;
+            };
+            match self.persons.get(pointer) {
+                Some(id) => partners.push(*id),
+                None => self.skip(
+                    "relationships",
+                    Some(record),
+                    &format!("{} {}", label, tag),
+                    &format!("Individual {} was not imported", pointer),
+                ),
+            }
+        }
+
+        // A family can be just the parents of its children, only a union makes them spouses
+        let united = UNION_EVENTS.iter().any(|tag| record.child(tag).is_some());
+        if let [first, second] = partners[..]
+            && (united || record.child("CHIL").is_none())
+        {
+            let label = format!("{} spouses", label);
+            self.relationship(
+                record,
+                &label,
+                RelationshipKind::Spouse,
+                (first, second),
+                &couple_documents,
+            )
+            .await?;
+        }
+
+        for child in record.children_with("CHIL").filter_map(Node::pointer) {
+            let label = format!("{} parents of {}", label, child);
+            let Some(child_id) = self.persons.get(child).copied() else {
+                let reason = format!("Individual {} was not imported", child);
+                self.skip("relationships", Some(record), &label, &reason);
+                continue;
+            };
+
+            // The birth or baptism of the child shows who the parents are
+            let mut child_documents = documents.clone();
+            if let Some(individual) = individuals.get(child) {
+                let citations = ["BIRT", "CHR", "BAPM"]
+                    .iter()
+                    .flat_map(|tag| individual.children_with(tag))
+                    .flat_map(|event| event.descendants_with("SOUR"));
+                child_documents.extend(self.cited_documents(citations));
+            }
+
+            for parent_id in &partners {
+                self.relationship(
+                    record,
+                    &label,
+                    RelationshipKind::Parent,
+                    (*parent_id, child_id),
+                    &child_documents,
+                )
+                .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /**
+     * The ids of the imported documents that `citations` point to.
+     */
+    fn cited_documents<'a>(&self, citations: impl IntoIterator<Item = &'a Node>) -> Vec<i32> {
+        citations
+            .into_iter()
+            .filter_map(Node::pointer)
+            .filter_map(|source| self.documents.get(source).copied())
+            .collect()
+    }
+
+    async fn relationship(
+        &mut self,
+        record: &Node,
+        label: &str,
+        kind: RelationshipKind,
+        (person_id, relative_id): (i32, i32),
+        documents: &[i32],
+    ) -> Result<(), AppError> {
+        if person_id == relative_id {
+            self.skip(
+                "relationships",
+                Some(record),
+                label,
+                "A person cannot be related to themselves",
+            );
+            return Ok(());
+        }
+        if documents.is_empty() {
+            self.skip(
+                "relationships",
+                Some(record),
+                label,
+                "No imported source supports the relationship",
+            );
+            return Ok(());
+        }
+        let (person_id, relative_id) = match kind {
+            RelationshipKind::Spouse if relative_id < person_id => (relative_id, person_id),
+            _ => (person_id, relative_id),
+        };
+
+        let created: Option<i32> = sqlx::query_scalar(
+            r#"
+            INSERT INTO relationships (kind, person_id, relative_id) VALUES ($1, $2, $3)
+            ON CONFLICT ON CONSTRAINT relationships_relationship_key DO NOTHING
+            RETURNING id
+        "#,
+        )
+        .bind(kind)
+        .bind(person_id)
+        .bind(relative_id)
+        .fetch_optional(&mut *self.tx)
+        .await?;
+        let (id, action) = match created {
+            Some(id) => (id, Action::Created),
+            None => {
+                let id = sqlx::query_scalar(
+                    "SELECT id FROM relationships WHERE kind = $1 AND person_id = $2 AND relative_id = $3",
+                )
+                .bind(kind)
+                .bind(person_id)
+                .bind(relative_id)
+                .fetch_one(&mut *self.tx)
+                .await?;
+                (id, Action::Merged)
+            }
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO relationship_documents (relationship_id, document_id)
+            SELECT $1, UNNEST($2::int[])
+            ON CONFLICT DO NOTHING
+        "#,
+        )
+        .bind(id)
+        .bind(documents)
+        .execute(&mut *self.tx)
+        .await?;
+
+        self.report("relationships", Some(record), label, action, Some(id));
+        Ok(())
+    }
+}
+
+/** Family events that make the partners of a family spouses */
+const UNION_EVENTS: [&str; 6] = ["MARR", "ENGA", "MARB", "MARC", "MARL", "MARS"];
+
+/**
+ * Matching a deleted item fails the whole import, like it fails a row of a CSV import.
+ */
+fn deleted(name: &str) -> AppError {
+    AppError::InvalidReference(format!("{} is deleted and must be restored first", name))
+}
+
+/**
+ * `Given names /Surname/` as its given names and surname.
+ */
+fn split_name(name: &str) -> (Option<String>, Option<String>) {
+    let non_empty = |part: &str| {
+        let part = part.trim();
+        (!part.is_empty()).then(|| part.to_string())
+    };
+
+    match name.split_once('/') {
+        Some((given_names, rest)) => {
+            let surname = rest.split('/').next().unwrap_or_default();
+            (non_empty(given_names), non_empty(surname))
+        }
+        None => (non_empty(name), None),
+    }
+}
+
+/**
+ * A latitude like `N52.38` or longitude like `W4.5`, plain numbers are accepted as well.
+ */
+fn parse_coordinate(value: &str, positive: char, negative: char) -> Option<f64> {
+    let value = value.trim();
+    let (sign, number) = match value.chars().next()?.to_ascii_uppercase() {
+        c if c == positive => (1.0, &value[1..]),
+        c if c == negative => (-1.0, &value[1..]),
+        _ => (1.0, value),
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .map(|number| sign * number)
+}
+
+/**
+ * The role of a person in a source citation, e.g. `FATH` or `WITN`.
+ */
+pub fn parse_role(value: &str) -> PersonRole {
+    match value
+        .trim()
+        .trim_matches(['(', ')'])
+        .to_uppercase()
+        .as_str()
+    {
+        "FATH" | "FATHER" => PersonRole::Father,
+        "MOTH" | "MOTHER" => PersonRole::Mother,
+        "HUSB" | "WIFE" | "SPOU" | "SPOUSE" => PersonRole::Spouse,
+        "WITN" | "WITNESS" => PersonRole::Witness,
+        "OFFICIATOR" | "CLERGY" | "OFFICIAL" => PersonRole::Official,
+        _ => PersonRole::Subject,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_names_and_coordinates() {
+        assert_eq!(
+            split_name("Jan /Pietersz/"),
+            (Some("Jan".to_string()), Some("Pietersz".to_string()))
+        );
+        assert_eq!(split_name("/Berg/"), (None, Some("Berg".to_string())));
+        assert_eq!(split_name("Cornelis"), (Some("Cornelis".to_string()), None));
+
+        assert_eq!(parse_coordinate("N52.38", 'N', 'S'), Some(52.38));
+        assert_eq!(parse_coordinate("W4.5", 'E', 'W'), Some(-4.5));
+        assert_eq!(parse_coordinate("4.64", 'E', 'W'), Some(4.64));
+        assert_eq!(parse_coordinate("E", 'E', 'W'), None);
+    }
+
+    #[test]
+    fn maps_citation_roles() {
+        assert_eq!(parse_role("FATH"), PersonRole::Father);
+        assert_eq!(parse_role("(Witness)"), PersonRole::Witness);
+        assert_eq!(parse_role("CHIL"), PersonRole::Subject);
+        assert_eq!(parse_role("OFFICIATOR"), PersonRole::Official);
+    }
+}
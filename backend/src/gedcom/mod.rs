@@ -0,0 +1,420 @@
+pub mod date;
+pub mod export;
+pub mod import;
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Version {
+    #[default]
+    V551,
+    V70,
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::V551 => write!(f, "5.5.1"),
+            Self::V70 => write!(f, "7.0"),
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "5.5.1" | "5.5" => Ok(Self::V551),
+            // 7.0.x releases only differ in documentation
+            version if version == "7" || version.starts_with("7.0") => Ok(Self::V70),
+            version => Err(format!(
+                "Unsupported GEDCOM version {}, supported are 5.5.1 and 7.0",
+                version
+            )),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Version {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/**
+ * A line of a GEDCOM file with the lines nested below it.
+ * Continuation lines (`CONT` and `CONC`) are folded into `value`.
+ */
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Node {
+    pub xref: Option<String>,
+    pub tag: String,
+    pub value: Option<String>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    pub fn child(&self, tag: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.tag == tag)
+    }
+
+    pub fn children_with<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a Node> {
+        self.children.iter().filter(move |child| child.tag == tag)
+    }
+
+    /**
+     * The non-empty value of the first child with `tag`.
+     */
+    pub fn value_of(&self, tag: &str) -> Option<&str> {
+        self.child(tag)?
+            .value
+            .as_deref()
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    }
+
+    /**
+     * The value if it is a pointer to another record, e.g. `@I1@`.
+     */
+    pub fn pointer(&self) -> Option<&str> {
+        self.value
+            .as_deref()
+            .filter(|value| value.len() > 2 && value.starts_with('@') && value.ends_with('@'))
+    }
+
+    /**
+     * All nodes below this one with `tag`, at any depth, in the order of the file.
+     * The tree is walked with a stack of its open levels rather than by recursion.
+     */
+    pub fn descendants_with<'a>(&'a self, tag: &'a str) -> Vec<&'a Node> {
+        let mut found = Vec::new();
+        let mut open = vec![self.children.iter()];
+        while let Some(children) = open.last_mut() {
+            match children.next() {
+                Some(child) => {
+                    if child.tag == tag {
+                        found.push(child);
+                    }
+                    open.push(child.children.iter());
+                }
+                None => {
+                    open.pop();
+                }
+            }
+        }
+        found
+    }
+}
+
+/** GEDCOM levels go up to 99, deeper files are rejected rather than nested without bound */
+const MAX_LEVEL: usize = 99;
+
+/**
+ * Parse the records of a GEDCOM file.
+ * Lines are `level [@xref@] TAG [value]`, each level at most one deeper than the line before.
+ */
+pub fn parse(text: &str) -> Result<Vec<Node>, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut records: Vec<Node> = Vec::new();
+    // The open nodes from the current record down to the last line
+    let mut open: Vec<Node> = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim_start();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let invalid = |reason: &str| format!("Line {}: {}", number + 1, reason);
+
+        let (level, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let level: usize = level
+            .parse()
+            .map_err(|_| invalid("expected a level number"))?;
+        if level > open.len() {
+            return Err(invalid(
+                "level is more than one deeper than the line before",
+            ));
+        }
+        if level > MAX_LEVEL {
+            return Err(invalid("level is deeper than 99"));
+        }
+
+        let (xref, rest) = match rest.strip_prefix('@') {
+            Some(_) => {
+                let (xref, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+                (Some(xref.to_string()), rest)
+            }
+            None => (None, rest),
+        };
+        let (tag, value) = match rest.split_once(' ') {
+            Some((tag, value)) => (tag, Some(value)),
+            None => (rest.trim_end(), None),
+        };
+        if tag.is_empty() {
+            return Err(invalid("expected a tag"));
+        }
+        let value = value.map(unescape);
+
+        while open.len() > level {
+            close_node(&mut open, &mut records);
+        }
+
+        match tag {
+            "CONT" | "CONC" if level > 0 => {
+                let parent = open
+                    .last_mut()
+                    .ok_or_else(|| invalid("continuation without a line to continue"))?;
+                let text = parent.value.get_or_insert_with(String::new);
+                if tag == "CONT" {
+                    text.push('\n');
+                }
+                text.push_str(value.as_deref().unwrap_or_default());
+                // A continuation is not a node, later lines nest below the continued line
+                open.push(Node {
+                    tag: tag.to_string(),
+                    ..Node::default()
+                });
+            }
+            _ => open.push(Node {
+                xref,
+                tag: tag.to_string(),
+                value,
+                children: Vec::new(),
+            }),
+        }
+    }
+
+    while !open.is_empty() {
+        close_node(&mut open, &mut records);
+    }
+
+    Ok(records)
+}
+
+fn close_node(open: &mut Vec<Node>, records: &mut Vec<Node>) {
+    let Some(node) = open.pop() else {
+        return;
+    };
+    if matches!(node.tag.as_str(), "CONT" | "CONC") {
+        return;
+    }
+    match open.last_mut() {
+        Some(parent) => parent.children.push(node),
+        None => records.push(node),
+    }
+}
+
+/**
+ * `@@` stands for a literal `@` in values that are not pointers.
+ */
+fn unescape(value: &str) -> String {
+    if value.len() > 2 && value.starts_with('@') && value.ends_with('@') && !value.contains(' ') {
+        return value.to_string();
+    }
+    value.replace("@@", "@")
+}
+
+/**
+ * Builds a GEDCOM file line by line.
+ */
+pub struct Writer {
+    version: Version,
+    out: String,
+}
+
+/** GEDCOM 5.5.1 limits lines to 255 characters, longer values continue on `CONC` lines */
+const MAX_VALUE_LENGTH: usize = 200;
+
+impl Writer {
+    pub fn new(version: Version) -> Self {
+        let mut writer = Self {
+            version,
+            out: String::new(),
+        };
+        writer.line(0, None, "HEAD", None);
+        match version {
+            Version::V551 => {
+                writer.line(1, None, "SOUR", Some("GOLIJATH"));
+                writer.line(2, None, "NAME", Some("Golijath"));
+                writer.line(1, None, "GEDC", None);
+                writer.line(2, None, "VERS", Some("5.5.1"));
+                writer.line(2, None, "FORM", Some("LINEAGE-LINKED"));
+                writer.line(1, None, "CHAR", Some("UTF-8"));
+            }
+            Version::V70 => {
+                writer.line(1, None, "GEDC", None);
+                writer.line(2, None, "VERS", Some("7.0"));
+                writer.line(1, None, "SOUR", Some("GOLIJATH"));
+                writer.line(2, None, "NAME", Some("Golijath"));
+            }
+        }
+        writer
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /**
+     * Write a line, splitting a multi-line value over `CONT` lines.
+     */
+    pub fn line(&mut self, level: usize, xref: Option<&str>, tag: &str, value: Option<&str>) {
+        let mut lines = value.map(|value| value.split('\n'));
+        let first = lines.as_mut().and_then(|lines| lines.next());
+        self.physical_line(level, xref, tag, first);
+        for line in lines.into_iter().flatten() {
+            self.physical_line(level + 1, None, "CONT", Some(line));
+        }
+    }
+
+    /**
+     * Write a line with a pointer to another record.
+     */
+    pub fn pointer(&mut self, level: usize, tag: &str, xref: &str) {
+        self.push(level, None, tag, Some(xref));
+    }
+
+    pub fn finish(mut self) -> String {
+        self.line(0, None, "TRLR", None);
+        self.out
+    }
+
+    fn physical_line(&mut self, level: usize, xref: Option<&str>, tag: &str, value: Option<&str>) {
+        let Some(value) = value.map(|value| self.escape(value)) else {
+            self.push(level, xref, tag, None);
+            return;
+        };
+        if self.version == Version::V70 || value.chars().count() <= MAX_VALUE_LENGTH {
+            self.push(level, xref, tag, Some(&value));
+            return;
+        }
+
+        let chars: Vec<char> = value.chars().collect();
+        let mut chunks = chars.chunks(MAX_VALUE_LENGTH).map(String::from_iter);
+        let first = chunks.next().unwrap_or_default();
+        self.push(level, xref, tag, Some(&first));
+        for chunk in chunks {
+            self.push(level + 1, None, "CONC", Some(&chunk));
+        }
+    }
+
+    fn escape(&self, value: &str) -> String {
+        match self.version {
+            Version::V551 => value.replace('@', "@@"),
+            // 7.0 only escapes a leading @
+            Version::V70 if value.starts_with('@') => format!("@{}", value),
+            Version::V70 => value.to_string(),
+        }
+    }
+
+    fn push(&mut self, level: usize, xref: Option<&str>, tag: &str, value: Option<&str>) {
+        self.out.push_str(&level.to_string());
+        if let Some(xref) = xref {
+            self.out.push(' ');
+            self.out.push_str(xref);
+        }
+        self.out.push(' ');
+        self.out.push_str(tag);
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            self.out.push(' ');
+            self.out.push_str(value);
+        }
+        self.out.push_str("\r\n");
+    }
+}
+
+/**
+ * The version a file declares in `HEAD.GEDC.VERS`, 5.5.1 when it declares none.
+ */
+pub fn declared_version(records: &[Node]) -> Result<Version, String> {
+    records
+        .iter()
+        .find(|record| record.tag == "HEAD")
+        .and_then(|head| head.child("GEDC"))
+        .and_then(|gedc| gedc.value_of("VERS"))
+        .map(str::parse)
+        .unwrap_or(Ok(Version::V551))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_records_and_continuations() {
+        let text = "\u{feff}0 HEAD\r\n1 GEDC\r\n2 VERS 7.0\r\n0 @I1@ INDI\n1 NAME Jan /Pietersz/\n1 NOTE Born in\n2 CONC  Haarlem\n2 CONT in the orphanage of @@ Haarlem\n1 SEX M\n0 TRLR\n";
+        let records = parse(text).unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(declared_version(&records), Ok(Version::V70));
+        let person = &records[1];
+        assert_eq!(person.xref.as_deref(), Some("@I1@"));
+        assert_eq!(person.value_of("NAME"), Some("Jan /Pietersz/"));
+        assert_eq!(
+            person.value_of("NOTE"),
+            Some("Born in Haarlem\nin the orphanage of @ Haarlem")
+        );
+        assert_eq!(person.value_of("SEX"), Some("M"));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert_eq!(
+            parse("0 HEAD\n2 VERS 5.5.1"),
+            Err("Line 2: level is more than one deeper than the line before".to_string())
+        );
+        assert!(parse("HEAD").is_err());
+
+        let deep: String = (0..=100).map(|level| format!("{} NOTE\n", level)).collect();
+        assert_eq!(
+            parse(&deep),
+            Err("Line 101: level is deeper than 99".to_string())
+        );
+    }
+
+    #[test]
+    fn finds_descendants_in_file_order() {
+        let text =
+            "0 @I1@ INDI\n1 SOUR @S1@\n1 BIRT\n2 SOUR @S2@\n3 NOTE\n4 SOUR @S3@\n1 SOUR @S4@\n";
+        let records = parse(text).unwrap();
+
+        let sources: Vec<&str> = records[0]
+            .descendants_with("SOUR")
+            .iter()
+            .filter_map(|node| node.pointer())
+            .collect();
+        assert_eq!(sources, ["@S1@", "@S2@", "@S3@", "@S4@"]);
+    }
+
+    #[test]
+    fn writer_continues_long_and_multi_line_values() {
+        let mut writer = Writer::new(Version::V551);
+        writer.line(0, Some("@S1@"), "SOUR", None);
+        writer.line(
+            1,
+            None,
+            "NOTE",
+            Some(&format!("{}\nmail@example.org", "a".repeat(250))),
+        );
+        let text = writer.finish();
+
+        assert!(text.contains(&format!(
+            "1 NOTE {}\r\n2 CONC {}\r\n",
+            "a".repeat(200),
+            "a".repeat(50)
+        )));
+        assert!(text.contains("2 CONT mail@@example.org\r\n"));
+        assert!(text.ends_with("0 TRLR\r\n"));
+
+        let records = parse(&text).unwrap();
+        assert_eq!(
+            records[1].value_of("NOTE"),
+            Some(format!("{}\nmail@example.org", "a".repeat(250)).as_str())
+        );
+    }
+}
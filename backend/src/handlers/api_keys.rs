@@ -0,0 +1,218 @@
+use crate::auth::AuthUser;
+use crate::auth::api_keys::{API_KEY_COLUMNS, generate};
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::extractors::{AppJson, AppPath};
+use crate::models::api_keys::ApiKey;
+use crate::schemas::api_keys::CreateApiKey;
+
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use chrono::{Duration, Utc};
+use serde_json::json;
+use std::sync::Arc;
+
+const TABLE: &str = "api_keys";
+
+/** Lifetime of a key created without `expires_at` */
+const DEFAULT_LIFETIME_DAYS: i64 = 90;
+
+/**
+ * List API Keys Handler
+ * All keys, newest first, including expired and revoked ones.
+ */
+pub async fn api_keys_list_handler(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let query = format!(
+        "SELECT {} FROM {} ORDER BY created_at DESC, id DESC",
+        API_KEY_COLUMNS, TABLE
+    );
+    let items = sqlx::query_as::<_, ApiKey>(&query)
+        .fetch_all(data.pool())
+        .await?;
+
+    let json_response = json!({
+        "status": "success",
+        "results": items.len(),
+        "items": items,
+    });
+    Ok(Json(json_response))
+}
+
+/**
+ * Fetch a single API Key
+ */
+pub async fn get_api_key_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let query = format!("SELECT {} FROM {} WHERE id = $1", API_KEY_COLUMNS, TABLE);
+    let item = sqlx::query_as::<_, ApiKey>(&query)
+        .bind(id)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Create API Key Handler
+ * The key itself is only part of this response, the database keeps its hash.
+ */
+pub async fn create_api_key_handler(
+    AuthUser(user): AuthUser,
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<CreateApiKey>,
+) -> Result<impl IntoResponse, AppError> {
+    body.validate().map_err(AppError::Validation)?;
+
+    let (key, prefix, key_hash) = generate();
+    let mut scopes: Vec<String> = body.scopes.iter().map(ToString::to_string).collect();
+    scopes.sort();
+    scopes.dedup();
+    let expires_at = body
+        .expires_at
+        .unwrap_or_else(|| Utc::now() + Duration::days(DEFAULT_LIFETIME_DAYS));
+
+    let query = format!(
+        r#"
+        INSERT INTO {} (name, prefix, key_hash, scopes, created_by, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING {}
+    "#,
+        TABLE, API_KEY_COLUMNS
+    );
+    let item = sqlx::query_as::<_, ApiKey>(&query)
+        .bind(body.name.trim())
+        .bind(prefix)
+        .bind(key_hash)
+        .bind(scopes)
+        .bind(user.user.id)
+        .bind(expires_at)
+        .fetch_one(data.pool())
+        .await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item,
+        "key": key
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
+}
+
+/**
+ * Revoke API Key Handler
+ * The key stops working at once. Revoking a revoked key keeps its first revocation time.
+ */
+pub async fn revoke_api_key_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let query = format!(
+        "UPDATE {} SET revoked_at = COALESCE(revoked_at, now()) WHERE id = $1 RETURNING {}",
+        TABLE, API_KEY_COLUMNS
+    );
+    let item = sqlx::query_as::<_, ApiKey>(&query)
+        .bind(id)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::test_utils::{app, insert_named, send, send_as};
+
+    #[sqlx::test]
+    async fn api_keys_act_within_their_scopes(pool: PgPool) {
+        let app = app(pool.clone());
+        let place = insert_named(&pool, "places", "Leiden").await;
+
+        let new_key = json!({"name": "Nightly places import", "scopes": ["places:create", "places:read", "places:create"]});
+        let (status, body) =
+            send(&app, Method::POST, "/api/v1/admin/api-keys", Some(new_key)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let key = body["data"]["key"].as_str().unwrap().to_string();
+        let item = &body["data"]["item"];
+        assert!(key.starts_with("glk_"));
+        assert!(key.starts_with(item["prefix"].as_str().unwrap()));
+        assert!(item.get("key_hash").is_none());
+        assert_eq!(item["scopes"], json!(["places:create", "places:read"]));
+        assert!(item["last_used_at"].is_null());
+        let id = item["id"].as_i64().unwrap();
+
+        let place_body = json!({"name": "Delft"});
+        let (status, _) =
+            send_as(&app, &key, Method::POST, "/api/v1/places", Some(place_body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let uri = format!("/api/v1/places/{}", place);
+        let (status, body) = send_as(&app, &key, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(
+            body["message"],
+            "The scopes of this API key do not include places:delete"
+        );
+
+        let (status, _) = send_as(&app, &key, Method::GET, "/api/v1/admin/api-keys", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send_as(&app, &key, Method::GET, "/api/v1/auth/me", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let uri = format!("/api/v1/admin/api-keys/{}", id);
+        let (_, body) = send(&app, Method::GET, &uri, None).await;
+        assert!(!body["data"]["item"]["last_used_at"].is_null());
+
+        let (status, body) = send(&app, Method::POST, &format!("{}/revoke", uri), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!body["data"]["item"]["revoked_at"].is_null());
+        let place_body = json!({"name": "Gouda"});
+        let (status, body) =
+            send_as(&app, &key, Method::POST, "/api/v1/places", Some(place_body)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["message"], "Invalid, expired or revoked API key");
+    }
+
+    #[sqlx::test]
+    async fn api_keys_expire_and_need_valid_scopes(pool: PgPool) {
+        let app = app(pool.clone());
+
+        let new_key = json!({"name": "Export", "scopes": ["documents:export", "documents:read"]});
+        let (_, body) = send(&app, Method::POST, "/api/v1/admin/api-keys", Some(new_key)).await;
+        let key = body["data"]["key"].as_str().unwrap().to_string();
+
+        let uri = "/api/v1/documents?format=csv";
+        let (status, _) = send_as(&app, &key, Method::GET, uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        sqlx::query("UPDATE api_keys SET expires_at = now() - interval '1 second'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (status, _) = send_as(&app, &key, Method::GET, uri, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        for scopes in [json!([]), json!(["documents:fly"]), json!(["users:read"])] {
+            let new_key = json!({"name": "Wrong", "scopes": scopes});
+            let (status, _) =
+                send(&app, Method::POST, "/api/v1/admin/api-keys", Some(new_key)).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        }
+
+        let new_key = json!({"name": "Old", "scopes": ["documents:read"], "expires_at": "2001-01-01T00:00:00Z"});
+        let (status, _) = send(&app, Method::POST, "/api/v1/admin/api-keys", Some(new_key)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -1,11 +1,19 @@
+use crate::audit;
+use crate::auth::Actor;
 use crate::db::AppState;
+use crate::errors::AppError;
+use crate::export::{ExportFormat, export_list};
+use crate::extractors::{AppJson, AppPath, AppQuery};
+use crate::handlers::common::{delete_referenced_item, restore_item};
 use crate::models::archives::Archive;
+use crate::pagination::{Listing, SortField, fetch_page};
 use crate::schemas::archives::{CreateArchive, UpdateArchive};
+use crate::schemas::{DeleteParams, DeletedParams, ExportParams, PageParams};
 
 use axum::{
     Json,
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{OriginalUri, State},
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
 };
 use serde_json::json;
@@ -13,65 +21,70 @@ use std::sync::Arc;
 
 const TABLE: &str = "archives";
 
+const LISTING: Listing = Listing {
+    table: TABLE,
+    sort_fields: &[
+        SortField {
+            name: "name",
+            column: "name",
+            sql_type: "text",
+        },
+        SortField {
+            name: "id",
+            column: "id",
+            sql_type: "integer",
+        },
+    ],
+    default_sort: "name",
+};
+
 /**
  * List Items Handler
- * This handler fetches a list of all items from postgres
+ * This handler fetches a page of items from postgres, see `PageParams` and `DeletedParams`,
+ * or exports all of them as CSV, XLSX or JSON, see `ExportParams`
  */
 pub async fn items_list_handler(
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    AppQuery(params): AppQuery<PageParams>,
+    AppQuery(export): AppQuery<ExportParams>,
+    AppQuery(deleted): AppQuery<DeletedParams>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("SELECT * FROM {} ORDER BY name", TABLE);
-    let query_result = sqlx::query_as::<_, Archive>(&query)
-        .fetch_all(data.pool())
-        .await;
-
-    if query_result.is_err() {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": "🔥 Something bad happened while fetching all items",
-        });
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)));
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(format) = ExportFormat::negotiate(export.format, &headers) {
+        let sort = params.sort.as_deref();
+        return export_list::<Archive>(data.pool(), &LISTING, sort, &deleted, &[], format).await;
     }
 
-    let items = query_result.unwrap();
+    let page = fetch_page::<Archive>(data.pool(), &LISTING, &params, &deleted).await?;
 
-    let json_response = serde_json::json!({
-        "status": "success",
-        "results": items.len(),
-        "items": items
-    });
-    Ok(Json(json_response))
+    Ok(page.into_list_response(&uri))
 }
 
 /**
  * Fetch a single Item
+ * A deleted item is only found with `?include_deleted=true`
  */
 pub async fn get_item_handler(
-    Path(id): Path<i32>,
+    AppPath(id): AppPath<i32>,
+    AppQuery(deleted): AppQuery<DeletedParams>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("SELECT * FROM {} WHERE id = $1", TABLE);
-    let query_result = sqlx::query_as::<_, Archive>(&query)
+) -> Result<impl IntoResponse, AppError> {
+    let query = format!(
+        "SELECT * FROM {} WHERE id = $1 AND ($2 OR deleted_at IS NULL)",
+        TABLE
+    );
+    let item = sqlx::query_as::<_, Archive>(&query)
         .bind(id)
-        .fetch_one(data.pool())
-        .await;
-
-    match query_result {
-        Ok(item) => {
-            let item_response = serde_json::json!({"status": "success","data": serde_json::json!({
-                "item": item
-            })});
+        .bind(deleted.include_deleted)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
 
-            return Ok(Json(item_response));
-        }
-        Err(_) => {
-            let error_response = serde_json::json!({
-                "status": "fail",
-                "message": format!("Item with ID: {} not found", id)
-            });
-            return Err((StatusCode::NOT_FOUND, Json(error_response)));
-        }
-    }
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
 }
 
 /**
@@ -79,38 +92,25 @@ pub async fn get_item_handler(
  * This handler adds a new item to postgres
  */
 pub async fn create_item_handler(
+    actor: Actor,
     State(data): State<Arc<AppState>>,
-    Json(body): Json<CreateArchive>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    AppJson(body): AppJson<CreateArchive>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
+
     let query = format!("INSERT INTO {} (name) VALUES ($1) RETURNING *", TABLE);
-    let query_result = sqlx::query_as::<_, Archive>(&query)
+    let item = sqlx::query_as::<_, Archive>(&query)
         .bind(body.name.to_string())
-        .fetch_one(data.pool())
-        .await;
-    match query_result {
-        Ok(item) => {
-            let item_response = json!({"status": "success","data": json!({
-                "item": item
-            })});
+        .fetch_one(&mut *tx)
+        .await?;
+    audit::record(&mut tx, &actor, TABLE, item.id, None, Some(json!(item))).await?;
 
-            return Ok((StatusCode::CREATED, Json(item_response)));
-        }
-        Err(e) => {
-            if e.to_string()
-                .contains("duplicate key value violates unique constraint")
-            {
-                let error_response = serde_json::json!({
-                    "status": "fail",
-                    "message": "Item with that name already exists",
-                });
-                return Err((StatusCode::CONFLICT, Json(error_response)));
-            }
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"status": "error","message": format!("{:?}", e)})),
-            ));
-        }
-    }
+    tx.commit().await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
 }
 
 /**
@@ -118,72 +118,423 @@ pub async fn create_item_handler(
  * This handler handles edits of existing items
  */
 pub async fn edit_item_handler(
-    Path(id): Path<i32>,
+    AppPath(id): AppPath<i32>,
+    actor: Actor,
     State(data): State<Arc<AppState>>,
-    Json(body): Json<UpdateArchive>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("SELECT * FROM {} WHERE id = $1", TABLE);
-    let query_result = sqlx::query_as::<_, Archive>(&query)
-        .bind(id)
-        .fetch_one(data.pool())
-        .await;
-
-    if query_result.is_err() {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": format!("🔥 Item with ID: {} not found", id)
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
-    }
+    AppJson(body): AppJson<UpdateArchive>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
 
-    let item = query_result.unwrap();
+    let query = format!(
+        "SELECT * FROM {} WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        TABLE
+    );
+    let before = sqlx::query_as::<_, Archive>(&query)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
 
-    let query = format!("UPDATE {} SET name = $1 WHERE id = $2 RETURNING *", TABLE);
-    let query_result = sqlx::query_as::<_, Archive>(&query)
-        .bind(body.name.to_owned().unwrap_or(item.name))
+    let query = format!(
+        "UPDATE {} SET name = COALESCE($1, name) WHERE id = $2 RETURNING *",
+        TABLE
+    );
+    let item = sqlx::query_as::<_, Archive>(&query)
+        .bind(body.name)
         .bind(id)
-        .fetch_one(data.pool())
-        .await;
+        .fetch_one(&mut *tx)
+        .await?;
+    audit::record(
+        &mut tx,
+        &actor,
+        TABLE,
+        id,
+        Some(json!(before)),
+        Some(json!(item)),
+    )
+    .await?;
 
-    match query_result {
-        Ok(item) => {
-            let item_response = serde_json::json!({"status": "success","data": serde_json::json!({
-                "item": item
-            })});
+    tx.commit().await?;
 
-            return Ok(Json(item_response));
-        }
-        Err(err) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"status": "error","message": format!("{:?}", err)})),
-            ));
-        }
-    }
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
 }
 
 /**
  * Delete Item Handler
+ * The item is marked as deleted, `?purge=true` removes it for good.
+ * Documents that refer to the item block the delete, unless `?reassign_to={id}` is given
  */
 pub async fn delete_item_handler(
-    Path(id): Path<i32>,
+    AppPath(id): AppPath<i32>,
+    AppQuery(params): AppQuery<DeleteParams>,
+    actor: Actor,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    delete_referenced_item(data.pool(), &actor, TABLE, "archive_id", &[], id, &params).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/**
+ * Restore Item Handler
+ * This handler undoes the delete of an item that was not purged
+ */
+pub async fn restore_item_handler(
+    AppPath(id): AppPath<i32>,
+    actor: Actor,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("DELETE FROM {} WHERE id = $1", TABLE);
-    let rows_affected = sqlx::query(&query)
-        .bind(id) // $1
-        .execute(data.pool())
+) -> Result<impl IntoResponse, AppError> {
+    let item = restore_item::<Archive>(data.pool(), &actor, TABLE, &[], id).await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{
+            Method, Request, StatusCode,
+            header::{ACCEPT, CONTENT_TYPE},
+        },
+    };
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::models::users::Role;
+    use crate::test_utils::{
+        app, app_state, insert_document, insert_named, send, send_request, signed_in_as, test_user,
+    };
+
+    /**
+     * Two archives, the first one referenced by two documents.
+     */
+    async fn seed(pool: &PgPool) -> (i32, i32) {
+        let archive_id = insert_named(pool, "archives", "Noord-Hollands Archief").await;
+        let other_id = insert_named(pool, "archives", "Stadsarchief Amsterdam").await;
+        let institute_id = insert_named(pool, "institutes", "Burgerweeshuis").await;
+        let place_id = insert_named(pool, "places", "Haarlem").await;
+        for inventory_number in ["INV-1", "INV-2"] {
+            insert_document(pool, inventory_number, archive_id, institute_id, place_id).await;
+        }
+
+        (archive_id, other_id)
+    }
+
+    async fn documents_in(pool: &PgPool, archive_id: i32) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM documents WHERE archive_id = $1")
+            .bind(archive_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn create_duplicate_name_returns_409(pool: PgPool) {
+        let app = app(pool);
+        let body = json!({ "name": "Noord-Hollands Archief" });
+        send(&app, Method::POST, "/api/v1/archives", Some(body.clone())).await;
+
+        let (status, body) = send(&app, Method::POST, "/api/v1/archives", Some(body)).await;
+
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(
+            body,
+            json!({
+                "status": "fail",
+                "code": "duplicate",
+                "message": "Item with that name already exists",
+            })
+        );
+    }
+
+    #[sqlx::test]
+    async fn unknown_item_returns_404(pool: PgPool) {
+        let app = app(pool);
+
+        for method in [Method::GET, Method::DELETE] {
+            let (status, body) = send(&app, method, "/api/v1/archives/4242", None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(body["code"], "not_found");
+        }
+
+        let (status, body) = send(
+            &app,
+            Method::PATCH,
+            "/api/v1/archives/4242",
+            Some(json!({ "name": "Nothing" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], "not_found");
+    }
+
+    #[sqlx::test]
+    async fn malformed_requests_return_error_body(pool: PgPool) {
+        let app = app(pool);
+
+        let (status, body) = send(&app, Method::GET, "/api/v1/archives/abc", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_request");
+
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            "/api/v1/archives",
+            Some(json!({ "title": 1 })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "invalid_request");
+    }
+
+    #[sqlx::test]
+    async fn delete_hides_the_item_until_it_is_restored(pool: PgPool) {
+        let (_, other_id) = seed(&pool).await;
+        let admin_id = test_user(&pool, Role::Admin).await;
+        let app = app(pool);
+        let uri = format!("/api/v1/archives/{}", other_id);
+
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (_, body) = send(&app, Method::GET, "/api/v1/archives", None).await;
+        assert_eq!(body["total"], 1);
+
+        let (_, body) = send(
+            &app,
+            Method::GET,
+            "/api/v1/archives?include_deleted=true",
+            None,
+        )
+        .await;
+        assert_eq!(body["total"], 2);
+        let (status, body) = send(
+            &app,
+            Method::GET,
+            &format!("{}?include_deleted=true", uri),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["item"]["deleted_by"], admin_id);
+        assert!(body["data"]["item"]["deleted_at"].is_string());
+
+        let restore_uri = format!("{}/restore", uri);
+        let (status, body) = send(&app, Method::POST, &restore_uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["data"]["item"]["deleted_at"].is_null());
+        assert!(body["data"]["item"]["deleted_by"].is_null());
+        let (status, _) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn only_admins_purge_deleted_items(pool: PgPool) {
+        let (archive_id, other_id) = seed(&pool).await;
+        let state = app_state(pool.clone());
+        let editor = signed_in_as(state.clone(), Role::Editor);
+        let volunteer = signed_in_as(state, Role::Volunteer);
+        let app = app(pool.clone());
+        let uri = format!("/api/v1/archives/{}", other_id);
+
+        let (status, _) = send(&volunteer, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&editor, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let restore_uri = format!("{}/restore", uri);
+        let (status, _) = send(&volunteer, Method::POST, &restore_uri, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Documents cannot be moved to a deleted archive
+        let reassign_uri = format!("/api/v1/archives/{}?reassign_to={}", archive_id, other_id);
+        let (status, _) = send(&app, Method::DELETE, &reassign_uri, None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let purge_uri = format!("{}?purge=true", uri);
+        let (status, body) = send(&editor, Method::DELETE, &purge_uri, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["message"], "Your roles do not allow archives:purge");
+        let encoded_uri = format!("{}?purg%65=true", uri);
+        let (status, _) = send(&editor, Method::DELETE, &encoded_uri, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let (status, _) = send(&app, Method::DELETE, &purge_uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, _) = send(
+            &app,
+            Method::GET,
+            &format!("{}?include_deleted=true", uri),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let actions: Vec<String> = sqlx::query_scalar(
+            "SELECT action::text FROM audit_log WHERE resource_id = $1 ORDER BY id",
+        )
+        .bind(other_id)
+        .fetch_all(&pool)
         .await
-        .unwrap()
-        .rows_affected();
-
-    if rows_affected == 0 {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": format!("Item with ID: {} not found", id)
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+        .unwrap();
+        assert_eq!(actions, ["delete", "purge"]);
     }
 
-    Ok(StatusCode::NO_CONTENT)
+    #[sqlx::test]
+    async fn delete_referenced_item_returns_409_with_document_count(pool: PgPool) {
+        let (archive_id, _) = seed(&pool).await;
+        let uri = format!("/api/v1/archives/{}", archive_id);
+
+        let (status, body) = send(&app(pool.clone()), Method::DELETE, &uri, None).await;
+
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "still_referenced");
+        assert_eq!(body["documents"], 2);
+        assert_eq!(documents_in(&pool, archive_id).await, 2);
+    }
+
+    #[sqlx::test]
+    async fn delete_with_reassign_moves_documents(pool: PgPool) {
+        let (archive_id, other_id) = seed(&pool).await;
+        let app = app(pool.clone());
+        let uri = format!("/api/v1/archives/{}?reassign_to={}", archive_id, other_id);
+
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(documents_in(&pool, other_id).await, 2);
+        let uri = format!("/api/v1/archives/{}", archive_id);
+        let (status, _) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn delete_with_invalid_reassign_target_changes_nothing(pool: PgPool) {
+        let (archive_id, _) = seed(&pool).await;
+        let app = app(pool.clone());
+
+        let uri = format!("/api/v1/archives/{}?reassign_to=4242", archive_id);
+        let (status, body) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "invalid_reference");
+
+        let uri = format!("/api/v1/archives/{}?reassign_to={}", archive_id, archive_id);
+        let (status, body) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "validation_failed");
+
+        assert_eq!(documents_in(&pool, archive_id).await, 2);
+    }
+
+    #[sqlx::test]
+    async fn list_walks_pages_with_cursor(pool: PgPool) {
+        for name in ["E", "A", "D", "B", "C"] {
+            insert_named(&pool, "archives", name).await;
+        }
+        let app = app(pool);
+
+        let mut names = Vec::new();
+        let mut uri = "/api/v1/archives?sort=-name&limit=2".to_string();
+        loop {
+            let (status, body) = send(&app, Method::GET, &uri, None).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["status"], "success");
+            assert_eq!(body["total"], 5);
+            assert_eq!(body["results"], body["items"].as_array().unwrap().len());
+            for item in body["items"].as_array().unwrap() {
+                names.push(item["name"].as_str().unwrap().to_string());
+            }
+
+            match body["next_cursor"].as_str() {
+                Some(cursor) => {
+                    uri = format!("/api/v1/archives?sort=-name&limit=2&cursor={}", cursor)
+                }
+                None => break,
+            }
+        }
+
+        assert_eq!(names, vec!["E", "D", "C", "B", "A"]);
+    }
+
+    #[sqlx::test]
+    async fn list_supports_offset_and_link_header(pool: PgPool) {
+        for name in ["A", "B", "C", "D", "E"] {
+            insert_named(&pool, "archives", name).await;
+        }
+        let request = axum::http::Request::builder()
+            .uri("/api/v1/archives?limit=2&offset=2")
+            .body(axum::body::Body::empty())
+            .unwrap();
+
+        let response = tower::ServiceExt::oneshot(app(pool), request)
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let link = response.headers()["link"].to_str().unwrap().to_string();
+        assert!(link.contains(r#"</api/v1/archives?limit=2&offset=0>; rel="prev""#));
+        assert!(link.contains(r#"</api/v1/archives?limit=2&offset=4>; rel="next""#));
+        assert!(link.contains(r#"</api/v1/archives?limit=2&offset=4>; rel="last""#));
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+        let names: Vec<&str> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["C", "D"]);
+        assert_eq!(body["total"], 5);
+    }
+
+    #[sqlx::test]
+    async fn list_rejects_invalid_pagination(pool: PgPool) {
+        let app = app(pool);
+
+        for uri in [
+            "/api/v1/archives?sort=secret",
+            "/api/v1/archives?limit=0",
+            "/api/v1/archives?offset=-1",
+            "/api/v1/archives?offset=1&cursor=abc",
+            "/api/v1/archives?cursor=abc",
+        ] {
+            let (status, body) = send(&app, Method::GET, uri, None).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", uri);
+            assert_eq!(body["code"], "validation_failed");
+        }
+    }
+
+    #[sqlx::test]
+    async fn list_exports_xlsx(pool: PgPool) {
+        seed(&pool).await;
+        let app = app(pool);
+
+        let request = Request::builder()
+            .uri("/api/v1/archives")
+            .header(
+                ACCEPT,
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            )
+            .body(Body::empty())
+            .unwrap();
+        let (status, headers, body) = send_request(&app, request).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            headers[CONTENT_TYPE],
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+        );
+        // XLSX files are zip archives
+        assert!(body.starts_with(b"PK"));
+    }
 }
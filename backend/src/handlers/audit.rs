@@ -0,0 +1,201 @@
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::extractors::AppQuery;
+use crate::models::audit::AuditEntry;
+use crate::pagination::{Filter, Listing, SortField, fetch_page};
+use crate::schemas::PageParams;
+use crate::schemas::audit::AuditFilters;
+
+use axum::{
+    extract::{OriginalUri, State},
+    response::IntoResponse,
+};
+use sqlx::{Postgres, QueryBuilder};
+use std::sync::Arc;
+
+const LISTING: Listing = Listing {
+    table: "audit_log",
+    sort_fields: &[
+        SortField {
+            name: "created_at",
+            column: "created_at",
+            sql_type: "timestamptz",
+        },
+        SortField {
+            name: "id",
+            column: "id",
+            sql_type: "bigint",
+        },
+    ],
+    default_sort: "-created_at,-id",
+};
+
+impl Filter for AuditFilters {
+    fn push_conditions(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        for (column, ids) in [
+            ("actor_user_id", &self.actor_user_id),
+            ("actor_api_key_id", &self.actor_api_key_id),
+            ("resource_id", &self.resource_id),
+        ] {
+            if !ids.is_empty() {
+                query.push(format!(" AND {} = ANY(", column));
+                query.push_bind(ids.clone());
+                query.push(")");
+            }
+        }
+        if !self.resource_type.is_empty() {
+            query
+                .push(" AND resource_type = ANY(")
+                .push_bind(self.resource_type.clone())
+                .push(")");
+        }
+        if !self.action.is_empty() {
+            query
+                .push(" AND action = ANY(")
+                .push_bind(self.action.clone())
+                .push(")");
+        }
+        if let Some(from) = self.from {
+            query.push(" AND created_at >= ").push_bind(from);
+        }
+        if let Some(to) = self.to {
+            query.push(" AND created_at < ").push_bind(to);
+        }
+    }
+}
+
+/**
+ * Audit Log Handler
+ * This handler fetches a page of the changes to archives, documents, institutes and places,
+ * newest first, see `PageParams` and `AuditFilters`
+ */
+pub async fn audit_list_handler(
+    OriginalUri(uri): OriginalUri,
+    AppQuery(params): AppQuery<PageParams>,
+    AppQuery(filters): AppQuery<AuditFilters>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let page = fetch_page::<AuditEntry>(data.pool(), &LISTING, &params, &filters).await?;
+
+    Ok(page.into_list_response(&uri))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+
+    use crate::models::users::Role;
+    use crate::test_utils::{
+        app, app_state, insert_document, insert_named, send, signed_in_as, test_user,
+    };
+
+    async fn audit(app: &axum::Router, query: &str) -> Vec<Value> {
+        let (status, body) =
+            send(app, Method::GET, &format!("/api/v1/audit?{}", query), None).await;
+        assert_eq!(status, StatusCode::OK);
+        body["items"].as_array().unwrap().clone()
+    }
+
+    #[sqlx::test]
+    async fn records_who_changed_what(pool: PgPool) {
+        let admin_id = test_user(&pool, Role::Admin).await;
+        let archive_id = insert_named(&pool, "archives", "Noord-Hollands Archief").await;
+        let institute_id = insert_named(&pool, "institutes", "Burgerweeshuis").await;
+        let app = app(pool.clone());
+
+        let (_, body) = send(
+            &app,
+            Method::POST,
+            "/api/v1/places",
+            Some(json!({ "name": "Leiden" })),
+        )
+        .await;
+        let place_id = body["data"]["item"]["id"].as_i64().unwrap();
+        let uri = format!("/api/v1/places/{}", place_id);
+        send(&app, Method::PATCH, &uri, Some(json!({ "name": "Leyden" }))).await;
+        // Changing nothing is not recorded
+        send(&app, Method::PATCH, &uri, Some(json!({ "name": "Leyden" }))).await;
+        let other_id = insert_named(&pool, "places", "Haarlem").await;
+        let document_id =
+            insert_document(&pool, "INV-1", archive_id, institute_id, place_id as i32).await;
+        let uri = format!("{}?reassign_to={}", uri, other_id);
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let items = audit(
+            &app,
+            &format!("resource_type=places&resource_id={}", place_id),
+        )
+        .await;
+        let actions: Vec<&str> = items
+            .iter()
+            .map(|item| item["action"].as_str().unwrap())
+            .collect();
+        assert_eq!(actions, ["delete", "update", "create"]);
+        assert!(
+            items
+                .iter()
+                .all(|item| item["actor_user_id"] == admin_id && item["actor_api_key_id"].is_null())
+        );
+        assert_eq!(
+            items[1]["changes"],
+            json!({ "name": { "before": "Leiden", "after": "Leyden" } })
+        );
+        // Deleting marks the place as deleted
+        assert_eq!(
+            items[0]["changes"]["deleted_by"],
+            json!({ "before": null, "after": admin_id })
+        );
+        assert!(items[0]["changes"]["deleted_at"]["after"].is_string());
+
+        // The moved document, in the same transaction as the delete
+        let items = audit(
+            &app,
+            &format!("resource_type=documents&resource_id={}", document_id),
+        )
+        .await;
+        assert_eq!(items.len(), 1);
+        assert_eq!(
+            items[0]["changes"],
+            json!({ "place_id": { "before": place_id, "after": other_id } })
+        );
+
+        let items = audit(&app, "action=create&action=delete").await;
+        assert_eq!(items.len(), 2);
+        assert!(audit(&app, "to=2000-01-01T00:00:00Z").await.is_empty());
+        assert_eq!(audit(&app, "from=2000-01-01T00:00:00Z").await.len(), 4);
+    }
+
+    #[sqlx::test]
+    async fn failed_changes_are_not_recorded(pool: PgPool) {
+        let archive_id = insert_named(&pool, "archives", "Noord-Hollands Archief").await;
+        let institute_id = insert_named(&pool, "institutes", "Burgerweeshuis").await;
+        let place_id = insert_named(&pool, "places", "Haarlem").await;
+        insert_document(&pool, "INV-1", archive_id, institute_id, place_id).await;
+        let app = app(pool);
+
+        let uri = format!("/api/v1/archives/{}", archive_id);
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let uri = format!("/api/v1/archives/{}?reassign_to=4242", archive_id);
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        assert!(audit(&app, "").await.is_empty());
+    }
+
+    #[sqlx::test]
+    async fn only_admins_read_the_audit_log(pool: PgPool) {
+        let state = app_state(pool);
+
+        let editor = signed_in_as(state.clone(), Role::Editor);
+        let (status, _) = send(&editor, Method::GET, "/api/v1/audit", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let anonymous = crate::create_app(state);
+        let (status, _) = send(&anonymous, Method::GET, "/api/v1/audit", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
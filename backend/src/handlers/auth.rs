@@ -0,0 +1,459 @@
+use crate::auth::{
+    ACCESS_COOKIE, AuthSettings, AuthUser, REFRESH_COOKIE, password::verify_password,
+    permissions::Permission, tokens,
+};
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::extractors::AppJson;
+use crate::models::users::User;
+use crate::schemas::auth::{LoginRequest, RefreshRequest};
+
+use axum::{Json, body::Bytes, extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::{
+    CookieJar,
+    cookie::{Cookie, SameSite},
+};
+use serde_json::{Value, json};
+use sqlx::{FromRow, PgConnection};
+use std::sync::Arc;
+
+const USER_COLUMNS: &str = "id, email, name, active, created_at, last_login_at";
+const ACCESS_COOKIE_PATH: &str = "/api";
+const REFRESH_COOKIE_PATH: &str = "/api/v1/auth";
+
+#[derive(FromRow)]
+struct Credentials {
+    #[sqlx(flatten)]
+    user: User,
+    password_hash: String,
+}
+
+/**
+ * Login Handler
+ * Check the email and password and start a session. The access and refresh token are
+ * returned in the body and set as HttpOnly cookies.
+ */
+pub async fn login_handler(
+    State(data): State<Arc<AppState>>,
+    jar: CookieJar,
+    AppJson(body): AppJson<LoginRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let query = format!(
+        "SELECT {}, password_hash FROM users WHERE email = $1 AND active",
+        USER_COLUMNS
+    );
+    let credentials = sqlx::query_as::<_, Credentials>(&query)
+        .bind(body.email.trim().to_lowercase())
+        .fetch_optional(data.pool())
+        .await?;
+
+    // Hashing takes a while on purpose, keep it off the async workers
+    let password_hash = credentials
+        .as_ref()
+        .map(|credentials| credentials.password_hash.clone());
+    let verified = tokio::task::spawn_blocking(move || {
+        verify_password(&body.password, password_hash.as_deref())
+    })
+    .await
+    .map_err(|err| AppError::Internal(format!("Password check failed: {}", err)))?;
+
+    let Some(credentials) = credentials.filter(|_| verified) else {
+        return Err(AppError::Unauthorized(
+            "Invalid email or password".to_string(),
+        ));
+    };
+
+    let mut tx = data.pool().begin().await?;
+
+    let query = format!(
+        "UPDATE users SET last_login_at = now() WHERE id = $1 RETURNING {}",
+        USER_COLUMNS
+    );
+    let user = sqlx::query_as::<_, User>(&query)
+        .bind(credentials.user.id)
+        .fetch_one(&mut *tx)
+        .await?;
+    let session = start_session(&mut tx, data.auth(), user).await?;
+
+    tx.commit().await?;
+
+    Ok(session.into_response(jar, data.auth()))
+}
+
+/**
+ * Refresh Handler
+ * Exchange a refresh token, from the body or the `refresh_token` cookie, for a new access
+ * and refresh token. Every refresh token works once: using one again ends all sessions of
+ * its user, as it has probably been stolen.
+ */
+pub async fn refresh_handler(
+    State(data): State<Arc<AppState>>,
+    jar: CookieJar,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let token = refresh_token(&jar, &body)?
+        .ok_or_else(|| AppError::Unauthorized("Refresh token required".to_string()))?;
+
+    let mut tx = data.pool().begin().await?;
+
+    let stored = sqlx::query_as::<_, (i32, i32, bool, bool)>(
+        r#"
+        SELECT id, user_id, revoked_at IS NOT NULL, expires_at <= now()
+        FROM refresh_tokens
+        WHERE token_hash = $1
+        FOR UPDATE
+    "#,
+    )
+    .bind(tokens::hash_token(&token))
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some((token_id, user_id, revoked, expired)) = stored else {
+        return Err(AppError::Unauthorized("Invalid refresh token".to_string()));
+    };
+
+    if revoked {
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = now() WHERE user_id = $1 AND revoked_at IS NULL",
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        return Err(AppError::Unauthorized(
+            "Refresh token was already used, all sessions have been ended".to_string(),
+        ));
+    }
+    if expired {
+        return Err(AppError::Unauthorized("Refresh token expired".to_string()));
+    }
+
+    sqlx::query("UPDATE refresh_tokens SET revoked_at = now() WHERE id = $1")
+        .bind(token_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let query = format!(
+        "SELECT {} FROM users WHERE id = $1 AND active",
+        USER_COLUMNS
+    );
+    let user = sqlx::query_as::<_, User>(&query)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::Unauthorized("User account is not active".to_string()))?;
+    let session = start_session(&mut tx, data.auth(), user).await?;
+
+    tx.commit().await?;
+
+    Ok(session.into_response(jar, data.auth()))
+}
+
+/**
+ * Logout Handler
+ * Revoke the refresh token and clear the session cookies.
+ * The access token stays valid until it expires.
+ */
+pub async fn logout_handler(
+    State(data): State<Arc<AppState>>,
+    jar: CookieJar,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(token) = refresh_token(&jar, &body)? {
+        sqlx::query(
+            "UPDATE refresh_tokens SET revoked_at = now() WHERE token_hash = $1 AND revoked_at IS NULL",
+        )
+        .bind(tokens::hash_token(&token))
+        .execute(data.pool())
+        .await?;
+    }
+
+    let jar = jar
+        .remove(Cookie::build(ACCESS_COOKIE).path(ACCESS_COOKIE_PATH))
+        .remove(Cookie::build(REFRESH_COOKIE).path(REFRESH_COOKIE_PATH));
+
+    Ok((jar, StatusCode::NO_CONTENT))
+}
+
+/**
+ * Current User Handler
+ * The signed in user with their roles and everything these permit.
+ */
+pub async fn me_handler(auth: AuthUser) -> Result<impl IntoResponse, AppError> {
+    let permissions: Vec<Permission> = Permission::all()
+        .filter(|permission| auth.0.roles.iter().any(|role| role.permits(*permission)))
+        .collect();
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": json!({
+            "user": auth.0,
+            "permissions": permissions
+        })
+    })))
+}
+
+struct Session {
+    user: User,
+    access_token: String,
+    refresh_token: String,
+}
+
+impl Session {
+    fn into_response(self, jar: CookieJar, settings: &AuthSettings) -> (CookieJar, Json<Value>) {
+        let cookie = |name, value, path, ttl| {
+            Cookie::build((name, value))
+                .path(path)
+                .http_only(true)
+                .secure(settings.secure_cookies())
+                .same_site(SameSite::Lax)
+                .max_age(time::Duration::seconds(ttl))
+        };
+        let jar = jar
+            .add(cookie(
+                ACCESS_COOKIE,
+                self.access_token.clone(),
+                ACCESS_COOKIE_PATH,
+                settings.access_token_ttl(),
+            ))
+            .add(cookie(
+                REFRESH_COOKIE,
+                self.refresh_token.clone(),
+                REFRESH_COOKIE_PATH,
+                settings.refresh_token_ttl(),
+            ));
+
+        let body = json!({
+            "status": "success",
+            "data": json!({
+                "user": self.user,
+                "access_token": self.access_token,
+                "token_type": "Bearer",
+                "expires_in": settings.access_token_ttl(),
+                "refresh_token": self.refresh_token,
+            })
+        });
+
+        (jar, Json(body))
+    }
+}
+
+/**
+ * Issue an access token for `user` and store a new refresh token.
+ */
+async fn start_session(
+    conn: &mut PgConnection,
+    settings: &AuthSettings,
+    user: User,
+) -> Result<Session, AppError> {
+    let access_token = tokens::issue_access_token(settings, user.id)?;
+    let refresh_token = tokens::random_token();
+
+    sqlx::query(
+        r#"
+        INSERT INTO refresh_tokens (user_id, token_hash, expires_at)
+        VALUES ($1, $2, now() + make_interval(secs => $3))
+    "#,
+    )
+    .bind(user.id)
+    .bind(tokens::hash_token(&refresh_token))
+    .bind(settings.refresh_token_ttl() as f64)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(Session {
+        user,
+        access_token,
+        refresh_token,
+    })
+}
+
+/**
+ * The refresh token from the JSON body, or else from the cookie.
+ */
+fn refresh_token(jar: &CookieJar, body: &Bytes) -> Result<Option<String>, AppError> {
+    let request: RefreshRequest = if body.is_empty() {
+        RefreshRequest::default()
+    } else {
+        serde_json::from_slice(body)
+            .map_err(|err| AppError::InvalidRequest(StatusCode::BAD_REQUEST, err.to_string()))?
+    };
+
+    Ok(request.refresh_token.or_else(|| {
+        jar.get(REFRESH_COOKIE)
+            .map(|cookie| cookie.value().to_string())
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{
+            Method, Request, StatusCode,
+            header::{CONTENT_TYPE, COOKIE, SET_COOKIE},
+        },
+    };
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+
+    use crate::auth::create_user;
+    use crate::create_app;
+    use crate::models::users::Role;
+    use crate::test_utils::{app_state, send, send_as, send_request};
+
+    #[sqlx::test]
+    async fn mutations_need_a_signed_in_user(pool: PgPool) {
+        let app = create_app(app_state(pool.clone()));
+        create_user(
+            &pool,
+            "Clara@Example.org",
+            "Clara",
+            "a long password",
+            &[Role::Editor],
+        )
+        .await
+        .unwrap();
+
+        let (status, body) = send(&app, Method::GET, "/api/v1/archives", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results"], 0);
+
+        let archive = json!({"name": "Stadsarchief"});
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            "/api/v1/archives",
+            Some(archive.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "unauthorized");
+        let uri = "/api/v1/archives";
+        let (status, _) = send_as(
+            &app,
+            "not.a.token",
+            Method::POST,
+            uri,
+            Some(archive.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let credentials = json!({"email": "clara@example.org", "password": "wrong password"});
+        let (status, _) = send(&app, Method::POST, "/api/v1/auth/login", Some(credentials)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let credentials = json!({"email": "nobody@example.org", "password": "a long password"});
+        let (status, _) = send(&app, Method::POST, "/api/v1/auth/login", Some(credentials)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let credentials = json!({"email": " CLARA@example.org", "password": "a long password"});
+        let (status, body) =
+            send(&app, Method::POST, "/api/v1/auth/login", Some(credentials)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["user"]["email"], "clara@example.org");
+        assert!(body["data"]["user"].get("password_hash").is_none());
+        assert!(!body["data"]["user"]["last_login_at"].is_null());
+        let token = body["data"]["access_token"].as_str().unwrap();
+
+        let (status, _) = send_as(&app, token, Method::POST, uri, Some(archive)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        sqlx::query("UPDATE users SET active = FALSE")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let archive = json!({"name": "Rijksarchief"});
+        let (status, _) = send_as(&app, token, Method::POST, uri, Some(archive)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[sqlx::test]
+    async fn refresh_tokens_rotate_and_logout_revokes_them(pool: PgPool) {
+        let app = create_app(app_state(pool.clone()));
+        create_user(&pool, "clara@example.org", "Clara", "a long password", &[])
+            .await
+            .unwrap();
+
+        let credentials = json!({"email": "clara@example.org", "password": "a long password"});
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/v1/auth/login")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(credentials.to_string()))
+            .unwrap();
+        let (status, headers, body) = send_request(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let cookies: Vec<&str> = headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .map(|cookie| cookie.to_str().unwrap())
+            .collect();
+        assert_eq!(cookies.len(), 2);
+        assert!(cookies.iter().all(|cookie| cookie.contains("HttpOnly")));
+        let refresh_cookie = cookies
+            .iter()
+            .find(|cookie| cookie.starts_with("refresh_token="))
+            .unwrap();
+        assert!(refresh_cookie.contains("Path=/api/v1/auth"));
+        let first: Value = serde_json::from_slice(&body).unwrap();
+        let first = first["data"]["refresh_token"].as_str().unwrap().to_string();
+
+        // Browsers send the refresh token as a cookie
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/v1/auth/refresh")
+            .header(COOKIE, refresh_cookie.split(';').next().unwrap())
+            .body(Body::empty())
+            .unwrap();
+        let (status, _, body) = send_request(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let second = body["data"]["refresh_token"].as_str().unwrap().to_string();
+        assert_ne!(first, second);
+
+        let (status, body) = send(&app, Method::GET, "/api/v1/auth/me", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["message"], "Authentication required");
+
+        // Using a refresh token twice ends all sessions
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            "/api/v1/auth/refresh",
+            Some(json!({"refresh_token": first})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            "/api/v1/auth/refresh",
+            Some(json!({"refresh_token": second})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let credentials = json!({"email": "clara@example.org", "password": "a long password"});
+        let (_, body) = send(&app, Method::POST, "/api/v1/auth/login", Some(credentials)).await;
+        let refresh_token = body["data"]["refresh_token"].as_str().unwrap().to_string();
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            "/api/v1/auth/logout",
+            Some(json!({"refresh_token": refresh_token})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            "/api/v1/auth/refresh",
+            Some(json!({"refresh_token": refresh_token})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["code"], "unauthorized");
+    }
+}
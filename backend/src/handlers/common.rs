@@ -0,0 +1,262 @@
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::{FromRow, PgConnection, PgPool, Postgres, QueryBuilder, postgres::PgRow};
+
+use crate::audit;
+use crate::auth::Actor;
+use crate::errors::AppError;
+use crate::models::audit::AuditAction;
+use crate::pagination::Filter;
+use crate::schemas::{DeleteParams, DeletedParams};
+
+/**
+ * Delete an archive, institute or place that documents refer to through `column`.
+ * The item is only marked as deleted, unless `params.purge` is set.
+ *
+ * Without `reassign_to` the delete is refused with a 409 while documents (deleted ones too
+ * when purging) or the rows of the `other_references` (`(table, column)` pairs) still refer
+ * to the item. With `reassign_to` those rows are first moved to the other item.
+ * Everything happens in one transaction, together with the audit log entries of the
+ * moved rows and the deleted item.
+ */
+pub async fn delete_referenced_item(
+    pool: &PgPool,
+    actor: &Actor,
+    table: &str,
+    column: &str,
+    other_references: &[(&str, &str)],
+    id: i32,
+    params: &DeleteParams,
+) -> Result<(), AppError> {
+    let mut tx = pool.begin().await?;
+
+    // Locking the item keeps new documents from referring to it until we are done.
+    // A deleted item can only be purged.
+    let query = format!(
+        "SELECT to_jsonb(item) FROM {} item WHERE id = $1 AND ($2 OR deleted_at IS NULL) FOR UPDATE",
+        table
+    );
+    let before: Value = sqlx::query_scalar(&query)
+        .bind(id)
+        .bind(params.purge)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    if let Some(target) = params.reassign_to {
+        if target == id {
+            return Err(AppError::Validation(
+                "Cannot reassign documents to the item that is being deleted".to_string(),
+            ));
+        }
+
+        let query = format!(
+            "SELECT id FROM {} WHERE id = $1 AND deleted_at IS NULL FOR KEY SHARE",
+            table
+        );
+        sqlx::query(&query)
+            .bind(target)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| {
+                AppError::InvalidReference(format!(
+                    "Item to reassign to with ID: {} not found",
+                    target
+                ))
+            })?;
+
+        for (referencing_table, column) in [("documents", column)].iter().chain(other_references) {
+            let query = format!(
+                "UPDATE {} SET {} = $1 WHERE {} = $2 RETURNING id",
+                referencing_table, column, column
+            );
+            let moved: Vec<i32> = sqlx::query_scalar(&query)
+                .bind(target)
+                .bind(id)
+                .fetch_all(&mut *tx)
+                .await?;
+            audit::record_moves(
+                &mut tx,
+                actor,
+                referencing_table,
+                &moved,
+                column,
+                id,
+                target,
+            )
+            .await?;
+        }
+    }
+
+    let query = format!(
+        "SELECT COUNT(*) FROM documents WHERE {} = $1 AND ($2 OR deleted_at IS NULL)",
+        column
+    );
+    let documents: i64 = sqlx::query_scalar(&query)
+        .bind(id)
+        .bind(params.purge)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    if documents > 0 {
+        return Err(AppError::referenced_by_documents(id, documents));
+    }
+
+    if params.purge {
+        let query = format!("DELETE FROM {} WHERE id = $1", table);
+        sqlx::query(&query)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(AppError::from_delete)?;
+        audit::record(&mut tx, actor, table, id, Some(before), None).await?;
+    } else {
+        // Purging checks these through the foreign keys
+        for (referencing_table, column) in other_references {
+            let query = format!(
+                "SELECT EXISTS (SELECT 1 FROM {} WHERE {} = $1)",
+                referencing_table, column
+            );
+            let referenced: bool = sqlx::query_scalar(&query)
+                .bind(id)
+                .fetch_one(&mut *tx)
+                .await?;
+            if referenced {
+                return Err(AppError::still_referenced());
+            }
+        }
+
+        let query = format!(
+            "UPDATE {} item SET deleted_at = now(), deleted_by = $2 WHERE id = $1 RETURNING to_jsonb(item)",
+            table
+        );
+        let after: Value = sqlx::query_scalar(&query)
+            .bind(id)
+            .bind(actor.user_id())
+            .fetch_one(&mut *tx)
+            .await?;
+        audit::record_as(
+            &mut tx,
+            actor,
+            AuditAction::Delete,
+            table,
+            id,
+            Some(before),
+            Some(after),
+        )
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/**
+ * Undo the soft delete of the item `id` in `table` and return it.
+ * Restoring an item that is not deleted changes nothing. An item that refers to a deleted
+ * item through one of its `references` (`(column, table)` pairs) is not restored, that item
+ * must be restored first.
+ */
+pub async fn restore_item<T>(
+    pool: &PgPool,
+    actor: &Actor,
+    table: &str,
+    references: &[(&str, &str)],
+    id: i32,
+) -> Result<T, AppError>
+where
+    T: for<'r> FromRow<'r, PgRow> + Serialize + Send + Unpin,
+{
+    let mut tx = pool.begin().await?;
+
+    let query = format!("SELECT * FROM {} WHERE id = $1 FOR UPDATE", table);
+    let before = sqlx::query_as::<_, T>(&query)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let before = json!(before);
+    for (column, referenced_table) in references {
+        if let Some(referenced_id) = before[column].as_i64() {
+            ensure_not_deleted(&mut tx, referenced_table, column, referenced_id as i32).await?;
+        }
+    }
+
+    let query = format!(
+        "UPDATE {} SET deleted_at = NULL, deleted_by = NULL WHERE id = $1 RETURNING *",
+        table
+    );
+    let item = sqlx::query_as::<_, T>(&query)
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+    audit::record_as(
+        &mut tx,
+        actor,
+        AuditAction::Restore,
+        table,
+        id,
+        Some(before),
+        Some(json!(item)),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(item)
+}
+
+/**
+ * Fail with a 422 when the item `id` in `table`, referred to through `column`, is deleted,
+ * as imports do. It is kept from being deleted until the transaction ends. An item that does
+ * not exist is left to the foreign key to report.
+ */
+pub async fn ensure_not_deleted(
+    conn: &mut PgConnection,
+    table: &str,
+    column: &str,
+    id: i32,
+) -> Result<(), AppError> {
+    let query = format!(
+        "SELECT deleted_at IS NOT NULL FROM {} WHERE id = $1 FOR KEY SHARE",
+        table
+    );
+    let deleted: Option<bool> = sqlx::query_scalar(&query)
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await?;
+    if deleted == Some(true) {
+        return Err(AppError::InvalidReference(format!(
+            "{} {} is deleted and must be restored first",
+            column, id
+        )));
+    }
+
+    Ok(())
+}
+
+/**
+ * Lists of archives, institutes and places leave out deleted items by default.
+ */
+impl Filter for DeletedParams {
+    fn push_conditions(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        if !self.include_deleted {
+            query.push(" AND deleted_at IS NULL");
+        }
+    }
+}
+
+/**
+ * Fail with a 404 when the document with `id` does not exist or is deleted.
+ */
+pub async fn ensure_document_exists(pool: &PgPool, id: i32) -> Result<(), AppError> {
+    sqlx::query("SELECT id FROM documents WHERE id = $1 AND deleted_at IS NULL")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    Ok(())
+}
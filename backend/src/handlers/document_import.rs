@@ -0,0 +1,643 @@
+use crate::audit;
+use crate::auth::Actor;
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::extractors::AppQuery;
+use crate::models::documents::Document;
+use crate::models::historical_date::HistoricalDate;
+use crate::schemas::documents::{DocumentImportParams, ImportMode};
+
+use axum::{Json, body::Bytes, extract::State, http::StatusCode, response::IntoResponse};
+use serde::Serialize;
+use serde_json::{Value, json};
+use sqlx::{Acquire, PgConnection};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/**
+ * The tables that documents refer to by name in an import, with the CSV field naming them.
+ */
+const LOOKUPS: [(&str, &str); 3] = [
+    ("archives", "archive"),
+    ("institutes", "institute"),
+    ("places", "place"),
+];
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum RowAction {
+    Created,
+    /** Would be created, but this is a dry run or another row is invalid */
+    Valid,
+    Invalid,
+}
+
+#[derive(Serialize, Debug)]
+struct RowReport {
+    /** Line of the row in the file, the header is line 1 */
+    row: u64,
+    inventory_number: Option<String>,
+    action: RowAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<i32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<String>,
+}
+
+/**
+ * The position of every field in the CSV records.
+ */
+struct Columns {
+    date: usize,
+    inventory_number: usize,
+    scan_number: Option<usize>,
+    page_number: Option<usize>,
+    notes: Option<usize>,
+    archive: usize,
+    institute: usize,
+    place: usize,
+}
+
+impl Columns {
+    fn resolve(
+        headers: &csv::StringRecord,
+        params: &DocumentImportParams,
+    ) -> Result<Self, AppError> {
+        let find = |field: &str, mapped: &Option<String>| -> Result<Option<usize>, AppError> {
+            let name = mapped.as_deref().unwrap_or(field).trim();
+            let index = headers.iter().position(|header| header.trim() == name);
+            match (index, mapped) {
+                (None, Some(_)) => Err(AppError::Validation(format!(
+                    "CSV has no column {} for {}",
+                    name, field
+                ))),
+                _ => Ok(index),
+            }
+        };
+        let required = |field: &str, mapped: &Option<String>| -> Result<usize, AppError> {
+            find(field, mapped)?.ok_or_else(|| {
+                AppError::Validation(format!(
+                    "CSV has no column {} for {}",
+                    mapped.as_deref().unwrap_or(field),
+                    field
+                ))
+            })
+        };
+
+        Ok(Self {
+            date: required("date", &params.date_column)?,
+            inventory_number: required("inventory_number", &params.inventory_number_column)?,
+            scan_number: find("scan_number", &params.scan_number_column)?,
+            page_number: find("page_number", &params.page_number_column)?,
+            notes: find("notes", &params.notes_column)?,
+            archive: required("archive", &params.archive_column)?,
+            institute: required("institute", &params.institute_column)?,
+            place: required("place", &params.place_column)?,
+        })
+    }
+}
+
+/**
+ * A row that passed validation.
+ */
+struct ValidRow {
+    date: HistoricalDate,
+    inventory_number: String,
+    scan_number: Option<String>,
+    page_number: Option<String>,
+    notes: Option<String>,
+    /** Archive, institute and place names, in the order of `LOOKUPS` */
+    names: [String; 3],
+}
+
+/**
+ * Validate a CSV record, with all of its problems as errors.
+ */
+fn validate_row(record: &csv::StringRecord, columns: &Columns) -> Result<ValidRow, Vec<String>> {
+    let value = |index: Option<usize>| {
+        index
+            .and_then(|index| record.get(index))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+    let mut errors = Vec::new();
+    let mut required = |field: &str, index: usize| {
+        let value = value(Some(index));
+        if value.is_none() {
+            errors.push(format!("{} is empty", field));
+        }
+        value.unwrap_or_default()
+    };
+
+    let date = required("date", columns.date);
+    let inventory_number = required("inventory_number", columns.inventory_number);
+    let names = [
+        required("archive", columns.archive),
+        required("institute", columns.institute),
+        required("place", columns.place),
+    ];
+    let date = match date.parse::<HistoricalDate>() {
+        Ok(date) => Some(date),
+        Err(error) if !date.is_empty() => {
+            errors.push(error);
+            None
+        }
+        Err(_) => None,
+    };
+
+    match date {
+        Some(date) if errors.is_empty() => Ok(ValidRow {
+            date,
+            inventory_number,
+            scan_number: value(columns.scan_number),
+            page_number: value(columns.page_number),
+            notes: value(columns.notes),
+            names,
+        }),
+        _ => Err(errors),
+    }
+}
+
+/**
+ * The id of the item with `name` in `table`, created when there is none yet.
+ * Returns whether it was created. Deleted items are not used, they must be restored first.
+ */
+async fn find_or_create(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    table: &str,
+    name: &str,
+) -> Result<(i32, bool), AppError> {
+    let query = format!(
+        "SELECT id, deleted_at IS NOT NULL FROM {} WHERE name = $1",
+        table
+    );
+    if let Some((id, deleted)) = sqlx::query_as::<_, (i32, bool)>(&query)
+        .bind(name)
+        .fetch_optional(&mut *conn)
+        .await?
+    {
+        if deleted {
+            return Err(AppError::InvalidReference(format!(
+                "{} is deleted and must be restored first",
+                name
+            )));
+        }
+        return Ok((id, false));
+    }
+
+    let query = format!(
+        "INSERT INTO {0} (name) VALUES ($1) RETURNING id, to_jsonb({0})",
+        table
+    );
+    let (id, item): (i32, Value) = sqlx::query_as(&query)
+        .bind(name)
+        .fetch_one(&mut *conn)
+        .await?;
+    audit::record(conn, actor, table, id, None, Some(item)).await?;
+    Ok((id, true))
+}
+
+/**
+ * Insert a document with its archive, institute and place, returning its id
+ * and the lookups it created.
+ */
+async fn insert_row(
+    conn: &mut PgConnection,
+    actor: &Actor,
+    row: &ValidRow,
+    ids: &HashMap<(&str, String), i32>,
+) -> Result<(i32, Vec<(&'static str, String, i32)>), AppError> {
+    let mut created = Vec::new();
+    let mut reference_ids = [0; 3];
+    for (i, (table, _)) in LOOKUPS.iter().enumerate() {
+        let name = &row.names[i];
+        reference_ids[i] = match ids.get(&(*table, name.clone())) {
+            Some(id) => *id,
+            None => {
+                let (id, is_new) = find_or_create(conn, actor, table, name).await?;
+                if is_new {
+                    created.push((*table, name.clone(), id));
+                }
+                id
+            }
+        };
+    }
+
+    let document = sqlx::query_as::<_, Document>(
+        r#"
+        INSERT INTO documents
+            (date_earliest, date_latest, date_precision, date_approximate, date_uncertain,
+             inventory_number, notes, archive_id, institute_id, place_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING *
+    "#,
+    )
+    .bind(row.date.earliest)
+    .bind(row.date.latest)
+    .bind(row.date.precision)
+    .bind(row.date.approximate)
+    .bind(row.date.uncertain)
+    .bind(&row.inventory_number)
+    .bind(&row.notes)
+    .bind(reference_ids[0])
+    .bind(reference_ids[1])
+    .bind(reference_ids[2])
+    .fetch_one(&mut *conn)
+    .await?;
+    let id = document.id;
+    audit::record(conn, actor, "documents", id, None, Some(json!(document))).await?;
+
+    // The scan is catalogued by number, its file can be uploaded later
+    if row.scan_number.is_some() || row.page_number.is_some() {
+        sqlx::query(
+            "INSERT INTO document_scans (document_id, scan_number, page_number) VALUES ($1, $2, $3)",
+        )
+        .bind(id)
+        .bind(&row.scan_number)
+        .bind(&row.page_number)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok((id, created))
+}
+
+/**
+ * Import Handler
+ * This handler imports documents from the CSV file in the request body, see
+ * `DocumentImportParams`, and reports per row whether it was created or why it is invalid.
+ *
+ * Dates must be valid historical dates and inventory numbers unique, both in the file and
+ * in the database. In the default `all_or_nothing` mode one invalid row fails the import
+ * with 422, in `skip_invalid` mode the valid rows are imported regardless.
+ */
+pub async fn import_handler(
+    actor: Actor,
+    AppQuery(params): AppQuery<DocumentImportParams>,
+    State(data): State<Arc<AppState>>,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let delimiter = match params.delimiter {
+        None => b',',
+        Some(delimiter) if delimiter.is_ascii() => delimiter as u8,
+        Some(_) => {
+            return Err(AppError::Validation(
+                "delimiter must be an ASCII character".to_string(),
+            ));
+        }
+    };
+    let text = std::str::from_utf8(&body)
+        .map_err(|_| AppError::Validation("CSV file must be UTF-8 encoded".to_string()))?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|error| AppError::Validation(format!("Invalid CSV header: {}", error)))?
+        .clone();
+    let columns = Columns::resolve(&headers, &params)?;
+
+    let mut reports = Vec::new();
+    let mut rows: Vec<(usize, ValidRow)> = Vec::new();
+    // The first row that uses each inventory number
+    let mut first_rows: HashMap<String, u64> = HashMap::new();
+
+    for record in reader.records() {
+        let (line, result) = match record {
+            Ok(record) => {
+                let line = record.position().map_or(0, |position| position.line());
+                (line, validate_row(&record, &columns))
+            }
+            Err(error) => {
+                let line = error.position().map_or(0, |position| position.line());
+                (line, Err(vec![format!("Invalid CSV: {}", error)]))
+            }
+        };
+
+        let mut report = RowReport {
+            row: line,
+            inventory_number: None,
+            action: RowAction::Valid,
+            id: None,
+            errors: Vec::new(),
+        };
+        match result {
+            Ok(row) => {
+                report.inventory_number = Some(row.inventory_number.clone());
+                match first_rows.get(&row.inventory_number) {
+                    Some(first) => {
+                        report.action = RowAction::Invalid;
+                        report.errors.push(format!(
+                            "inventory_number {} is already used in row {}",
+                            row.inventory_number, first
+                        ));
+                    }
+                    None => {
+                        first_rows.insert(row.inventory_number.clone(), line);
+                        rows.push((reports.len(), row));
+                    }
+                }
+            }
+            Err(errors) => {
+                report.action = RowAction::Invalid;
+                report.errors = errors;
+            }
+        }
+        reports.push(report);
+    }
+
+    let mut tx = data.pool().begin().await?;
+
+    let inventory_numbers: Vec<&str> = rows
+        .iter()
+        .map(|(_, row)| row.inventory_number.as_str())
+        .collect();
+    let existing: Vec<String> = sqlx::query_scalar(
+        "SELECT inventory_number FROM documents WHERE inventory_number = ANY($1)",
+    )
+    .bind(&inventory_numbers)
+    .fetch_all(&mut *tx)
+    .await?;
+    rows.retain(|(index, row)| {
+        let exists = existing.contains(&row.inventory_number);
+        if exists {
+            reports[*index].action = RowAction::Invalid;
+            reports[*index].errors.push(format!(
+                "Document with inventory_number {} already exists",
+                row.inventory_number
+            ));
+        }
+        !exists
+    });
+
+    let invalid = |reports: &[RowReport]| {
+        reports
+            .iter()
+            .filter(|report| report.action == RowAction::Invalid)
+            .count()
+    };
+    let mut new_items: BTreeMap<&str, Vec<String>> = LOOKUPS
+        .iter()
+        .map(|(table, _)| (*table, Vec::new()))
+        .collect();
+
+    if params.mode == ImportMode::SkipInvalid || invalid(&reports) == 0 {
+        let mut ids: HashMap<(&str, String), i32> = HashMap::new();
+        for (index, row) in &rows {
+            // A savepoint per row, so that a failing row does not abort the whole import
+            let mut savepoint = tx.begin().await?;
+            match insert_row(&mut savepoint, &actor, row, &ids).await {
+                Ok((id, created)) => {
+                    savepoint.commit().await?;
+                    reports[*index].action = RowAction::Created;
+                    reports[*index].id = Some(id);
+                    for (table, name, id) in created {
+                        new_items.entry(table).or_default().push(name.clone());
+                        ids.insert((table, name), id);
+                    }
+                }
+                Err(error) => {
+                    savepoint.rollback().await?;
+                    reports[*index].action = RowAction::Invalid;
+                    reports[*index].errors.push(error.message().to_string());
+                }
+            }
+        }
+    }
+
+    let invalid = invalid(&reports);
+    let committed = !params.dry_run && (params.mode == ImportMode::SkipInvalid || invalid == 0);
+    if committed {
+        tx.commit().await?;
+    } else {
+        tx.rollback().await?;
+        for report in reports.iter_mut() {
+            if report.action == RowAction::Created {
+                report.action = RowAction::Valid;
+                report.id = None;
+            }
+        }
+    }
+
+    let created = reports
+        .iter()
+        .filter(|report| report.action == RowAction::Created)
+        .count();
+    let report = json!({
+        "mode": params.mode,
+        "dry_run": params.dry_run,
+        "committed": committed,
+        "summary": {
+            "rows": reports.len(),
+            "created": created,
+            "invalid": invalid,
+        },
+        "new_items": new_items,
+        "rows": reports,
+    });
+
+    if params.mode == ImportMode::AllOrNothing && invalid > 0 {
+        let body = json!({
+            "status": "fail",
+            "code": "validation_failed",
+            "message": format!("{} row(s) are invalid, nothing was imported", invalid),
+            "data": report,
+        });
+        return Ok((StatusCode::UNPROCESSABLE_ENTITY, Json(body)));
+    }
+
+    let status = match committed && created > 0 {
+        true => StatusCode::CREATED,
+        false => StatusCode::OK,
+    };
+    Ok((status, Json(json!({"status": "success", "data": report}))))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Router,
+        body::Body,
+        http::{Method, Request, StatusCode},
+    };
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{app, insert_document, insert_named, send_request};
+
+    async fn import(app: &Router, query: &str, csv: &str) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(format!("/api/v1/documents/import{}", query))
+            .header("content-type", "text/csv")
+            .body(Body::from(csv.to_string()))
+            .unwrap();
+        let (status, _, bytes) = send_request(app, request).await;
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    async fn count(pool: &PgPool, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    const INVENTORY: &str = "\
+date,inventory_number,archive,institute,place,notes
+1743-05-12,INV-1,Noord-Hollands Archief,Burgerweeshuis,Haarlem,Doop
+circa 1690,INV-2,Noord-Hollands Archief,Burgerweeshuis,Amsterdam,
+";
+
+    #[sqlx::test]
+    async fn imports_rows_and_creates_lookups_by_name(pool: PgPool) {
+        let archive_id = insert_named(&pool, "archives", "Noord-Hollands Archief").await;
+        let app = app(pool.clone());
+
+        let (status, body) = import(&app, "", INVENTORY).await;
+
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        assert_eq!(
+            body["data"]["summary"],
+            json!({"rows": 2, "created": 2, "invalid": 0})
+        );
+        assert_eq!(
+            body["data"]["new_items"],
+            json!({"archives": [], "institutes": ["Burgerweeshuis"], "places": ["Haarlem", "Amsterdam"]})
+        );
+        assert_eq!(body["data"]["rows"][1]["row"], 3);
+        assert_eq!(body["data"]["rows"][1]["action"], "created");
+
+        let archives: Vec<i32> = sqlx::query_scalar("SELECT archive_id FROM documents")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(archives, vec![archive_id, archive_id]);
+        assert_eq!(count(&pool, "institutes").await, 1);
+        let (date, notes): (String, Option<String>) = sqlx::query_as(
+            "SELECT date_approximate::text, notes FROM documents WHERE inventory_number = 'INV-2'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!((date.as_str(), notes), ("true", None));
+
+        // Every created document and lookup is in the audit log
+        assert_eq!(count(&pool, "audit_log").await, 5);
+    }
+
+    #[sqlx::test]
+    async fn all_or_nothing_rejects_the_file_with_a_row_report(pool: PgPool) {
+        let archive_id = insert_named(&pool, "archives", "Noord-Hollands Archief").await;
+        let institute_id = insert_named(&pool, "institutes", "Burgerweeshuis").await;
+        let place_id = insert_named(&pool, "places", "Haarlem").await;
+        insert_document(&pool, "INV-9", archive_id, institute_id, place_id).await;
+        let app = app(pool.clone());
+        let csv = "\
+Datum;Inv. nr.;Archief;Instelling;Plaats
+1743-05-12;INV-1;Nieuw Archief;Burgerweeshuis;Haarlem
+May 1743;INV-2;Nieuw Archief;Burgerweeshuis;Haarlem
+1744;INV-1;Nieuw Archief;Burgerweeshuis;Haarlem
+1745;INV-9;Nieuw Archief;;Haarlem
+";
+        let query = "?delimiter=%3B&date_column=Datum&inventory_number_column=Inv.%20nr.\
+            &archive_column=Archief&institute_column=Instelling&place_column=Plaats";
+
+        let (status, body) = import(&app, query, csv).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "validation_failed");
+        assert_eq!(body["data"]["committed"], false);
+        let rows = body["data"]["rows"].as_array().unwrap();
+        let actions: Vec<&str> = rows
+            .iter()
+            .map(|row| row["action"].as_str().unwrap())
+            .collect();
+        assert_eq!(actions, vec!["valid", "invalid", "invalid", "invalid"]);
+        assert_eq!(
+            rows[1]["errors"],
+            json!(["Invalid historical date: May 1743"])
+        );
+        assert_eq!(
+            rows[2]["errors"],
+            json!(["inventory_number INV-1 is already used in row 2"])
+        );
+        assert_eq!(rows[3]["errors"], json!(["institute is empty"]));
+        assert_eq!(count(&pool, "documents").await, 1);
+        assert_eq!(count(&pool, "archives").await, 1);
+        assert_eq!(count(&pool, "audit_log").await, 0);
+
+        let (status, body) = import(&app, &format!("{}&mode=skip_invalid", query), csv).await;
+
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(
+            body["data"]["summary"],
+            json!({"rows": 4, "created": 1, "invalid": 3})
+        );
+        assert_eq!(
+            body["data"]["new_items"]["archives"],
+            json!(["Nieuw Archief"])
+        );
+        assert_eq!(count(&pool, "documents").await, 2);
+    }
+
+    #[sqlx::test]
+    async fn existing_inventory_numbers_and_dry_runs(pool: PgPool) {
+        let app = app(pool.clone());
+        import(&app, "", INVENTORY).await;
+
+        let (status, body) = import(&app, "?mode=skip_invalid", INVENTORY).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body["data"]["rows"][0]["errors"],
+            json!(["Document with inventory_number INV-1 already exists"])
+        );
+
+        sqlx::query("TRUNCATE documents CASCADE")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (status, body) = import(&app, "?dry_run=true", INVENTORY).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["rows"][0]["action"], "valid");
+        assert_eq!(body["data"]["committed"], false);
+        assert_eq!(count(&pool, "documents").await, 0);
+    }
+
+    #[sqlx::test]
+    async fn rows_naming_a_deleted_archive_are_invalid(pool: PgPool) {
+        let archive_id = insert_named(&pool, "archives", "Noord-Hollands Archief").await;
+        sqlx::query("UPDATE archives SET deleted_at = now() WHERE id = $1")
+            .bind(archive_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let app = app(pool.clone());
+
+        let (status, body) = import(&app, "", INVENTORY).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body["data"]["rows"][0]["errors"],
+            json!(["Noord-Hollands Archief is deleted and must be restored first"])
+        );
+        assert_eq!(count(&pool, "documents").await, 0);
+    }
+
+    #[sqlx::test]
+    async fn rejects_files_without_mapped_columns(pool: PgPool) {
+        let app = app(pool);
+
+        let (status, body) = import(&app, "?notes_column=Opmerkingen", INVENTORY).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["message"], "CSV has no column Opmerkingen for notes");
+
+        let (status, body) = import(&app, "", "inventory_number,archive\nINV-1,A\n").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["message"], "CSV has no column date for date");
+    }
+}
@@ -1,77 +1,271 @@
+use crate::audit;
+use crate::auth::Actor;
 use crate::db::AppState;
-use crate::models::documents::Document;
-use crate::schemas::documents::{CreateDocument, UpdateDocument};
+use crate::errors::AppError;
+use crate::export::{ExportFormat, export_list};
+use crate::extractors::{AppJson, AppPath, AppQuery};
+use crate::handlers::common::{ensure_not_deleted, restore_item};
+use crate::models::audit::AuditAction;
+use crate::models::documents::{Document, ExpandedDocument};
+use crate::pagination::{
+    Embed, Filter, Listing, SortField, embed_columns, embed_joins, fetch_page_with_embeds,
+    parse_expand,
+};
+use crate::scans::file_hashes_sql;
+use crate::schemas::documents::{CreateDocument, DocumentFilters, UpdateDocument};
+use crate::schemas::{DeletedParams, ExpandParams, ExportParams, PageParams, PurgeParams};
 
 use axum::{
     Json,
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{OriginalUri, State},
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
 };
 use serde_json::json;
+use sqlx::{PgConnection, Postgres, QueryBuilder};
 use std::sync::Arc;
 
 const TABLE: &str = "documents";
 
+/** The lookups a document refers to, as `(column, table)` pairs */
+const REFERENCES: [(&str, &str); 3] = [
+    ("archive_id", "archives"),
+    ("institute_id", "institutes"),
+    ("place_id", "places"),
+];
+
+const LISTING: Listing = Listing {
+    table: TABLE,
+    sort_fields: &[
+        SortField {
+            name: "date",
+            column: "date_earliest",
+            sql_type: "date",
+        },
+        SortField {
+            name: "inventory_number",
+            column: "inventory_number",
+            sql_type: "text",
+        },
+        SortField {
+            name: "archive_id",
+            column: "archive_id",
+            sql_type: "integer",
+        },
+        SortField {
+            name: "institute_id",
+            column: "institute_id",
+            sql_type: "integer",
+        },
+        SortField {
+            name: "place_id",
+            column: "place_id",
+            sql_type: "integer",
+        },
+        SortField {
+            name: "id",
+            column: "id",
+            sql_type: "integer",
+        },
+    ],
+    default_sort: "date",
+};
+
+pub const EMBEDS: &[Embed] = &[
+    Embed {
+        name: "archive",
+        table: "archives",
+        foreign_key: "archive_id",
+    },
+    Embed {
+        name: "institute",
+        table: "institutes",
+        foreign_key: "institute_id",
+    },
+    Embed {
+        name: "place",
+        table: "places",
+        foreign_key: "place_id",
+    },
+];
+
+impl Filter for DocumentFilters {
+    fn push_conditions(&self, query: &mut QueryBuilder<'_, Postgres>) {
+        if !self.include_deleted {
+            query.push(" AND documents.deleted_at IS NULL");
+        }
+        for (column, ids) in [
+            ("archive_id", &self.archive_id),
+            ("institute_id", &self.institute_id),
+            ("place_id", &self.place_id),
+        ] {
+            if !ids.is_empty() {
+                query.push(format!(" AND {} = ANY(", column));
+                query.push_bind(ids.clone());
+                query.push(")");
+            }
+        }
+
+        // A document matches a date range when any of its possible days falls within it
+        push_any(query, &self.date_from, |query, date| {
+            query.push("date_latest >= ").push_bind(date.earliest);
+        });
+        push_any(query, &self.date_to, |query, date| {
+            query.push("date_earliest <= ").push_bind(date.latest);
+        });
+        push_any(query, &self.inventory_number, |query, prefix| {
+            query
+                .push("starts_with(inventory_number, ")
+                .push_bind(prefix.clone())
+                .push(")");
+        });
+        push_any(query, &self.has_scan, |query, has_scan| {
+            query
+                .push("EXISTS (SELECT 1 FROM document_scans WHERE document_id = documents.id) = ")
+                .push_bind(*has_scan);
+        });
+        push_any(query, &self.notes, |query, text| {
+            query
+                .push("strpos(lower(notes), lower(")
+                .push_bind(text.clone())
+                .push(")) > 0");
+        });
+
+        if !self.person_id.is_empty() || !self.role.is_empty() {
+            query.push(" AND documents.id IN (SELECT document_id FROM document_persons WHERE TRUE");
+            if !self.person_id.is_empty() {
+                query
+                    .push(" AND person_id = ANY(")
+                    .push_bind(self.person_id.clone())
+                    .push(")");
+            }
+            if !self.role.is_empty() {
+                query
+                    .push(" AND role = ANY(")
+                    .push_bind(self.role.clone())
+                    .push(")");
+            }
+            query.push(")");
+        }
+
+        if !self.term_id.is_empty() {
+            query
+                .push(
+                    r#" AND documents.id IN (
+                    SELECT document_id FROM document_terms WHERE term_id IN (
+                        WITH RECURSIVE terms AS (
+                            SELECT id FROM vocabulary_terms WHERE id = ANY("#,
+                )
+                .push_bind(self.term_id.clone())
+                .push(
+                    r#")
+                            UNION
+                            SELECT vocabulary_terms.id FROM vocabulary_terms
+                            JOIN terms ON vocabulary_terms.parent_id = terms.id
+                        )
+                        SELECT id FROM terms
+                    )
+                )"#,
+                );
+        }
+    }
+}
+
+/**
+ * Push ` AND (a OR b OR ...)` with one condition per value, or nothing without values.
+ */
+fn push_any<'args, T>(
+    query: &mut QueryBuilder<'args, Postgres>,
+    values: &[T],
+    mut push_condition: impl FnMut(&mut QueryBuilder<'args, Postgres>, &T),
+) {
+    if values.is_empty() {
+        return;
+    }
+
+    query.push(" AND (");
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            query.push(" OR ");
+        }
+        push_condition(query, value);
+    }
+    query.push(")");
+}
+
 /**
  * List Items Handler
- * This handler fetches a list of all items from postgres
+ * This handler fetches a page of items from postgres,
+ * see `PageParams`, `DocumentFilters` and `ExpandParams`,
+ * or exports all items that pass the filters as CSV, XLSX or JSON, see `ExportParams`
  */
 pub async fn items_list_handler(
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    AppQuery(params): AppQuery<PageParams>,
+    AppQuery(filters): AppQuery<DocumentFilters>,
+    AppQuery(expand): AppQuery<ExpandParams>,
+    AppQuery(export): AppQuery<ExportParams>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("SELECT * FROM {}", TABLE);
-    let query_result = sqlx::query_as::<_, Document>(&query)
-        .fetch_all(data.pool())
+) -> Result<impl IntoResponse, AppError> {
+    let embeds = parse_expand(EMBEDS, &expand.expand)?;
+    if let Some(format) = ExportFormat::negotiate(export.format, &headers) {
+        let sort = params.sort.as_deref();
+        return export_list::<ExpandedDocument>(
+            data.pool(),
+            &LISTING,
+            sort,
+            &filters,
+            &embeds,
+            format,
+        )
         .await;
-
-    if query_result.is_err() {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": "🔥 Something bad happened while fetching all items",
-        });
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)));
     }
 
-    let items = query_result.unwrap();
+    let page = fetch_page_with_embeds::<ExpandedDocument>(
+        data.pool(),
+        &LISTING,
+        &params,
+        &filters,
+        &embeds,
+    )
+    .await?;
 
-    let json_response = serde_json::json!({
-        "status": "success",
-        "results": items.len(),
-        "items": items
-    });
-    Ok(Json(json_response))
+    Ok(page.into_list_response(&uri))
 }
 
 /**
  * Fetch a single Item
+ * The referenced items can be embedded with `?expand=archive,institute,place`.
+ * A deleted document is only found with `?include_deleted=true`
  */
 pub async fn get_item_handler(
-    Path(id): Path<i32>,
+    AppPath(id): AppPath<i32>,
+    AppQuery(expand): AppQuery<ExpandParams>,
+    AppQuery(deleted): AppQuery<DeletedParams>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("SELECT * FROM {} WHERE id = $1", TABLE);
-    let query_result = sqlx::query_as::<_, Document>(&query)
+) -> Result<impl IntoResponse, AppError> {
+    let embeds = parse_expand(EMBEDS, &expand.expand)?;
+    let query = format!(
+        "SELECT {}.*{} FROM {}{} WHERE {}.id = $1 AND ($2 OR {}.deleted_at IS NULL)",
+        TABLE,
+        embed_columns(&embeds),
+        TABLE,
+        embed_joins(TABLE, &embeds),
+        TABLE,
+        TABLE
+    );
+    let item = sqlx::query_as::<_, ExpandedDocument>(&query)
         .bind(id)
-        .fetch_one(data.pool())
-        .await;
+        .bind(deleted.include_deleted)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
 
-    match query_result {
-        Ok(item) => {
-            let item_response = serde_json::json!({"status": "success","data": serde_json::json!({
-                "item": item
-            })});
-
-            return Ok(Json(item_response));
-        }
-        Err(_) => {
-            let error_response = serde_json::json!({
-                "status": "fail",
-                "message": format!("Item with ID: {} not found", id)
-            });
-            return Err((StatusCode::NOT_FOUND, Json(error_response)));
-        }
-    }
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
 }
 
 /**
@@ -79,141 +273,875 @@ pub async fn get_item_handler(
  * This handler adds a new item to postgres
  */
 pub async fn create_item_handler(
+    actor: Actor,
     State(data): State<Arc<AppState>>,
-    Json(body): Json<CreateDocument>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    AppJson(body): AppJson<CreateDocument>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
+    for ((column, table), id) in
+        REFERENCES
+            .iter()
+            .zip([body.archive_id, body.institute_id, body.place_id])
+    {
+        ensure_not_deleted(&mut tx, table, column, id).await?;
+    }
+
     let query = format!(
         r#"
         INSERT INTO {}
-            (date, inventory_number, scan_number, page_number, notes, archive_id, institute_id, place_id)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            (date_earliest, date_latest, date_precision, date_approximate, date_uncertain,
+             inventory_number, notes, archive_id, institute_id, place_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
         RETURNING *
     "#,
         TABLE
     );
 
-    let query_result = sqlx::query_as::<_, Document>(&query)
-        .bind(body.date)
+    let item = sqlx::query_as::<_, Document>(&query)
+        .bind(body.date.earliest)
+        .bind(body.date.latest)
+        .bind(body.date.precision)
+        .bind(body.date.approximate)
+        .bind(body.date.uncertain)
         .bind(body.inventory_number)
-        .bind(body.scan_number)
-        .bind(body.page_number)
         .bind(body.notes)
         .bind(body.archive_id)
         .bind(body.institute_id)
         .bind(body.place_id)
-        .fetch_one(data.pool())
-        .await;
-    match query_result {
-        Ok(item) => {
-            let item_response = json!({"status": "success","data": json!({
-                "item": item
-            })});
+        .fetch_one(&mut *tx)
+        .await?;
+    audit::record(&mut tx, &actor, TABLE, item.id, None, Some(json!(item))).await?;
 
-            return Ok((StatusCode::CREATED, Json(item_response)));
-        }
-        Err(e) => {
-            if e.to_string()
-                .contains("duplicate key value violates unique constraint")
-            {
-                let error_response = serde_json::json!({
-                    "status": "fail",
-                    "message": "Item with that name already exists",
-                });
-                return Err((StatusCode::CONFLICT, Json(error_response)));
-            }
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"status": "error","message": format!("{:?}", e)})),
-            ));
-        }
-    }
+    tx.commit().await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
 }
 
 /**
  * Edit Item Handler
- * This handler handles edits of existing items
+ * This handler handles edits of existing items.
+ * Fields that are left out of the request body keep their stored value,
+ * `"notes": null` clears the notes.
  */
 pub async fn edit_item_handler(
-    Path(id): Path<i32>,
+    AppPath(id): AppPath<i32>,
+    actor: Actor,
     State(data): State<Arc<AppState>>,
-    Json(body): Json<UpdateDocument>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("SELECT * FROM {} WHERE id = $1", TABLE);
-    let query_result = sqlx::query_as::<_, Document>(&query)
-        .bind(id)
-        .fetch_one(data.pool())
-        .await;
-
-    if query_result.is_err() {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": format!("🔥 Item with ID: {} not found", id)
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+    AppJson(body): AppJson<UpdateDocument>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
+    let before = lock_document(&mut tx, id, false).await?;
+    for ((column, table), id) in
+        REFERENCES
+            .iter()
+            .zip([body.archive_id, body.institute_id, body.place_id])
+    {
+        if let Some(id) = id {
+            ensure_not_deleted(&mut tx, table, column, id).await?;
+        }
     }
 
-    let item = query_result.unwrap();
-
     let query = format!(
         r#"
-        INSERT INTO {}
-            (date, inventory_number, scan_number, page_number, notes, archive_id, institute_id, place_id)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        UPDATE {} SET
+            date_earliest = COALESCE($1, date_earliest),
+            date_latest = COALESCE($2, date_latest),
+            date_precision = COALESCE($3, date_precision),
+            date_approximate = COALESCE($4, date_approximate),
+            date_uncertain = COALESCE($5, date_uncertain),
+            inventory_number = COALESCE($6, inventory_number),
+            notes = $7,
+            archive_id = COALESCE($8, archive_id),
+            institute_id = COALESCE($9, institute_id),
+            place_id = COALESCE($10, place_id)
+        WHERE id = $11
         RETURNING *
     "#,
         TABLE
     );
 
-    let query_result = sqlx::query_as::<_, Document>(&query)
-        .bind(body.date.unwrap_or_else(|| chrono::Utc::now())) // provide default for required date
-        .bind(body.inventory_number.unwrap_or(item.inventory_number))
-        .bind(body.scan_number.unwrap_or(item.scan_number))
-        .bind(body.page_number.unwrap_or(item.page_number))
-        .bind(body.notes.unwrap_or(item.notes))
-        .bind(body.archive_id.unwrap_or(item.archive_id))
-        .bind(body.institute_id.unwrap_or(item.institute_id))
-        .bind(body.place_id.unwrap_or(item.place_id))
-        .fetch_one(data.pool())
-        .await;
-    match query_result {
-        Ok(item) => {
-            let item_response = serde_json::json!({"status": "success","data": serde_json::json!({
-                "item": item
-            })});
+    let item = sqlx::query_as::<_, Document>(&query)
+        .bind(body.date.map(|date| date.earliest))
+        .bind(body.date.map(|date| date.latest))
+        .bind(body.date.map(|date| date.precision))
+        .bind(body.date.map(|date| date.approximate))
+        .bind(body.date.map(|date| date.uncertain))
+        .bind(body.inventory_number)
+        .bind(body.notes.unwrap_or_else(|| before.notes.clone()))
+        .bind(body.archive_id)
+        .bind(body.institute_id)
+        .bind(body.place_id)
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+    audit::record(
+        &mut tx,
+        &actor,
+        TABLE,
+        id,
+        Some(json!(before)),
+        Some(json!(item)),
+    )
+    .await?;
 
-            return Ok(Json(item_response));
-        }
-        Err(err) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"status": "error","message": format!("{:?}", err)})),
-            ));
-        }
-    }
+    tx.commit().await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
 }
 
 /**
  * Delete Item Handler
+ * The document is marked as deleted, `?purge=true` removes it for good. The scans of a
+ * purged document are deleted with it, as are their files unless other documents have
+ * the same files
  */
 pub async fn delete_item_handler(
-    Path(id): Path<i32>,
+    AppPath(id): AppPath<i32>,
+    AppQuery(params): AppQuery<PurgeParams>,
+    actor: Actor,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("DELETE FROM {} WHERE id = $1", TABLE);
-    let rows_affected = sqlx::query(&query)
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
+    // A deleted document can only be purged
+    let before = lock_document(&mut tx, id, params.purge).await?;
+
+    if !params.purge {
+        let query = format!(
+            "UPDATE {} SET deleted_at = now(), deleted_by = $2 WHERE id = $1 RETURNING *",
+            TABLE
+        );
+        let item = sqlx::query_as::<_, Document>(&query)
+            .bind(id)
+            .bind(actor.user_id())
+            .fetch_one(&mut *tx)
+            .await?;
+        audit::record_as(
+            &mut tx,
+            &actor,
+            AuditAction::Delete,
+            TABLE,
+            id,
+            Some(json!(before)),
+            Some(json!(item)),
+        )
+        .await?;
+
+        tx.commit().await?;
+
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    let query = format!(
+        "DELETE FROM {} WHERE id = $1 RETURNING {}",
+        TABLE,
+        file_hashes_sql("document_scans.document_id = $1")
+    );
+    let hashes: Vec<String> = sqlx::query_scalar(&query)
         .bind(id) // $1
-        .execute(data.pool())
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(AppError::from_delete)?;
+    audit::record(&mut tx, &actor, TABLE, id, Some(json!(before)), None).await?;
+
+    tx.commit().await?;
+
+    data.scans()
+        .remove_unreferenced(data.pool(), &hashes)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/**
+ * Restore Item Handler
+ * This handler undoes the delete of a document that was not purged
+ */
+pub async fn restore_item_handler(
+    AppPath(id): AppPath<i32>,
+    actor: Actor,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = restore_item::<Document>(data.pool(), &actor, TABLE, &REFERENCES, id).await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * The document with `id`, locked until the end of the transaction.
+ */
+async fn lock_document(
+    conn: &mut PgConnection,
+    id: i32,
+    include_deleted: bool,
+) -> Result<Document, AppError> {
+    let query = format!(
+        "SELECT * FROM {} WHERE id = $1 AND ($2 OR deleted_at IS NULL) FOR UPDATE",
+        TABLE
+    );
+    sqlx::query_as::<_, Document>(&query)
+        .bind(id)
+        .bind(include_deleted)
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{
+            Method, Request, StatusCode,
+            header::{ACCEPT, CONTENT_DISPOSITION, CONTENT_TYPE},
+        },
+    };
+    use chrono::NaiveDate;
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{app, insert_document, insert_named, send, send_request};
+
+    struct Fixture {
+        id: i32,
+        archive_id: i32,
+        institute_id: i32,
+        place_id: i32,
+    }
+
+    async fn seed(pool: &PgPool) -> Fixture {
+        let archive_id = insert_named(pool, "archives", "Noord-Hollands Archief").await;
+        let institute_id = insert_named(pool, "institutes", "Burgerweeshuis").await;
+        let place_id = insert_named(pool, "places", "Haarlem").await;
+
+        let id = sqlx::query_scalar(
+            r#"
+            INSERT INTO documents
+                (date_earliest, date_latest, date_precision,
+                 inventory_number, notes, archive_id, institute_id, place_id)
+            VALUES ('1743-05-12', '1743-05-12', 'day', 'INV-1', 'Baptism', $1, $2, $3)
+            RETURNING id
+        "#,
+        )
+        .bind(archive_id)
+        .bind(institute_id)
+        .bind(place_id)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        sqlx::query(
+            "INSERT INTO document_scans (document_id, scan_number, page_number) VALUES ($1, 'SCAN-1', '12')",
+        )
+        .bind(id)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        Fixture {
+            id,
+            archive_id,
+            institute_id,
+            place_id,
+        }
+    }
+
+    async fn patch(pool: &PgPool, id: i32, body: Value) -> (StatusCode, Value) {
+        let uri = format!("/api/v1/documents/{}", id);
+        send(&app(pool.clone()), Method::PATCH, &uri, Some(body)).await
+    }
+
+    async fn document_count(pool: &PgPool) -> i64 {
+        sqlx::query_scalar("SELECT COUNT(*) FROM documents")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    /**
+     * Patch a single field and assert that only that field changed.
+     */
+    async fn assert_patches_field(pool: &PgPool, field: &str, value: Value) {
+        let fixture = seed(pool).await;
+        let (_, before) = send(
+            &app(pool.clone()),
+            Method::GET,
+            &format!("/api/v1/documents/{}", fixture.id),
+            None,
+        )
+        .await;
+
+        let (status, body) = patch(pool, fixture.id, json!({ field: value.clone() })).await;
+
+        assert_eq!(status, StatusCode::OK);
+        let mut expected = before["data"]["item"].clone();
+        expected[field] = value;
+        assert_eq!(body["data"]["item"], expected);
+        assert_eq!(document_count(pool).await, 1);
+    }
+
+    #[sqlx::test]
+    async fn edit_with_empty_body_keeps_every_field(pool: PgPool) {
+        let fixture = seed(&pool).await;
+
+        let (status, body) = patch(&pool, fixture.id, json!({})).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body["data"]["item"],
+            json!({
+                "id": fixture.id,
+                "date": "1743-05-12",
+                "inventory_number": "INV-1",
+                "notes": "Baptism",
+                "archive_id": fixture.archive_id,
+                "institute_id": fixture.institute_id,
+                "place_id": fixture.place_id,
+                "deleted_at": null,
+                "deleted_by": null,
+            })
+        );
+        assert_eq!(document_count(&pool).await, 1);
+    }
+
+    #[sqlx::test]
+    async fn edit_updates_date(pool: PgPool) {
+        assert_patches_field(&pool, "date", json!("1745-01-31")).await;
+    }
+
+    #[sqlx::test]
+    async fn edit_updates_date_to_a_range(pool: PgPool) {
+        assert_patches_field(&pool, "date", json!("1743/1745")).await;
+    }
+
+    #[sqlx::test]
+    async fn edit_stores_bounds_of_approximate_date(pool: PgPool) {
+        assert_patches_field(&pool, "date", json!("1690~")).await;
+
+        let (earliest, latest): (NaiveDate, NaiveDate) =
+            sqlx::query_as("SELECT date_earliest, date_latest FROM documents")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(earliest, NaiveDate::from_ymd_opt(1690, 1, 1).unwrap());
+        assert_eq!(latest, NaiveDate::from_ymd_opt(1690, 12, 31).unwrap());
+    }
+
+    #[sqlx::test]
+    async fn edit_updates_inventory_number(pool: PgPool) {
+        assert_patches_field(&pool, "inventory_number", json!("INV-2")).await;
+    }
+
+    #[sqlx::test]
+    async fn edit_updates_notes(pool: PgPool) {
+        assert_patches_field(&pool, "notes", json!("Baptism of Jan")).await;
+    }
+
+    #[sqlx::test]
+    async fn edit_clears_notes_with_null(pool: PgPool) {
+        assert_patches_field(&pool, "notes", Value::Null).await;
+    }
+
+    #[sqlx::test]
+    async fn edit_updates_archive_id(pool: PgPool) {
+        let archive_id = insert_named(&pool, "archives", "Stadsarchief Amsterdam").await;
+        assert_patches_field(&pool, "archive_id", json!(archive_id)).await;
+    }
+
+    #[sqlx::test]
+    async fn edit_updates_institute_id(pool: PgPool) {
+        let institute_id = insert_named(&pool, "institutes", "Diaconieweeshuis").await;
+        assert_patches_field(&pool, "institute_id", json!(institute_id)).await;
+    }
+
+    #[sqlx::test]
+    async fn edit_updates_place_id(pool: PgPool) {
+        let place_id = insert_named(&pool, "places", "Amsterdam").await;
+        assert_patches_field(&pool, "place_id", json!(place_id)).await;
+    }
+
+    #[sqlx::test]
+    async fn edit_unknown_document_returns_404(pool: PgPool) {
+        let (status, body) = patch(&pool, 4242, json!({ "notes": "Nothing" })).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["status"], "fail");
+        assert_eq!(document_count(&pool).await, 0);
+    }
+
+    #[sqlx::test]
+    async fn edit_to_existing_inventory_number_returns_409(pool: PgPool) {
+        let fixture = seed(&pool).await;
+        sqlx::query(
+            r#"
+            INSERT INTO documents
+                (date_earliest, date_latest, date_precision, inventory_number, archive_id, institute_id, place_id)
+            VALUES ('1750-01-01', '1750-01-01', 'day', 'INV-TAKEN', $1, $2, $3)
+        "#,
+        )
+        .bind(fixture.archive_id)
+        .bind(fixture.institute_id)
+        .bind(fixture.place_id)
+        .execute(&pool)
         .await
-        .unwrap()
-        .rows_affected();
+        .unwrap();
+
+        let (status, _) = patch(
+            &pool,
+            fixture.id,
+            json!({ "inventory_number": "INV-TAKEN" }),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(document_count(&pool).await, 2);
+    }
+
+    #[sqlx::test]
+    async fn edit_to_unknown_reference_returns_422(pool: PgPool) {
+        let fixture = seed(&pool).await;
+
+        for (field, name) in [
+            ("archive_id", "archive"),
+            ("institute_id", "institute"),
+            ("place_id", "place"),
+        ] {
+            let (status, body) = patch(&pool, fixture.id, json!({ field: 4242 })).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", field);
+            assert_eq!(body["code"], "invalid_reference");
+            assert_eq!(
+                body["message"],
+                format!("Referenced {} does not exist", name)
+            );
+        }
+    }
+
+    #[sqlx::test]
+    async fn deleted_references_return_422(pool: PgPool) {
+        let fixture = seed(&pool).await;
+        let app = app(pool.clone());
+        let place_id = insert_named(&pool, "places", "Amsterdam").await;
+        let place_uri = format!("/api/v1/places/{}", place_id);
+        let (status, _) = send(&app, Method::DELETE, &place_uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let message = format!(
+            "place_id {} is deleted and must be restored first",
+            place_id
+        );
+
+        let (status, body) = patch(&pool, fixture.id, json!({ "place_id": place_id })).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["message"], message);
 
-    if rows_affected == 0 {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": format!("Item with ID: {} not found", id)
+        let body = json!({
+            "date": "1750",
+            "inventory_number": "INV-2",
+            "archive_id": fixture.archive_id,
+            "institute_id": fixture.institute_id,
+            "place_id": place_id,
         });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
+        let (status, body) = send(&app, Method::POST, "/api/v1/documents", Some(body)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "invalid_reference");
+        assert_eq!(document_count(&pool).await, 1);
+
+        // A deleted document is only restored after the items it refers to
+        let uri = format!("/api/v1/documents/{}", fixture.id);
+        send(&app, Method::DELETE, &uri, None).await;
+        let place_uri = format!("/api/v1/places/{}", fixture.place_id);
+        let (status, _) = send(&app, Method::DELETE, &place_uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let restore_uri = format!("{}/restore", uri);
+        let (status, body) = send(&app, Method::POST, &restore_uri, None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body["message"],
+            format!(
+                "place_id {} is deleted and must be restored first",
+                fixture.place_id
+            )
+        );
+
+        send(&app, Method::POST, &format!("{}/restore", place_uri), None).await;
+        let (status, _) = send(&app, Method::POST, &restore_uri, None).await;
+        assert_eq!(status, StatusCode::OK);
     }
 
-    Ok(StatusCode::NO_CONTENT)
+    #[sqlx::test]
+    async fn edit_with_invalid_date_returns_422(pool: PgPool) {
+        let fixture = seed(&pool).await;
+
+        let (status, _) = patch(&pool, fixture.id, json!({ "date": "not a date" })).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[sqlx::test]
+    async fn list_walks_mixed_direction_sort_with_cursor(pool: PgPool) {
+        let fixture = seed(&pool).await;
+        for (inventory_number, date) in [("INV-2", "1745"), ("INV-3", "1743"), ("INV-4", "1745")] {
+            let id = insert_document(
+                &pool,
+                inventory_number,
+                fixture.archive_id,
+                fixture.institute_id,
+                fixture.place_id,
+            )
+            .await;
+            let uri = format!("/api/v1/documents/{}", id);
+            send(
+                &app(pool.clone()),
+                Method::PATCH,
+                &uri,
+                Some(json!({ "date": date })),
+            )
+            .await;
+        }
+        let app = app(pool);
+
+        let mut inventory_numbers = Vec::new();
+        let mut uri = "/api/v1/documents?sort=date,-inventory_number&limit=1".to_string();
+        loop {
+            let (status, body) = send(&app, Method::GET, &uri, None).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["total"], 4);
+            inventory_numbers.push(body["items"][0]["inventory_number"].clone());
+
+            match body["next_cursor"].as_str() {
+                Some(cursor) => {
+                    uri = format!(
+                        "/api/v1/documents?sort=date,-inventory_number&limit=1&cursor={}",
+                        cursor
+                    )
+                }
+                None => break,
+            }
+        }
+
+        assert_eq!(inventory_numbers, vec!["INV-3", "INV-1", "INV-4", "INV-2"]);
+    }
+
+    /**
+     * Four documents spread over two archives, institutes and places, returning their ids.
+     */
+    async fn seed_for_filters(pool: &PgPool) -> (Fixture, Vec<i32>) {
+        let fixture = seed(pool).await;
+        let other_archive = insert_named(pool, "archives", "Stadsarchief Amsterdam").await;
+        let other_institute = insert_named(pool, "institutes", "Diaconieweeshuis").await;
+        let other_place = insert_named(pool, "places", "Amsterdam").await;
+
+        let mut ids = vec![fixture.id];
+        for (inventory_number, date, notes, archive, institute, place) in [
+            (
+                "INV-2",
+                "1745",
+                "Marriage",
+                fixture.archive_id,
+                other_institute,
+                other_place,
+            ),
+            (
+                "OWH-1",
+                "1690~",
+                "Admission of an orphan",
+                other_archive,
+                fixture.institute_id,
+                fixture.place_id,
+            ),
+            (
+                "OWH-2",
+                "1800-03",
+                "Discharge; DROP TABLE documents",
+                other_archive,
+                other_institute,
+                other_place,
+            ),
+        ] {
+            let (_, body) = send(
+                &app(pool.clone()),
+                Method::POST,
+                "/api/v1/documents",
+                Some(json!({
+                    "date": date,
+                    "inventory_number": inventory_number,
+                    "notes": notes,
+                    "archive_id": archive,
+                    "institute_id": institute,
+                    "place_id": place,
+                })),
+            )
+            .await;
+            ids.push(body["data"]["item"]["id"].as_i64().unwrap() as i32);
+        }
+
+        (fixture, ids)
+    }
+
+    async fn list_ids(pool: &PgPool, query: &str) -> Vec<i32> {
+        let uri = format!("/api/v1/documents?sort=id&{}", query);
+        let (status, body) = send(&app(pool.clone()), Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", query, body);
+        assert_eq!(body["total"], body["results"], "{}", query);
+
+        body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["id"].as_i64().unwrap() as i32)
+            .collect()
+    }
+
+    #[sqlx::test]
+    async fn list_filters_by_references_with_or_for_repeated_values(pool: PgPool) {
+        let (fixture, ids) = seed_for_filters(&pool).await;
+
+        let query = format!("archive_id={}", fixture.archive_id);
+        assert_eq!(list_ids(&pool, &query).await, vec![ids[0], ids[1]]);
+
+        let query = format!("institute_id={}", fixture.institute_id);
+        assert_eq!(list_ids(&pool, &query).await, vec![ids[0], ids[2]]);
+
+        let query = format!("place_id={}&place_id={}", fixture.place_id, 4242);
+        assert_eq!(list_ids(&pool, &query).await, vec![ids[0], ids[2]]);
+
+        let query = format!(
+            "archive_id={}&institute_id={}",
+            fixture.archive_id, fixture.institute_id
+        );
+        assert_eq!(list_ids(&pool, &query).await, vec![ids[0]]);
+    }
+
+    #[sqlx::test]
+    async fn list_filters_by_date_range(pool: PgPool) {
+        let (_, ids) = seed_for_filters(&pool).await;
+
+        assert_eq!(
+            list_ids(&pool, "date_from=1743&date_to=1745-06").await,
+            vec![ids[0], ids[1]]
+        );
+        assert_eq!(list_ids(&pool, "date_to=1700").await, vec![ids[2]]);
+        assert_eq!(list_ids(&pool, "date_from=1800-03-31").await, vec![ids[3]]);
+        assert_eq!(
+            list_ids(&pool, "date_from=1800&date_from=1745").await,
+            vec![ids[1], ids[3]]
+        );
+    }
+
+    #[sqlx::test]
+    async fn list_filters_by_text_and_scan(pool: PgPool) {
+        let (_, ids) = seed_for_filters(&pool).await;
+
+        assert_eq!(
+            list_ids(&pool, "inventory_number=OWH").await,
+            vec![ids[2], ids[3]]
+        );
+        assert_eq!(
+            list_ids(&pool, "inventory_number=OWH-1&inventory_number=INV-2").await,
+            vec![ids[1], ids[2]]
+        );
+        assert_eq!(
+            list_ids(&pool, "inventory_number=%25").await,
+            Vec::<i32>::new()
+        );
+        assert_eq!(list_ids(&pool, "has_scan=true").await, vec![ids[0]]);
+        assert_eq!(list_ids(&pool, "has_scan=false").await, ids[1..].to_vec());
+        assert_eq!(list_ids(&pool, "notes=ORPHAN").await, vec![ids[2]]);
+        assert_eq!(
+            list_ids(&pool, "notes=%27%3B%20DROP%20TABLE%20documents").await,
+            Vec::<i32>::new()
+        );
+        assert_eq!(list_ids(&pool, "notes=DROP%20TABLE").await, vec![ids[3]]);
+    }
+
+    #[sqlx::test]
+    async fn deleted_documents_are_left_out_until_restored(pool: PgPool) {
+        let fixture = seed(&pool).await;
+        let app = app(pool.clone());
+        let uri = format!("/api/v1/documents/{}", fixture.id);
+
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(document_count(&pool).await, 1);
+        let (_, body) = send(&app, Method::GET, "/api/v1/documents", None).await;
+        assert_eq!(body["total"], 0);
+        let (_, body) = send(
+            &app,
+            Method::GET,
+            "/api/v1/documents?include_deleted=true",
+            None,
+        )
+        .await;
+        assert_eq!(body["items"][0]["id"], fixture.id);
+        let body = json!({ "notes": "Burial" });
+        let (status, _) = send(&app, Method::PATCH, &uri, Some(body)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, body) = send(&app, Method::POST, &format!("{}/restore", uri), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["item"]["notes"], "Baptism");
+        let (_, body) = send(&app, Method::GET, "/api/v1/documents", None).await;
+        assert_eq!(body["total"], 1);
+    }
+
+    #[sqlx::test]
+    async fn list_rejects_invalid_filters(pool: PgPool) {
+        let app = app(pool);
+
+        for uri in [
+            "/api/v1/documents?archive_id=abc",
+            "/api/v1/documents?date_from=yesterday",
+        ] {
+            let (status, body) = send(&app, Method::GET, uri, None).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+            assert_eq!(body["code"], "invalid_request");
+        }
+    }
+
+    #[sqlx::test]
+    async fn get_expands_referenced_items(pool: PgPool) {
+        let fixture = seed(&pool).await;
+        let app = app(pool);
+
+        let uri = format!("/api/v1/documents/{}", fixture.id);
+        let (_, body) = send(&app, Method::GET, &uri, None).await;
+        let item = &body["data"]["item"];
+        assert_eq!(item["archive_id"], fixture.archive_id);
+        assert!(item.get("archive").is_none());
+
+        let uri = format!("/api/v1/documents/{}?expand=archive,place", fixture.id);
+        let (status, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let item = &body["data"]["item"];
+        assert_eq!(item["id"], fixture.id);
+        assert_eq!(
+            item["archive"],
+            json!({
+                "id": fixture.archive_id,
+                "name": "Noord-Hollands Archief",
+                "deleted_at": null,
+                "deleted_by": null
+            })
+        );
+        assert_eq!(item["place"]["name"], "Haarlem");
+        assert!(item["place"]["latitude"].is_null());
+        assert!(item.get("institute").is_none());
+    }
+
+    #[sqlx::test]
+    async fn list_expands_referenced_items_while_paginating(pool: PgPool) {
+        let (fixture, ids) = seed_for_filters(&pool).await;
+        let app = app(pool);
+
+        let first = "/api/v1/documents?expand=archive&expand=institute,place&sort=-id&limit=3";
+        let mut seen = Vec::new();
+        let mut uri = first.to_string();
+        loop {
+            let (status, body) = send(&app, Method::GET, &uri, None).await;
+            assert_eq!(status, StatusCode::OK);
+            for item in body["items"].as_array().unwrap() {
+                assert_eq!(item["archive"]["id"], item["archive_id"]);
+                assert_eq!(item["institute"]["id"], item["institute_id"]);
+                assert_eq!(item["place"]["id"], item["place_id"]);
+                seen.push(item["id"].as_i64().unwrap() as i32);
+            }
+
+            match body["next_cursor"].as_str() {
+                Some(cursor) => uri = format!("{}&cursor={}", first, cursor),
+                None => break,
+            }
+        }
+
+        assert_eq!(seen, ids.into_iter().rev().collect::<Vec<_>>());
+
+        let uri = format!(
+            "/api/v1/documents?expand=archive&archive_id={}",
+            fixture.archive_id
+        );
+        let (_, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(
+            body["items"][0]["archive"]["name"],
+            "Noord-Hollands Archief"
+        );
+    }
+
+    #[sqlx::test]
+    async fn expand_rejects_unknown_items(pool: PgPool) {
+        let fixture = seed(&pool).await;
+        let app = app(pool);
+
+        let uri = format!("/api/v1/documents/{}?expand=notes", fixture.id);
+        for uri in [uri.as_str(), "/api/v1/documents?expand=archive,persons"] {
+            let (status, body) = send(&app, Method::GET, uri, None).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", uri);
+            assert_eq!(body["code"], "validation_failed");
+        }
+    }
+
+    #[sqlx::test]
+    async fn list_exports_filtered_rows_as_csv_and_json(pool: PgPool) {
+        let fixture = seed(&pool).await;
+        let other_place = insert_named(&pool, "places", "Amsterdam").await;
+        insert_document(
+            &pool,
+            "INV-2",
+            fixture.archive_id,
+            fixture.institute_id,
+            other_place,
+        )
+        .await;
+        let app = app(pool);
+
+        let uri = format!(
+            "/api/v1/documents?place_id={}&expand=place,archive&limit=1",
+            fixture.place_id
+        );
+        let request = Request::builder()
+            .uri(&uri)
+            .header(ACCEPT, "text/csv")
+            .body(Body::empty())
+            .unwrap();
+        let (status, headers, body) = send_request(&app, request).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[CONTENT_TYPE], "text/csv; charset=utf-8");
+        assert_eq!(
+            headers[CONTENT_DISPOSITION],
+            "attachment; filename=\"documents.csv\""
+        );
+        assert_eq!(
+            String::from_utf8(body.to_vec()).unwrap(),
+            format!(
+                "id,date,date_earliest,date_latest,inventory_number,notes,archive_id,institute_id,\
+                 place_id,archive,place\n\
+                 {},1743-05-12,1743-05-12,1743-05-12,INV-1,Baptism,{},{},{},\
+                 Noord-Hollands Archief,Haarlem\n",
+                fixture.id, fixture.archive_id, fixture.institute_id, fixture.place_id
+            )
+        );
+
+        let request = Request::builder()
+            .uri("/api/v1/documents?format=json&sort=-inventory_number")
+            .body(Body::empty())
+            .unwrap();
+        let (status, _, body) = send_request(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let numbers: Vec<&str> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["inventory_number"].as_str().unwrap())
+            .collect();
+        assert_eq!(numbers, vec!["INV-2", "INV-1"]);
+
+        let (status, _) = send(&app, Method::GET, "/api/v1/documents?format=pdf", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,453 @@
+use crate::auth::Actor;
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::extractors::AppQuery;
+use crate::gedcom::export::{Tree, export};
+use crate::gedcom::import::import;
+use crate::handlers::lineage::{DEFAULT_GENERATIONS, MAX_GENERATIONS};
+use crate::schemas::gedcom::{ExportParams, ImportParams};
+
+use axum::{
+    Json,
+    body::Bytes,
+    extract::State,
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::IntoResponse,
+};
+use serde_json::json;
+use std::sync::Arc;
+
+/**
+ * Import Handler
+ * This handler imports the GEDCOM file in the request body and reports per record
+ * what was created, merged with an existing item or skipped.
+ * With `?dry_run=true` nothing is changed, the report shows what would happen
+ */
+pub async fn import_handler(
+    actor: Actor,
+    AppQuery(params): AppQuery<ImportParams>,
+    State(data): State<Arc<AppState>>,
+    body: Bytes,
+) -> Result<impl IntoResponse, AppError> {
+    let text = std::str::from_utf8(&body)
+        .map_err(|_| AppError::Validation("GEDCOM file must be UTF-8 encoded".to_string()))?;
+
+    let report = import(data.pool(), &actor, text, params.dry_run).await?;
+
+    Ok(Json(json!({"status": "success", "data": report})))
+}
+
+/**
+ * Export Handler
+ * This handler exports the whole database as a GEDCOM file, or with `?person_id=` the
+ * tree of a single person up to `?generations=` away. `?version=` is 5.5.1 or 7.0
+ */
+pub async fn export_handler(
+    AppQuery(params): AppQuery<ExportParams>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let generations = params.generations.unwrap_or(DEFAULT_GENERATIONS);
+    if !(1..=MAX_GENERATIONS).contains(&generations) {
+        return Err(AppError::Validation(format!(
+            "generations must be between 1 and {}",
+            MAX_GENERATIONS
+        )));
+    }
+
+    let tree = params.person_id.map(|person_id| Tree {
+        person_id,
+        generations,
+    });
+    let filename = match &tree {
+        Some(tree) => format!("golijath-person-{}.ged", tree.person_id),
+        None => "golijath.ged".to_string(),
+    };
+    let file = export(data.pool(), params.version, tree).await?;
+
+    Ok((
+        [
+            (
+                CONTENT_TYPE,
+                "text/vnd.familysearch.gedcom; charset=utf-8".to_string(),
+            ),
+            (
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        file,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::Body,
+        http::{Method, Request, StatusCode},
+    };
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{app, insert_person, send, send_request};
+
+    const FAMILY: &str = "0 HEAD
+1 GEDC
+2 VERS 5.5.1
+2 FORM LINEAGE-LINKED
+1 CHAR UTF-8
+0 @R1@ REPO
+1 NAME Noord-Hollands Archief
+0 @S1@ SOUR
+1 TITL Doopboek Haarlem
+1 AUTH Burgerweeshuis
+1 REPO @R1@
+2 CALN DTB-1
+1 DATA
+2 EVEN BIRT
+3 DATE 12 MAY 1743
+3 PLAC Haarlem, Noord-Holland
+4 MAP
+5 LATI N52.38
+5 LONG E4.64
+0 @S2@ SOUR
+1 TITL Undated
+1 REPO @R1@
+2 CALN DTB-2
+0 @I1@ INDI
+1 NAME Jan /Pietersz/
+1 SEX M
+1 BIRT
+2 DATE ABT 1710
+2 PLAC Haarlem
+1 SOUR @S1@
+2 EVEN BIRT
+3 ROLE FATH
+0 @I2@ INDI
+1 NAME Maria /Jansdr/
+1 SEX F
+1 SOUR @S1@
+2 EVEN BIRT
+3 ROLE MOTH
+0 @I3@ INDI
+1 NAME Pieter /Jansz/
+1 SEX M
+1 BIRT
+2 DATE 12 MAY 1743
+2 SOUR @S1@
+3 EVEN BIRT
+4 ROLE CHIL
+0 @I4@ INDI
+1 SEX U
+0 @F1@ FAM
+1 HUSB @I1@
+1 WIFE @I2@
+1 CHIL @I3@
+1 MARR
+2 SOUR @S1@
+0 TRLR
+";
+
+    async fn import(app: &axum::Router, uri: &str, file: &str) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header("content-type", "text/vnd.familysearch.gedcom")
+            .body(Body::from(file.to_string()))
+            .unwrap();
+        let (status, _, bytes) = send_request(app, request).await;
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    async fn export(app: &axum::Router, uri: &str) -> (StatusCode, String) {
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let (status, _, bytes) = send_request(app, request).await;
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    async fn count(pool: &PgPool, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn dry_run_reports_and_changes_nothing(pool: PgPool) {
+        let app = app(pool.clone());
+
+        let (status, body) = import(&app, "/api/v1/gedcom/import?dry_run=true", FAMILY).await;
+
+        assert_eq!(status, StatusCode::OK);
+        let summary = &body["data"]["summary"];
+        assert_eq!(
+            summary["archives"],
+            json!({"created": 1, "merged": 0, "skipped": 0})
+        );
+        assert_eq!(
+            summary["places"],
+            json!({"created": 1, "merged": 0, "skipped": 0})
+        );
+        assert_eq!(
+            summary["documents"],
+            json!({"created": 1, "merged": 0, "skipped": 1})
+        );
+        assert_eq!(
+            summary["persons"],
+            json!({"created": 3, "merged": 0, "skipped": 1})
+        );
+        assert_eq!(
+            summary["mentions"],
+            json!({"created": 3, "merged": 0, "skipped": 0})
+        );
+        assert_eq!(
+            summary["relationships"],
+            json!({"created": 3, "merged": 0, "skipped": 0})
+        );
+        let skipped = body["data"]["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|entry| entry["xref"] == "@S2@")
+            .unwrap();
+        assert_eq!(skipped["action"], "skipped");
+        assert!(skipped["reason"].as_str().unwrap().contains("AUTH"));
+
+        for table in [
+            "archives",
+            "places",
+            "documents",
+            "persons",
+            "relationships",
+        ] {
+            assert_eq!(count(&pool, table).await, 0, "{}", table);
+        }
+    }
+
+    #[sqlx::test]
+    async fn import_creates_and_reimport_merges(pool: PgPool) {
+        let app = app(pool.clone());
+
+        let (status, _) = import(&app, "/api/v1/gedcom/import", FAMILY).await;
+        assert_eq!(status, StatusCode::OK);
+        let place: (f64, f64) = sqlx::query_as("SELECT latitude, longitude FROM places")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(place, (52.38, 4.64));
+        let roles: Vec<String> =
+            sqlx::query_scalar("SELECT role::text FROM document_persons ORDER BY person_id")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(roles, vec!["father", "mother", "subject"]);
+        let birth: Option<String> = sqlx::query_scalar(
+            "SELECT birth_date_approximate::text FROM persons WHERE given_names = 'Jan'",
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(birth.as_deref(), Some("true"));
+
+        let (status, body) = import(&app, "/api/v1/gedcom/import", FAMILY).await;
+        assert_eq!(status, StatusCode::OK);
+        let summary = &body["data"]["summary"];
+        assert_eq!(
+            summary["documents"],
+            json!({"created": 0, "merged": 1, "skipped": 1})
+        );
+        assert_eq!(
+            summary["persons"],
+            json!({"created": 0, "merged": 3, "skipped": 1})
+        );
+        assert_eq!(
+            summary["relationships"],
+            json!({"created": 0, "merged": 3, "skipped": 0})
+        );
+        assert_eq!(count(&pool, "persons").await, 3);
+        assert_eq!(count(&pool, "document_persons").await, 3);
+        assert_eq!(count(&pool, "relationships").await, 3);
+    }
+
+    #[sqlx::test]
+    async fn import_is_audited(pool: PgPool) {
+        let app = app(pool.clone());
+        sqlx::query("INSERT INTO places (name) VALUES ('Haarlem')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        import(&app, "/api/v1/gedcom/import?dry_run=true", FAMILY).await;
+        assert_eq!(count(&pool, "audit_log").await, 0);
+
+        let (status, _) = import(&app, "/api/v1/gedcom/import", FAMILY).await;
+        assert_eq!(status, StatusCode::OK);
+        let entries: Vec<(String, String, Value)> = sqlx::query_as(
+            "SELECT resource_type, action::text, changes FROM audit_log ORDER BY id",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let actions: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|(table, action, _)| (table.as_str(), action.as_str()))
+            .collect();
+        assert_eq!(
+            actions,
+            vec![
+                ("archives", "create"),
+                ("institutes", "create"),
+                ("places", "update"),
+                ("documents", "create"),
+            ]
+        );
+        assert_eq!(
+            entries[2].2["latitude"],
+            json!({"before": null, "after": 52.38})
+        );
+    }
+
+    #[sqlx::test]
+    async fn import_rejects_deleted_matches(pool: PgPool) {
+        let app = app(pool.clone());
+
+        for (table, name) in [
+            ("archives", "Noord-Hollands Archief"),
+            ("institutes", "Burgerweeshuis"),
+            ("places", "Haarlem"),
+        ] {
+            let query = format!(
+                "INSERT INTO {} (name, deleted_at) VALUES ($1, now()) RETURNING id",
+                table
+            );
+            let id: i32 = sqlx::query_scalar(&query)
+                .bind(name)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+
+            let (status, body) = import(&app, "/api/v1/gedcom/import", FAMILY).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", table);
+            assert_eq!(
+                body["message"],
+                format!("{} is deleted and must be restored first", name)
+            );
+            assert_eq!(count(&pool, "persons").await, 0);
+
+            let query = format!("UPDATE {} SET deleted_at = NULL WHERE id = $1", table);
+            sqlx::query(&query).bind(id).execute(&pool).await.unwrap();
+        }
+
+        let (status, _) = import(&app, "/api/v1/gedcom/import", FAMILY).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[sqlx::test]
+    async fn import_rejects_malformed_files(pool: PgPool) {
+        let app = app(pool);
+
+        let (status, body) = import(&app, "/api/v1/gedcom/import", "0 HEAD\n2 VERS 5.5.1\n").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(body["message"].as_str().unwrap().starts_with("Line 2"));
+
+        let (status, _) = import(&app, "/api/v1/gedcom/import", "0 @I1@ INDI\n").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let file = "0 HEAD\n1 GEDC\n2 VERS 5.3\n0 TRLR\n";
+        let (status, _) = import(&app, "/api/v1/gedcom/import", file).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[sqlx::test]
+    async fn export_round_trips_through_import(pool: PgPool) {
+        let app = app(pool.clone());
+        import(&app, "/api/v1/gedcom/import", FAMILY).await;
+
+        for version in ["5.5.1", "7.0"] {
+            let uri = format!("/api/v1/gedcom/export?version={}", version);
+            let (status, file) = export(&app, &uri).await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(
+                file.contains(&format!("2 VERS {}\r\n", version)),
+                "{}",
+                file
+            );
+            assert!(file.contains("1 NAME Jan /Pietersz/\r\n"));
+            assert!(file.contains("2 CALN DTB-1\r\n"));
+            assert!(file.contains("5 LATI N52.38\r\n"));
+            assert!(file.contains("2 DATE ABT 1710\r\n"));
+
+            sqlx::query(
+                "TRUNCATE archives, institutes, places, documents, persons RESTART IDENTITY CASCADE",
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
+            let (status, body) = import(&app, "/api/v1/gedcom/import", &file).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+            assert_eq!(body["data"]["version"], version);
+            assert_eq!(
+                body["data"]["summary"]["persons"],
+                json!({"created": 3, "merged": 0, "skipped": 0})
+            );
+
+            let notes: Option<String> = sqlx::query_scalar("SELECT notes FROM documents")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+            assert_eq!(notes.as_deref(), Some("Doopboek Haarlem"));
+            let roles: Vec<String> =
+                sqlx::query_scalar("SELECT role::text FROM document_persons ORDER BY person_id")
+                    .fetch_all(&pool)
+                    .await
+                    .unwrap();
+            assert_eq!(roles, vec!["father", "mother", "subject"], "{}", version);
+            let kinds: Vec<String> = sqlx::query_scalar(
+                "SELECT kind::text FROM relationships ORDER BY kind, relative_id",
+            )
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+            assert_eq!(kinds, vec!["parent", "parent", "spouse"], "{}", version);
+        }
+    }
+
+    #[sqlx::test]
+    async fn export_of_a_person_only_has_their_tree(pool: PgPool) {
+        let app = app(pool.clone());
+        import(&app, "/api/v1/gedcom/import", FAMILY).await;
+        let stranger = insert_person(&pool, "Cornelis").await;
+        let child: i32 = sqlx::query_scalar("SELECT id FROM persons WHERE given_names = 'Pieter'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        let uri = format!("/api/v1/gedcom/export?person_id={}&generations=1", child);
+        let (status, file) = export(&app, &uri).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(file.matches(" INDI\r\n").count(), 3);
+        assert!(!file.contains(&format!("@I{}@", stranger)));
+        assert_eq!(file.matches(" FAM\r\n").count(), 1);
+        assert_eq!(file.matches(" SOUR\r\n").count(), 1);
+
+        // Deleted documents are neither exported nor cited
+        let document: i32 = sqlx::query_scalar("SELECT id FROM documents")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        let uri = format!("/api/v1/documents/{}", document);
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (_, file) = export(&app, "/api/v1/gedcom/export").await;
+        assert_eq!(file.matches(" INDI\r\n").count(), 4);
+        assert!(!file.contains(&format!("@S{}@", document)), "{}", file);
+
+        let uri = format!("/api/v1/gedcom/export?person_id={}", stranger + 1);
+        let (status, _) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let uri = format!("/api/v1/gedcom/export?person_id={}&generations=0", child);
+        let (status, _) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
@@ -0,0 +1,601 @@
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::extractors::AppPath;
+use crate::handlers::documents::EMBEDS;
+use crate::handlers::scans::SCAN_ORDER;
+use crate::iiif::{self, ImageRequest, MAX_AREA, MAX_SIZE, TILE_SIZE};
+use crate::models::documents::ExpandedDocument;
+use crate::models::scans::{DerivativeFormat, DocumentScan, ScanDerivative};
+use crate::pagination::{Embed, embed_columns, embed_joins};
+
+use axum::{
+    Json,
+    extract::State,
+    http::{
+        HeaderMap, StatusCode,
+        header::{CACHE_CONTROL, CONTENT_TYPE, LINK, LOCATION},
+    },
+    response::IntoResponse,
+};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/**
+ * Where the IIIF routes are mounted, to build the absolute URLs that IIIF requires.
+ */
+const PREFIX: &str = "/api/v1/iiif";
+
+const IMAGE_CONTEXT: &str = "http://iiif.io/api/image/3/context.json";
+const PRESENTATION_CONTEXT: &str = "http://iiif.io/api/presentation/3/context.json";
+const PROFILE: &str = "http://iiif.io/api/image/3/level2.json";
+
+/**
+ * Scan files are never replaced, so rendered images can be cached for a day.
+ */
+const CACHE_MAX_AGE: &str = "public, max-age=86400";
+
+/**
+ * Image Service Handler
+ * Redirects the base URI of an image to its `info.json`, as the Image API requires
+ */
+pub async fn image_service_handler(
+    AppPath(id): AppPath<i32>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let base_url = data.iiif().base_url(&headers);
+    (
+        StatusCode::SEE_OTHER,
+        [(LOCATION, format!("{}/info.json", image_id(&base_url, id)))],
+    )
+}
+
+/**
+ * Image Information Handler
+ * The `info.json` of a scan image, with the sizes of its derivatives as preferred sizes
+ */
+pub async fn image_info_handler(
+    AppPath(id): AppPath<i32>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let (scan, width, height) = fetch_image(data.pool(), id).await?;
+    let sizes: Vec<Value> = fetch_derivatives(data.pool(), scan.id)
+        .await?
+        .iter()
+        .map(|derivative| json!({"width": derivative.width, "height": derivative.height}))
+        .collect();
+
+    let info = json!({
+        "@context": IMAGE_CONTEXT,
+        "id": image_id(&data.iiif().base_url(&headers), scan.id),
+        "type": "ImageService3",
+        "protocol": "http://iiif.io/api/image",
+        "profile": "level2",
+        "width": width,
+        "height": height,
+        "maxArea": MAX_AREA,
+        "maxWidth": MAX_SIZE,
+        "maxHeight": MAX_SIZE,
+        "sizes": sizes,
+        "tiles": [{"width": TILE_SIZE, "scaleFactors": [1, 2, 4, 8, 16]}],
+        "extraQualities": ["color", "gray", "bitonal"],
+        "extraFormats": ["webp", "tif"],
+        "extraFeatures": ["mirroring", "sizeUpscaling"],
+    });
+
+    Ok((
+        [
+            (
+                CONTENT_TYPE,
+                format!("application/ld+json;profile=\"{}\"", IMAGE_CONTEXT),
+            ),
+            (LINK, format!("<{}>;rel=\"profile\"", PROFILE)),
+        ],
+        info.to_string(),
+    ))
+}
+
+/**
+ * Image Handler
+ * Renders `{region}/{size}/{rotation}/{quality}.{format}` of a scan image. The smallest
+ * derivative that is still large enough is used as source, the original otherwise.
+ */
+pub async fn image_handler(
+    AppPath((id, region, size, rotation, file)): AppPath<(i32, String, String, String, String)>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let request = ImageRequest::parse(&region, &size, &rotation, &file)?;
+    let (scan, width, height) = fetch_image(data.pool(), id).await?;
+    let plan = request.plan(width, height)?;
+
+    // A derivative is large enough when the region has at least as many pixels in it as the output
+    let derivative = fetch_derivatives(data.pool(), scan.id)
+        .await?
+        .into_iter()
+        .find(|derivative| {
+            let scale = derivative.width as f64 / width as f64;
+            plan.w as f64 * scale >= plan.width as f64
+                && plan.h as f64 * scale >= plan.height as f64
+        });
+    let (sha256, source_scale) = match &derivative {
+        Some(derivative) => (
+            derivative.sha256.clone(),
+            derivative.width as f64 / width as f64,
+        ),
+        None => (scan.sha256.clone().unwrap_or_default(), 1.0),
+    };
+
+    let path = data.scans().path(&sha256);
+    // The permit moves into the task, a render keeps its turn when the client goes away
+    let permit = data.iiif().render_permit().await?;
+    let bytes = tokio::task::spawn_blocking(move || {
+        let _permit = permit;
+        let source = image::ImageReader::open(&path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(|err| format!("Cannot read image {}: {}", sha256, err))?
+            .decode()
+            .map_err(|err| format!("Cannot decode image {}: {}", sha256, err))?;
+        iiif::render(&source, source_scale, &plan, &request)
+    })
+    .await
+    .map_err(|err| AppError::Internal(err.to_string()))?
+    .map_err(AppError::Internal)?;
+
+    Ok((
+        [
+            (CONTENT_TYPE, request.format.mime_type().to_string()),
+            (CACHE_CONTROL, CACHE_MAX_AGE.to_string()),
+            (LINK, format!("<{}>;rel=\"profile\"", PROFILE)),
+        ],
+        bytes,
+    ))
+}
+
+/**
+ * Manifest Handler
+ * A Presentation API 3.0 manifest of a document, with a canvas per scan image in page order
+ */
+pub async fn manifest_handler(
+    AppPath(id): AppPath<i32>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let embeds: Vec<&Embed> = EMBEDS.iter().collect();
+    let query = format!(
+        "SELECT documents.*{} FROM documents{} WHERE documents.id = $1 AND documents.deleted_at IS NULL",
+        embed_columns(&embeds),
+        embed_joins("documents", &embeds),
+    );
+    let item = sqlx::query_as::<_, ExpandedDocument>(&query)
+        .bind(id)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let query = format!(
+        r#"
+        SELECT * FROM document_scans
+        WHERE document_id = $1 AND mime_type LIKE 'image/%' AND width IS NOT NULL
+        ORDER BY {}
+    "#,
+        SCAN_ORDER
+    );
+    let scans = sqlx::query_as::<_, DocumentScan>(&query)
+        .bind(id)
+        .fetch_all(data.pool())
+        .await?;
+
+    let base_url = data.iiif().base_url(&headers);
+    let manifest_id = format!("{}{}/manifests/{}", base_url, PREFIX, id);
+    let thumbnail_size = data.derivatives().settings().sizes[0].max_dimension;
+    let canvases: Vec<Value> = scans
+        .iter()
+        .map(|scan| {
+            let canvas_id = format!("{}/canvas/{}", manifest_id, scan.id);
+            let service_id = image_id(&base_url, scan.id);
+            let label = scan
+                .page_number
+                .clone()
+                .or_else(|| scan.scan_number.clone())
+                .unwrap_or_else(|| format!("Scan {}", scan.id));
+            json!({
+                "id": canvas_id,
+                "type": "Canvas",
+                "label": {"none": [label]},
+                "width": scan.width,
+                "height": scan.height,
+                "thumbnail": [{
+                    "id": format!(
+                        "{}/full/!{},{}/0/default.jpg",
+                        service_id, thumbnail_size, thumbnail_size
+                    ),
+                    "type": "Image",
+                    "format": "image/jpeg",
+                }],
+                "items": [{
+                    "id": format!("{}/page", canvas_id),
+                    "type": "AnnotationPage",
+                    "items": [{
+                        "id": format!("{}/annotation", canvas_id),
+                        "type": "Annotation",
+                        "motivation": "painting",
+                        "target": canvas_id,
+                        "body": {
+                            "id": format!("{}/full/max/0/default.jpg", service_id),
+                            "type": "Image",
+                            "format": "image/jpeg",
+                            "width": scan.width,
+                            "height": scan.height,
+                            "service": [{
+                                "id": service_id,
+                                "type": "ImageService3",
+                                "profile": "level2",
+                            }],
+                        },
+                    }],
+                }],
+            })
+        })
+        .collect();
+
+    let document = &item.document;
+    let name = |name: Option<&str>| name.map(str::to_string).unwrap_or_default();
+    let metadata = [
+        (
+            "Archive",
+            "Archief",
+            name(item.archive.as_ref().map(|archive| archive.name.as_str())),
+        ),
+        (
+            "Institute",
+            "Instelling",
+            name(
+                item.institute
+                    .as_ref()
+                    .map(|institute| institute.name.as_str()),
+            ),
+        ),
+        (
+            "Place",
+            "Plaats",
+            name(item.place.as_ref().map(|place| place.name.as_str())),
+        ),
+        ("Date", "Datum", document.date.to_string()),
+        (
+            "Inventory number",
+            "Inventarisnummer",
+            document.inventory_number.clone(),
+        ),
+    ];
+    let metadata: Vec<Value> = metadata
+        .into_iter()
+        .map(|(en, nl, value)| {
+            json!({
+                "label": {"en": [en], "nl": [nl]},
+                "value": {"none": [value]},
+            })
+        })
+        .collect();
+
+    let mut manifest = json!({
+        "@context": PRESENTATION_CONTEXT,
+        "id": manifest_id,
+        "type": "Manifest",
+        "label": {"none": [document.inventory_number]},
+        "metadata": metadata,
+        "items": canvases,
+    });
+    if let Some(notes) = &document.notes {
+        manifest["summary"] = json!({"none": [notes]});
+    }
+
+    Ok((
+        [(
+            CONTENT_TYPE,
+            format!("application/ld+json;profile=\"{}\"", PRESENTATION_CONTEXT),
+        )],
+        Json(manifest),
+    ))
+}
+
+/**
+ * The scan with `id` with its width and height, or a 404 when it has no image file,
+ * e.g. because it is a PDF, or its document is deleted.
+ */
+async fn fetch_image(pool: &PgPool, id: i32) -> Result<(DocumentScan, u32, u32), AppError> {
+    let scan = sqlx::query_as::<_, DocumentScan>(
+        r#"
+        SELECT document_scans.* FROM document_scans
+        JOIN documents ON documents.id = document_scans.document_id
+        WHERE document_scans.id = $1 AND documents.deleted_at IS NULL
+    "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::not_found(id))?;
+
+    let is_image = scan
+        .mime_type
+        .as_deref()
+        .is_some_and(|mime_type| mime_type.starts_with("image/"));
+    match (is_image, scan.width, scan.height) {
+        (true, Some(width), Some(height)) => Ok((scan, width as u32, height as u32)),
+        _ => Err(AppError::NotFound(format!(
+            "Scan with ID: {} has no image",
+            id
+        ))),
+    }
+}
+
+/**
+ * The WebP derivatives of a scan from small to large. WebP derivatives are lossless,
+ * which makes them as good a source as the original at their size.
+ */
+async fn fetch_derivatives(pool: &PgPool, scan_id: i32) -> Result<Vec<ScanDerivative>, AppError> {
+    let derivatives = sqlx::query_as::<_, ScanDerivative>(
+        r#"
+        SELECT scan_derivatives.* FROM scan_derivatives
+        JOIN document_scans ON document_scans.id = scan_derivatives.scan_id
+        WHERE scan_id = $1 AND format = $2 AND derivatives_status = 'done'
+        ORDER BY scan_derivatives.width
+    "#,
+    )
+    .bind(scan_id)
+    .bind(DerivativeFormat::Webp)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(derivatives)
+}
+
+/**
+ * The URL of the image service of a scan.
+ */
+fn image_id(base_url: &str, id: i32) -> String {
+    format!("{}{}/image/{}", base_url, PREFIX, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        app, app_state, insert_document, insert_named, multipart_request, png_image, send,
+        send_request, signed_in,
+    };
+
+    use axum::{
+        Router,
+        body::Body,
+        http::{
+            Method, Request,
+            header::{HOST, ORIGIN},
+        },
+    };
+
+    async fn seed_document(pool: &PgPool) -> i32 {
+        let archive = insert_named(pool, "archives", "Notarieel archief").await;
+        let institute = insert_named(pool, "institutes", "Regionaal Archief Alkmaar").await;
+        let place = insert_named(pool, "places", "Alkmaar").await;
+        insert_document(pool, "NA-1743-12", archive, institute, place).await
+    }
+
+    async fn upload(app: &Router, id: i32, png: &[u8], page_number: &str) -> i32 {
+        let request = multipart_request(
+            Method::POST,
+            &format!("/api/v1/documents/{}/scans", id),
+            &[
+                ("file", Some("scan.png"), png),
+                ("page_number", None, page_number.as_bytes()),
+            ],
+        );
+        let (status, _, body) = send_request(app, request).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        body["data"]["item"]["id"].as_i64().unwrap() as i32
+    }
+
+    async fn get(app: &Router, uri: &str) -> (StatusCode, HeaderMap, axum::body::Bytes) {
+        let request = Request::builder()
+            .uri(uri)
+            .header(HOST, "archive.example")
+            .body(Body::empty())
+            .unwrap();
+        send_request(app, request).await
+    }
+
+    #[sqlx::test]
+    async fn info_describes_scan_image(pool: PgPool) {
+        let app = app(pool.clone());
+        let id = seed_document(&pool).await;
+        let scan_id = upload(&app, id, &png_image(600, 400), "1").await;
+
+        let uri = format!("/api/v1/iiif/image/{}", scan_id);
+        let (status, headers, _) = get(&app, &uri).await;
+        assert_eq!(status, StatusCode::SEE_OTHER);
+        assert_eq!(
+            headers[LOCATION],
+            format!("http://archive.example{}/info.json", uri)
+        );
+
+        let (status, headers, body) = get(&app, &format!("{}/info.json", uri)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(
+            headers[CONTENT_TYPE]
+                .to_str()
+                .unwrap()
+                .starts_with("application/ld+json")
+        );
+        let info: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(info["id"], format!("http://archive.example{}", uri));
+        assert_eq!(info["type"], "ImageService3");
+        assert_eq!(info["profile"], "level2");
+        assert_eq!(
+            (info["width"].as_i64(), info["height"].as_i64()),
+            (Some(600), Some(400))
+        );
+        assert_eq!(info["sizes"], json!([]));
+
+        let (status, _, _) = get(&app, "/api/v1/iiif/image/999999/info.json").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn images_are_cut_scaled_and_rotated(pool: PgPool) {
+        let state = app_state(pool.clone());
+        let app = signed_in(state.clone());
+        let id = seed_document(&pool).await;
+        let scan_id = upload(&app, id, &png_image(600, 400), "1").await;
+        let base = format!("/api/v1/iiif/image/{}", scan_id);
+
+        let image = |bytes: &[u8]| image::load_from_memory(bytes).unwrap();
+        let (status, headers, body) = get(&app, &format!("{}/full/max/0/default.jpg", base)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[CONTENT_TYPE], "image/jpeg");
+        assert_eq!(image(&body).to_rgb8().dimensions(), (600, 400));
+
+        let (_, _, body) = get(
+            &app,
+            &format!("{}/100,50,200,100/100,/90/default.png", base),
+        )
+        .await;
+        let rendered = image(&body).to_rgb8();
+        assert_eq!(rendered.dimensions(), (50, 100));
+        // The top left of the region, red 100 and green 50, is now at the top right
+        let pixel = rendered.get_pixel(49, 0).0;
+        assert!(pixel[0].abs_diff(100) <= 4 && pixel[1].abs_diff(50) <= 4);
+
+        let (_, headers, body) = get(&app, &format!("{}/square/!64,64/0/gray.webp", base)).await;
+        assert_eq!(headers[CONTENT_TYPE], "image/webp");
+        let rendered = image(&body).to_rgb8();
+        assert_eq!(rendered.dimensions(), (64, 64));
+        assert!(
+            rendered
+                .pixels()
+                .all(|pixel| pixel[0] == pixel[1] && pixel[1] == pixel[2])
+        );
+
+        // Served from the derivatives once they are made, at the same size
+        state.derivatives().process_pending().await.unwrap();
+        let (_, _, body) = get(&app, &format!("{}/info.json", base)).await;
+        let info: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(info["sizes"][0], json!({"width": 256, "height": 171}));
+        let (status, _, body) = get(&app, &format!("{}/full/300,/0/default.jpg", base)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(image(&body).to_rgb8().dimensions(), (300, 200));
+
+        for (path, expected) in [
+            ("full/max/45/default.jpg", StatusCode::NOT_IMPLEMENTED),
+            ("full/1200,/0/default.jpg", StatusCode::BAD_REQUEST),
+            ("700,0,10,10/max/0/default.jpg", StatusCode::BAD_REQUEST),
+            ("full/max/0/sepia.jpg", StatusCode::BAD_REQUEST),
+        ] {
+            let (status, _, _) = get(&app, &format!("{}/{}", base, path)).await;
+            assert_eq!(status, expected, "{}", path);
+        }
+    }
+
+    #[sqlx::test]
+    async fn iiif_is_open_to_any_origin(pool: PgPool) {
+        let app = app(pool.clone());
+        let id = seed_document(&pool).await;
+
+        let request = Request::builder()
+            .uri(format!("/api/v1/iiif/manifests/{}", id))
+            .header(ORIGIN, "https://viewer.example")
+            .body(Body::empty())
+            .unwrap();
+        let (status, headers, _) = send_request(&app, request).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["access-control-allow-origin"], "*");
+    }
+
+    #[sqlx::test]
+    async fn manifest_has_canvases_in_page_order(pool: PgPool) {
+        let app = app(pool.clone());
+        let id = seed_document(&pool).await;
+        let second = upload(&app, id, &png_image(30, 40), "10").await;
+        let first = upload(&app, id, &png_image(20, 10), "9").await;
+        sqlx::query("INSERT INTO document_scans (document_id, scan_number) VALUES ($1, 'S-3')")
+            .bind(id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let (status, manifest) = send(
+            &app,
+            Method::GET,
+            &format!("/api/v1/iiif/manifests/{}", id),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(manifest["type"], "Manifest");
+        assert_eq!(manifest["label"], json!({"none": ["NA-1743-12"]}));
+
+        let metadata: Vec<(String, String)> = manifest["metadata"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                (
+                    entry["label"]["en"][0].as_str().unwrap().to_string(),
+                    entry["value"]["none"][0].as_str().unwrap().to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            metadata,
+            [
+                ("Archive", "Notarieel archief"),
+                ("Institute", "Regionaal Archief Alkmaar"),
+                ("Place", "Alkmaar"),
+                ("Date", "1743-05-12"),
+                ("Inventory number", "NA-1743-12"),
+            ]
+            .map(|(label, value)| (label.to_string(), value.to_string()))
+        );
+
+        // The scan without a file has no image and no canvas
+        let canvases = manifest["items"].as_array().unwrap();
+        assert_eq!(canvases.len(), 2);
+        assert_eq!(canvases[0]["label"], json!({"none": ["9"]}));
+        assert_eq!(canvases[1]["label"], json!({"none": ["10"]}));
+        assert_eq!(
+            (
+                canvases[0]["width"].as_i64(),
+                canvases[0]["height"].as_i64()
+            ),
+            (Some(20), Some(10))
+        );
+
+        let annotation = &canvases[0]["items"][0]["items"][0];
+        assert_eq!(annotation["motivation"], "painting");
+        assert_eq!(annotation["target"], canvases[0]["id"]);
+        let service_id = annotation["body"]["service"][0]["id"].as_str().unwrap();
+        assert!(service_id.ends_with(&format!("/api/v1/iiif/image/{}", first)));
+        assert!(
+            canvases[1]["items"][0]["items"][0]["body"]["id"]
+                .as_str()
+                .unwrap()
+                .contains(&format!("/image/{}/full/max/0/default.jpg", second))
+        );
+
+        let (status, _) = send(&app, Method::GET, "/api/v1/iiif/manifests/999999", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Deleted documents have no manifest and their scans no images
+        let uri = format!("/api/v1/documents/{}", id);
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        for uri in [
+            format!("/api/v1/iiif/manifests/{}", id),
+            format!("/api/v1/iiif/image/{}/info.json", first),
+        ] {
+            let (status, _) = send(&app, Method::GET, &uri, None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{}", uri);
+        }
+    }
+}
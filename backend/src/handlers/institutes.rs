@@ -1,11 +1,19 @@
+use crate::audit;
+use crate::auth::Actor;
 use crate::db::AppState;
+use crate::errors::AppError;
+use crate::export::{ExportFormat, export_list};
+use crate::extractors::{AppJson, AppPath, AppQuery};
+use crate::handlers::common::{delete_referenced_item, restore_item};
 use crate::models::institutes::Institute;
+use crate::pagination::{Listing, SortField, fetch_page};
 use crate::schemas::institutes::{CreateInstitute, UpdateInstitute};
+use crate::schemas::{DeleteParams, DeletedParams, ExportParams, PageParams};
 
 use axum::{
     Json,
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{OriginalUri, State},
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
 };
 use serde_json::json;
@@ -13,65 +21,70 @@ use std::sync::Arc;
 
 const TABLE: &str = "institutes";
 
+const LISTING: Listing = Listing {
+    table: TABLE,
+    sort_fields: &[
+        SortField {
+            name: "name",
+            column: "name",
+            sql_type: "text",
+        },
+        SortField {
+            name: "id",
+            column: "id",
+            sql_type: "integer",
+        },
+    ],
+    default_sort: "name",
+};
+
 /**
  * List Items Handler
- * This handler fetches a list of all items from postgres
+ * This handler fetches a page of items from postgres, see `PageParams` and `DeletedParams`,
+ * or exports all of them as CSV, XLSX or JSON, see `ExportParams`
  */
 pub async fn items_list_handler(
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    AppQuery(params): AppQuery<PageParams>,
+    AppQuery(export): AppQuery<ExportParams>,
+    AppQuery(deleted): AppQuery<DeletedParams>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("SELECT * FROM {} ORDER BY name", TABLE);
-    let query_result = sqlx::query_as::<_, Institute>(&query)
-        .fetch_all(data.pool())
-        .await;
-
-    if query_result.is_err() {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": "🔥 Something bad happened while fetching all items",
-        });
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)));
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(format) = ExportFormat::negotiate(export.format, &headers) {
+        let sort = params.sort.as_deref();
+        return export_list::<Institute>(data.pool(), &LISTING, sort, &deleted, &[], format).await;
     }
 
-    let items = query_result.unwrap();
+    let page = fetch_page::<Institute>(data.pool(), &LISTING, &params, &deleted).await?;
 
-    let json_response = serde_json::json!({
-        "status": "success",
-        "results": items.len(),
-        "items": items
-    });
-    Ok(Json(json_response))
+    Ok(page.into_list_response(&uri))
 }
 
 /**
  * Fetch a single Item
+ * A deleted item is only found with `?include_deleted=true`
  */
 pub async fn get_item_handler(
-    Path(id): Path<i32>,
+    AppPath(id): AppPath<i32>,
+    AppQuery(deleted): AppQuery<DeletedParams>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("SELECT * FROM {} WHERE id = $1", TABLE);
-    let query_result = sqlx::query_as::<_, Institute>(&query)
+) -> Result<impl IntoResponse, AppError> {
+    let query = format!(
+        "SELECT * FROM {} WHERE id = $1 AND ($2 OR deleted_at IS NULL)",
+        TABLE
+    );
+    let item = sqlx::query_as::<_, Institute>(&query)
         .bind(id)
-        .fetch_one(data.pool())
-        .await;
-
-    match query_result {
-        Ok(item) => {
-            let item_response = serde_json::json!({"status": "success","data": serde_json::json!({
-                "item": item
-            })});
-
-            return Ok(Json(item_response));
-        }
-        Err(_) => {
-            let error_response = serde_json::json!({
-                "status": "fail",
-                "message": format!("Item with ID: {} not found", id)
-            });
-            return Err((StatusCode::NOT_FOUND, Json(error_response)));
-        }
-    }
+        .bind(deleted.include_deleted)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
 }
 
 /**
@@ -79,38 +92,25 @@ pub async fn get_item_handler(
  * This handler adds a new item to postgres
  */
 pub async fn create_item_handler(
+    actor: Actor,
     State(data): State<Arc<AppState>>,
-    Json(body): Json<CreateInstitute>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+    AppJson(body): AppJson<CreateInstitute>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
+
     let query = format!("INSERT INTO {} (name) VALUES ($1) RETURNING *", TABLE);
-    let query_result = sqlx::query_as::<_, Institute>(&query)
+    let item = sqlx::query_as::<_, Institute>(&query)
         .bind(body.name.to_string())
-        .fetch_one(data.pool())
-        .await;
-    match query_result {
-        Ok(item) => {
-            let item_response = json!({"status": "success","data": json!({
-                "item": item
-            })});
-
-            return Ok((StatusCode::CREATED, Json(item_response)));
-        }
-        Err(e) => {
-            if e.to_string()
-                .contains("duplicate key value violates unique constraint")
-            {
-                let error_response = serde_json::json!({
-                    "status": "fail",
-                    "message": "Item with that name already exists",
-                });
-                return Err((StatusCode::CONFLICT, Json(error_response)));
-            }
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"status": "error","message": format!("{:?}", e)})),
-            ));
-        }
-    }
+        .fetch_one(&mut *tx)
+        .await?;
+    audit::record(&mut tx, &actor, TABLE, item.id, None, Some(json!(item))).await?;
+
+    tx.commit().await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
 }
 
 /**
@@ -118,72 +118,79 @@ pub async fn create_item_handler(
  * This handler handles edits of existing items
  */
 pub async fn edit_item_handler(
-    Path(id): Path<i32>,
+    AppPath(id): AppPath<i32>,
+    actor: Actor,
     State(data): State<Arc<AppState>>,
-    Json(body): Json<UpdateInstitute>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("SELECT * FROM {} WHERE id = $1", TABLE);
-    let query_result = sqlx::query_as::<_, Institute>(&query)
+    AppJson(body): AppJson<UpdateInstitute>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
+
+    let query = format!(
+        "SELECT * FROM {} WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        TABLE
+    );
+    let before = sqlx::query_as::<_, Institute>(&query)
         .bind(id)
-        .fetch_one(data.pool())
-        .await;
-
-    if query_result.is_err() {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": format!("🔥 Item with ID: {} not found", id)
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
-    }
-
-    let item = query_result.unwrap();
-
-    let query = format!("UPDATE {} SET name = $1 WHERE id = $2 RETURNING *", TABLE);
-    let query_result = sqlx::query_as::<_, Institute>(&query)
-        .bind(body.name.to_owned().unwrap_or(item.name))
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let query = format!(
+        "UPDATE {} SET name = COALESCE($1, name) WHERE id = $2 RETURNING *",
+        TABLE
+    );
+    let item = sqlx::query_as::<_, Institute>(&query)
+        .bind(body.name)
         .bind(id)
-        .fetch_one(data.pool())
-        .await;
-
-    match query_result {
-        Ok(item) => {
-            let item_response = serde_json::json!({"status": "success","data": serde_json::json!({
-                "item": item
-            })});
-
-            return Ok(Json(item_response));
-        }
-        Err(err) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"status": "error","message": format!("{:?}", err)})),
-            ));
-        }
-    }
+        .fetch_one(&mut *tx)
+        .await?;
+    audit::record(
+        &mut tx,
+        &actor,
+        TABLE,
+        id,
+        Some(json!(before)),
+        Some(json!(item)),
+    )
+    .await?;
+
+    tx.commit().await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
 }
 
 /**
  * Delete Item Handler
+ * The item is marked as deleted, `?purge=true` removes it for good.
+ * Documents that refer to the item block the delete, unless `?reassign_to={id}` is given
  */
 pub async fn delete_item_handler(
-    Path(id): Path<i32>,
+    AppPath(id): AppPath<i32>,
+    AppQuery(params): AppQuery<DeleteParams>,
+    actor: Actor,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("DELETE FROM {} WHERE id = $1", TABLE);
-    let rows_affected = sqlx::query(&query)
-        .bind(id) // $1
-        .execute(data.pool())
-        .await
-        .unwrap()
-        .rows_affected();
-
-    if rows_affected == 0 {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": format!("Item with ID: {} not found", id)
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
-    }
+) -> Result<impl IntoResponse, AppError> {
+    delete_referenced_item(data.pool(), &actor, TABLE, "institute_id", &[], id, &params).await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
+
+/**
+ * Restore Item Handler
+ * This handler undoes the delete of an item that was not purged
+ */
+pub async fn restore_item_handler(
+    AppPath(id): AppPath<i32>,
+    actor: Actor,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = restore_item::<Institute>(data.pool(), &actor, TABLE, &[], id).await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
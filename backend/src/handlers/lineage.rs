@@ -0,0 +1,457 @@
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::extractors::{AppPath, AppQuery};
+use crate::models::persons::Person;
+use crate::models::relationships::LineageEdge;
+use crate::schemas::relationships::{LineageParams, LineageShape};
+
+use axum::{Json, extract::State, response::IntoResponse};
+use serde_json::{Value, json};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+pub const DEFAULT_GENERATIONS: i32 = 4;
+/** Keeps a cycle or a very deep pedigree from making the query walk on */
+pub const MAX_GENERATIONS: i32 = 16;
+
+#[derive(Clone, Copy)]
+pub enum Direction {
+    Ancestors,
+    Descendants,
+}
+
+impl Direction {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Ancestors => "ancestors",
+            Self::Descendants => "descendants",
+        }
+    }
+
+    /**
+     * The relationship columns of the person a step starts at and the person it reaches.
+     */
+    fn columns(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Ancestors => ("relative_id", "person_id"),
+            Self::Descendants => ("person_id", "relative_id"),
+        }
+    }
+
+    fn near(self, edge: &LineageEdge) -> i32 {
+        match self {
+            Self::Ancestors => edge.child_id,
+            Self::Descendants => edge.parent_id,
+        }
+    }
+
+    fn far(self, edge: &LineageEdge) -> i32 {
+        match self {
+            Self::Ancestors => edge.parent_id,
+            Self::Descendants => edge.child_id,
+        }
+    }
+}
+
+/**
+ * The parent relationships within `generations` of the person with `id`, each with the
+ * generation it is first reached at. The walk goes one generation further than asked,
+ * so that the caller can tell whether the lineage was cut off.
+ *
+ * `UNION` drops rows that were already found in the same generation, so a cycle in the data
+ * only repeats until the generation limit instead of forever.
+ */
+pub async fn fetch_edges(
+    pool: &PgPool,
+    id: i32,
+    generations: i32,
+    direction: Direction,
+) -> Result<Vec<LineageEdge>, AppError> {
+    let (near, far) = direction.columns();
+    let query = format!(
+        r#"
+        WITH RECURSIVE lineage (id, parent_id, child_id, far_id, generation) AS (
+            SELECT id, person_id, relative_id, {far}, 1
+            FROM relationships
+            WHERE kind = 'parent' AND {near} = $1
+        UNION
+            SELECT relationships.id, relationships.person_id, relationships.relative_id,
+                relationships.{far}, lineage.generation + 1
+            FROM lineage
+            JOIN relationships
+                ON relationships.kind = 'parent' AND relationships.{near} = lineage.far_id
+            WHERE lineage.generation <= $2
+        )
+        SELECT id, parent_id, child_id, MIN(generation) AS generation
+        FROM lineage
+        GROUP BY id, parent_id, child_id
+        ORDER BY generation, id
+    "#
+    );
+
+    let edges = sqlx::query_as::<_, LineageEdge>(&query)
+        .bind(id)
+        .bind(generations)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(edges)
+}
+
+/**
+ * The ids of the edges that lead back to a person on the path from `root` to them,
+ * found with a depth-first search that visits every person once.
+ */
+fn find_cycles(root: i32, edges: &[LineageEdge], direction: Direction) -> Vec<i32> {
+    let mut next: HashMap<i32, Vec<&LineageEdge>> = HashMap::new();
+    for edge in edges {
+        next.entry(direction.near(edge)).or_default().push(edge);
+    }
+
+    let mut cycles = Vec::new();
+    let mut on_path: HashSet<i32> = HashSet::from([root]);
+    let mut done: HashSet<i32> = HashSet::new();
+    // Each frame is a person and the index of the next edge to follow from them
+    let mut stack: Vec<(i32, usize)> = vec![(root, 0)];
+
+    while let Some((person, index)) = stack.pop() {
+        let Some(edge) = next.get(&person).and_then(|edges| edges.get(index)) else {
+            on_path.remove(&person);
+            done.insert(person);
+            continue;
+        };
+        stack.push((person, index + 1));
+
+        let relative = direction.far(edge);
+        if on_path.contains(&relative) {
+            cycles.push(edge.id);
+        } else if !done.contains(&relative) {
+            on_path.insert(relative);
+            stack.push((relative, 0));
+        }
+    }
+
+    cycles.sort_unstable();
+    cycles
+}
+
+/**
+ * A person in the lineage, with the generation they are first reached at.
+ */
+fn node(person: &Person, generation: i32) -> Value {
+    let mut node = json!(person);
+    node["generation"] = json!(generation);
+    node
+}
+
+/**
+ * Nest the lineage below `person`. Edges that close a cycle are left out and persons that
+ * are reached more than once (pedigree collapse) are only expanded the first time.
+ */
+fn build_tree(
+    person: i32,
+    generation: i32,
+    persons: &HashMap<i32, Person>,
+    next: &HashMap<i32, Vec<&LineageEdge>>,
+    direction: Direction,
+    expanded: &mut HashSet<i32>,
+) -> Value {
+    let Some(found) = persons.get(&person) else {
+        return json!({ "id": person, "generation": generation });
+    };
+    let mut tree = node(found, generation);
+    let key = match direction {
+        Direction::Ancestors => "parents",
+        Direction::Descendants => "children",
+    };
+
+    if !expanded.insert(person) {
+        tree["repeated"] = json!(true);
+        return tree;
+    }
+
+    let relatives: Vec<Value> = next
+        .get(&person)
+        .into_iter()
+        .flatten()
+        .map(|edge| {
+            build_tree(
+                direction.far(edge),
+                generation + 1,
+                persons,
+                next,
+                direction,
+                expanded,
+            )
+        })
+        .collect();
+    tree[key] = json!(relatives);
+
+    tree
+}
+
+async fn lineage(
+    data: &AppState,
+    id: i32,
+    params: LineageParams,
+    direction: Direction,
+) -> Result<Value, AppError> {
+    let generations = params.generations.unwrap_or(DEFAULT_GENERATIONS);
+    if !(1..=MAX_GENERATIONS).contains(&generations) {
+        return Err(AppError::Validation(format!(
+            "generations must be between 1 and {}",
+            MAX_GENERATIONS
+        )));
+    }
+
+    let mut edges = fetch_edges(data.pool(), id, generations, direction).await?;
+    let truncated = edges.iter().any(|edge| edge.generation > generations);
+    edges.retain(|edge| edge.generation <= generations);
+    let cycles = find_cycles(id, &edges, direction);
+
+    let mut generation_of: HashMap<i32, i32> = HashMap::from([(id, 0)]);
+    for edge in &edges {
+        generation_of
+            .entry(direction.far(edge))
+            .or_insert(edge.generation);
+    }
+
+    let ids: Vec<i32> = generation_of.keys().copied().collect();
+    let persons: HashMap<i32, Person> =
+        sqlx::query_as::<_, Person>("SELECT * FROM persons WHERE id = ANY($1)")
+            .bind(ids)
+            .fetch_all(data.pool())
+            .await?
+            .into_iter()
+            .map(|person| (person.id, person))
+            .collect();
+    if !persons.contains_key(&id) {
+        return Err(AppError::not_found(id));
+    }
+
+    let mut lineage = json!({
+        "person_id": id,
+        "direction": direction.name(),
+        "generations": generations,
+        "truncated": truncated,
+        "cycles": cycles,
+    });
+
+    match params.shape {
+        LineageShape::Graph => {
+            let mut nodes: Vec<(&i32, &Person)> = persons.iter().collect();
+            nodes.sort_by_key(|(id, _)| (generation_of[*id], **id));
+            lineage["nodes"] = nodes
+                .into_iter()
+                .map(|(id, person)| node(person, generation_of[id]))
+                .collect();
+            lineage["edges"] = json!(edges);
+        }
+        LineageShape::Tree => {
+            let cycles: HashSet<i32> = cycles.into_iter().collect();
+            let mut next: HashMap<i32, Vec<&LineageEdge>> = HashMap::new();
+            for edge in edges.iter().filter(|edge| !cycles.contains(&edge.id)) {
+                next.entry(direction.near(edge)).or_default().push(edge);
+            }
+            lineage["tree"] = build_tree(id, 0, &persons, &next, direction, &mut HashSet::new());
+        }
+    }
+
+    Ok(lineage)
+}
+
+/**
+ * Ancestors Handler
+ * The parents of a person, their parents and so on, up to `?generations=` generations back
+ */
+pub async fn ancestors_handler(
+    AppPath(id): AppPath<i32>,
+    AppQuery(params): AppQuery<LineageParams>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let lineage = lineage(&data, id, params, Direction::Ancestors).await?;
+
+    Ok(Json(json!({"status": "success", "data": lineage})))
+}
+
+/**
+ * Descendants Handler
+ * The children of a person, their children and so on, up to `?generations=` generations down
+ */
+pub async fn descendants_handler(
+    AppPath(id): AppPath<i32>,
+    AppQuery(params): AppQuery<LineageParams>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let lineage = lineage(&data, id, params, Direction::Descendants).await?;
+
+    Ok(Json(json!({"status": "success", "data": lineage})))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::Value;
+    use sqlx::PgPool;
+
+    use crate::test_utils::{app, insert_document, insert_named, insert_person, send};
+
+    struct Family {
+        grandfather: i32,
+        grandmother: i32,
+        father: i32,
+        mother: i32,
+        child: i32,
+    }
+
+    async fn insert_parent(pool: &PgPool, parent: i32, child: i32) -> i32 {
+        sqlx::query_scalar(
+            "INSERT INTO relationships (kind, person_id, relative_id) VALUES ('parent', $1, $2) RETURNING id",
+        )
+        .bind(parent)
+        .bind(child)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    /**
+     * Three generations: both parents of the child, and both parents of the father.
+     */
+    async fn seed(pool: &PgPool) -> Family {
+        let archive_id = insert_named(pool, "archives", "Noord-Hollands Archief").await;
+        let institute_id = insert_named(pool, "institutes", "Burgerweeshuis").await;
+        let place_id = insert_named(pool, "places", "Haarlem").await;
+        let document_id = insert_document(pool, "INV-1", archive_id, institute_id, place_id).await;
+
+        let family = Family {
+            grandfather: insert_person(pool, "Pieter").await,
+            grandmother: insert_person(pool, "Grietje").await,
+            father: insert_person(pool, "Jan").await,
+            mother: insert_person(pool, "Maria").await,
+            child: insert_person(pool, "Cornelis").await,
+        };
+        for (parent, child) in [
+            (family.grandfather, family.father),
+            (family.grandmother, family.father),
+            (family.father, family.child),
+            (family.mother, family.child),
+        ] {
+            let id = insert_parent(pool, parent, child).await;
+            sqlx::query("INSERT INTO relationship_documents VALUES ($1, $2)")
+                .bind(id)
+                .bind(document_id)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+
+        family
+    }
+
+    fn ids(values: &Value) -> Vec<i32> {
+        values
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value["id"].as_i64().unwrap() as i32)
+            .collect()
+    }
+
+    #[sqlx::test]
+    async fn ancestors_returns_a_graph_per_generation(pool: PgPool) {
+        let family = seed(&pool).await;
+        let app = app(pool);
+
+        let uri = format!("/api/v1/persons/{}/ancestors", family.child);
+        let (status, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let lineage = &body["data"];
+        assert_eq!(
+            ids(&lineage["nodes"]),
+            vec![
+                family.child,
+                family.father,
+                family.mother,
+                family.grandfather,
+                family.grandmother
+            ]
+        );
+        assert_eq!(lineage["nodes"][3]["generation"], 2);
+        assert_eq!(lineage["edges"].as_array().unwrap().len(), 4);
+        assert_eq!(lineage["truncated"], false);
+        assert!(lineage["cycles"].as_array().unwrap().is_empty());
+
+        let uri = format!("/api/v1/persons/{}/ancestors?generations=1", family.child);
+        let (_, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(
+            ids(&body["data"]["nodes"]),
+            vec![family.child, family.father, family.mother]
+        );
+        assert_eq!(body["data"]["truncated"], true);
+    }
+
+    #[sqlx::test]
+    async fn descendants_returns_a_nested_tree(pool: PgPool) {
+        let family = seed(&pool).await;
+        let app = app(pool);
+
+        let uri = format!(
+            "/api/v1/persons/{}/descendants?shape=tree",
+            family.grandmother
+        );
+        let (status, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        let tree = &body["data"]["tree"];
+        assert_eq!(tree["id"], family.grandmother);
+        assert_eq!(ids(&tree["children"]), vec![family.father]);
+        assert_eq!(ids(&tree["children"][0]["children"]), vec![family.child]);
+        assert_eq!(tree["children"][0]["children"][0]["generation"], 2);
+        assert!(body["data"].get("nodes").is_none());
+    }
+
+    #[sqlx::test]
+    async fn cycles_are_reported_and_stop_the_walk(pool: PgPool) {
+        let family = seed(&pool).await;
+        // A bad record that makes the child a parent of their own grandfather
+        let bad = insert_parent(&pool, family.child, family.grandfather).await;
+        let app = app(pool);
+
+        let uri = format!(
+            "/api/v1/persons/{}/ancestors?generations={}",
+            family.child,
+            super::MAX_GENERATIONS
+        );
+        let (status, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["cycles"], serde_json::json!([bad]));
+        assert_eq!(body["data"]["nodes"].as_array().unwrap().len(), 5);
+        assert_eq!(body["data"]["truncated"], false);
+
+        let (status, body) = send(&app, Method::GET, &format!("{}&shape=tree", uri), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let grandfather = &body["data"]["tree"]["parents"][0]["parents"][0];
+        assert_eq!(grandfather["id"], family.grandfather);
+        assert!(grandfather["parents"].as_array().unwrap().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn lineage_rejects_unknown_persons_and_deep_walks(pool: PgPool) {
+        let family = seed(&pool).await;
+        let app = app(pool);
+
+        let (status, _) = send(&app, Method::GET, "/api/v1/persons/999/ancestors", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        for generations in [0, super::MAX_GENERATIONS + 1] {
+            let uri = format!(
+                "/api/v1/persons/{}/descendants?generations={}",
+                family.grandfather, generations
+            );
+            let (status, body) = send(&app, Method::GET, &uri, None).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", generations);
+            assert_eq!(body["code"], "validation_failed");
+        }
+    }
+}
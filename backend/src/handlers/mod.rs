@@ -1,5 +1,20 @@
+pub mod api_keys;
 pub mod archives;
+pub mod audit;
+pub mod auth;
+pub mod common;
+pub mod document_import;
 pub mod documents;
+pub mod gedcom;
 pub mod health_check;
+pub mod iiif;
 pub mod institutes;
+pub mod lineage;
+pub mod persons;
 pub mod places;
+pub mod relationships;
+pub mod scans;
+pub mod search;
+pub mod transcriptions;
+pub mod users;
+pub mod vocabularies;
@@ -0,0 +1,591 @@
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::extractors::{AppJson, AppPath, AppQuery};
+use crate::handlers::common::{ensure_document_exists, ensure_not_deleted};
+use crate::models::persons::{DocumentPerson, Person, PersonMention, Sex};
+use crate::pagination::{Listing, SortField, fetch_page};
+use crate::schemas::PageParams;
+use crate::schemas::persons::{CreateDocumentPerson, CreatePerson, UpdatePerson};
+
+use axum::{
+    Json,
+    extract::{OriginalUri, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use serde_json::json;
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+use std::sync::Arc;
+
+const TABLE: &str = "persons";
+
+const LISTING: Listing = Listing {
+    table: TABLE,
+    sort_fields: &[
+        SortField {
+            name: "name",
+            column: "sort_name",
+            sql_type: "text",
+        },
+        SortField {
+            name: "id",
+            column: "id",
+            sql_type: "integer",
+        },
+    ],
+    default_sort: "name",
+};
+
+/**
+ * The columns that create and edit write, in the order of `push_person_values`.
+ */
+pub const PERSON_COLUMNS: &str = r#"
+    given_names, patronymic, surname_prefix, surname, sex,
+    birth_date_earliest, birth_date_latest, birth_date_precision,
+    birth_date_approximate, birth_date_uncertain, birth_place_id,
+    death_date_earliest, death_date_latest, death_date_precision,
+    death_date_approximate, death_date_uncertain, death_place_id
+"#;
+
+/**
+ * Push the values of `person` as a comma separated list of binds.
+ */
+pub fn push_person_values(query: &mut QueryBuilder<'_, Postgres>, person: CreatePerson) {
+    let mut values = query.separated(", ");
+    values
+        .push_bind(person.given_names)
+        .push_bind(person.patronymic)
+        .push_bind(person.surname_prefix)
+        .push_bind(person.surname)
+        .push_bind(person.sex.unwrap_or(Sex::Unknown));
+    for (date, place_id) in [
+        (person.birth_date, person.birth_place_id),
+        (person.death_date, person.death_place_id),
+    ] {
+        values
+            .push_bind(date.map(|date| date.earliest))
+            .push_bind(date.map(|date| date.latest))
+            .push_bind(date.map(|date| date.precision))
+            .push_bind(date.is_some_and(|date| date.approximate))
+            .push_bind(date.is_some_and(|date| date.uncertain))
+            .push_bind(place_id);
+    }
+}
+
+/**
+ * List Items Handler
+ * This handler fetches a page of items from postgres, see `PageParams`
+ */
+pub async fn items_list_handler(
+    OriginalUri(uri): OriginalUri,
+    AppQuery(params): AppQuery<PageParams>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let page = fetch_page::<Person>(data.pool(), &LISTING, &params, &()).await?;
+
+    Ok(page.into_list_response(&uri))
+}
+
+/**
+ * Fetch a single Item
+ */
+pub async fn get_item_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let query = format!("SELECT * FROM {} WHERE id = $1", TABLE);
+    let item = sqlx::query_as::<_, Person>(&query)
+        .bind(id)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Create Item Handler
+ * This handler adds a new item to postgres
+ */
+pub async fn create_item_handler(
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<CreatePerson>,
+) -> Result<impl IntoResponse, AppError> {
+    body.validate().map_err(AppError::Validation)?;
+
+    let mut tx = data.pool().begin().await?;
+    ensure_places_not_deleted(&mut tx, &body).await?;
+
+    let mut query = QueryBuilder::new(format!(
+        "INSERT INTO {} ({}) VALUES (",
+        TABLE, PERSON_COLUMNS
+    ));
+    push_person_values(&mut query, body);
+    query.push(") RETURNING *");
+    let item = query.build_query_as::<Person>().fetch_one(&mut *tx).await?;
+
+    tx.commit().await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
+}
+
+/**
+ * Edit Item Handler
+ * This handler handles edits of existing items.
+ * Fields that are left out of the request body keep their stored value, `null` clears them.
+ */
+pub async fn edit_item_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<UpdatePerson>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
+
+    // Locking the person keeps concurrent edits of other fields from being lost
+    let query = format!("SELECT * FROM {} WHERE id = $1 FOR UPDATE", TABLE);
+    let item = sqlx::query_as::<_, Person>(&query)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let person = body.apply_to(item);
+    person.validate().map_err(AppError::Validation)?;
+    ensure_places_not_deleted(&mut tx, &person).await?;
+
+    let mut query = QueryBuilder::new(format!("UPDATE {} SET ({}) = (", TABLE, PERSON_COLUMNS));
+    push_person_values(&mut query, person);
+    query.push(") WHERE id = ").push_bind(id);
+    query.push(" RETURNING *");
+    let item = query.build_query_as::<Person>().fetch_one(&mut *tx).await?;
+
+    tx.commit().await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Delete Item Handler
+ * A person that is still mentioned in documents cannot be deleted
+ */
+pub async fn delete_item_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
+
+    // Locking the person keeps new mentions from referring to it until we are done
+    let query = format!("SELECT id FROM {} WHERE id = $1 FOR UPDATE", TABLE);
+    sqlx::query(&query)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let documents: i64 = sqlx::query_scalar(
+        "SELECT COUNT(DISTINCT document_id) FROM document_persons WHERE person_id = $1",
+    )
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if documents > 0 {
+        return Err(AppError::referenced_by_documents(id, documents));
+    }
+
+    let query = format!("DELETE FROM {} WHERE id = $1", TABLE);
+    sqlx::query(&query)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::from_delete)?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/**
+ * List Document Persons Handler
+ * The persons mentioned in a document, each with their role
+ */
+pub async fn document_persons_list_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_document_exists(data.pool(), id).await?;
+
+    let items = sqlx::query_as::<_, PersonMention>(
+        r#"
+        SELECT persons.*, document_persons.id AS mention_id, document_persons.role
+        FROM document_persons
+        JOIN persons ON persons.id = document_persons.person_id
+        WHERE document_persons.document_id = $1
+        ORDER BY document_persons.role, persons.sort_name, persons.id
+    "#,
+    )
+    .bind(id)
+    .fetch_all(data.pool())
+    .await?;
+
+    let json_response = json!({
+        "status": "success",
+        "results": items.len(),
+        "items": items,
+    });
+    Ok(Json(json_response))
+}
+
+/**
+ * Add Document Person Handler
+ * Mention a person in a document in a role
+ */
+pub async fn add_document_person_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<CreateDocumentPerson>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_document_exists(data.pool(), id).await?;
+
+    let item = sqlx::query_as::<_, DocumentPerson>(
+        "INSERT INTO document_persons (document_id, person_id, role) VALUES ($1, $2, $3) RETURNING *",
+    )
+    .bind(id)
+    .bind(body.person_id)
+    .bind(body.role)
+    .fetch_one(data.pool())
+    .await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
+}
+
+/**
+ * Remove Document Person Handler
+ * Remove a single mention of a person from a document
+ */
+pub async fn remove_document_person_handler(
+    AppPath((id, mention_id)): AppPath<(i32, i32)>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_document_exists(data.pool(), id).await?;
+
+    let rows_affected =
+        sqlx::query("DELETE FROM document_persons WHERE id = $1 AND document_id = $2")
+            .bind(mention_id)
+            .bind(id)
+            .execute(data.pool())
+            .await?
+            .rows_affected();
+
+    if rows_affected == 0 {
+        return Err(AppError::not_found(mention_id));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/**
+ * Fail with a 422 when the birth or death place of `person` is deleted.
+ */
+async fn ensure_places_not_deleted(
+    conn: &mut PgConnection,
+    person: &CreatePerson,
+) -> Result<(), AppError> {
+    for (column, place_id) in [
+        ("birth_place_id", person.birth_place_id),
+        ("death_place_id", person.death_place_id),
+    ] {
+        if let Some(place_id) = place_id {
+            ensure_not_deleted(conn, "places", column, place_id).await?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{app, insert_document, insert_named, send};
+
+    async fn create_person(app: &axum::Router, body: Value) -> i32 {
+        let (status, body) = send(app, Method::POST, "/api/v1/persons", Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        body["data"]["item"]["id"].as_i64().unwrap() as i32
+    }
+
+    #[sqlx::test]
+    async fn create_and_edit_keep_dates_and_places(pool: PgPool) {
+        let place_id = insert_named(&pool, "places", "Haarlem").await;
+        let app = app(pool);
+
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            "/api/v1/persons",
+            Some(json!({
+                "given_names": "Jan",
+                "patronymic": "Pietersz",
+                "sex": "male",
+                "birth_date": "1720~",
+                "birth_place_id": place_id,
+                "death_date": "1781-03-02",
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let item = &body["data"]["item"];
+        assert_eq!(item["birth_date"], "1720~");
+        assert_eq!(item["birth_place_id"], place_id);
+        assert_eq!(item["death_date"], "1781-03-02");
+        assert!(item["death_place_id"].is_null());
+        assert!(item["surname"].is_null());
+
+        let uri = format!("/api/v1/persons/{}", item["id"]);
+        let (status, body) = send(
+            &app,
+            Method::PATCH,
+            &uri,
+            Some(json!({ "surname_prefix": "van", "surname": "Dam", "birth_date": null })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let item = &body["data"]["item"];
+        assert_eq!(item["surname_prefix"], "van");
+        assert_eq!(item["surname"], "Dam");
+        assert!(item["birth_date"].is_null());
+        assert_eq!(item["birth_place_id"], place_id);
+        assert_eq!(item["sex"], "male");
+        assert_eq!(item["death_date"], "1781-03-02");
+    }
+
+    #[sqlx::test]
+    async fn create_and_edit_reject_invalid_persons(pool: PgPool) {
+        let app = app(pool.clone());
+
+        for (body, code) in [
+            (json!({ "surname_prefix": "van" }), "validation_failed"),
+            (json!({ "given_names": " " }), "validation_failed"),
+            (
+                json!({ "given_names": "Jan", "birth_date": "1750", "death_date": "1749" }),
+                "validation_failed",
+            ),
+            (
+                json!({ "given_names": "Jan", "birth_place_id": 999 }),
+                "invalid_reference",
+            ),
+        ] {
+            let (status, response) =
+                send(&app, Method::POST, "/api/v1/persons", Some(body.clone())).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+            assert_eq!(response["code"], code, "{}", body);
+        }
+
+        let id = create_person(&app, json!({ "given_names": "Jan" })).await;
+        let uri = format!("/api/v1/persons/{}", id);
+        let (status, _) = send(
+            &app,
+            Method::PATCH,
+            &uri,
+            Some(json!({ "given_names": null })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, _) = send(&app, Method::PATCH, "/api/v1/persons/999", Some(json!({}))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let place_id = insert_named(&pool, "places", "Haarlem").await;
+        sqlx::query("UPDATE places SET deleted_at = now() WHERE id = $1")
+            .bind(place_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let body = json!({ "given_names": "Jan", "birth_place_id": place_id });
+        let (status, response) = send(&app, Method::POST, "/api/v1/persons", Some(body)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            response["message"],
+            format!(
+                "birth_place_id {} is deleted and must be restored first",
+                place_id
+            )
+        );
+        let body = json!({ "death_place_id": place_id });
+        let (status, _) = send(&app, Method::PATCH, &uri, Some(body)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[sqlx::test]
+    async fn list_sorts_by_surname_without_prefix(pool: PgPool) {
+        let app = app(pool);
+        for body in [
+            json!({ "given_names": "Maria", "surname_prefix": "van der", "surname": "Berg" }),
+            json!({ "given_names": "Pieter", "surname": "Aalders" }),
+            json!({ "given_names": "Anna", "surname": "Berg" }),
+        ] {
+            create_person(&app, body).await;
+        }
+
+        let (status, body) = send(&app, Method::GET, "/api/v1/persons", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let names: Vec<&str> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["given_names"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["Pieter", "Anna", "Maria"]);
+    }
+
+    #[sqlx::test]
+    async fn mentions_link_persons_to_documents(pool: PgPool) {
+        let archive_id = insert_named(&pool, "archives", "Noord-Hollands Archief").await;
+        let institute_id = insert_named(&pool, "institutes", "Burgerweeshuis").await;
+        let place_id = insert_named(&pool, "places", "Haarlem").await;
+        let document_id = insert_document(&pool, "INV-1", archive_id, institute_id, place_id).await;
+        let other_id = insert_document(&pool, "INV-2", archive_id, institute_id, place_id).await;
+        let app = app(pool);
+        let child_id = create_person(&app, json!({ "given_names": "Cornelis" })).await;
+        let father_id = create_person(&app, json!({ "given_names": "Jan" })).await;
+
+        let uri = format!("/api/v1/documents/{}/persons", document_id);
+        for (person_id, role) in [(child_id, "subject"), (father_id, "father")] {
+            let body = json!({ "person_id": person_id, "role": role });
+            let (status, _) = send(&app, Method::POST, &uri, Some(body)).await;
+            assert_eq!(status, StatusCode::CREATED);
+        }
+        let other_uri = format!("/api/v1/documents/{}/persons", other_id);
+        let body = json!({ "person_id": father_id, "role": "witness" });
+        send(&app, Method::POST, &other_uri, Some(body)).await;
+
+        let body = json!({ "person_id": father_id, "role": "father" });
+        let (status, response) = send(&app, Method::POST, &uri, Some(body)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(response["code"], "duplicate");
+
+        let body = json!({ "person_id": 999, "role": "mother" });
+        let (status, _) = send(&app, Method::POST, &uri, Some(body)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body = json!({ "person_id": father_id, "role": "uncle" });
+        let (status, _) = send(&app, Method::POST, &uri, Some(body)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results"], 2);
+        assert_eq!(body["items"][0]["role"], "subject");
+        assert_eq!(body["items"][0]["person"]["given_names"], "Cornelis");
+        assert_eq!(body["items"][1]["person"]["id"], father_id);
+
+        let list = |query: String| {
+            let app = app.clone();
+            async move {
+                let uri = format!("/api/v1/documents?{}", query);
+                let (_, body) = send(&app, Method::GET, &uri, None).await;
+                body["items"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|item| item["id"].as_i64().unwrap() as i32)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(
+            list(format!("person_id={}&sort=id", father_id)).await,
+            vec![document_id, other_id]
+        );
+        assert_eq!(
+            list(format!("person_id={}&role=witness", father_id)).await,
+            vec![other_id]
+        );
+        assert_eq!(list("role=subject".to_string()).await, vec![document_id]);
+
+        let mention_uri = format!("{}/{}", uri, body["items"][0]["id"]);
+        let (status, _) = send(&app, Method::DELETE, &mention_uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, Method::DELETE, &mention_uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(&app, Method::GET, "/api/v1/documents/999/persons", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn delete_refuses_persons_mentioned_in_documents(pool: PgPool) {
+        let archive_id = insert_named(&pool, "archives", "Noord-Hollands Archief").await;
+        let institute_id = insert_named(&pool, "institutes", "Burgerweeshuis").await;
+        let place_id = insert_named(&pool, "places", "Haarlem").await;
+        let document_id = insert_document(&pool, "INV-1", archive_id, institute_id, place_id).await;
+        let app = app(pool);
+        let person_id = create_person(&app, json!({ "given_names": "Jan" })).await;
+
+        let uri = format!("/api/v1/documents/{}/persons", document_id);
+        for role in ["father", "witness"] {
+            let body = json!({ "person_id": person_id, "role": role });
+            send(&app, Method::POST, &uri, Some(body)).await;
+        }
+
+        let person_uri = format!("/api/v1/persons/{}", person_id);
+        let (status, body) = send(&app, Method::DELETE, &person_uri, None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["documents"], 1);
+
+        // Purging the document removes its mentions
+        let document_uri = format!("/api/v1/documents/{}?purge=true", document_id);
+        let (status, _) = send(&app, Method::DELETE, &document_uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, _) = send(&app, Method::DELETE, &person_uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, Method::GET, &person_uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn mentions_of_deleted_documents_are_hidden(pool: PgPool) {
+        let archive_id = insert_named(&pool, "archives", "Noord-Hollands Archief").await;
+        let institute_id = insert_named(&pool, "institutes", "Burgerweeshuis").await;
+        let place_id = insert_named(&pool, "places", "Haarlem").await;
+        let document_id = insert_document(&pool, "INV-1", archive_id, institute_id, place_id).await;
+        let app = app(pool);
+        let person_id = create_person(&app, json!({ "given_names": "Cornelis" })).await;
+        let uri = format!("/api/v1/documents/{}/persons", document_id);
+        let body = json!({ "person_id": person_id, "role": "subject" });
+        let (_, mention) = send(&app, Method::POST, &uri, Some(body.clone())).await;
+        let (status, _) = send(
+            &app,
+            Method::DELETE,
+            &format!("/api/v1/documents/{}", document_id),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let mention_uri = format!("{}/{}", uri, mention["data"]["item"]["id"]);
+        for (method, uri, body) in [
+            (Method::GET, uri.clone(), None),
+            (Method::POST, uri.clone(), Some(body)),
+            (Method::DELETE, mention_uri, None),
+        ] {
+            let (status, _) = send(&app, method.clone(), &uri, body).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{} {}", method, uri);
+        }
+    }
+}
@@ -1,11 +1,19 @@
+use crate::audit;
+use crate::auth::Actor;
 use crate::db::AppState;
+use crate::errors::AppError;
+use crate::export::{ExportFormat, export_list};
+use crate::extractors::{AppJson, AppPath, AppQuery};
+use crate::handlers::common::{delete_referenced_item, restore_item};
 use crate::models::places::Place;
+use crate::pagination::{Listing, SortField, fetch_page};
 use crate::schemas::places::{CreatePlace, UpdatePlace};
+use crate::schemas::{DeleteParams, DeletedParams, ExportParams, PageParams};
 
 use axum::{
     Json,
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{OriginalUri, State},
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
 };
 use serde_json::json;
@@ -13,65 +21,76 @@ use std::sync::Arc;
 
 const TABLE: &str = "places";
 
+const LISTING: Listing = Listing {
+    table: TABLE,
+    sort_fields: &[
+        SortField {
+            name: "name",
+            column: "name",
+            sql_type: "text",
+        },
+        SortField {
+            name: "id",
+            column: "id",
+            sql_type: "integer",
+        },
+    ],
+    default_sort: "name",
+};
+
+/**
+ * Persons refer to their places of birth and death.
+ */
+const PERSON_REFERENCES: &[(&str, &str)] =
+    &[("persons", "birth_place_id"), ("persons", "death_place_id")];
+
 /**
  * List Items Handler
- * This handler fetches a list of all items from postgres
+ * This handler fetches a page of items from postgres, see `PageParams` and `DeletedParams`,
+ * or exports all of them as CSV, XLSX or JSON, see `ExportParams`
  */
 pub async fn items_list_handler(
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    AppQuery(params): AppQuery<PageParams>,
+    AppQuery(export): AppQuery<ExportParams>,
+    AppQuery(deleted): AppQuery<DeletedParams>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("SELECT * FROM {} ORDER BY name", TABLE);
-    let query_result = sqlx::query_as::<_, Place>(&query)
-        .fetch_all(data.pool())
-        .await;
-
-    if query_result.is_err() {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": "🔥 Something bad happened while fetching all items",
-        });
-        return Err((StatusCode::INTERNAL_SERVER_ERROR, Json(error_response)));
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(format) = ExportFormat::negotiate(export.format, &headers) {
+        let sort = params.sort.as_deref();
+        return export_list::<Place>(data.pool(), &LISTING, sort, &deleted, &[], format).await;
     }
 
-    let items = query_result.unwrap();
+    let page = fetch_page::<Place>(data.pool(), &LISTING, &params, &deleted).await?;
 
-    let json_response = serde_json::json!({
-        "status": "success",
-        "results": items.len(),
-        "items": items
-    });
-    Ok(Json(json_response))
+    Ok(page.into_list_response(&uri))
 }
 
 /**
  * Fetch a single Item
+ * A deleted item is only found with `?include_deleted=true`
  */
 pub async fn get_item_handler(
-    Path(id): Path<i32>,
+    AppPath(id): AppPath<i32>,
+    AppQuery(deleted): AppQuery<DeletedParams>,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("SELECT * FROM {} WHERE id = $1", TABLE);
-    let query_result = sqlx::query_as::<_, Place>(&query)
+) -> Result<impl IntoResponse, AppError> {
+    let query = format!(
+        "SELECT * FROM {} WHERE id = $1 AND ($2 OR deleted_at IS NULL)",
+        TABLE
+    );
+    let item = sqlx::query_as::<_, Place>(&query)
         .bind(id)
-        .fetch_one(data.pool())
-        .await;
-
-    match query_result {
-        Ok(item) => {
-            let item_response = serde_json::json!({"status": "success","data": serde_json::json!({
-                "item": item
-            })});
+        .bind(deleted.include_deleted)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
 
-            return Ok(Json(item_response));
-        }
-        Err(_) => {
-            let error_response = serde_json::json!({
-                "status": "fail",
-                "message": format!("Item with ID: {} not found", id)
-            });
-            return Err((StatusCode::NOT_FOUND, Json(error_response)));
-        }
-    }
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
 }
 
 /**
@@ -79,38 +98,32 @@ pub async fn get_item_handler(
  * This handler adds a new item to postgres
  */
 pub async fn create_item_handler(
+    actor: Actor,
     State(data): State<Arc<AppState>>,
-    Json(body): Json<CreatePlace>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("INSERT INTO {} (name) VALUES ($1) RETURNING *", TABLE);
-    let query_result = sqlx::query_as::<_, Place>(&query)
+    AppJson(body): AppJson<CreatePlace>,
+) -> Result<impl IntoResponse, AppError> {
+    body.validate().map_err(AppError::Validation)?;
+
+    let mut tx = data.pool().begin().await?;
+
+    let query = format!(
+        "INSERT INTO {} (name, latitude, longitude) VALUES ($1, $2, $3) RETURNING *",
+        TABLE
+    );
+    let item = sqlx::query_as::<_, Place>(&query)
         .bind(body.name.to_string())
-        .fetch_one(data.pool())
-        .await;
-    match query_result {
-        Ok(item) => {
-            let item_response = json!({"status": "success","data": json!({
-                "item": item
-            })});
+        .bind(body.latitude)
+        .bind(body.longitude)
+        .fetch_one(&mut *tx)
+        .await?;
+    audit::record(&mut tx, &actor, TABLE, item.id, None, Some(json!(item))).await?;
 
-            return Ok((StatusCode::CREATED, Json(item_response)));
-        }
-        Err(e) => {
-            if e.to_string()
-                .contains("duplicate key value violates unique constraint")
-            {
-                let error_response = serde_json::json!({
-                    "status": "fail",
-                    "message": "Item with that name already exists",
-                });
-                return Err((StatusCode::CONFLICT, Json(error_response)));
-            }
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"status": "error","message": format!("{:?}", e)})),
-            ));
-        }
-    }
+    tx.commit().await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
 }
 
 /**
@@ -118,72 +131,240 @@ pub async fn create_item_handler(
  * This handler handles edits of existing items
  */
 pub async fn edit_item_handler(
-    Path(id): Path<i32>,
+    AppPath(id): AppPath<i32>,
+    actor: Actor,
     State(data): State<Arc<AppState>>,
-    Json(body): Json<UpdatePlace>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("SELECT * FROM {} WHERE id = $1", TABLE);
-    let query_result = sqlx::query_as::<_, Place>(&query)
-        .bind(id)
-        .fetch_one(data.pool())
-        .await;
+    AppJson(body): AppJson<UpdatePlace>,
+) -> Result<impl IntoResponse, AppError> {
+    body.validate().map_err(AppError::Validation)?;
 
-    if query_result.is_err() {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": format!("🔥 Item with ID: {} not found", id)
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
-    }
+    let mut tx = data.pool().begin().await?;
 
-    let item = query_result.unwrap();
+    let query = format!(
+        "SELECT * FROM {} WHERE id = $1 AND deleted_at IS NULL FOR UPDATE",
+        TABLE
+    );
+    let before = sqlx::query_as::<_, Place>(&query)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
 
-    let query = format!("UPDATE {} SET name = $1 WHERE id = $2 RETURNING *", TABLE);
-    let query_result = sqlx::query_as::<_, Place>(&query)
-        .bind(body.name.to_owned().unwrap_or(item.name))
+    let query = format!(
+        "UPDATE {} SET name = $1, latitude = $2, longitude = $3 WHERE id = $4 RETURNING *",
+        TABLE
+    );
+    let item = sqlx::query_as::<_, Place>(&query)
+        .bind(body.name.unwrap_or_else(|| before.name.clone()))
+        .bind(body.latitude.unwrap_or(before.latitude))
+        .bind(body.longitude.unwrap_or(before.longitude))
         .bind(id)
-        .fetch_one(data.pool())
-        .await;
+        .fetch_one(&mut *tx)
+        .await?;
+    audit::record(
+        &mut tx,
+        &actor,
+        TABLE,
+        id,
+        Some(json!(before)),
+        Some(json!(item)),
+    )
+    .await?;
 
-    match query_result {
-        Ok(item) => {
-            let item_response = serde_json::json!({"status": "success","data": serde_json::json!({
-                "item": item
-            })});
+    tx.commit().await?;
 
-            return Ok(Json(item_response));
-        }
-        Err(err) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({"status": "error","message": format!("{:?}", err)})),
-            ));
-        }
-    }
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
 }
 
 /**
  * Delete Item Handler
+ * The item is marked as deleted, `?purge=true` removes it for good.
+ * Documents and persons that refer to the item block the delete,
+ * unless `?reassign_to={id}` is given
  */
 pub async fn delete_item_handler(
-    Path(id): Path<i32>,
+    AppPath(id): AppPath<i32>,
+    AppQuery(params): AppQuery<DeleteParams>,
+    actor: Actor,
     State(data): State<Arc<AppState>>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
-    let query = format!("DELETE FROM {} WHERE id = $1", TABLE);
-    let rows_affected = sqlx::query(&query)
-        .bind(id) // $1
-        .execute(data.pool())
-        .await
-        .unwrap()
-        .rows_affected();
-
-    if rows_affected == 0 {
-        let error_response = serde_json::json!({
-            "status": "fail",
-            "message": format!("Item with ID: {} not found", id)
-        });
-        return Err((StatusCode::NOT_FOUND, Json(error_response)));
-    }
+) -> Result<impl IntoResponse, AppError> {
+    delete_referenced_item(
+        data.pool(),
+        &actor,
+        TABLE,
+        "place_id",
+        PERSON_REFERENCES,
+        id,
+        &params,
+    )
+    .await?;
 
     Ok(StatusCode::NO_CONTENT)
 }
+
+/**
+ * Restore Item Handler
+ * This handler undoes the delete of an item that was not purged
+ */
+pub async fn restore_item_handler(
+    AppPath(id): AppPath<i32>,
+    actor: Actor,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = restore_item::<Place>(data.pool(), &actor, TABLE, &[], id).await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::test_utils::{app, insert_document, insert_named, send};
+
+    #[sqlx::test]
+    async fn create_persists_coordinates(pool: PgPool) {
+        let (status, body) = send(
+            &app(pool),
+            Method::POST,
+            "/api/v1/places",
+            Some(json!({ "name": "Haarlem", "latitude": 52.38, "longitude": 4.64 })),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["data"]["item"]["latitude"], 52.38);
+        assert_eq!(body["data"]["item"]["longitude"], 4.64);
+    }
+
+    #[sqlx::test]
+    async fn create_rejects_out_of_range_coordinates(pool: PgPool) {
+        let app = app(pool);
+
+        for coordinates in [
+            json!({ "latitude": 90.5 }),
+            json!({ "latitude": -91 }),
+            json!({ "longitude": 180.5 }),
+            json!({ "longitude": -181 }),
+        ] {
+            let mut body = coordinates.clone();
+            body["name"] = json!("Nowhere");
+            let (status, _) = send(&app, Method::POST, "/api/v1/places", Some(body)).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", coordinates);
+        }
+    }
+
+    #[sqlx::test]
+    async fn edit_updates_keeps_and_clears_coordinates(pool: PgPool) {
+        let app = app(pool);
+        let (_, body) = send(
+            &app,
+            Method::POST,
+            "/api/v1/places",
+            Some(json!({ "name": "Haarlem", "latitude": 52.38, "longitude": 4.64 })),
+        )
+        .await;
+        let uri = format!("/api/v1/places/{}", body["data"]["item"]["id"]);
+
+        let (status, body) =
+            send(&app, Method::PATCH, &uri, Some(json!({ "latitude": 52.0 }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["item"]["latitude"], 52.0);
+        assert_eq!(body["data"]["item"]["longitude"], 4.64);
+
+        let (status, body) =
+            send(&app, Method::PATCH, &uri, Some(json!({ "name": "Harlem" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["item"]["latitude"], 52.0);
+        assert_eq!(body["data"]["item"]["longitude"], 4.64);
+
+        let (status, body) = send(
+            &app,
+            Method::PATCH,
+            &uri,
+            Some(json!({ "longitude": null })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["item"]["latitude"], 52.0);
+        assert!(body["data"]["item"]["longitude"].is_null());
+    }
+
+    #[sqlx::test]
+    async fn edit_rejects_out_of_range_coordinates(pool: PgPool) {
+        let app = app(pool);
+        let (_, body) = send(
+            &app,
+            Method::POST,
+            "/api/v1/places",
+            Some(json!({ "name": "Haarlem" })),
+        )
+        .await;
+        let uri = format!("/api/v1/places/{}", body["data"]["item"]["id"]);
+
+        let (status, _) = send(&app, Method::PATCH, &uri, Some(json!({ "latitude": 100 }))).await;
+
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[sqlx::test]
+    async fn delete_with_reassign_moves_documents(pool: PgPool) {
+        let archive_id = insert_named(&pool, "archives", "Noord-Hollands Archief").await;
+        let institute_id = insert_named(&pool, "institutes", "Burgerweeshuis").await;
+        let place_id = insert_named(&pool, "places", "Harlem").await;
+        let other_id = insert_named(&pool, "places", "Haarlem").await;
+        insert_document(&pool, "INV-1", archive_id, institute_id, place_id).await;
+        let app = app(pool.clone());
+
+        let uri = format!("/api/v1/places/{}", place_id);
+        let (status, body) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["documents"], 1);
+
+        let uri = format!("/api/v1/places/{}?reassign_to={}", place_id, other_id);
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let moved: i32 = sqlx::query_scalar("SELECT place_id FROM documents")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(moved, other_id);
+    }
+
+    #[sqlx::test]
+    async fn delete_with_reassign_moves_persons(pool: PgPool) {
+        let place_id = insert_named(&pool, "places", "Harlem").await;
+        let other_id = insert_named(&pool, "places", "Haarlem").await;
+        sqlx::query("INSERT INTO persons (given_names, birth_place_id, death_place_id) VALUES ('Jan', $1, $1)")
+            .bind(place_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let app = app(pool.clone());
+
+        let uri = format!("/api/v1/places/{}", place_id);
+        let (status, body) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "still_referenced");
+
+        let uri = format!("/api/v1/places/{}?reassign_to={}", place_id, other_id);
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let moved: (i32, i32) =
+            sqlx::query_as("SELECT birth_place_id, death_place_id FROM persons")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(moved, (other_id, other_id));
+    }
+}
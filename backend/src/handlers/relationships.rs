@@ -0,0 +1,351 @@
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::extractors::{AppJson, AppPath};
+use crate::models::relationships::{Relationship, RelationshipKind};
+use crate::schemas::relationships::{CreateRelationship, UpdateRelationship};
+
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use serde_json::json;
+use sqlx::{Postgres, Transaction};
+use std::sync::Arc;
+
+const TABLE: &str = "relationships";
+
+/**
+ * Relationships with the ids of their supporting documents, to be followed by a `WHERE` clause.
+ */
+pub const SELECT_RELATIONSHIPS: &str = r#"
+    SELECT relationships.*, ARRAY(
+        SELECT document_id FROM relationship_documents
+        WHERE relationship_id = relationships.id
+        ORDER BY document_id
+    ) AS document_ids
+    FROM relationships
+"#;
+
+/**
+ * Replace the supporting documents of the relationship with `id`.
+ */
+async fn replace_documents(
+    tx: &mut Transaction<'_, Postgres>,
+    id: i32,
+    mut document_ids: Vec<i32>,
+) -> Result<(), AppError> {
+    document_ids.sort_unstable();
+    document_ids.dedup();
+
+    sqlx::query("DELETE FROM relationship_documents WHERE relationship_id = $1")
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+    sqlx::query(
+        "INSERT INTO relationship_documents (relationship_id, document_id) SELECT $1, UNNEST($2::int[])",
+    )
+    .bind(id)
+    .bind(document_ids)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+async fn fetch_relationship(
+    tx: &mut Transaction<'_, Postgres>,
+    id: i32,
+) -> Result<Relationship, AppError> {
+    let query = format!("{} WHERE relationships.id = $1", SELECT_RELATIONSHIPS);
+    sqlx::query_as::<_, Relationship>(&query)
+        .bind(id)
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))
+}
+
+/**
+ * Fetch a single Item
+ */
+pub async fn get_item_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let query = format!("{} WHERE relationships.id = $1", SELECT_RELATIONSHIPS);
+    let item = sqlx::query_as::<_, Relationship>(&query)
+        .bind(id)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Create Item Handler
+ * This handler adds a new relationship with its supporting documents to postgres
+ */
+pub async fn create_item_handler(
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<CreateRelationship>,
+) -> Result<impl IntoResponse, AppError> {
+    body.validate().map_err(AppError::Validation)?;
+
+    // Spouses are stored in a fixed order, so that each couple is stored only once
+    let (person_id, relative_id) = match body.kind {
+        RelationshipKind::Spouse if body.relative_id < body.person_id => {
+            (body.relative_id, body.person_id)
+        }
+        _ => (body.person_id, body.relative_id),
+    };
+
+    let mut tx = data.pool().begin().await?;
+
+    let query = format!(
+        "INSERT INTO {} (kind, person_id, relative_id) VALUES ($1, $2, $3) RETURNING id",
+        TABLE
+    );
+    let id: i32 = sqlx::query_scalar(&query)
+        .bind(body.kind)
+        .bind(person_id)
+        .bind(relative_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    replace_documents(&mut tx, id, body.document_ids).await?;
+    let item = fetch_relationship(&mut tx, id).await?;
+
+    tx.commit().await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
+}
+
+/**
+ * Edit Item Handler
+ * Only the supporting documents of a relationship can change,
+ * a relationship between other persons is a new relationship
+ */
+pub async fn edit_item_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<UpdateRelationship>,
+) -> Result<impl IntoResponse, AppError> {
+    body.validate().map_err(AppError::Validation)?;
+
+    let mut tx = data.pool().begin().await?;
+
+    let query = format!("SELECT id FROM {} WHERE id = $1 FOR UPDATE", TABLE);
+    sqlx::query(&query)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    if let Some(document_ids) = body.document_ids {
+        replace_documents(&mut tx, id, document_ids).await?;
+    }
+    let item = fetch_relationship(&mut tx, id).await?;
+
+    tx.commit().await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Delete Item Handler
+ */
+pub async fn delete_item_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let query = format!("DELETE FROM {} WHERE id = $1", TABLE);
+    let rows_affected = sqlx::query(&query)
+        .bind(id)
+        .execute(data.pool())
+        .await
+        .map_err(AppError::from_delete)?
+        .rows_affected();
+
+    if rows_affected == 0 {
+        return Err(AppError::not_found(id));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/**
+ * List Person Relationships Handler
+ * The parents, children and spouses of a person
+ */
+pub async fn person_relationships_list_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    sqlx::query("SELECT id FROM persons WHERE id = $1")
+        .bind(id)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let query = format!(
+        "{} WHERE person_id = $1 OR relative_id = $1 ORDER BY kind, id",
+        SELECT_RELATIONSHIPS
+    );
+    let items = sqlx::query_as::<_, Relationship>(&query)
+        .bind(id)
+        .fetch_all(data.pool())
+        .await?;
+
+    let json_response = json!({
+        "status": "success",
+        "results": items.len(),
+        "items": items,
+    });
+    Ok(Json(json_response))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::test_utils::{app, insert_document, insert_named, insert_person, send};
+
+    async fn seed_document(pool: &PgPool, inventory_number: &str) -> i32 {
+        let archive_id = insert_named(pool, "archives", inventory_number).await;
+        let institute_id = insert_named(pool, "institutes", inventory_number).await;
+        let place_id = insert_named(pool, "places", inventory_number).await;
+        insert_document(pool, inventory_number, archive_id, institute_id, place_id).await
+    }
+
+    #[sqlx::test]
+    async fn create_edit_and_delete_relationships(pool: PgPool) {
+        let baptism_id = seed_document(&pool, "INV-1").await;
+        let marriage_id = seed_document(&pool, "INV-2").await;
+        let father_id = insert_person(&pool, "Jan").await;
+        let child_id = insert_person(&pool, "Cornelis").await;
+        let app = app(pool);
+
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            "/api/v1/relationships",
+            Some(json!({
+                "kind": "parent",
+                "person_id": father_id,
+                "relative_id": child_id,
+                "document_ids": [baptism_id, baptism_id],
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let item = &body["data"]["item"];
+        assert_eq!(item["person_id"], father_id);
+        assert_eq!(item["document_ids"], json!([baptism_id]));
+
+        let uri = format!("/api/v1/relationships/{}", item["id"]);
+        let body = json!({ "document_ids": [marriage_id, baptism_id] });
+        let (status, body) = send(&app, Method::PATCH, &uri, Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body["data"]["item"]["document_ids"],
+            json!([baptism_id, marriage_id])
+        );
+
+        let uri_of_person = format!("/api/v1/persons/{}/relationships", child_id);
+        let (_, body) = send(&app, Method::GET, &uri_of_person, None).await;
+        assert_eq!(body["results"], 1);
+
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn spouses_are_stored_once(pool: PgPool) {
+        let document_id = seed_document(&pool, "INV-1").await;
+        let wife_id = insert_person(&pool, "Maria").await;
+        let husband_id = insert_person(&pool, "Jan").await;
+        let app = app(pool);
+
+        let spouses = |person_id: i32, relative_id: i32| {
+            json!({
+                "kind": "spouse",
+                "person_id": person_id,
+                "relative_id": relative_id,
+                "document_ids": [document_id],
+            })
+        };
+
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            "/api/v1/relationships",
+            Some(spouses(husband_id, wife_id)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["data"]["item"]["person_id"], wife_id);
+        assert_eq!(body["data"]["item"]["relative_id"], husband_id);
+
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            "/api/v1/relationships",
+            Some(spouses(wife_id, husband_id)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "duplicate");
+    }
+
+    #[sqlx::test]
+    async fn create_rejects_invalid_relationships(pool: PgPool) {
+        let document_id = seed_document(&pool, "INV-1").await;
+        let person_id = insert_person(&pool, "Jan").await;
+        let relative_id = insert_person(&pool, "Cornelis").await;
+        let app = app(pool.clone());
+
+        for (body, code) in [
+            (
+                json!({ "kind": "parent", "person_id": person_id, "relative_id": person_id, "document_ids": [document_id] }),
+                "validation_failed",
+            ),
+            (
+                json!({ "kind": "parent", "person_id": person_id, "relative_id": relative_id, "document_ids": [] }),
+                "validation_failed",
+            ),
+            (
+                json!({ "kind": "parent", "person_id": person_id, "relative_id": relative_id, "document_ids": [999] }),
+                "invalid_reference",
+            ),
+            (
+                json!({ "kind": "parent", "person_id": 999, "relative_id": relative_id, "document_ids": [document_id] }),
+                "invalid_reference",
+            ),
+        ] {
+            let (status, response) = send(
+                &app,
+                Method::POST,
+                "/api/v1/relationships",
+                Some(body.clone()),
+            )
+            .await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+            assert_eq!(response["code"], code, "{}", body);
+        }
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM relationships")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+}
@@ -0,0 +1,1007 @@
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::extractors::{AppJson, AppMultipart, AppPath, AppQuery};
+use crate::handlers::common::ensure_document_exists;
+use crate::models::scans::{DerivativeFormat, DerivativesStatus, DocumentScan, ScanDerivative};
+use crate::scans::range::ByteRange;
+use crate::scans::{StoredFile, file_hashes_sql};
+use crate::schemas::scans::{ThumbnailParams, UpdateScan};
+
+use axum::{
+    Json,
+    body::Body,
+    extract::State,
+    http::{
+        HeaderMap, StatusCode,
+        header::{
+            ACCEPT, ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE,
+            CONTENT_TYPE, ETAG, IF_NONE_MATCH, IF_RANGE, RANGE, RETRY_AFTER, VARY,
+        },
+    },
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use sqlx::PgPool;
+use std::io::SeekFrom;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+const TABLE: &str = "document_scans";
+
+/**
+ * Scans in reading order: by the number their page number starts with, so that "9" comes
+ * before "10r", then by page and scan number.
+ */
+pub const SCAN_ORDER: &str = "substring(page_number FROM '^[0-9]+')::INT NULLS LAST, \
+    page_number NULLS LAST, scan_number NULLS LAST, id";
+
+/**
+ * List Document Scans Handler
+ * The scans of a document in reading order
+ */
+pub async fn document_scans_list_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_document_exists(data.pool(), id).await?;
+
+    let query = format!(
+        "SELECT * FROM {} WHERE document_id = $1 ORDER BY {}",
+        TABLE, SCAN_ORDER
+    );
+    let items = sqlx::query_as::<_, DocumentScan>(&query)
+        .bind(id)
+        .fetch_all(data.pool())
+        .await?;
+
+    let json_response = json!({
+        "status": "success",
+        "results": items.len(),
+        "items": items,
+    });
+    Ok(Json(json_response))
+}
+
+/**
+ * Fetch a single Document Scan
+ */
+pub async fn get_scan_handler(
+    AppPath((id, scan_id)): AppPath<(i32, i32)>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = fetch_scan(data.pool(), id, scan_id).await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Upload Document Scan Handler
+ * Adds a scan to a document from a `multipart/form-data` body with the fields
+ * `file`, `scan_number` and `page_number`. All are optional, but a scan needs at least one.
+ * The type of the file is sniffed from its content, its size and dimensions are stored with it.
+ */
+pub async fn upload_scan_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+    AppMultipart(mut multipart): AppMultipart,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_document_exists(data.pool(), id).await?;
+
+    let mut file: Option<StoredFile> = None;
+    let result = async {
+        let mut file_name = None;
+        let mut scan_number = None;
+        let mut page_number = None;
+        while let Some(field) = multipart.next_field().await? {
+            match field.name().unwrap_or_default() {
+                "file" => {
+                    if file.is_some() {
+                        return Err(AppError::Validation(
+                            "A scan has a single file, upload each file as its own scan"
+                                .to_string(),
+                        ));
+                    }
+                    file_name = field.file_name().and_then(base_name);
+                    file = Some(data.scans().save(field).await?);
+                }
+                "scan_number" => scan_number = non_empty(field.text().await?),
+                "page_number" => page_number = non_empty(field.text().await?),
+                name => {
+                    return Err(AppError::Validation(format!(
+                        "Unknown field: {}, expected file, scan_number or page_number",
+                        name
+                    )));
+                }
+            }
+        }
+
+        if file.is_none() && scan_number.is_none() && page_number.is_none() {
+            return Err(AppError::Validation(
+                "A scan needs a file, a scan number or a page number".to_string(),
+            ));
+        }
+
+        let query = format!(
+            r#"
+            INSERT INTO {}
+                (document_id, scan_number, page_number, sha256, file_name, mime_type, size_bytes, width, height)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING *
+        "#,
+            TABLE
+        );
+        let mut tx = data.pool().begin().await?;
+        if let Some(file) = &file {
+            data.scans().keep(&mut tx, file).await?;
+        }
+        let item = sqlx::query_as::<_, DocumentScan>(&query)
+            .bind(id)
+            .bind(scan_number)
+            .bind(page_number)
+            .bind(file.as_ref().map(|file| &file.sha256))
+            .bind(file.as_ref().and(file_name))
+            .bind(file.as_ref().map(|file| file.mime_type))
+            .bind(file.as_ref().map(|file| file.size_bytes))
+            .bind(file.as_ref().and_then(|file| file.width))
+            .bind(file.as_ref().and_then(|file| file.height))
+            .fetch_one(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(item)
+    }
+    .await;
+
+    let item = match result {
+        Ok(item) => item,
+        Err(err) => {
+            // Do not keep a file that no scan ended up referring to
+            if let Some(file) = file {
+                data.scans().discard(&file).await;
+                data.scans()
+                    .remove_unreferenced(data.pool(), &[file.sha256])
+                    .await?;
+            }
+            return Err(err);
+        }
+    };
+    data.derivatives().wake();
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
+}
+
+/**
+ * Edit Document Scan Handler
+ * Changes the scan and page number of a scan
+ */
+pub async fn edit_scan_handler(
+    AppPath((id, scan_id)): AppPath<(i32, i32)>,
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<UpdateScan>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = fetch_scan(data.pool(), id, scan_id).await?;
+
+    let query = format!(
+        "UPDATE {} SET scan_number = $1, page_number = $2 WHERE id = $3 RETURNING *",
+        TABLE
+    );
+    let item = sqlx::query_as::<_, DocumentScan>(&query)
+        .bind(body.scan_number.unwrap_or(item.scan_number))
+        .bind(body.page_number.unwrap_or(item.page_number))
+        .bind(scan_id)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(scan_id))?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Delete Document Scan Handler
+ * The file and derivatives are removed from disk as well, unless another scan has the same file
+ */
+pub async fn delete_scan_handler(
+    AppPath((id, scan_id)): AppPath<(i32, i32)>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_document_exists(data.pool(), id).await?;
+
+    let query = format!(
+        "DELETE FROM {} WHERE id = $1 AND document_id = $2 RETURNING {}",
+        TABLE,
+        file_hashes_sql("document_scans.id = $1")
+    );
+    let hashes: Vec<String> = sqlx::query_scalar(&query)
+        .bind(scan_id)
+        .bind(id)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(scan_id))?;
+
+    data.scans()
+        .remove_unreferenced(data.pool(), &hashes)
+        .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/**
+ * Download Document Scan Handler
+ * Sends the file of a scan. A single byte range can be requested with a `Range` header,
+ * e.g. to page through a large TIFF or PDF. The ETag is the SHA-256 of the file.
+ */
+pub async fn download_scan_handler(
+    AppPath((id, scan_id)): AppPath<(i32, i32)>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<Response, AppError> {
+    let scan = fetch_scan(data.pool(), id, scan_id).await?;
+    let (Some(sha256), Some(mime_type)) = (scan.sha256, scan.mime_type) else {
+        return Err(AppError::NotFound(format!(
+            "Scan with ID: {} has no file",
+            scan_id
+        )));
+    };
+
+    let mut file = tokio::fs::File::open(data.scans().path(&sha256))
+        .await
+        .map_err(|err| AppError::Internal(format!("Scan file {}: {}", sha256, err)))?;
+    let size = file
+        .metadata()
+        .await
+        .map_err(|err| AppError::Internal(format!("Scan file {}: {}", sha256, err)))?
+        .len();
+
+    let etag = format!("\"{}\"", sha256);
+    let file_headers = [
+        (CONTENT_TYPE, mime_type.clone()),
+        (ACCEPT_RANGES, "bytes".to_string()),
+        (ETAG, etag.clone()),
+        (
+            CONTENT_DISPOSITION,
+            format!(
+                "inline; filename=\"scan-{}.{}\"",
+                scan_id,
+                extension(&mime_type)
+            ),
+        ),
+    ];
+
+    // With `If-Range` the range only applies when the client has the current file
+    let range = headers
+        .get(RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| {
+            headers
+                .get(IF_RANGE)
+                .is_none_or(|value| value == etag.as_str())
+        });
+
+    let response = match ByteRange::parse(range, size) {
+        ByteRange::Full => (
+            StatusCode::OK,
+            file_headers,
+            [(CONTENT_LENGTH, size.to_string())],
+            Body::from_stream(ReaderStream::new(file)),
+        )
+            .into_response(),
+        ByteRange::Partial { start, end } => {
+            file.seek(SeekFrom::Start(start))
+                .await
+                .map_err(|err| AppError::Internal(format!("Scan file {}: {}", sha256, err)))?;
+            let length = end - start + 1;
+            (
+                StatusCode::PARTIAL_CONTENT,
+                file_headers,
+                [
+                    (CONTENT_LENGTH, length.to_string()),
+                    (CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, size)),
+                ],
+                Body::from_stream(ReaderStream::new(file.take(length))),
+            )
+                .into_response()
+        }
+        ByteRange::Unsatisfiable => (
+            [(CONTENT_RANGE, format!("bytes */{}", size))],
+            AppError::InvalidRequest(
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                format!("Range is outside of the {} bytes of the file", size),
+            ),
+        )
+            .into_response(),
+    };
+
+    Ok(response)
+}
+
+/**
+ * Scan Thumbnail Handler
+ * Sends a derivative of a scan image in one of the configured sizes, the smallest one by
+ * default, see `ThumbnailParams`. While the derivatives are still being made it responds
+ * with a 503 and a `Retry-After` header.
+ */
+pub async fn scan_thumbnail_handler(
+    AppPath((id, scan_id)): AppPath<(i32, i32)>,
+    AppQuery(params): AppQuery<ThumbnailParams>,
+    headers: HeaderMap,
+    State(data): State<Arc<AppState>>,
+) -> Result<Response, AppError> {
+    let settings = data.derivatives().settings();
+    let size = match &params.size {
+        Some(name) => settings.size(name).ok_or_else(|| {
+            let names: Vec<&str> = settings
+                .sizes
+                .iter()
+                .map(|size| size.name.as_str())
+                .collect();
+            AppError::Validation(format!(
+                "Unknown size: {}, expected one of {}",
+                name,
+                names.join(", ")
+            ))
+        })?,
+        None => &settings.sizes[0],
+    };
+    let format = params.format.unwrap_or_else(|| {
+        let accepts_webp = headers
+            .get_all(ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .any(|value| value.contains("image/webp"));
+        if accepts_webp {
+            DerivativeFormat::Webp
+        } else {
+            DerivativeFormat::Jpeg
+        }
+    });
+
+    let scan = fetch_scan(data.pool(), id, scan_id).await?;
+    match scan.derivatives_status {
+        DerivativesStatus::Done => {}
+        DerivativesStatus::Pending | DerivativesStatus::Processing => {
+            let error = AppError::InvalidRequest(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The thumbnails of the scan are still being made".to_string(),
+            );
+            return Ok(([(RETRY_AFTER, "5")], error).into_response());
+        }
+        DerivativesStatus::Failed | DerivativesStatus::Skipped => {
+            return Err(AppError::NotFound(format!(
+                "Scan with ID: {} has no thumbnails",
+                scan_id
+            )));
+        }
+    }
+
+    let derivative = sqlx::query_as::<_, ScanDerivative>(
+        "SELECT * FROM scan_derivatives WHERE scan_id = $1 AND size = $2 AND format = $3",
+    )
+    .bind(scan_id)
+    .bind(&size.name)
+    .bind(format)
+    .fetch_optional(data.pool())
+    .await?
+    .ok_or_else(|| {
+        AppError::NotFound(format!(
+            "Scan with ID: {} has no {} thumbnail",
+            scan_id, size.name
+        ))
+    })?;
+
+    let etag = format!("\"{}\"", derivative.sha256);
+    let cache_headers = [(ETAG, etag.clone()), (VARY, ACCEPT.to_string())];
+    if headers
+        .get(IF_NONE_MATCH)
+        .is_some_and(|value| value == etag.as_str())
+    {
+        return Ok((StatusCode::NOT_MODIFIED, cache_headers).into_response());
+    }
+
+    let bytes = tokio::fs::read(data.scans().path(&derivative.sha256))
+        .await
+        .map_err(|err| AppError::Internal(format!("Derivative {}: {}", derivative.sha256, err)))?;
+
+    Ok((
+        [(CONTENT_TYPE, format.mime_type().to_string())],
+        cache_headers,
+        bytes,
+    )
+        .into_response())
+}
+
+/**
+ * The scan with `scan_id` of the document with `id`, or a 404. The scans of a deleted
+ * document are hidden with it.
+ */
+async fn fetch_scan(pool: &PgPool, id: i32, scan_id: i32) -> Result<DocumentScan, AppError> {
+    let query = format!(
+        r#"
+        SELECT {0}.* FROM {0} JOIN documents ON documents.id = {0}.document_id
+        WHERE {0}.id = $1 AND {0}.document_id = $2 AND documents.deleted_at IS NULL
+    "#,
+        TABLE
+    );
+    sqlx::query_as::<_, DocumentScan>(&query)
+        .bind(scan_id)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::not_found(scan_id))
+}
+
+/**
+ * The name of an uploaded file without the directories some browsers send along.
+ */
+fn base_name(file_name: &str) -> Option<String> {
+    let name = file_name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+fn extension(mime_type: &str) -> &'static str {
+    match mime_type {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/tiff" => "tif",
+        "application/pdf" => "pdf",
+        _ => "bin",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Router,
+        body::Body,
+        http::{
+            Method, Request, StatusCode,
+            header::{
+                ACCEPT, ACCEPT_RANGES, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_NONE_MATCH, IF_RANGE,
+                RANGE, RETRY_AFTER,
+            },
+        },
+    };
+    use serde_json::{Value, json};
+    use sha2::{Digest, Sha256};
+    use sqlx::PgPool;
+
+    use crate::db::AppState;
+    use crate::scans::derivatives::{DerivativeSettings, DerivativeSize};
+    use crate::test_utils::{
+        app, app_state, insert_document, insert_named, multipart_request, png_header, png_image,
+        send, send_request, signed_in,
+    };
+
+    async fn seed_document(pool: &PgPool, inventory_number: &str) -> i32 {
+        let archive_id = insert_named(pool, "archives", inventory_number).await;
+        let institute_id = insert_named(pool, "institutes", inventory_number).await;
+        let place_id = insert_named(pool, "places", inventory_number).await;
+        insert_document(pool, inventory_number, archive_id, institute_id, place_id).await
+    }
+
+    async fn upload(
+        app: &Router,
+        document_id: i32,
+        parts: &[(&str, Option<&str>, &[u8])],
+    ) -> (StatusCode, Value) {
+        let uri = format!("/api/v1/documents/{}/scans", document_id);
+        let (status, _, body) =
+            send_request(app, multipart_request(Method::POST, &uri, parts)).await;
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    async fn download(
+        app: &Router,
+        uri: &str,
+        headers: &[(&str, &str)],
+    ) -> (StatusCode, axum::http::HeaderMap, Vec<u8>) {
+        let mut request = Request::builder().uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let (status, headers, body) = send_request(app, request.body(Body::empty()).unwrap()).await;
+        (status, headers, body.to_vec())
+    }
+
+    #[sqlx::test]
+    async fn upload_stores_file_with_metadata(pool: PgPool) {
+        let document_id = seed_document(&pool, "INV-1").await;
+        let app = app(pool);
+        let png = png_header(1200, 1800);
+
+        let (status, body) = upload(
+            &app,
+            document_id,
+            &[
+                ("scan_number", None, b"NL-HlmNHA_1_12"),
+                ("page_number", None, b"12v"),
+                ("file", Some("C:\\scans\\page 12.png"), &png),
+            ],
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        let item = &body["data"]["item"];
+        assert_eq!(item["document_id"], document_id);
+        assert_eq!(item["scan_number"], "NL-HlmNHA_1_12");
+        assert_eq!(item["page_number"], "12v");
+        assert_eq!(item["file_name"], "page 12.png");
+        assert_eq!(item["mime_type"], "image/png");
+        assert_eq!(item["size_bytes"], png.len());
+        assert_eq!(
+            (item["width"].clone(), item["height"].clone()),
+            (json!(1200), json!(1800))
+        );
+        assert_eq!(item["sha256"], format!("{:x}", Sha256::digest(&png)));
+
+        let uri = format!("/api/v1/documents/{}/scans", document_id);
+        let (_, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(body["results"], 1);
+        assert_eq!(body["items"][0], *item);
+    }
+
+    #[sqlx::test]
+    async fn upload_rejects_unsupported_and_empty_scans(pool: PgPool) {
+        let document_id = seed_document(&pool, "INV-1").await;
+        let state = app_state(pool.clone());
+        let app = signed_in(state.clone());
+
+        // A GIF, whatever its name says
+        let (status, body) = upload(
+            &app,
+            document_id,
+            &[("file", Some("scan.png"), b"GIF89a....")],
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(body["code"], "invalid_request");
+
+        let (status, _) = upload(&app, document_id, &[("scan_number", None, b" ")]).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, _) = upload(&app, document_id, &[("thumbnail", None, b"1")]).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        // A file that is not used for a scan is not kept
+        let png = png_header(10, 10);
+        let sha256 = format!("{:x}", Sha256::digest(&png));
+        for parts in [
+            &[("file", Some("a.png"), &png[..]), ("thumbnail", None, b"1")][..],
+            &[
+                ("file", Some("a.png"), &png[..]),
+                ("file", Some("b.png"), &png[..]),
+            ][..],
+        ] {
+            let (status, _) = upload(&app, document_id, parts).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert!(!state.scans().path(&sha256).exists());
+        }
+
+        let uri = format!("/api/v1/documents/{}/scans", document_id);
+        let (status, _) = send(&app, Method::POST, &uri, Some(json!({}))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = upload(&app, 4242, &[("scan_number", None, b"1")]).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let scans: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM document_scans")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(scans, 0);
+    }
+
+    #[sqlx::test]
+    async fn download_serves_whole_file_and_byte_ranges(pool: PgPool) {
+        let document_id = seed_document(&pool, "INV-1").await;
+        let app = app(pool);
+        let pdf = b"%PDF-1.7\nscanned page\n%%EOF\n".to_vec();
+        let (_, body) = upload(&app, document_id, &[("file", Some("page.pdf"), &pdf)]).await;
+        let scan = &body["data"]["item"];
+        assert!(scan["width"].is_null());
+        let uri = format!(
+            "/api/v1/documents/{}/scans/{}/file",
+            document_id, scan["id"]
+        );
+        let etag = format!("\"{}\"", scan["sha256"].as_str().unwrap());
+
+        let (status, headers, body) = download(&app, &uri, &[]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, pdf);
+        assert_eq!(headers[CONTENT_TYPE], "application/pdf");
+        assert_eq!(headers[ACCEPT_RANGES], "bytes");
+        assert_eq!(headers[ETAG], etag.as_str());
+
+        let (status, headers, body) = download(&app, &uri, &[("range", "bytes=0-7")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body, b"%PDF-1.7");
+        assert_eq!(headers[CONTENT_RANGE], format!("bytes 0-7/{}", pdf.len()));
+
+        let (status, _, body) = download(&app, &uri, &[("range", "bytes=-6")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body, b"%%EOF\n");
+
+        let (status, headers, _) = download(&app, &uri, &[("range", "bytes=1000-")]).await;
+        assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(headers[CONTENT_RANGE], format!("bytes */{}", pdf.len()));
+
+        // A range for another version of the file is ignored
+        let headers = [
+            (RANGE.as_str(), "bytes=0-7"),
+            (IF_RANGE.as_str(), "\"stale\""),
+        ];
+        let (status, _, body) = download(&app, &uri, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, pdf);
+        let headers = [
+            (RANGE.as_str(), "bytes=0-7"),
+            (IF_RANGE.as_str(), etag.as_str()),
+        ];
+        let (status, _, _) = download(&app, &uri, &headers).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    }
+
+    #[sqlx::test]
+    async fn scans_without_file_have_no_download(pool: PgPool) {
+        let document_id = seed_document(&pool, "INV-1").await;
+        let app = app(pool);
+        let (status, body) = upload(&app, document_id, &[("scan_number", None, b"SCAN-1")]).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert!(body["data"]["item"]["sha256"].is_null());
+
+        let uri = format!(
+            "/api/v1/documents/{}/scans/{}/file",
+            document_id, body["data"]["item"]["id"]
+        );
+        let (status, _, _) = download(&app, &uri, &[]).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn files_are_stored_once_and_removed_with_their_last_scan(pool: PgPool) {
+        let first = seed_document(&pool, "INV-1").await;
+        let second = seed_document(&pool, "INV-2").await;
+        let state = app_state(pool);
+        let scans = state.scans();
+        let app = signed_in(state.clone());
+        let png = png_header(10, 10);
+
+        let (_, body) = upload(&app, first, &[("file", Some("a.png"), &png)]).await;
+        let first_scan = body["data"]["item"]["id"].clone();
+        let sha256 = body["data"]["item"]["sha256"].as_str().unwrap().to_string();
+        let (status, body) = upload(&app, second, &[("file", Some("b.png"), &png)]).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["data"]["item"]["sha256"], sha256.as_str());
+        assert!(scans.path(&sha256).exists());
+
+        // The same file twice for one document
+        let (status, body) = upload(&app, first, &[("file", Some("c.png"), &png)]).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["message"], "Item with that file already exists");
+
+        let uri = format!("/api/v1/documents/{}/scans/{}", first, first_scan);
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(scans.path(&sha256).exists());
+
+        // A deleted document keeps its scans until it is purged
+        let uri = format!("/api/v1/documents/{}", second);
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(scans.path(&sha256).exists());
+        let uri = format!("/api/v1/documents/{}?purge=true", second);
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(!scans.path(&sha256).exists());
+    }
+
+    #[sqlx::test]
+    async fn edit_renumbers_scan_and_reindexes_document(pool: PgPool) {
+        let document_id = seed_document(&pool, "INV-1").await;
+        let app = app(pool);
+        let (_, body) = upload(
+            &app,
+            document_id,
+            &[
+                ("scan_number", None, b"SCAN-1"),
+                ("page_number", None, b"12"),
+            ],
+        )
+        .await;
+        let uri = format!(
+            "/api/v1/documents/{}/scans/{}",
+            document_id, body["data"]["item"]["id"]
+        );
+
+        let (status, body) = send(
+            &app,
+            Method::PATCH,
+            &uri,
+            Some(json!({ "scan_number": "SCAN-2" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["item"]["scan_number"], "SCAN-2");
+        assert_eq!(body["data"]["item"]["page_number"], "12");
+
+        let (_, body) = send(&app, Method::GET, "/api/v1/search?q=SCAN-2", None).await;
+        assert_eq!(body["total"], 1);
+        let (_, body) = send(&app, Method::GET, "/api/v1/search?q=SCAN-1", None).await;
+        assert_eq!(body["total"], 0);
+
+        let (_, body) = send(
+            &app,
+            Method::PATCH,
+            &uri,
+            Some(json!({ "page_number": null })),
+        )
+        .await;
+        assert!(body["data"]["item"]["page_number"].is_null());
+        assert_eq!(body["data"]["item"]["scan_number"], "SCAN-2");
+
+        // Scans are addressed through their own document only
+        let uri = format!(
+            "/api/v1/documents/{}/scans/{}",
+            document_id + 1,
+            body["data"]["item"]["id"]
+        );
+        let (status, _) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn scans_are_listed_in_page_order(pool: PgPool) {
+        let document_id = seed_document(&pool, "INV-1").await;
+        let app = app(pool);
+        for page in ["10r", "9v", "9r", "1"] {
+            upload(&app, document_id, &[("page_number", None, page.as_bytes())]).await;
+        }
+
+        let uri = format!("/api/v1/documents/{}/scans", document_id);
+        let (_, body) = send(&app, Method::GET, &uri, None).await;
+        let pages: Vec<&str> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["page_number"].as_str().unwrap())
+            .collect();
+        assert_eq!(pages, vec!["1", "9r", "9v", "10r"]);
+    }
+
+    fn image_size(bytes: &[u8]) -> (u32, u32) {
+        let image = image::load_from_memory(bytes).unwrap();
+        (image.width(), image.height())
+    }
+
+    #[sqlx::test]
+    async fn thumbnails_are_made_in_the_background(pool: PgPool) {
+        let document_id = seed_document(&pool, "INV-1").await;
+        let state = app_state(pool);
+        let app = signed_in(state.clone());
+        let (_, body) = upload(
+            &app,
+            document_id,
+            &[("file", Some("page.png"), &png_image(600, 300))],
+        )
+        .await;
+        assert_eq!(body["data"]["item"]["derivatives_status"], "pending");
+        let uri = format!(
+            "/api/v1/documents/{}/scans/{}",
+            document_id, body["data"]["item"]["id"]
+        );
+        let thumbnail = format!("{}/thumbnail", uri);
+
+        let (status, headers, _) = download(&app, &thumbnail, &[]).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(headers[RETRY_AFTER], "5");
+
+        assert_eq!(state.derivatives().process_pending().await.unwrap(), 1);
+        let (_, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(body["data"]["item"]["derivatives_status"], "done");
+
+        let accept = [(ACCEPT.as_str(), "image/avif,image/webp,*/*")];
+        let (status, headers, body) = download(&app, &thumbnail, &accept).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[CONTENT_TYPE], "image/webp");
+        assert_eq!(image_size(&body), (256, 128));
+
+        // Images are not scaled up
+        let medium = format!("{}?size=medium&format=jpeg", thumbnail);
+        let (status, headers, body) = download(&app, &medium, &accept).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[CONTENT_TYPE], "image/jpeg");
+        assert_eq!(image_size(&body), (600, 300));
+
+        let etag = headers[ETAG].to_str().unwrap().to_string();
+        let (status, _, body) =
+            download(&app, &medium, &[(IF_NONE_MATCH.as_str(), etag.as_str())]).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert!(body.is_empty());
+
+        let (status, _, _) = download(&app, &format!("{}?size=huge", thumbnail), &[]).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[sqlx::test]
+    async fn scans_without_image_are_skipped_and_broken_images_fail(pool: PgPool) {
+        let document_id = seed_document(&pool, "INV-1").await;
+        let state = app_state(pool);
+        let app = signed_in(state.clone());
+        for parts in [
+            vec![("file", Some("page.pdf"), b"%PDF-1.7\n".as_slice())],
+            vec![("scan_number", None, b"SCAN-1".as_slice())],
+        ] {
+            upload(&app, document_id, &parts).await;
+        }
+        let broken = png_header(10, 10);
+        upload(&app, document_id, &[("file", Some("broken.png"), &broken)]).await;
+
+        assert_eq!(state.derivatives().process_pending().await.unwrap(), 3);
+
+        let uri = format!("/api/v1/documents/{}/scans", document_id);
+        let (_, body) = send(&app, Method::GET, &uri, None).await;
+        let statuses: Vec<(&str, bool)> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| {
+                (
+                    item["derivatives_status"].as_str().unwrap(),
+                    item["derivatives_error"].is_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![("skipped", false), ("skipped", false), ("failed", true)]
+        );
+
+        let thumbnail = format!("{}/{}/thumbnail", uri, body["items"][0]["id"]);
+        let (status, _, _) = download(&app, &thumbnail, &[]).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[sqlx::test]
+    async fn derivatives_are_made_again_when_the_settings_change(pool: PgPool) {
+        let document_id = seed_document(&pool, "INV-1").await;
+        let state = app_state(pool.clone());
+        let app = signed_in(state.clone());
+        let (_, body) = upload(
+            &app,
+            document_id,
+            &[("file", Some("page.png"), &png_image(300, 600))],
+        )
+        .await;
+        let thumbnail = format!(
+            "/api/v1/documents/{}/scans/{}/thumbnail",
+            document_id, body["data"]["item"]["id"]
+        );
+        state.derivatives().process_pending().await.unwrap();
+        let (_, headers, _) = download(&app, &thumbnail, &[]).await;
+        let old_sha256 = headers[ETAG]
+            .to_str()
+            .unwrap()
+            .trim_matches('"')
+            .to_string();
+        assert_eq!(state.derivatives().requeue_stale().await.unwrap(), 0);
+
+        let settings = DerivativeSettings {
+            sizes: vec![DerivativeSize {
+                name: "small".to_string(),
+                max_dimension: 100,
+            }],
+            jpeg_quality: 60,
+        };
+        let state = std::sync::Arc::new(AppState::new(
+            pool,
+            state.scans().clone(),
+            settings,
+            state.auth().clone(),
+            state.iiif().clone(),
+        ));
+        let app = signed_in(state.clone());
+
+        assert_eq!(state.derivatives().requeue_stale().await.unwrap(), 1);
+        let (status, _, _) = download(&app, &thumbnail, &[]).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(state.derivatives().process_pending().await.unwrap(), 1);
+
+        let (status, _, body) = download(&app, &thumbnail, &[]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(image_size(&body), (50, 100));
+        assert!(!state.scans().path(&old_sha256).exists());
+    }
+
+    #[sqlx::test]
+    async fn only_stale_claims_are_queued_again(pool: PgPool) {
+        let document_id = seed_document(&pool, "INV-1").await;
+        let state = app_state(pool.clone());
+        let app = signed_in(state.clone());
+        upload(
+            &app,
+            document_id,
+            &[("file", Some("page.png"), &png_image(300, 600))],
+        )
+        .await;
+        state.derivatives().process_pending().await.unwrap();
+
+        // Another worker is still making the derivatives
+        sqlx::query(
+            "UPDATE document_scans SET derivatives_status = 'processing', derivatives_claimed_at = now()",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert_eq!(state.derivatives().requeue_stale().await.unwrap(), 0);
+
+        // That worker crashed an hour ago
+        sqlx::query("UPDATE document_scans SET derivatives_claimed_at = now() - interval '1 hour'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(state.derivatives().requeue_stale().await.unwrap(), 1);
+        assert_eq!(state.derivatives().process_pending().await.unwrap(), 1);
+        let status: String =
+            sqlx::query_scalar("SELECT derivatives_status::text FROM document_scans")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(status, "done");
+    }
+
+    #[sqlx::test]
+    async fn scans_of_deleted_documents_are_hidden(pool: PgPool) {
+        let document_id = seed_document(&pool, "INV-1").await;
+        let app = app(pool.clone());
+        let (_, body) = upload(
+            &app,
+            document_id,
+            &[("file", Some("page.png"), &png_image(30, 60))],
+        )
+        .await;
+        let uri = format!(
+            "/api/v1/documents/{}/scans/{}",
+            document_id, body["data"]["item"]["id"]
+        );
+        let (status, _) = send(
+            &app,
+            Method::DELETE,
+            &format!("/api/v1/documents/{}", document_id),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        for (method, uri, body) in [
+            (Method::GET, uri.clone(), None),
+            (
+                Method::PATCH,
+                uri.clone(),
+                Some(json!({"page_number": "2"})),
+            ),
+            (Method::GET, format!("{}/file", uri), None),
+            (Method::GET, format!("{}/thumbnail", uri), None),
+            (Method::DELETE, uri.clone(), None),
+        ] {
+            let (status, _) = send(&app, method.clone(), &uri, body).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{} {}", method, uri);
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::extractors::AppQuery;
+use crate::models::search::{Facet, SearchHit};
+use crate::pagination::{Filter, validate_limit};
+use crate::schemas::documents::DocumentFilters;
+use crate::schemas::search::SearchParams;
+
+use axum::{Json, extract::State, response::IntoResponse};
+use serde_json::json;
+use sqlx::{Postgres, QueryBuilder};
+use std::sync::Arc;
+
+/**
+ * The search terms as a tsquery that matches Dutch and English stems as well as exact words,
+ * e.g. inventory numbers. Supports the web search syntax: `"quoted phrases"`, `or` and `-word`.
+ */
+fn push_tsquery<'args>(query: &mut QueryBuilder<'args, Postgres>, q: &str) {
+    for (i, config) in ["dutch", "english", "simple"].iter().enumerate() {
+        if i > 0 {
+            query.push(" || ");
+        }
+        query.push(format!("websearch_to_tsquery('{}', ", config));
+        query.push_bind(q.to_string());
+        query.push(")");
+    }
+}
+
+/**
+ * `FROM` clause shared by the hits and the facets, joined with the linked names
+ * and restricted to documents that match the search terms and `filters`.
+ */
+fn push_matches<'args>(
+    query: &mut QueryBuilder<'args, Postgres>,
+    q: &str,
+    filters: &DocumentFilters,
+) {
+    query.push(" FROM documents CROSS JOIN (SELECT ");
+    push_tsquery(query, q);
+    query.push(
+        r#" AS query) search
+        JOIN archives ON archives.id = documents.archive_id
+        JOIN institutes ON institutes.id = documents.institute_id
+        JOIN places ON places.id = documents.place_id
+        WHERE documents.search_vector @@ search.query"#,
+    );
+    filters.push_conditions(query);
+}
+
+/**
+ * Search Handler
+ * Full-text search across document notes and transcriptions, inventory and scan numbers and
+ * the names of the linked archive, institute and place. Returns ranked hits with a highlighted snippet and
+ * facet counts over all hits. Accepts the same filters as the documents list.
+ */
+pub async fn search_handler(
+    AppQuery(params): AppQuery<SearchParams>,
+    AppQuery(filters): AppQuery<DocumentFilters>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let q = params.q.trim();
+    if q.is_empty() {
+        return Err(AppError::Validation("q must not be empty".to_string()));
+    }
+    let limit = validate_limit(params.limit, params.offset)?;
+
+    let mut query = QueryBuilder::new("SELECT COUNT(*)");
+    push_matches(&mut query, q, &filters);
+    let total: i64 = query.build_query_scalar().fetch_one(data.pool()).await?;
+
+    // The text is escaped first, so that only the <mark> tags in the snippet are markup
+    let mut query = QueryBuilder::new(
+        r#"SELECT documents.*,
+            ts_rank_cd(documents.search_vector, search.query) AS rank,
+            ts_headline(
+                'dutch',
+                replace(replace(replace(
+                    concat_ws(' · ', documents.inventory_number, documents.notes,
+                        (SELECT string_agg(text, ' · ') FROM transcriptions
+                            WHERE document_id = documents.id),
+                        archives.name, institutes.name, places.name),
+                    '&', '&amp;'), '<', '&lt;'), '>', '&gt;'),
+                search.query,
+                'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=20, MinWords=5'
+            ) AS snippet"#,
+    );
+    push_matches(&mut query, q, &filters);
+    query.push(" ORDER BY rank DESC, documents.id LIMIT ");
+    query.push_bind(limit);
+    query.push(" OFFSET ");
+    query.push_bind(params.offset.unwrap_or(0));
+    let hits = query
+        .build_query_as::<SearchHit>()
+        .fetch_all(data.pool())
+        .await?;
+
+    let mut facets = serde_json::Map::new();
+    for table in ["archives", "institutes", "places"] {
+        let mut query = QueryBuilder::new(format!(
+            "SELECT {table}.id, {table}.name, COUNT(*) AS count"
+        ));
+        push_matches(&mut query, q, &filters);
+        query.push(format!(
+            " GROUP BY {table}.id, {table}.name ORDER BY count DESC, {table}.name"
+        ));
+        let counts = query
+            .build_query_as::<Facet>()
+            .fetch_all(data.pool())
+            .await?;
+        facets.insert(table.to_string(), json!(counts));
+    }
+
+    let json_response = json!({
+        "status": "success",
+        "results": hits.len(),
+        "total": total,
+        "limit": limit,
+        "offset": params.offset.unwrap_or(0),
+        "items": hits,
+        "facets": facets,
+    });
+    Ok(Json(json_response))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{app, insert_document, insert_named, send};
+
+    /**
+     * Three documents in two archives, with Dutch and English notes.
+     */
+    async fn seed(pool: &PgPool) -> (i32, i32) {
+        let archive_id = insert_named(pool, "archives", "Noord-Hollands Archief").await;
+        let other_id = insert_named(pool, "archives", "Stadsarchief Amsterdam").await;
+        let institute_id = insert_named(pool, "institutes", "Burgerweeshuis").await;
+        let place_id = insert_named(pool, "places", "Haarlem").await;
+
+        for (inventory_number, archive_id, notes) in [
+            (
+                "INV-1",
+                archive_id,
+                "Doopinschrijving in een van de kerken van Haarlem",
+            ),
+            (
+                "INV-2",
+                archive_id,
+                "Baptism of the orphans, witnessed by the regents",
+            ),
+            ("INV-3", other_id, "Begraven in de Grote Kerk, kind van Jan"),
+        ] {
+            let id =
+                insert_document(pool, inventory_number, archive_id, institute_id, place_id).await;
+            sqlx::query("UPDATE documents SET notes = $1 WHERE id = $2")
+                .bind(notes)
+                .bind(id)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+
+        (archive_id, other_id)
+    }
+
+    fn inventory_numbers(body: &Value) -> Vec<&str> {
+        body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["inventory_number"].as_str().unwrap())
+            .collect()
+    }
+
+    #[sqlx::test]
+    async fn search_matches_dutch_and_english_stems(pool: PgPool) {
+        seed(&pool).await;
+        let app = app(pool);
+
+        // "kerk" and "kerken" share a Dutch stem
+        let (status, body) = send(&app, Method::GET, "/api/v1/search?q=kerk", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut found = inventory_numbers(&body);
+        found.sort();
+        assert_eq!(found, vec!["INV-1", "INV-3"]);
+        assert_eq!(body["total"], 2);
+
+        // "orphan" matches "orphans" through the English stemmer
+        let (_, body) = send(&app, Method::GET, "/api/v1/search?q=orphan", None).await;
+        assert_eq!(inventory_numbers(&body), vec!["INV-2"]);
+    }
+
+    #[sqlx::test]
+    async fn search_matches_identifiers_and_linked_names(pool: PgPool) {
+        let (archive_id, _) = seed(&pool).await;
+        let app = app(pool);
+
+        let (_, body) = send(&app, Method::GET, "/api/v1/search?q=INV-2", None).await;
+        assert_eq!(inventory_numbers(&body), vec!["INV-2"]);
+
+        let (_, body) = send(&app, Method::GET, "/api/v1/search?q=amsterdam", None).await;
+        assert_eq!(inventory_numbers(&body), vec!["INV-3"]);
+
+        // Renaming the archive re-indexes its documents
+        let uri = format!("/api/v1/archives/{}", archive_id);
+        send(
+            &app,
+            Method::PATCH,
+            &uri,
+            Some(json!({ "name": "Rijksarchief" })),
+        )
+        .await;
+        let (_, body) = send(&app, Method::GET, "/api/v1/search?q=rijksarchief", None).await;
+        assert_eq!(body["total"], 2);
+    }
+
+    #[sqlx::test]
+    async fn search_ranks_and_highlights_hits(pool: PgPool) {
+        seed(&pool).await;
+        let app = app(pool);
+
+        // INV-3 mentions both terms, INV-1 only one of them
+        let (_, body) = send(&app, Method::GET, "/api/v1/search?q=kind%20or%20kerk", None).await;
+        assert_eq!(inventory_numbers(&body), vec!["INV-3", "INV-1"]);
+        assert!(
+            body["items"][0]["rank"].as_f64().unwrap() > body["items"][1]["rank"].as_f64().unwrap()
+        );
+
+        let snippet = body["items"][0]["snippet"].as_str().unwrap();
+        assert!(snippet.contains("<mark>Kerk</mark>"), "{}", snippet);
+    }
+
+    #[sqlx::test]
+    async fn search_counts_facets_and_applies_filters(pool: PgPool) {
+        let (archive_id, other_id) = seed(&pool).await;
+        let app = app(pool);
+
+        let (_, body) = send(&app, Method::GET, "/api/v1/search?q=haarlem", None).await;
+        assert_eq!(body["total"], 3);
+        assert_eq!(
+            body["facets"]["archives"],
+            json!([
+                { "id": archive_id, "name": "Noord-Hollands Archief", "count": 2 },
+                { "id": other_id, "name": "Stadsarchief Amsterdam", "count": 1 },
+            ])
+        );
+        assert_eq!(body["facets"]["places"][0]["count"], 3);
+
+        let uri = format!("/api/v1/search?q=haarlem&archive_id={}&limit=1", other_id);
+        let (_, body) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(inventory_numbers(&body), vec!["INV-3"]);
+        assert_eq!(body["facets"]["archives"][0]["id"], other_id);
+        assert_eq!(body["facets"]["archives"].as_array().unwrap().len(), 1);
+    }
+
+    #[sqlx::test]
+    async fn search_rejects_empty_query(pool: PgPool) {
+        let app = app(pool);
+
+        for uri in [
+            "/api/v1/search",
+            "/api/v1/search?q=%20",
+            "/api/v1/search?q=a&limit=0",
+        ] {
+            let (status, body) = send(&app, Method::GET, uri, None).await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", uri);
+            assert_eq!(body["code"], "validation_failed");
+        }
+    }
+}
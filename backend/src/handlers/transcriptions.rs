@@ -0,0 +1,723 @@
+use crate::auth::Actor;
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::extractors::{AppJson, AppPath, AppQuery};
+use crate::handlers::common::ensure_document_exists;
+use crate::models::transcriptions::{Transcription, TranscriptionRevision, TranscriptionStatus};
+use crate::schemas::transcriptions::{CreateTranscription, DiffParams, UpdateTranscription};
+
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use serde_json::{Value, json};
+use similar::{ChangeTag, TextDiff};
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+
+const TABLE: &str = "transcriptions";
+
+/**
+ * List Document Transcriptions Handler
+ * The transcriptions of a document in the order they were added
+ */
+pub async fn document_transcriptions_list_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_document_exists(data.pool(), id).await?;
+
+    let query = format!("SELECT * FROM {} WHERE document_id = $1 ORDER BY id", TABLE);
+    let items = sqlx::query_as::<_, Transcription>(&query)
+        .bind(id)
+        .fetch_all(data.pool())
+        .await?;
+
+    let json_response = json!({
+        "status": "success",
+        "results": items.len(),
+        "items": items,
+    });
+    Ok(Json(json_response))
+}
+
+/**
+ * Fetch a single Transcription
+ */
+pub async fn get_transcription_handler(
+    AppPath((id, transcription_id)): AppPath<(i32, i32)>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = fetch_transcription(data.pool(), id, transcription_id).await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Create Transcription Handler
+ * Adds a transcription to a document as its first revision, transcribed by the signed in
+ * user. A `scan_id` must be a scan of the same document.
+ */
+pub async fn create_transcription_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+    actor: Actor,
+    AppJson(body): AppJson<CreateTranscription>,
+) -> Result<impl IntoResponse, AppError> {
+    body.validate().map_err(AppError::Validation)?;
+    ensure_document_exists(data.pool(), id).await?;
+
+    let mut tx = data.pool().begin().await?;
+
+    let query = format!(
+        r#"
+        INSERT INTO {} (document_id, scan_id, transcriber_id, transcriber, language, status, text)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+    "#,
+        TABLE
+    );
+    let (transcriber_id, transcriber) = transcriber(&actor);
+    let item = sqlx::query_as::<_, Transcription>(&query)
+        .bind(id)
+        .bind(body.scan_id)
+        .bind(transcriber_id)
+        .bind(transcriber)
+        .bind(&body.language)
+        .bind(body.status.unwrap_or(TranscriptionStatus::Draft))
+        .bind(&body.text)
+        .fetch_one(&mut *tx)
+        .await?;
+    record_revision(&mut tx, item.id).await?;
+
+    tx.commit().await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
+}
+
+/**
+ * Edit Transcription Handler
+ * A change of the text, language or status adds a revision by the signed in user.
+ * Moving the transcription to another scan does not.
+ */
+pub async fn edit_transcription_handler(
+    AppPath((id, transcription_id)): AppPath<(i32, i32)>,
+    State(data): State<Arc<AppState>>,
+    actor: Actor,
+    AppJson(body): AppJson<UpdateTranscription>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
+
+    let current = lock_transcription(&mut tx, id, transcription_id).await?;
+    let changes = body.apply_to(current.clone());
+    changes.validate().map_err(AppError::Validation)?;
+    let item = save_changes(&mut tx, &current, changes, &actor).await?;
+
+    tx.commit().await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Delete Transcription Handler
+ * The revision history is deleted with it
+ */
+pub async fn delete_transcription_handler(
+    AppPath((id, transcription_id)): AppPath<(i32, i32)>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_document_exists(data.pool(), id).await?;
+
+    let query = format!("DELETE FROM {} WHERE id = $1 AND document_id = $2", TABLE);
+    let result = sqlx::query(&query)
+        .bind(transcription_id)
+        .bind(id)
+        .execute(data.pool())
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::not_found(transcription_id));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/**
+ * List Transcription Revisions Handler
+ * Every version of a transcription, from the first to the current one
+ */
+pub async fn transcription_revisions_list_handler(
+    AppPath((id, transcription_id)): AppPath<(i32, i32)>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    fetch_transcription(data.pool(), id, transcription_id).await?;
+
+    let items = sqlx::query_as::<_, TranscriptionRevision>(
+        "SELECT * FROM transcription_revisions WHERE transcription_id = $1 ORDER BY revision",
+    )
+    .bind(transcription_id)
+    .fetch_all(data.pool())
+    .await?;
+
+    let json_response = json!({
+        "status": "success",
+        "results": items.len(),
+        "items": items,
+    });
+    Ok(Json(json_response))
+}
+
+/**
+ * Fetch a single Transcription Revision
+ */
+pub async fn get_revision_handler(
+    AppPath((id, transcription_id, revision)): AppPath<(i32, i32, i32)>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    fetch_transcription(data.pool(), id, transcription_id).await?;
+    let item = fetch_revision(data.pool(), transcription_id, revision).await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Transcription Diff Handler
+ * Compares two revisions: the fields that changed, and the text line by line, both as a
+ * list of changes and as a unified diff. By default the current revision is compared
+ * with the one before it.
+ */
+pub async fn transcription_diff_handler(
+    AppPath((id, transcription_id)): AppPath<(i32, i32)>,
+    AppQuery(params): AppQuery<DiffParams>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let transcription = fetch_transcription(data.pool(), id, transcription_id).await?;
+    let to = params.to.unwrap_or(transcription.revision);
+    let from = params.from.unwrap_or((to - 1).max(1));
+
+    let old = fetch_revision(data.pool(), transcription_id, from).await?;
+    let new = fetch_revision(data.pool(), transcription_id, to).await?;
+
+    let mut fields = serde_json::Map::new();
+    for (name, old_value, new_value) in [
+        (
+            "transcriber",
+            json!(old.transcriber),
+            json!(new.transcriber),
+        ),
+        ("language", json!(old.language), json!(new.language)),
+        ("status", json!(old.status), json!(new.status)),
+    ] {
+        if old_value != new_value {
+            fields.insert(
+                name.to_string(),
+                json!({"from": old_value, "to": new_value}),
+            );
+        }
+    }
+
+    let text_diff = TextDiff::from_lines(&old.text, &new.text);
+    let changes: Vec<Value> = text_diff
+        .iter_all_changes()
+        .map(|change| {
+            let tag = match change.tag() {
+                ChangeTag::Equal => "equal",
+                ChangeTag::Delete => "delete",
+                ChangeTag::Insert => "insert",
+            };
+            json!({
+                "tag": tag,
+                "old_line": change.old_index().map(|index| index + 1),
+                "new_line": change.new_index().map(|index| index + 1),
+                "value": change.value(),
+            })
+        })
+        .collect();
+    let unified = text_diff
+        .unified_diff()
+        .header(&format!("revision {}", from), &format!("revision {}", to))
+        .to_string();
+
+    let json_response = json!({"status": "success","data": json!({
+        "diff": {
+            "from": from,
+            "to": to,
+            "fields": fields,
+            "changes": changes,
+            "unified": unified,
+        }
+    })});
+    Ok(Json(json_response))
+}
+
+/**
+ * Restore Transcription Revision Handler
+ * Makes an older revision current again by adding it as a new revision,
+ * so that the revisions after it stay in the history
+ */
+pub async fn restore_revision_handler(
+    AppPath((id, transcription_id, revision)): AppPath<(i32, i32, i32)>,
+    State(data): State<Arc<AppState>>,
+    actor: Actor,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
+
+    let current = lock_transcription(&mut tx, id, transcription_id).await?;
+    let restored = fetch_revision(&mut *tx, transcription_id, revision).await?;
+    let changes = CreateTranscription {
+        scan_id: current.scan_id,
+        language: restored.language,
+        status: Some(restored.status),
+        text: restored.text,
+    };
+    let item = save_changes(&mut tx, &current, changes, &actor).await?;
+
+    tx.commit().await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * The transcription with `transcription_id` of the document with `id`, or a 404.
+ * The transcriptions of a deleted document are hidden with it.
+ */
+async fn fetch_transcription(
+    pool: &PgPool,
+    id: i32,
+    transcription_id: i32,
+) -> Result<Transcription, AppError> {
+    let query = format!(
+        r#"
+        SELECT {0}.* FROM {0} JOIN documents ON documents.id = {0}.document_id
+        WHERE {0}.id = $1 AND {0}.document_id = $2 AND documents.deleted_at IS NULL
+    "#,
+        TABLE
+    );
+    sqlx::query_as::<_, Transcription>(&query)
+        .bind(transcription_id)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::not_found(transcription_id))
+}
+
+/**
+ * Like `fetch_transcription`, but locks the transcription until the transaction ends,
+ * so that concurrent edits get consecutive revision numbers.
+ */
+async fn lock_transcription(
+    conn: &mut PgConnection,
+    id: i32,
+    transcription_id: i32,
+) -> Result<Transcription, AppError> {
+    let query = format!(
+        r#"
+        SELECT {0}.* FROM {0} JOIN documents ON documents.id = {0}.document_id
+        WHERE {0}.id = $1 AND {0}.document_id = $2 AND documents.deleted_at IS NULL
+        FOR UPDATE OF {0}
+    "#,
+        TABLE
+    );
+    sqlx::query_as::<_, Transcription>(&query)
+        .bind(transcription_id)
+        .bind(id)
+        .fetch_optional(conn)
+        .await?
+        .ok_or_else(|| AppError::not_found(transcription_id))
+}
+
+async fn fetch_revision<'c, E>(
+    executor: E,
+    transcription_id: i32,
+    revision: i32,
+) -> Result<TranscriptionRevision, AppError>
+where
+    E: sqlx::PgExecutor<'c>,
+{
+    sqlx::query_as::<_, TranscriptionRevision>(
+        "SELECT * FROM transcription_revisions WHERE transcription_id = $1 AND revision = $2",
+    )
+    .bind(transcription_id)
+    .bind(revision)
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| {
+        AppError::NotFound(format!(
+            "Revision {} of transcription with ID: {} not found",
+            revision, transcription_id
+        ))
+    })
+}
+
+/**
+ * Store `changes` to the locked transcription `current`, as a new revision by `actor` if
+ * its content changed.
+ */
+async fn save_changes(
+    conn: &mut PgConnection,
+    current: &Transcription,
+    changes: CreateTranscription,
+    actor: &Actor,
+) -> Result<Transcription, AppError> {
+    let status = changes.status.unwrap_or(current.status);
+    let new_revision = changes.language != current.language
+        || status != current.status
+        || changes.text != current.text;
+    let (transcriber_id, transcriber) = match new_revision {
+        true => transcriber(actor),
+        false => (current.transcriber_id, current.transcriber.as_str()),
+    };
+
+    let query = format!(
+        r#"
+        UPDATE {}
+        SET scan_id = $1, transcriber_id = $2, transcriber = $3, language = $4, status = $5,
+            text = $6, revision = revision + $7, updated_at = now()
+        WHERE id = $8
+        RETURNING *
+    "#,
+        TABLE
+    );
+    let item = sqlx::query_as::<_, Transcription>(&query)
+        .bind(changes.scan_id)
+        .bind(transcriber_id)
+        .bind(transcriber)
+        .bind(&changes.language)
+        .bind(status)
+        .bind(&changes.text)
+        .bind(new_revision as i32)
+        .bind(current.id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    if new_revision {
+        record_revision(conn, item.id).await?;
+    }
+
+    Ok(item)
+}
+
+/**
+ * Copy the current content of a transcription into its revision history.
+ */
+async fn record_revision(conn: &mut PgConnection, transcription_id: i32) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO transcription_revisions
+            (transcription_id, revision, transcriber_id, transcriber, language, status, text)
+        SELECT id, revision, transcriber_id, transcriber, language, status, text
+        FROM transcriptions
+        WHERE id = $1
+    "#,
+    )
+    .bind(transcription_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/**
+ * The id and name of who transcribes when `actor` makes a revision. A script with an API key
+ * transcribes on behalf of the user who created the key, under the name of the key.
+ */
+fn transcriber(actor: &Actor) -> (Option<i32>, &str) {
+    match actor {
+        Actor::User(user) => (Some(user.user.id), &user.user.name),
+        Actor::ApiKey(key) => (key.created_by, &key.name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+
+    use crate::models::users::Role;
+    use crate::test_utils::{
+        app, app_state, insert_document, insert_named, send, signed_in, signed_in_as, test_user,
+    };
+
+    async fn seed_document(pool: &PgPool, inventory_number: &str) -> i32 {
+        let archive =
+            insert_named(pool, "archives", &format!("Archief {}", inventory_number)).await;
+        let institute = insert_named(
+            pool,
+            "institutes",
+            &format!("Instelling {}", inventory_number),
+        )
+        .await;
+        let place = insert_named(pool, "places", &format!("Plaats {}", inventory_number)).await;
+        insert_document(pool, inventory_number, archive, institute, place).await
+    }
+
+    async fn insert_scan(pool: &PgPool, document_id: i32, page_number: &str) -> i32 {
+        sqlx::query_scalar(
+            "INSERT INTO document_scans (document_id, page_number) VALUES ($1, $2) RETURNING id",
+        )
+        .bind(document_id)
+        .bind(page_number)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    fn transcription(text: &str) -> Value {
+        json!({"language": "nl", "text": text})
+    }
+
+    #[sqlx::test]
+    async fn create_validates_and_ties_to_scans_of_the_document(pool: PgPool) {
+        let app = app(pool.clone());
+        let id = seed_document(&pool, "INV-1").await;
+        let other_id = seed_document(&pool, "INV-2").await;
+        let scan_id = insert_scan(&pool, id, "1").await;
+        let other_scan_id = insert_scan(&pool, other_id, "1").await;
+        let uri = format!("/api/v1/documents/{}/transcriptions", id);
+
+        let mut body = transcription("Compareerde voor mij");
+        body["scan_id"] = json!(scan_id);
+        let (status, json) = send(&app, Method::POST, &uri, Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let item = &json["data"]["item"];
+        assert_eq!(item["scan_id"], scan_id);
+        assert_eq!(item["transcriber_id"], test_user(&pool, Role::Admin).await);
+        assert_eq!(item["transcriber"], "Test admin");
+        assert_eq!(item["status"], "draft");
+        assert_eq!(item["revision"], 1);
+
+        let mut body = transcription("Elders");
+        body["scan_id"] = json!(other_scan_id);
+        let (status, json) = send(&app, Method::POST, &uri, Some(body)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(json["message"], "Referenced scan does not exist");
+
+        let mut body = transcription("Tekst");
+        body["language"] = json!("Nederlands");
+        let (status, _) = send(&app, Method::POST, &uri, Some(body)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            "/api/v1/documents/999999/transcriptions",
+            Some(transcription("Tekst")),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Deleting the scan keeps the transcription with the document
+        sqlx::query("DELETE FROM document_scans WHERE id = $1")
+            .bind(scan_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let (_, json) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(json["results"], 1);
+        assert_eq!(json["items"][0]["scan_id"], Value::Null);
+    }
+
+    #[sqlx::test]
+    async fn edits_add_revisions_that_can_be_compared_and_restored(pool: PgPool) {
+        let state = app_state(pool.clone());
+        let app = signed_in(state.clone());
+        let editor = signed_in_as(state, Role::Editor);
+        let id = seed_document(&pool, "INV-1").await;
+        let uri = format!("/api/v1/documents/{}/transcriptions", id);
+
+        let (_, json) = send(
+            &app,
+            Method::POST,
+            &uri,
+            Some(transcription("Compareerde voor mij\nJan Pietersz\n")),
+        )
+        .await;
+        let uri = format!("{}/{}", uri, json["data"]["item"]["id"]);
+
+        let (status, json) = send(
+            &editor,
+            Method::PATCH,
+            &uri,
+            Some(json!({"text": "Compareerde voor mij\nJan Pieterszoon\n", "status": "reviewed"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(json["data"]["item"]["revision"], 2);
+        assert_eq!(json["data"]["item"]["transcriber"], "Test editor");
+
+        // Changing nothing but the scan adds no revision, and keeps the transcriber
+        let (_, json) = send(&app, Method::PATCH, &uri, Some(json!({"scan_id": null}))).await;
+        assert_eq!(json["data"]["item"]["revision"], 2);
+        assert_eq!(json["data"]["item"]["transcriber"], "Test editor");
+
+        let (_, json) = send(&app, Method::GET, &format!("{}/revisions", uri), None).await;
+        assert_eq!(json["results"], 2);
+        assert_eq!(json["items"][0]["status"], "draft");
+        assert_eq!(json["items"][0]["transcriber"], "Test admin");
+        assert_eq!(json["items"][1]["status"], "reviewed");
+        assert_eq!(json["items"][1]["transcriber"], "Test editor");
+
+        let (status, json) = send(&app, Method::GET, &format!("{}/diff", uri), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let diff = &json["data"]["diff"];
+        assert_eq!(
+            (diff["from"].as_i64(), diff["to"].as_i64()),
+            (Some(1), Some(2))
+        );
+        assert_eq!(
+            diff["fields"],
+            json!({
+                "transcriber": {"from": "Test admin", "to": "Test editor"},
+                "status": {"from": "draft", "to": "reviewed"},
+            })
+        );
+        let changes: Vec<(&str, &str)> = diff["changes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|change| {
+                (
+                    change["tag"].as_str().unwrap(),
+                    change["value"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("equal", "Compareerde voor mij\n"),
+                ("delete", "Jan Pietersz\n"),
+                ("insert", "Jan Pieterszoon\n"),
+            ]
+        );
+        let unified = diff["unified"].as_str().unwrap();
+        assert!(unified.starts_with("--- revision 1\n+++ revision 2\n"));
+        assert!(unified.contains("-Jan Pietersz\n+Jan Pieterszoon\n"));
+
+        let (status, json) = send(
+            &app,
+            Method::POST,
+            &format!("{}/revisions/1/restore", uri),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let item = &json["data"]["item"];
+        assert_eq!(item["revision"], 3);
+        assert_eq!(item["status"], "draft");
+        assert_eq!(item["text"], "Compareerde voor mij\nJan Pietersz\n");
+
+        let (_, json) = send(
+            &app,
+            Method::GET,
+            &format!("{}/diff?from=1&to=3", uri),
+            None,
+        )
+        .await;
+        assert_eq!(json["data"]["diff"]["fields"], json!({}));
+        assert_eq!(json["data"]["diff"]["unified"], "");
+
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            &format!("{}/revisions/9/restore", uri),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let revisions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM transcription_revisions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(revisions, 0);
+    }
+
+    #[sqlx::test]
+    async fn transcribed_text_is_searchable(pool: PgPool) {
+        let app = app(pool.clone());
+        let id = seed_document(&pool, "INV-1").await;
+        let uri = format!("/api/v1/documents/{}/transcriptions", id);
+
+        let (_, json) = send(
+            &app,
+            Method::POST,
+            &uri,
+            Some(transcription("Weeskinderen van het Burgerweeshuis")),
+        )
+        .await;
+        let uri = format!("{}/{}", uri, json["data"]["item"]["id"]);
+
+        let (_, json) = send(&app, Method::GET, "/api/v1/search?q=weeskinderen", None).await;
+        assert_eq!(json["total"], 1);
+        assert!(
+            json["items"][0]["snippet"]
+                .as_str()
+                .unwrap()
+                .contains("<mark>Weeskinderen</mark>")
+        );
+
+        send(&app, Method::PATCH, &uri, Some(json!({"text": "Doopboek"}))).await;
+        let (_, json) = send(&app, Method::GET, "/api/v1/search?q=weeskinderen", None).await;
+        assert_eq!(json["total"], 0);
+        let (_, json) = send(&app, Method::GET, "/api/v1/search?q=doopboek", None).await;
+        assert_eq!(json["total"], 1);
+
+        send(&app, Method::DELETE, &uri, None).await;
+        let (_, json) = send(&app, Method::GET, "/api/v1/search?q=doopboek", None).await;
+        assert_eq!(json["total"], 0);
+    }
+
+    #[sqlx::test]
+    async fn transcriptions_of_deleted_documents_are_hidden(pool: PgPool) {
+        let app = app(pool.clone());
+        let id = seed_document(&pool, "INV-1").await;
+        let (_, json) = send(
+            &app,
+            Method::POST,
+            &format!("/api/v1/documents/{}/transcriptions", id),
+            Some(transcription("Compareerde voor mij")),
+        )
+        .await;
+        let uri = format!(
+            "/api/v1/documents/{}/transcriptions/{}",
+            id, json["data"]["item"]["id"]
+        );
+        let (status, _) = send(
+            &app,
+            Method::DELETE,
+            &format!("/api/v1/documents/{}", id),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        for (method, uri, body) in [
+            (Method::GET, uri.clone(), None),
+            (Method::PATCH, uri.clone(), Some(json!({"text": "Elders"}))),
+            (Method::GET, format!("{}/revisions", uri), None),
+            (Method::GET, format!("{}/revisions/1", uri), None),
+            (Method::GET, format!("{}/diff", uri), None),
+            (Method::POST, format!("{}/revisions/1/restore", uri), None),
+            (Method::DELETE, uri.clone(), None),
+        ] {
+            let (status, _) = send(&app, method.clone(), &uri, body).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{} {}", method, uri);
+        }
+    }
+}
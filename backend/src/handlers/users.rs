@@ -0,0 +1,305 @@
+use crate::auth::{create_user, fetch_user_with_roles, set_roles};
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::extractors::{AppJson, AppPath};
+use crate::models::users::{Role, UserWithRoles};
+use crate::schemas::users::{CreateUser, UpdateRoles};
+
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use serde_json::json;
+use std::sync::Arc;
+
+/**
+ * List Roles Handler
+ * Every role with the permissions it grants.
+ */
+pub async fn roles_list_handler() -> Result<impl IntoResponse, AppError> {
+    let items: Vec<_> = Role::ALL
+        .into_iter()
+        .map(|role| json!({"role": role, "permissions": role.permissions()}))
+        .collect();
+
+    let json_response = json!({
+        "status": "success",
+        "results": items.len(),
+        "items": items,
+    });
+    Ok(Json(json_response))
+}
+
+/**
+ * List Users Handler
+ * All users with their roles, ordered by email.
+ */
+pub async fn users_list_handler(
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let items = sqlx::query_as::<_, UserWithRoles>(
+        r#"
+        SELECT users.id, email, name, active, created_at, last_login_at,
+            array_remove(array_agg(role ORDER BY role), NULL) AS roles
+        FROM users
+        LEFT JOIN user_roles ON user_roles.user_id = users.id
+        GROUP BY users.id
+        ORDER BY email
+    "#,
+    )
+    .fetch_all(data.pool())
+    .await?;
+
+    let json_response = json!({
+        "status": "success",
+        "results": items.len(),
+        "items": items,
+    });
+    Ok(Json(json_response))
+}
+
+/**
+ * Fetch a single User
+ */
+pub async fn get_user_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = fetch_user_with_roles(data.pool(), id)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Create User Handler
+ * Add an account with a password and roles.
+ */
+pub async fn create_user_handler(
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<CreateUser>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = create_user(
+        data.pool(),
+        &body.email,
+        &body.name,
+        &body.password,
+        &body.roles,
+    )
+    .await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
+}
+
+/**
+ * Update Roles Handler
+ * Replace the roles of a user. The last active admin keeps the admin role, so the
+ * roles can still be managed afterwards.
+ */
+pub async fn update_user_roles_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<UpdateRoles>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
+
+    // Serializes concurrent role changes, so two admins cannot demote each other at once
+    sqlx::query("LOCK TABLE user_roles IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *tx)
+        .await?;
+
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if !exists {
+        return Err(AppError::not_found(id));
+    }
+
+    set_roles(&mut tx, id, &body.roles).await?;
+
+    let admins: i64 = sqlx::query_scalar(
+        r#"
+        SELECT count(*)
+        FROM user_roles
+        JOIN users ON users.id = user_roles.user_id
+        WHERE role = 'admin' AND active
+    "#,
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if admins == 0 {
+        return Err(AppError::Validation(
+            "At least one active user must keep the admin role".to_string(),
+        ));
+    }
+
+    tx.commit().await?;
+
+    let item = fetch_user_with_roles(data.pool(), id)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+    use sqlx::PgPool;
+
+    use crate::models::users::Role;
+    use crate::test_utils::{
+        app, app_state, insert_document, insert_named, send, signed_in_as, test_user,
+    };
+
+    #[sqlx::test]
+    async fn forbidden_actions_return_403(pool: PgPool) {
+        let archive = insert_named(&pool, "archives", "Stadsarchief").await;
+        let institute = insert_named(&pool, "institutes", "Weeshuis").await;
+        let place = insert_named(&pool, "places", "Leiden").await;
+        let other_place = insert_named(&pool, "places", "Leyden").await;
+        let document = insert_document(&pool, "A-1", archive, institute, place).await;
+        let volunteer = signed_in_as(app_state(pool.clone()), Role::Volunteer);
+
+        let (status, _) = send(&volunteer, Method::GET, "/api/v1/archives", None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let uri = format!("/api/v1/archives/{}", archive);
+        let (status, body) = send(&volunteer, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["code"], "forbidden");
+        assert_eq!(body["message"], "Your roles do not allow archives:delete");
+
+        let uri = format!("/api/v1/places/{}?reassign_to={}", other_place, place);
+        let (status, _) = send(&volunteer, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Also when the parameter name is percent-encoded
+        for uri in [
+            "/api/v1/documents?format=csv",
+            "/api/v1/documents?%66ormat=csv",
+        ] {
+            let (status, _) = send(&volunteer, Method::GET, uri, None).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+        }
+
+        let transcription = json!({"transcriber": "Anna", "language": "nl", "text": "Op heden"});
+        let uri = format!("/api/v1/documents/{}/transcriptions", document);
+        let (status, body) = send(&volunteer, Method::POST, &uri, Some(transcription)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let uri = format!("{}/{}", uri, body["data"]["item"]["id"]);
+        let (status, _) = send(
+            &volunteer,
+            Method::PATCH,
+            &uri,
+            Some(json!({"text": "Heden"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(&volunteer, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let uri = format!("/api/v1/documents/{}/terms", document);
+        let (status, _) = send(&volunteer, Method::POST, &uri, Some(json!({"term_id": 1}))).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) = send(&volunteer, Method::GET, "/api/v1/admin/users", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let reader = signed_in_as(app_state(pool.clone()), Role::Reader);
+        let (status, _) = send(&reader, Method::GET, "/api/v1/documents?format=csv", None).await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(
+            &reader,
+            Method::POST,
+            "/api/v1/places",
+            Some(json!({"name": "Delft"})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let editor = signed_in_as(app_state(pool.clone()), Role::Editor);
+        let (status, _) = send(&editor, Method::GET, "/api/v1/admin/roles", None).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let uri = format!("/api/v1/places/{}?reassign_to={}", other_place, place);
+        let (status, _) = send(&editor, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+
+    #[sqlx::test]
+    async fn admins_manage_role_assignments(pool: PgPool) {
+        let app = app(pool.clone());
+
+        let (status, body) = send(&app, Method::GET, "/api/v1/admin/roles", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results"], 4);
+        assert_eq!(body["items"][2]["role"], "volunteer");
+
+        let user = json!({
+            "email": "anna@example.org",
+            "name": "Anna",
+            "password": "a long password",
+            "roles": ["volunteer"]
+        });
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            "/api/v1/admin/users",
+            Some(user.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["data"]["item"]["roles"], json!(["volunteer"]));
+        assert!(body["data"]["item"].get("password_hash").is_none());
+        let id = body["data"]["item"]["id"].as_i64().unwrap();
+
+        let (status, _) = send(&app, Method::POST, "/api/v1/admin/users", Some(user)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let uri = format!("/api/v1/admin/users/{}/roles", id);
+        let roles = json!({"roles": ["reader", "editor", "reader"]});
+        let (status, body) = send(&app, Method::PUT, &uri, Some(roles)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["item"]["roles"], json!(["editor", "reader"]));
+
+        let (status, body) = send(&app, Method::GET, "/api/v1/admin/users", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["results"], 2);
+
+        // The test admin is the only admin
+        let admin = test_user(&pool, Role::Admin).await;
+        let uri = format!("/api/v1/admin/users/{}/roles", admin);
+        let (status, _) = send(&app, Method::PUT, &uri, Some(json!({"roles": ["editor"]}))).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        let (_, body) = send(
+            &app,
+            Method::GET,
+            &format!("/api/v1/admin/users/{}", admin),
+            None,
+        )
+        .await;
+        assert_eq!(body["data"]["item"]["roles"], json!(["admin"]));
+
+        let (status, _) = send(
+            &app,
+            Method::PUT,
+            "/api/v1/admin/users/9999/roles",
+            Some(json!({"roles": []})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let anonymous = crate::create_app(app_state(pool));
+        let (status, _) = send(&anonymous, Method::GET, "/api/v1/admin/users", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+}
@@ -0,0 +1,830 @@
+use crate::db::AppState;
+use crate::errors::AppError;
+use crate::export::{ExportFormat, export_list};
+use crate::extractors::{AppJson, AppPath, AppQuery};
+use crate::handlers::common::ensure_document_exists;
+use crate::models::vocabularies::{Vocabulary, VocabularyTerm};
+use crate::pagination::{Listing, SortField, fetch_page};
+use crate::schemas::vocabularies::{
+    CreateDocumentTerm, CreateTerm, CreateVocabulary, UpdateTerm, UpdateVocabulary,
+};
+use crate::schemas::{ExportParams, PageParams};
+
+use axum::{
+    Json,
+    extract::{OriginalUri, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use serde_json::json;
+use sqlx::PgPool;
+use sqlx::types::Json as SqlJson;
+use std::sync::Arc;
+
+const TABLE: &str = "vocabularies";
+
+const TERMS_TABLE: &str = "vocabulary_terms";
+
+const LISTING: Listing = Listing {
+    table: TABLE,
+    sort_fields: &[
+        SortField {
+            name: "name",
+            column: "name",
+            sql_type: "text",
+        },
+        SortField {
+            name: "id",
+            column: "id",
+            sql_type: "integer",
+        },
+    ],
+    default_sort: "name",
+};
+
+/**
+ * List Items Handler
+ * This handler fetches a page of items from postgres, see `PageParams`,
+ * or exports all of them as CSV, XLSX or JSON, see `ExportParams`
+ */
+pub async fn items_list_handler(
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    AppQuery(params): AppQuery<PageParams>,
+    AppQuery(export): AppQuery<ExportParams>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(format) = ExportFormat::negotiate(export.format, &headers) {
+        let sort = params.sort.as_deref();
+        return export_list::<Vocabulary>(data.pool(), &LISTING, sort, &(), &[], format).await;
+    }
+
+    let page = fetch_page::<Vocabulary>(data.pool(), &LISTING, &params, &()).await?;
+
+    Ok(page.into_list_response(&uri))
+}
+
+/**
+ * Fetch a single Item
+ */
+pub async fn get_item_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let query = format!("SELECT * FROM {} WHERE id = $1", TABLE);
+    let item = sqlx::query_as::<_, Vocabulary>(&query)
+        .bind(id)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Create Item Handler
+ * This handler adds a new item to postgres
+ */
+pub async fn create_item_handler(
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<CreateVocabulary>,
+) -> Result<impl IntoResponse, AppError> {
+    let query = format!(
+        "INSERT INTO {} (name, description) VALUES ($1, $2) RETURNING *",
+        TABLE
+    );
+    let item = sqlx::query_as::<_, Vocabulary>(&query)
+        .bind(body.name)
+        .bind(body.description)
+        .fetch_one(data.pool())
+        .await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
+}
+
+/**
+ * Edit Item Handler
+ * This handler handles edits of existing items
+ */
+pub async fn edit_item_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<UpdateVocabulary>,
+) -> Result<impl IntoResponse, AppError> {
+    let query = format!(
+        r#"
+        UPDATE {}
+        SET name = COALESCE($1, name),
+            description = CASE WHEN $2 THEN $3 ELSE description END
+        WHERE id = $4
+        RETURNING *
+    "#,
+        TABLE
+    );
+    let item = sqlx::query_as::<_, Vocabulary>(&query)
+        .bind(body.name)
+        .bind(body.description.is_some())
+        .bind(body.description.flatten())
+        .bind(id)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Delete Item Handler
+ * The terms of the vocabulary are deleted with it, unless documents are classified with them
+ */
+pub async fn delete_item_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
+
+    // Locking the vocabulary keeps new terms from being added to it until we are done
+    let query = format!("SELECT id FROM {} WHERE id = $1 FOR UPDATE", TABLE);
+    sqlx::query(&query)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    let documents: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(DISTINCT document_id) FROM document_terms
+        JOIN vocabulary_terms ON vocabulary_terms.id = document_terms.term_id
+        WHERE vocabulary_id = $1
+    "#,
+    )
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if documents > 0 {
+        return Err(AppError::referenced_by_documents(id, documents));
+    }
+
+    let query = format!("DELETE FROM {} WHERE id = $1", TABLE);
+    sqlx::query(&query)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::from_delete)?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/**
+ * List Vocabulary Terms Handler
+ * All terms of a vocabulary, each broader term followed by its narrower terms,
+ * ordered by code on each level
+ */
+pub async fn vocabulary_terms_list_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_vocabulary_exists(data.pool(), id).await?;
+
+    let query = format!(
+        r#"
+        WITH RECURSIVE tree AS (
+            SELECT {table}.*, ARRAY[code] AS path FROM {table}
+            WHERE vocabulary_id = $1 AND parent_id IS NULL
+            UNION ALL
+            SELECT {table}.*, tree.path || {table}.code FROM {table}
+            JOIN tree ON {table}.parent_id = tree.id
+        )
+        SELECT * FROM tree ORDER BY path
+    "#,
+        table = TERMS_TABLE
+    );
+    let items = sqlx::query_as::<_, VocabularyTerm>(&query)
+        .bind(id)
+        .fetch_all(data.pool())
+        .await?;
+
+    let json_response = json!({
+        "status": "success",
+        "results": items.len(),
+        "items": items,
+    });
+    Ok(Json(json_response))
+}
+
+/**
+ * Fetch a single Vocabulary Term
+ */
+pub async fn get_term_handler(
+    AppPath((id, term_id)): AppPath<(i32, i32)>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let item = fetch_term(data.pool(), id, term_id).await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Create Vocabulary Term Handler
+ * A `parent_id` must be a term of the same vocabulary
+ */
+pub async fn create_term_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<CreateTerm>,
+) -> Result<impl IntoResponse, AppError> {
+    body.validate().map_err(AppError::Validation)?;
+    ensure_vocabulary_exists(data.pool(), id).await?;
+
+    let query = format!(
+        "INSERT INTO {} (vocabulary_id, parent_id, code, labels) VALUES ($1, $2, $3, $4) RETURNING *",
+        TERMS_TABLE
+    );
+    let item = sqlx::query_as::<_, VocabularyTerm>(&query)
+        .bind(id)
+        .bind(body.parent_id)
+        .bind(body.code.trim())
+        .bind(SqlJson(body.labels))
+        .fetch_one(data.pool())
+        .await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
+}
+
+/**
+ * Edit Vocabulary Term Handler
+ * A term can be moved under another term of its vocabulary, but not under itself
+ * or one of its narrower terms
+ */
+pub async fn edit_term_handler(
+    AppPath((id, term_id)): AppPath<(i32, i32)>,
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<UpdateTerm>,
+) -> Result<impl IntoResponse, AppError> {
+    let term = fetch_term(data.pool(), id, term_id).await?;
+    let term = body.apply_to(term);
+    term.validate().map_err(AppError::Validation)?;
+
+    if let Some(parent_id) = term.parent_id {
+        let query = format!(
+            r#"
+            WITH RECURSIVE ancestors AS (
+                SELECT id, parent_id FROM {table} WHERE id = $1
+                UNION
+                SELECT {table}.id, {table}.parent_id FROM {table}
+                JOIN ancestors ON {table}.id = ancestors.parent_id
+            )
+            SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = $2)
+        "#,
+            table = TERMS_TABLE
+        );
+        let is_cycle: bool = sqlx::query_scalar(&query)
+            .bind(parent_id)
+            .bind(term_id)
+            .fetch_one(data.pool())
+            .await?;
+        if is_cycle {
+            return Err(AppError::Validation(
+                "A term cannot be nested under itself or one of its narrower terms".to_string(),
+            ));
+        }
+    }
+
+    let query = format!(
+        "UPDATE {} SET parent_id = $1, code = $2, labels = $3 WHERE id = $4 RETURNING *",
+        TERMS_TABLE
+    );
+    let item = sqlx::query_as::<_, VocabularyTerm>(&query)
+        .bind(term.parent_id)
+        .bind(term.code.trim())
+        .bind(SqlJson(term.labels))
+        .bind(term_id)
+        .fetch_optional(data.pool())
+        .await?
+        .ok_or_else(|| AppError::not_found(term_id))?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok(Json(item_response))
+}
+
+/**
+ * Delete Vocabulary Term Handler
+ * Terms that documents are classified with, or that have narrower terms, cannot be deleted
+ */
+pub async fn delete_term_handler(
+    AppPath((id, term_id)): AppPath<(i32, i32)>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut tx = data.pool().begin().await?;
+
+    // Locking the term keeps it from being assigned to documents until we are done
+    let query = format!(
+        "SELECT id FROM {} WHERE id = $1 AND vocabulary_id = $2 FOR UPDATE",
+        TERMS_TABLE
+    );
+    sqlx::query(&query)
+        .bind(term_id)
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::not_found(term_id))?;
+
+    let documents: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM document_terms WHERE term_id = $1")
+            .bind(term_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+    if documents > 0 {
+        return Err(AppError::referenced_by_documents(term_id, documents));
+    }
+
+    let query = format!("DELETE FROM {} WHERE id = $1", TERMS_TABLE);
+    sqlx::query(&query)
+        .bind(term_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(AppError::from_delete)?;
+
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/**
+ * List Document Terms Handler
+ * The terms a document is classified with, by vocabulary
+ */
+pub async fn document_terms_list_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_document_exists(data.pool(), id).await?;
+
+    let query = format!(
+        r#"
+        SELECT {table}.* FROM document_terms
+        JOIN {table} ON {table}.id = document_terms.term_id
+        WHERE document_id = $1
+        ORDER BY vocabulary_id, code
+    "#,
+        table = TERMS_TABLE
+    );
+    let items = sqlx::query_as::<_, VocabularyTerm>(&query)
+        .bind(id)
+        .fetch_all(data.pool())
+        .await?;
+
+    let json_response = json!({
+        "status": "success",
+        "results": items.len(),
+        "items": items,
+    });
+    Ok(Json(json_response))
+}
+
+/**
+ * Add Document Term Handler
+ * Classify a document with a term of any vocabulary
+ */
+pub async fn add_document_term_handler(
+    AppPath(id): AppPath<i32>,
+    State(data): State<Arc<AppState>>,
+    AppJson(body): AppJson<CreateDocumentTerm>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_document_exists(data.pool(), id).await?;
+
+    let query = format!(
+        r#"
+        WITH assigned AS (
+            INSERT INTO document_terms (document_id, term_id) VALUES ($1, $2)
+            RETURNING term_id
+        )
+        SELECT {table}.* FROM {table} JOIN assigned ON {table}.id = assigned.term_id
+    "#,
+        table = TERMS_TABLE
+    );
+    let item = sqlx::query_as::<_, VocabularyTerm>(&query)
+        .bind(id)
+        .bind(body.term_id)
+        .fetch_one(data.pool())
+        .await?;
+
+    let item_response = json!({"status": "success","data": json!({
+        "item": item
+    })});
+    Ok((StatusCode::CREATED, Json(item_response)))
+}
+
+/**
+ * Remove Document Term Handler
+ */
+pub async fn remove_document_term_handler(
+    AppPath((id, term_id)): AppPath<(i32, i32)>,
+    State(data): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_document_exists(data.pool(), id).await?;
+
+    let rows_affected =
+        sqlx::query("DELETE FROM document_terms WHERE document_id = $1 AND term_id = $2")
+            .bind(id)
+            .bind(term_id)
+            .execute(data.pool())
+            .await?
+            .rows_affected();
+
+    if rows_affected == 0 {
+        return Err(AppError::not_found(term_id));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn ensure_vocabulary_exists(pool: &PgPool, id: i32) -> Result<(), AppError> {
+    let query = format!("SELECT id FROM {} WHERE id = $1", TABLE);
+    sqlx::query(&query)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::not_found(id))?;
+
+    Ok(())
+}
+
+/**
+ * The term with `term_id` of the vocabulary with `id`, or a 404.
+ */
+async fn fetch_term(pool: &PgPool, id: i32, term_id: i32) -> Result<VocabularyTerm, AppError> {
+    let query = format!(
+        "SELECT * FROM {} WHERE id = $1 AND vocabulary_id = $2",
+        TERMS_TABLE
+    );
+    sqlx::query_as::<_, VocabularyTerm>(&query)
+        .bind(term_id)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::not_found(term_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        Router,
+        http::{Method, StatusCode},
+    };
+    use serde_json::{Value, json};
+    use sqlx::PgPool;
+
+    use crate::test_utils::{app, insert_document, insert_named, send};
+
+    async fn create(app: &Router, uri: &str, body: Value) -> i32 {
+        let (status, body) = send(app, Method::POST, uri, Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED, "{}", body);
+        body["data"]["item"]["id"].as_i64().unwrap() as i32
+    }
+
+    /**
+     * A vocabulary of document types: sacraments with baptism and marriage under it,
+     * and notarial deeds. Returns the ids of the vocabulary and of the four terms.
+     */
+    async fn seed_types(app: &Router) -> (i32, [i32; 4]) {
+        let id = create(
+            app,
+            "/api/v1/vocabularies",
+            json!({"name": "Document type", "description": "Kind of record"}),
+        )
+        .await;
+        let uri = format!("/api/v1/vocabularies/{}/terms", id);
+        let sacraments = create(
+            app,
+            &uri,
+            json!({"code": "sacraments", "labels": {"en": "Sacraments", "nl": "Sacramenten"}}),
+        )
+        .await;
+        let marriage = create(
+            app,
+            &uri,
+            json!({"code": "marriage", "parent_id": sacraments, "labels": {"en": "Marriage"}}),
+        )
+        .await;
+        let baptism = create(
+            app,
+            &uri,
+            json!({"code": "baptism", "parent_id": sacraments, "labels": {"en": "Baptism", "nl": "Doop"}}),
+        )
+        .await;
+        let deed = create(
+            app,
+            &uri,
+            json!({"code": "notarial_deed", "labels": {"nl": "Notariële akte"}}),
+        )
+        .await;
+
+        (id, [sacraments, baptism, marriage, deed])
+    }
+
+    async fn seed_documents(pool: &PgPool) -> [i32; 3] {
+        let archive_id = insert_named(pool, "archives", "Noord-Hollands Archief").await;
+        let institute_id = insert_named(pool, "institutes", "Burgerweeshuis").await;
+        let place_id = insert_named(pool, "places", "Haarlem").await;
+        let mut ids = [0; 3];
+        for (i, inventory_number) in ["INV-1", "INV-2", "INV-3"].iter().enumerate() {
+            ids[i] =
+                insert_document(pool, inventory_number, archive_id, institute_id, place_id).await;
+        }
+        ids
+    }
+
+    #[sqlx::test]
+    async fn terms_are_listed_as_a_tree(pool: PgPool) {
+        let app = app(pool);
+        let (id, [sacraments, baptism, marriage, deed]) = seed_types(&app).await;
+
+        let (status, body) = send(
+            &app,
+            Method::GET,
+            &format!("/api/v1/vocabularies/{}/terms", id),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let ids: Vec<i64> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["id"].as_i64().unwrap())
+            .collect();
+        assert_eq!(
+            ids,
+            [deed, sacraments, baptism, marriage].map(|id| id as i64)
+        );
+        assert_eq!(
+            body["items"][2]["labels"],
+            json!({"en": "Baptism", "nl": "Doop"})
+        );
+        assert_eq!(body["items"][2]["parent_id"], sacraments);
+    }
+
+    #[sqlx::test]
+    async fn terms_are_validated(pool: PgPool) {
+        let app = app(pool);
+        let (id, [sacraments, baptism, _, _]) = seed_types(&app).await;
+        let other_id = create(&app, "/api/v1/vocabularies", json!({"name": "Topic"})).await;
+        let uri = format!("/api/v1/vocabularies/{}/terms", id);
+
+        for (body, code) in [
+            (
+                json!({"code": "baptism", "labels": {"en": "Baptism"}}),
+                "duplicate",
+            ),
+            (json!({"code": "death", "labels": {}}), "validation_failed"),
+            (
+                json!({"code": "death", "labels": {"english": "Death"}}),
+                "validation_failed",
+            ),
+            (
+                json!({"code": " ", "labels": {"en": "Death"}}),
+                "validation_failed",
+            ),
+        ] {
+            let (_, response) = send(&app, Method::POST, &uri, Some(body.clone())).await;
+            assert_eq!(response["code"], code, "{}", body);
+        }
+
+        // Parents come from the same vocabulary
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            &format!("/api/v1/vocabularies/{}/terms", other_id),
+            Some(json!({"code": "orphans", "parent_id": sacraments, "labels": {"en": "Orphans"}})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["message"], "Referenced parent does not exist");
+
+        // Terms cannot be nested under themselves
+        let (status, body) = send(
+            &app,
+            Method::PATCH,
+            &format!("{}/{}", uri, sacraments),
+            Some(json!({"parent_id": baptism})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], "validation_failed");
+
+        let (status, body) = send(
+            &app,
+            Method::PATCH,
+            &format!("{}/{}", uri, baptism),
+            Some(json!({"parent_id": null, "labels": {"la": "Baptismus"}})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"]["item"]["parent_id"], Value::Null);
+        assert_eq!(body["data"]["item"]["labels"], json!({"la": "Baptismus"}));
+        assert_eq!(body["data"]["item"]["code"], "baptism");
+    }
+
+    #[sqlx::test]
+    async fn documents_are_filtered_by_term_and_narrower_terms(pool: PgPool) {
+        let [first, second, third] = seed_documents(&pool).await;
+        let app = app(pool);
+        let (_, [sacraments, baptism, marriage, deed]) = seed_types(&app).await;
+
+        for (document_id, term_id) in [(first, baptism), (second, marriage), (third, deed)] {
+            create(
+                &app,
+                &format!("/api/v1/documents/{}/terms", document_id),
+                json!({"term_id": term_id}),
+            )
+            .await;
+        }
+        let (status, body) = send(
+            &app,
+            Method::POST,
+            &format!("/api/v1/documents/{}/terms", first),
+            Some(json!({"term_id": baptism})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT, "{}", body);
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            &format!("/api/v1/documents/{}/terms", first),
+            Some(json!({"term_id": 999999})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (_, body) = send(
+            &app,
+            Method::GET,
+            &format!("/api/v1/documents/{}/terms", first),
+            None,
+        )
+        .await;
+        assert_eq!(body["results"], 1);
+        assert_eq!(body["items"][0]["code"], "baptism");
+
+        let documents = |query: String| {
+            let app = app.clone();
+            async move {
+                let (status, body) = send(
+                    &app,
+                    Method::GET,
+                    &format!("/api/v1/documents?{}", query),
+                    None,
+                )
+                .await;
+                assert_eq!(status, StatusCode::OK);
+                let mut ids: Vec<i64> = body["items"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|item| item["id"].as_i64().unwrap())
+                    .collect();
+                ids.sort();
+                ids
+            }
+        };
+        assert_eq!(
+            documents(format!("term_id={}", baptism)).await,
+            [first as i64]
+        );
+        assert_eq!(
+            documents(format!("term_id={}", sacraments)).await,
+            [first as i64, second as i64]
+        );
+        assert_eq!(
+            documents(format!("term_id={}&term_id={}", baptism, deed)).await,
+            [first as i64, third as i64]
+        );
+
+        let (status, _) = send(
+            &app,
+            Method::DELETE,
+            &format!("/api/v1/documents/{}/terms/{}", first, baptism),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(
+            documents(format!("term_id={}", baptism)).await,
+            Vec::<i64>::new()
+        );
+    }
+
+    #[sqlx::test]
+    async fn terms_in_use_cannot_be_deleted(pool: PgPool) {
+        let [first, ..] = seed_documents(&pool).await;
+        let app = app(pool.clone());
+        let (id, [sacraments, baptism, _, deed]) = seed_types(&app).await;
+        create(
+            &app,
+            &format!("/api/v1/documents/{}/terms", first),
+            json!({"term_id": baptism}),
+        )
+        .await;
+        let uri = format!("/api/v1/vocabularies/{}", id);
+
+        let (status, body) = send(
+            &app,
+            Method::DELETE,
+            &format!("{}/terms/{}", uri, baptism),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["documents"], 1);
+
+        let (status, body) = send(
+            &app,
+            Method::DELETE,
+            &format!("{}/terms/{}", uri, sacraments),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "still_referenced");
+
+        let (status, _) = send(
+            &app,
+            Method::DELETE,
+            &format!("{}/terms/{}", uri, deed),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let (status, body) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["documents"], 1);
+
+        send(
+            &app,
+            Method::DELETE,
+            &format!("/api/v1/documents/{}/terms/{}", first, baptism),
+            None,
+        )
+        .await;
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let terms: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM vocabulary_terms")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(terms, 0);
+    }
+
+    #[sqlx::test]
+    async fn terms_of_deleted_documents_are_hidden(pool: PgPool) {
+        let [document_id, ..] = seed_documents(&pool).await;
+        let app = app(pool);
+        let (_, [sacraments, baptism, ..]) = seed_types(&app).await;
+        let uri = format!("/api/v1/documents/{}/terms", document_id);
+        create(&app, &uri, json!({"term_id": baptism})).await;
+        let (status, _) = send(
+            &app,
+            Method::DELETE,
+            &format!("/api/v1/documents/{}", document_id),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        for (method, uri, body) in [
+            (Method::GET, uri.clone(), None),
+            (
+                Method::POST,
+                uri.clone(),
+                Some(json!({"term_id": sacraments})),
+            ),
+            (Method::DELETE, format!("{}/{}", uri, baptism), None),
+        ] {
+            let (status, _) = send(&app, method.clone(), &uri, body).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{} {}", method, uri);
+        }
+    }
+}
@@ -0,0 +1,698 @@
+use std::io::Cursor;
+use std::sync::Arc;
+
+use axum::http::{HeaderMap, StatusCode, header::HOST};
+use image::{DynamicImage, ImageFormat, codecs::jpeg::JpegEncoder, imageops::FilterType};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::errors::AppError;
+
+/**
+ * Largest image the Image API returns, in pixels. It is announced as `maxArea` in `info.json`.
+ */
+pub const MAX_AREA: u64 = 40_000_000;
+
+/**
+ * Largest width and height the Image API returns, in pixels. They are announced as `maxWidth`
+ * and `maxHeight` in `info.json`.
+ */
+pub const MAX_SIZE: u32 = 10_000;
+
+/**
+ * Tile size announced in `info.json`, for deep zoom in viewers such as Mirador.
+ */
+pub const TILE_SIZE: u32 = 512;
+
+const JPEG_QUALITY: u8 = 90;
+
+/**
+ * Where clients reach the server, for the ids in `info.json` files and manifests.
+ * Read from `PUBLIC_BASE_URL`, e.g. `https://archive.example`. Without it the `Host` of the
+ * request is used, or the `X-Forwarded-Proto` and `X-Forwarded-Host` headers when `TRUST_PROXY`
+ * is `true` or `1`. Any client can send those, so only a reverse proxy that sets them may be
+ * trusted.
+ *
+ * Decoding a scan takes memory in proportion to its pixels, so at most `IIIF_MAX_RENDERS`
+ * images are rendered at once, by default one per CPU. Other requests wait for their turn.
+ */
+#[derive(Clone, Debug)]
+pub struct IiifSettings {
+    base_url: Option<String>,
+    trust_proxy: bool,
+    renders: Arc<Semaphore>,
+}
+
+impl Default for IiifSettings {
+    fn default() -> Self {
+        Self {
+            base_url: None,
+            trust_proxy: false,
+            renders: Arc::new(Semaphore::new(default_max_renders())),
+        }
+    }
+}
+
+impl IiifSettings {
+    pub fn new(
+        base_url: Option<&str>,
+        trust_proxy: bool,
+        max_renders: usize,
+    ) -> Result<Self, String> {
+        if max_renders == 0 {
+            return Err("Invalid IIIF_MAX_RENDERS: 0, expected at least 1".to_string());
+        }
+
+        let base_url = match base_url.map(|url| url.trim().trim_end_matches('/')) {
+            Some(url) if url.starts_with("http://") || url.starts_with("https://") => {
+                Some(url.to_string())
+            }
+            Some(url) => {
+                return Err(format!(
+                    "Invalid PUBLIC_BASE_URL: {}, expected an http:// or https:// URL",
+                    url
+                ));
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            base_url,
+            trust_proxy,
+            renders: Arc::new(Semaphore::new(max_renders)),
+        })
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        let base_url = std::env::var("PUBLIC_BASE_URL").ok();
+        let trust_proxy = std::env::var("TRUST_PROXY")
+            .is_ok_and(|value| matches!(value.trim().to_lowercase().as_str(), "true" | "1"));
+        let max_renders = match std::env::var("IIIF_MAX_RENDERS") {
+            Ok(value) => value.trim().parse::<usize>().map_err(|_| {
+                format!(
+                    "Invalid IIIF_MAX_RENDERS: {}, expected a number of images",
+                    value
+                )
+            })?,
+            Err(_) => default_max_renders(),
+        };
+        Self::new(base_url.as_deref(), trust_proxy, max_renders)
+    }
+
+    /**
+     * Wait for a turn to render an image. The turn ends when the permit is dropped.
+     */
+    pub async fn render_permit(&self) -> Result<OwnedSemaphorePermit, AppError> {
+        self.renders
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|err| AppError::Internal(err.to_string()))
+    }
+
+    /**
+     * The scheme and host to put in front of the paths of the IIIF routes.
+     */
+    pub fn base_url(&self, headers: &HeaderMap) -> String {
+        if let Some(base_url) = &self.base_url {
+            return base_url.clone();
+        }
+
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.split(',').next())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        let forwarded = |name: &str| header(name).filter(|_| self.trust_proxy);
+        let scheme = forwarded("x-forwarded-proto").unwrap_or("http");
+        let host = forwarded("x-forwarded-host")
+            .or_else(|| header(HOST.as_str()))
+            .unwrap_or("localhost");
+
+        format!("{}://{}", scheme, host)
+    }
+}
+
+fn default_max_renders() -> usize {
+    std::thread::available_parallelism().map_or(1, usize::from)
+}
+
+/**
+ * The part of the image to return, see the IIIF Image API 3.0, section 4.1.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Region {
+    Full,
+    Square,
+    Pixels { x: u32, y: u32, w: u32, h: u32 },
+    Percent { x: f64, y: f64, w: f64, h: f64 },
+}
+
+/**
+ * How the region is scaled, see section 4.2. `upscale` is the `^` prefix.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Size {
+    pub upscale: bool,
+    pub kind: SizeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeKind {
+    Max,
+    Width(u32),
+    Height(u32),
+    Percent(f64),
+    Exact(u32, u32),
+    Confined(u32, u32),
+}
+
+/**
+ * Mirroring and clockwise rotation, see section 4.3. Only multiples of 90 degrees are supported.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rotation {
+    pub mirror: bool,
+    pub degrees: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quality {
+    Default,
+    Color,
+    Gray,
+    Bitonal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Jpg,
+    Png,
+    Webp,
+    Tif,
+}
+
+impl Format {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Jpg => "image/jpeg",
+            Self::Png => "image/png",
+            Self::Webp => "image/webp",
+            Self::Tif => "image/tiff",
+        }
+    }
+}
+
+/**
+ * A parsed `{region}/{size}/{rotation}/{quality}.{format}` request.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageRequest {
+    pub region: Region,
+    pub size: Size,
+    pub rotation: Rotation,
+    pub quality: Quality,
+    pub format: Format,
+}
+
+/**
+ * What to cut out of the full image, in pixels of the full image, and the size to scale it to.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Plan {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageRequest {
+    /**
+     * Parse the path segments of an image request. Malformed parameters are a 400,
+     * valid ones that are not supported, such as a rotation by 45 degrees, a 501.
+     */
+    pub fn parse(region: &str, size: &str, rotation: &str, file: &str) -> Result<Self, AppError> {
+        let (quality, format) = file
+            .rsplit_once('.')
+            .ok_or_else(|| bad_request(format!("Missing format in {}", file)))?;
+
+        Ok(Self {
+            region: parse_region(region)?,
+            size: parse_size(size)?,
+            rotation: parse_rotation(rotation)?,
+            quality: match quality {
+                "default" => Quality::Default,
+                "color" => Quality::Color,
+                "gray" => Quality::Gray,
+                "bitonal" => Quality::Bitonal,
+                _ => return Err(bad_request(format!("Invalid quality: {}", quality))),
+            },
+            format: match format {
+                "jpg" => Format::Jpg,
+                "png" => Format::Png,
+                "webp" => Format::Webp,
+                "tif" => Format::Tif,
+                "gif" | "jp2" | "pdf" => {
+                    return Err(not_implemented(format!("Unsupported format: {}", format)));
+                }
+                _ => return Err(bad_request(format!("Invalid format: {}", format))),
+            },
+        })
+    }
+
+    /**
+     * Resolve the region and size against an image of `width` by `height` pixels.
+     */
+    pub fn plan(&self, width: u32, height: u32) -> Result<Plan, AppError> {
+        let (x, y, w, h) = match self.region {
+            Region::Full => (0, 0, width, height),
+            Region::Square => {
+                let side = width.min(height);
+                ((width - side) / 2, (height - side) / 2, side, side)
+            }
+            Region::Pixels { x, y, w, h } => (x, y, w, h),
+            Region::Percent { x, y, w, h } => (
+                percent_of(x, width),
+                percent_of(y, height),
+                percent_of(w, width),
+                percent_of(h, height),
+            ),
+        };
+        if x >= width || y >= height || w == 0 || h == 0 {
+            return Err(bad_request(
+                "Region lies outside of the image or is empty".to_string(),
+            ));
+        }
+        // Regions that extend beyond the image are cropped to it
+        let (w, h) = (w.min(width - x), h.min(height - y));
+
+        let (region_w, region_h) = (w as f64, h as f64);
+        let upscale = self.size.upscale;
+        let (out_w, out_h) = match self.size.kind {
+            SizeKind::Max => {
+                let mut scale = (MAX_AREA as f64 / (region_w * region_h))
+                    .sqrt()
+                    .min(MAX_SIZE as f64 / region_w.max(region_h));
+                if !upscale {
+                    scale = scale.min(1.0);
+                }
+                scaled(region_w * scale, region_h * scale)
+            }
+            SizeKind::Width(out_w) => (out_w, scaled_side(region_h * out_w as f64 / region_w)),
+            SizeKind::Height(out_h) => (scaled_side(region_w * out_h as f64 / region_h), out_h),
+            SizeKind::Percent(percent) => {
+                scaled(region_w * percent / 100.0, region_h * percent / 100.0)
+            }
+            SizeKind::Exact(out_w, out_h) => (out_w, out_h),
+            SizeKind::Confined(max_w, max_h) => {
+                let mut scale = (max_w as f64 / region_w).min(max_h as f64 / region_h);
+                if !upscale {
+                    scale = scale.min(1.0);
+                }
+                scaled(region_w * scale, region_h * scale)
+            }
+        };
+
+        if out_w == 0 || out_h == 0 {
+            return Err(bad_request("Size is empty".to_string()));
+        }
+        if !upscale && (out_w > w || out_h > h) {
+            return Err(bad_request(
+                "Size is larger than the region, prefix it with ^ to scale up".to_string(),
+            ));
+        }
+        if out_w > MAX_SIZE || out_h > MAX_SIZE {
+            return Err(bad_request(format!(
+                "Size is larger than the maximum of {} by {} pixels",
+                MAX_SIZE, MAX_SIZE
+            )));
+        }
+        if out_w as u64 * out_h as u64 > MAX_AREA {
+            return Err(bad_request(format!(
+                "Size is larger than the maximum of {} pixels",
+                MAX_AREA
+            )));
+        }
+
+        Ok(Plan {
+            x,
+            y,
+            w,
+            h,
+            width: out_w,
+            height: out_h,
+        })
+    }
+}
+
+/**
+ * Render the planned image from `source`, which is the full image scaled by `source_scale`,
+ * e.g. a derivative of half the size has a scale of 0.5.
+ */
+pub fn render(
+    source: &DynamicImage,
+    source_scale: f64,
+    plan: &Plan,
+    request: &ImageRequest,
+) -> Result<Vec<u8>, String> {
+    let scale = |value: u32| (value as f64 * source_scale).floor() as u32;
+    let x = scale(plan.x).min(source.width().saturating_sub(1));
+    let y = scale(plan.y).min(source.height().saturating_sub(1));
+    let w = scale(plan.w).clamp(1, source.width() - x);
+    let h = scale(plan.h).clamp(1, source.height() - y);
+
+    let mut image = source.crop_imm(x, y, w, h);
+    if (w, h) != (plan.width, plan.height) {
+        let filter = if plan.width < w {
+            FilterType::Triangle
+        } else {
+            FilterType::CatmullRom
+        };
+        image = image.resize_exact(plan.width, plan.height, filter);
+    }
+
+    if request.rotation.mirror {
+        image = image.fliph();
+    }
+    image = match request.rotation.degrees {
+        90 => image.rotate90(),
+        180 => image.rotate180(),
+        270 => image.rotate270(),
+        _ => image,
+    };
+
+    image = match request.quality {
+        Quality::Default | Quality::Color => DynamicImage::ImageRgb8(image.to_rgb8()),
+        Quality::Gray => DynamicImage::ImageLuma8(image.to_luma8()),
+        Quality::Bitonal => {
+            let mut luma = image.to_luma8();
+            for pixel in luma.pixels_mut() {
+                pixel.0[0] = if pixel.0[0] < 128 { 0 } else { 255 };
+            }
+            DynamicImage::ImageLuma8(luma)
+        }
+    };
+
+    let mut bytes = Vec::new();
+    let mut cursor = Cursor::new(&mut bytes);
+    let result = match request.format {
+        Format::Jpg => {
+            image.write_with_encoder(JpegEncoder::new_with_quality(&mut cursor, JPEG_QUALITY))
+        }
+        Format::Png => image.write_to(&mut cursor, ImageFormat::Png),
+        Format::Webp => image.write_to(&mut cursor, ImageFormat::WebP),
+        Format::Tif => image.write_to(&mut cursor, ImageFormat::Tiff),
+    };
+    result.map_err(|err| format!("Cannot encode image: {}", err))?;
+
+    Ok(bytes)
+}
+
+fn parse_region(region: &str) -> Result<Region, AppError> {
+    let invalid = || bad_request(format!("Invalid region: {}", region));
+
+    match region {
+        "full" => Ok(Region::Full),
+        "square" => Ok(Region::Square),
+        _ => match region.strip_prefix("pct:") {
+            Some(values) => {
+                let [x, y, w, h] = parse_numbers::<f64>(values).ok_or_else(invalid)?;
+                if [x, y, w, h]
+                    .iter()
+                    .any(|value| !value.is_finite() || *value < 0.0)
+                {
+                    return Err(invalid());
+                }
+                Ok(Region::Percent { x, y, w, h })
+            }
+            None => {
+                let [x, y, w, h] = parse_numbers::<u32>(region).ok_or_else(invalid)?;
+                Ok(Region::Pixels { x, y, w, h })
+            }
+        },
+    }
+}
+
+fn parse_size(size: &str) -> Result<Size, AppError> {
+    let invalid = || bad_request(format!("Invalid size: {}", size));
+    let (upscale, spec) = match size.strip_prefix('^') {
+        Some(spec) => (true, spec),
+        None => (false, size),
+    };
+
+    let kind = if spec == "max" {
+        SizeKind::Max
+    } else if let Some(percent) = spec.strip_prefix("pct:") {
+        match percent.parse::<f64>() {
+            Ok(percent) if percent.is_finite() && percent > 0.0 => {
+                if percent > 100.0 && !upscale {
+                    return Err(invalid());
+                }
+                SizeKind::Percent(percent)
+            }
+            _ => return Err(invalid()),
+        }
+    } else {
+        let (confined, spec) = match spec.strip_prefix('!') {
+            Some(spec) => (true, spec),
+            None => (false, spec),
+        };
+        let (w, h) = spec.split_once(',').ok_or_else(invalid)?;
+        let parse = |value: &str| value.parse::<u32>().ok().filter(|value| *value > 0);
+        match (w, h, confined) {
+            ("", "", _) => return Err(invalid()),
+            (w, "", false) => SizeKind::Width(parse(w).ok_or_else(invalid)?),
+            ("", h, false) => SizeKind::Height(parse(h).ok_or_else(invalid)?),
+            (w, h, false) => {
+                SizeKind::Exact(parse(w).ok_or_else(invalid)?, parse(h).ok_or_else(invalid)?)
+            }
+            (w, h, true) => {
+                SizeKind::Confined(parse(w).ok_or_else(invalid)?, parse(h).ok_or_else(invalid)?)
+            }
+        }
+    };
+
+    Ok(Size { upscale, kind })
+}
+
+fn parse_rotation(rotation: &str) -> Result<Rotation, AppError> {
+    let (mirror, degrees) = match rotation.strip_prefix('!') {
+        Some(degrees) => (true, degrees),
+        None => (false, rotation),
+    };
+    let degrees = degrees
+        .parse::<f64>()
+        .ok()
+        .filter(|degrees| (0.0..=360.0).contains(degrees))
+        .ok_or_else(|| bad_request(format!("Invalid rotation: {}", rotation)))?;
+
+    match degrees {
+        0.0 | 360.0 => Ok(Rotation { mirror, degrees: 0 }),
+        90.0 | 180.0 | 270.0 => Ok(Rotation {
+            mirror,
+            degrees: degrees as u32,
+        }),
+        _ => Err(not_implemented(format!(
+            "Only rotations by multiples of 90 degrees are supported, not {}",
+            rotation
+        ))),
+    }
+}
+
+fn parse_numbers<T: std::str::FromStr>(values: &str) -> Option<[T; 4]> {
+    let numbers: Vec<T> = values
+        .split(',')
+        .map(|value| value.parse().ok())
+        .collect::<Option<_>>()?;
+    numbers.try_into().ok()
+}
+
+fn percent_of(percent: f64, total: u32) -> u32 {
+    (percent * total as f64 / 100.0).round() as u32
+}
+
+fn scaled_side(value: f64) -> u32 {
+    (value.round() as u32).max(1)
+}
+
+fn scaled(w: f64, h: f64) -> (u32, u32) {
+    (scaled_side(w), scaled_side(h))
+}
+
+fn bad_request(message: String) -> AppError {
+    AppError::InvalidRequest(StatusCode::BAD_REQUEST, message)
+}
+
+fn not_implemented(message: String) -> AppError {
+    AppError::InvalidRequest(StatusCode::NOT_IMPLEMENTED, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base_url_trusts_forwarded_headers_only_behind_a_proxy() {
+        let mut headers = HeaderMap::new();
+        headers.insert(HOST, "localhost:8000".parse().unwrap());
+        headers.insert("x-forwarded-proto", "https".parse().unwrap());
+        headers.insert("x-forwarded-host", "evil.example".parse().unwrap());
+
+        let direct = IiifSettings::new(None, false, 1).unwrap();
+        assert_eq!(direct.base_url(&headers), "http://localhost:8000");
+        let proxied = IiifSettings::new(None, true, 1).unwrap();
+        assert_eq!(proxied.base_url(&headers), "https://evil.example");
+        let configured = IiifSettings::new(Some("https://archive.example/"), true, 1).unwrap();
+        assert_eq!(configured.base_url(&headers), "https://archive.example");
+        assert!(IiifSettings::new(Some("archive.example"), false, 1).is_err());
+        assert!(IiifSettings::new(None, false, 0).is_err());
+    }
+
+    #[tokio::test]
+    async fn renders_wait_for_a_turn() {
+        let settings = IiifSettings::new(None, false, 1).unwrap();
+        let permit = settings.render_permit().await.unwrap();
+        assert!(settings.renders.clone().try_acquire_owned().is_err());
+        drop(permit);
+        assert!(settings.render_permit().await.is_ok());
+    }
+
+    fn plan(region: &str, size: &str, width: u32, height: u32) -> Result<Plan, StatusCode> {
+        ImageRequest::parse(region, size, "0", "default.jpg")
+            .and_then(|request| request.plan(width, height))
+            .map_err(|err| err.status())
+    }
+
+    fn output(region: &str, size: &str) -> (u32, u32) {
+        let plan = plan(region, size, 1000, 500).unwrap();
+        (plan.width, plan.height)
+    }
+
+    #[test]
+    fn parses_request_parameters() {
+        let request =
+            ImageRequest::parse("pct:10,20,30.5,40", "^!200,100", "!90", "gray.png").unwrap();
+        assert_eq!(
+            request,
+            ImageRequest {
+                region: Region::Percent {
+                    x: 10.0,
+                    y: 20.0,
+                    w: 30.5,
+                    h: 40.0
+                },
+                size: Size {
+                    upscale: true,
+                    kind: SizeKind::Confined(200, 100)
+                },
+                rotation: Rotation {
+                    mirror: true,
+                    degrees: 90
+                },
+                quality: Quality::Gray,
+                format: Format::Png,
+            }
+        );
+
+        let parse = |region, size, rotation, file| {
+            ImageRequest::parse(region, size, rotation, file).map_err(|err| err.status())
+        };
+        for (region, size, rotation, file) in [
+            ("0,0,10", "max", "0", "default.jpg"),
+            ("full", "0,", "0", "default.jpg"),
+            ("full", ",", "0", "default.jpg"),
+            ("full", "pct:101", "0", "default.jpg"),
+            ("full", "max", "-90", "default.jpg"),
+            ("full", "max", "0", "sepia.jpg"),
+            ("full", "max", "0", "default"),
+        ] {
+            assert_eq!(
+                parse(region, size, rotation, file),
+                Err(StatusCode::BAD_REQUEST),
+                "{}/{}/{}/{}",
+                region,
+                size,
+                rotation,
+                file
+            );
+        }
+        assert_eq!(
+            parse("full", "max", "45", "default.jpg"),
+            Err(StatusCode::NOT_IMPLEMENTED)
+        );
+        assert_eq!(
+            parse("full", "max", "0", "default.jp2"),
+            Err(StatusCode::NOT_IMPLEMENTED)
+        );
+    }
+
+    #[test]
+    fn plans_regions() {
+        let region = |region| {
+            let plan = plan(region, "max", 1000, 500).unwrap();
+            (plan.x, plan.y, plan.w, plan.h)
+        };
+        assert_eq!(region("full"), (0, 0, 1000, 500));
+        assert_eq!(region("square"), (250, 0, 500, 500));
+        assert_eq!(region("100,50,200,100"), (100, 50, 200, 100));
+        assert_eq!(region("900,400,500,500"), (900, 400, 100, 100));
+        assert_eq!(region("pct:10,10,50,50"), (100, 50, 500, 250));
+
+        assert_eq!(
+            plan("1000,0,10,10", "max", 1000, 500),
+            Err(StatusCode::BAD_REQUEST)
+        );
+        assert_eq!(
+            plan("0,0,0,10", "max", 1000, 500),
+            Err(StatusCode::BAD_REQUEST)
+        );
+    }
+
+    #[test]
+    fn plans_sizes() {
+        assert_eq!(output("full", "max"), (1000, 500));
+        assert_eq!(output("full", "500,"), (500, 250));
+        assert_eq!(output("full", ",100"), (200, 100));
+        assert_eq!(output("full", "pct:50"), (500, 250));
+        assert_eq!(output("full", "300,300"), (300, 300));
+        assert_eq!(output("full", "!300,300"), (300, 150));
+        assert_eq!(output("full", "!3000,3000"), (1000, 500));
+        assert_eq!(output("full", "^!3000,3000"), (3000, 1500));
+        assert_eq!(output("0,0,100,100", "^200,"), (200, 200));
+        assert_eq!(output("0,0,1000,10", "^max"), (10_000, 100));
+
+        assert_eq!(
+            plan("full", "2000,", 1000, 500),
+            Err(StatusCode::BAD_REQUEST)
+        );
+        assert_eq!(
+            plan("full", "^10000,10000", 1000, 500),
+            Err(StatusCode::BAD_REQUEST)
+        );
+        assert_eq!(
+            plan("0,0,1000,1", "^4000000000,", 1000, 500),
+            Err(StatusCode::BAD_REQUEST)
+        );
+    }
+
+    #[test]
+    fn renders_from_scaled_sources() {
+        let full = DynamicImage::ImageRgb8(image::RgbImage::from_fn(400, 200, |x, _| {
+            image::Rgb([if x < 200 { 0 } else { 255 }, 0, 0])
+        }));
+        let half = full.resize_exact(200, 100, FilterType::Triangle);
+
+        let request = ImageRequest::parse("200,0,200,200", "100,", "!90", "default.png").unwrap();
+        let plan = request.plan(400, 200).unwrap();
+        for (source, scale) in [(&full, 1.0), (&half, 0.5)] {
+            let bytes = render(source, scale, &plan, &request).unwrap();
+            let image = image::load_from_memory(&bytes).unwrap().to_rgb8();
+            assert_eq!(image.dimensions(), (100, 100));
+            assert_eq!(image.get_pixel(50, 50).0, [255, 0, 0]);
+        }
+    }
+}
@@ -1,8 +1,20 @@
+mod audit;
+mod auth;
 mod db;
+mod errors;
+mod export;
+mod extractors;
+mod gedcom;
 mod handlers;
+mod iiif;
+mod migrate;
 mod models;
+mod pagination;
 mod routes;
+mod scans;
 mod schemas;
+#[cfg(test)]
+mod test_utils;
 
 use axum::{
     Router,
@@ -10,12 +22,14 @@ use axum::{
         HeaderValue, Method,
         header::{ACCEPT, AUTHORIZATION, CONTENT_TYPE},
     },
+    middleware::from_fn_with_state,
 };
 use dotenv::dotenv;
 use std::sync::Arc;
-use tower_http::cors::CorsLayer;
+use tower_http::cors::{Any, CorsLayer};
 
 use db::AppState;
+use models::users::Role;
 
 pub fn create_app(app_state: Arc<AppState>) -> Router {
     let frontend_origin =
@@ -23,37 +37,124 @@ pub fn create_app(app_state: Arc<AppState>) -> Router {
 
     let cors = CorsLayer::new()
         .allow_origin(frontend_origin.parse::<HeaderValue>().unwrap())
-        .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::DELETE])
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
         .allow_credentials(true)
         .allow_headers([AUTHORIZATION, ACCEPT, CONTENT_TYPE]);
 
-    return Router::new()
+    Router::new()
         .nest("/api/v1/healthcheck", routes::health_check::get_routes())
+        .nest(
+            "/api/v1/admin",
+            routes::admin::get_routes(app_state.clone()),
+        )
         .nest(
             "/api/v1/archives",
             routes::archives::get_routes(app_state.clone()),
         )
+        .nest(
+            "/api/v1/audit",
+            routes::audit::get_routes(app_state.clone()),
+        )
         .nest(
             "/api/v1/documents",
             routes::documents::get_routes(app_state.clone()),
         )
+        .nest(
+            "/api/v1/gedcom",
+            routes::gedcom::get_routes(app_state.clone()),
+        )
         .nest(
             "/api/v1/institutes",
             routes::institutes::get_routes(app_state.clone()),
         )
+        .nest(
+            "/api/v1/persons",
+            routes::persons::get_routes(app_state.clone()),
+        )
         .nest(
             "/api/v1/places",
             routes::places::get_routes(app_state.clone()),
         )
-        .layer(cors);
+        .nest(
+            "/api/v1/relationships",
+            routes::relationships::get_routes(app_state.clone()),
+        )
+        .nest(
+            "/api/v1/search",
+            routes::search::get_routes(app_state.clone()),
+        )
+        .nest(
+            "/api/v1/vocabularies",
+            routes::vocabularies::get_routes(app_state.clone()),
+        )
+        // Everything but reads needs a signed in user, except for signing in itself
+        .layer(from_fn_with_state(
+            app_state.clone(),
+            auth::require_user_for_mutations,
+        ))
+        .nest("/api/v1/auth", routes::auth::get_routes(app_state.clone()))
+        .layer(cors)
+        // Added after the frontend CORS layer: IIIF viewers on any site may load the images
+        .nest(
+            "/api/v1/iiif",
+            routes::iiif::get_routes(app_state.clone()).layer(
+                CorsLayer::new()
+                    .allow_origin(Any)
+                    .allow_methods([Method::GET]),
+            ),
+        )
 }
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
     let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let args: Vec<String> = std::env::args().skip(1).collect();
 
     let app_state = Arc::new(AppState::init(&database_url).await);
+
+    // `backend migrate up|down|status` manages the schema and `backend create-user` adds
+    // a user, instead of starting the server
+    if let Some(command) = args.first() {
+        let result = match command.as_str() {
+            "migrate" => {
+                let subcommand = args.get(1).map(String::as_str);
+                migrate::run_command(subcommand, app_state.pool()).await
+            }
+            "create-user" => create_user_command(app_state.pool(), &args[1..]).await,
+            _ => {
+                eprintln!(
+                    "Usage: backend [migrate up|down|status | {}]",
+                    CREATE_USER_USAGE
+                );
+                std::process::exit(2);
+            }
+        };
+
+        if let Err(err) = result {
+            eprintln!("🔥 {}", err);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if migrate::auto_migrate_enabled() {
+        migrate::up(app_state.pool()).await.unwrap_or_else(|err| {
+            eprintln!("🔥 Failed to run the database migrations: {:?}", err);
+            std::process::exit(1);
+        });
+
+        println!("✅ Database migrations are up to date!");
+    }
+
+    tokio::spawn(app_state.derivatives().clone().run());
+
     let app = create_app(app_state.clone());
 
     let port: u16 = std::env::var("PORT")
@@ -69,3 +170,49 @@ async fn main() {
 
     axum::serve(listener, app).await.unwrap();
 }
+
+const CREATE_USER_USAGE: &str = "create-user [--role=admin|editor|volunteer|reader] <email> <name>";
+
+/**
+ * Add a user with the password read from standard input, so it stays out of the shell history.
+ * `--role=` may be given more than once.
+ */
+async fn create_user_command(pool: &sqlx::PgPool, args: &[String]) -> Result<(), String> {
+    let (flags, words): (Vec<&String>, Vec<&String>) =
+        args.iter().partition(|arg| arg.starts_with("--"));
+    let roles = flags
+        .iter()
+        .map(|flag| match flag.strip_prefix("--role=") {
+            Some(role) => role.parse::<Role>(),
+            None => Err(format!("Unknown option: {}", flag)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let [email, name @ ..] = words.as_slice() else {
+        return Err(format!("Usage: backend {}", CREATE_USER_USAGE));
+    };
+    if name.is_empty() {
+        return Err(format!("Usage: backend {}", CREATE_USER_USAGE));
+    }
+    let name = name
+        .iter()
+        .map(|word| word.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    eprint!("Password for {}: ", email);
+    let mut password = String::new();
+    std::io::stdin()
+        .read_line(&mut password)
+        .map_err(|err| format!("Failed to read the password: {}", err))?;
+
+    let password = password.trim_end_matches(['\r', '\n']);
+    let user = auth::create_user(pool, email, &name, password, &roles)
+        .await
+        .map_err(|err| err.message().to_string())?;
+
+    println!(
+        "✅ Created user {} with ID: {}",
+        user.user.email, user.user.id
+    );
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use sqlx::{
+    PgPool,
+    migrate::{Migrate, MigrateError, Migrator},
+};
+
+/**
+ * The migrations in `backend/migrations`, embedded into the binary at compile time.
+ */
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/**
+ * Whether pending migrations are applied on startup.
+ * Enabled unless `AUTO_MIGRATE` is set to `false` or `0`.
+ */
+pub fn auto_migrate_enabled() -> bool {
+    match std::env::var("AUTO_MIGRATE") {
+        Ok(value) => !matches!(value.trim().to_lowercase().as_str(), "false" | "0"),
+        Err(_) => true,
+    }
+}
+
+/**
+ * Apply all pending migrations.
+ */
+pub async fn up(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+/**
+ * Revert the most recently applied migration.
+ * Returns the version that was reverted, if any.
+ */
+pub async fn down(pool: &PgPool) -> Result<Option<i64>, MigrateError> {
+    let mut applied = applied_versions(pool).await?;
+    let Some(latest) = applied.pop() else {
+        return Ok(None);
+    };
+
+    MIGRATOR.undo(pool, applied.pop().unwrap_or(0)).await?;
+
+    Ok(Some(latest))
+}
+
+/**
+ * Print every known migration and whether it has been applied.
+ */
+pub async fn status(pool: &PgPool) -> Result<(), MigrateError> {
+    let applied = applied_versions(pool).await?;
+
+    for migration in MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+    {
+        let state = if applied.contains(&migration.version) {
+            "applied"
+        } else {
+            "pending"
+        };
+        println!(
+            "{:<16}{:<10}{}",
+            migration.version, state, migration.description
+        );
+    }
+
+    for version in applied
+        .iter()
+        .filter(|version| !MIGRATOR.version_exists(**version))
+    {
+        println!("{:<16}{:<10}", version, "missing");
+    }
+
+    Ok(())
+}
+
+/**
+ * Run the `migrate up|down|status` subcommand.
+ */
+pub async fn run_command(command: Option<&str>, pool: &PgPool) -> Result<(), String> {
+    match command {
+        Some("up") => {
+            up(pool).await.map_err(|e| e.to_string())?;
+            println!("✅ All migrations are applied");
+        }
+        Some("down") => match down(pool).await.map_err(|e| e.to_string())? {
+            Some(version) => println!("✅ Reverted migration {}", version),
+            None => println!("Nothing to revert"),
+        },
+        Some("status") => status(pool).await.map_err(|e| e.to_string())?,
+        _ => return Err("Usage: backend migrate up|down|status".to_string()),
+    }
+
+    Ok(())
+}
+
+async fn applied_versions(pool: &PgPool) -> Result<Vec<i64>, MigrateError> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    let mut versions: Vec<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    versions.sort();
+
+    Ok(versions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn table_exists(pool: &PgPool, table: &str) -> bool {
+        sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+            .bind(table)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn up_and_down_walk_through_every_migration(pool: PgPool) {
+        up(&pool).await.unwrap();
+        assert!(table_exists(&pool, "documents").await);
+        let versions = applied_versions(&pool).await.unwrap();
+        let expected: Vec<i64> = MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .map(|migration| migration.version)
+            .collect();
+        assert_eq!(versions, expected);
+
+        for version in versions.iter().rev() {
+            assert_eq!(down(&pool).await.unwrap(), Some(*version));
+        }
+        assert!(!table_exists(&pool, "documents").await);
+        assert_eq!(down(&pool).await.unwrap(), None);
+
+        up(&pool).await.unwrap();
+        assert!(table_exists(&pool, "documents").await);
+    }
+}
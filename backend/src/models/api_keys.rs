@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::auth::permissions::Permission;
+
+/**
+ * A key for scripts, without the key itself, which is only shown when it is created.
+ */
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_by: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn permits(&self, permission: Permission) -> bool {
+        self.scopes
+            .iter()
+            .any(|scope| scope.parse() == Ok(permission))
+    }
+}
@@ -1,8 +1,24 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use crate::export::{Cell, Tabular};
+use crate::pagination::Embed;
+
 #[derive(Serialize, Deserialize, FromRow)]
 pub struct Archive {
     pub id: i32,
     pub name: String,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub deleted_by: Option<i32>,
+}
+
+impl Tabular for Archive {
+    fn headers(_embeds: &[&Embed]) -> Vec<&'static str> {
+        vec!["id", "name"]
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![self.id.into(), self.name.clone().into()]
+    }
 }
@@ -0,0 +1,32 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, types::Json};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "audit_action", rename_all = "lowercase")]
+pub enum AuditAction {
+    Create,
+    Update,
+    /** A soft delete, see `deleted_at` */
+    Delete,
+    Restore,
+    /** The item is removed for good */
+    Purge,
+}
+
+/**
+ * A change of an item, see `audit::record`.
+ */
+#[derive(Serialize, Deserialize, FromRow, Debug)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub created_at: DateTime<Utc>,
+    pub actor_user_id: Option<i32>,
+    pub actor_api_key_id: Option<i32>,
+    pub resource_type: String,
+    pub resource_id: i32,
+    pub action: AuditAction,
+    pub changes: Json<Value>,
+}
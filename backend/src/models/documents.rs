@@ -2,15 +2,92 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use super::archives::Archive;
+use super::historical_date::HistoricalDate;
+use super::institutes::Institute;
+use super::places::Place;
+use crate::export::{Cell, Tabular};
+use crate::pagination::Embed;
+
 #[derive(Serialize, Deserialize, FromRow)]
 pub struct Document {
     pub id: i32,
-    pub date: DateTime<Utc>,
+    #[sqlx(flatten)]
+    pub date: HistoricalDate,
     pub inventory_number: String,
-    pub scan_number: String,
-    pub page_number: String,
-    pub notes: String,
+    pub notes: Option<String>,
     pub archive_id: i32,
     pub institute_id: i32,
     pub place_id: i32,
+    /** Set when the document is deleted, until it is restored or purged */
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub deleted_by: Option<i32>,
+}
+
+/**
+ * A document with the items it refers to, as far as they were asked for with `?expand=`.
+ * Items that were not expanded are left out of the JSON.
+ */
+#[derive(Serialize, FromRow)]
+pub struct ExpandedDocument {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub document: Document,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[sqlx(default, json(nullable))]
+    pub archive: Option<Archive>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[sqlx(default, json(nullable))]
+    pub institute: Option<Institute>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[sqlx(default, json(nullable))]
+    pub place: Option<Place>,
+}
+
+/**
+ * Expanded items are exported by name, in a column named after the embed.
+ */
+impl Tabular for ExpandedDocument {
+    fn headers(embeds: &[&Embed]) -> Vec<&'static str> {
+        let mut headers = vec![
+            "id",
+            "date",
+            "date_earliest",
+            "date_latest",
+            "inventory_number",
+            "notes",
+            "archive_id",
+            "institute_id",
+            "place_id",
+        ];
+        // In the order of the cells, whatever the order of `?expand=`
+        for name in ["archive", "institute", "place"] {
+            if embeds.iter().any(|embed| embed.name == name) {
+                headers.push(name);
+            }
+        }
+        headers
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        let document = &self.document;
+        let mut cells = vec![
+            document.id.into(),
+            document.date.to_string().into(),
+            document.date.earliest.to_string().into(),
+            document.date.latest.to_string().into(),
+            document.inventory_number.clone().into(),
+            document.notes.clone().into(),
+            document.archive_id.into(),
+            document.institute_id.into(),
+            document.place_id.into(),
+        ];
+        let names = [
+            self.archive.as_ref().map(|archive| &archive.name),
+            self.institute.as_ref().map(|institute| &institute.name),
+            self.place.as_ref().map(|place| &place.name),
+        ];
+        cells.extend(names.into_iter().flatten().map(|name| name.clone().into()));
+        cells
+    }
 }
@@ -0,0 +1,358 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::{FromRow, Row, postgres::PgRow};
+
+/**
+ * How precisely a historical date is known.
+ * Variants are ordered from coarse to fine.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, sqlx::Type)]
+#[sqlx(type_name = "date_precision", rename_all = "lowercase")]
+pub enum DatePrecision {
+    Year,
+    Month,
+    Day,
+}
+
+/**
+ * A possibly partial, ranged or uncertain date as found in archival sources,
+ * e.g. "1743", "1743-05", "1743/1745" or "1690~".
+ *
+ * The date is stored as the earliest and latest day it can refer to, which makes it
+ * sortable and usable in range filters. It is (de)serialized as an EDTF-style string:
+ * `~` marks an approximate date, `?` an uncertain one and `%` both.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, FromRow)]
+pub struct HistoricalDate {
+    #[sqlx(rename = "date_earliest")]
+    pub earliest: NaiveDate,
+    #[sqlx(rename = "date_latest")]
+    pub latest: NaiveDate,
+    #[sqlx(rename = "date_precision")]
+    pub precision: DatePrecision,
+    #[sqlx(rename = "date_approximate")]
+    pub approximate: bool,
+    #[sqlx(rename = "date_uncertain")]
+    pub uncertain: bool,
+}
+
+impl HistoricalDate {
+    /**
+     * Read an optional date from the `{prefix}_date_*` columns of `row`, e.g. `birth_date_earliest`.
+     * The date is `None` when its bounds are NULL.
+     */
+    pub fn from_prefixed_row(row: &PgRow, prefix: &str) -> Result<Option<Self>, sqlx::Error> {
+        let column = |name: &str| format!("{}_date_{}", prefix, name);
+
+        let earliest: Option<NaiveDate> = row.try_get(column("earliest").as_str())?;
+        let latest: Option<NaiveDate> = row.try_get(column("latest").as_str())?;
+        let precision: Option<DatePrecision> = row.try_get(column("precision").as_str())?;
+
+        match (earliest, latest, precision) {
+            (Some(earliest), Some(latest), Some(precision)) => Ok(Some(Self {
+                earliest,
+                latest,
+                precision,
+                approximate: row.try_get(column("approximate").as_str())?,
+                uncertain: row.try_get(column("uncertain").as_str())?,
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    fn is_single_unit(&self) -> bool {
+        unit_bounds(self.earliest, self.precision) == (self.earliest, self.latest)
+    }
+
+    fn qualifier(&self) -> &'static str {
+        match (self.approximate, self.uncertain) {
+            (false, false) => "",
+            (true, false) => "~",
+            (false, true) => "?",
+            (true, true) => "%",
+        }
+    }
+}
+
+impl fmt::Display for HistoricalDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let qualifier = self.qualifier();
+        let earliest = format_at(self.earliest, self.precision);
+
+        if self.is_single_unit() {
+            write!(f, "{}{}", earliest, qualifier)
+        } else {
+            let latest = format_at(self.latest, self.precision);
+            write!(f, "{}{}/{}{}", earliest, qualifier, latest, qualifier)
+        }
+    }
+}
+
+impl FromStr for HistoricalDate {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let trimmed = value.trim();
+        let (circa, rest) = strip_circa(trimmed);
+
+        let mut parts = rest.split(['/', '–']);
+        let start = parts.next().unwrap_or_default();
+        let end = parts.next();
+        if parts.next().is_some() {
+            return Err(format!("Invalid historical date: {}", value));
+        }
+
+        let start =
+            Endpoint::parse(start).ok_or_else(|| format!("Invalid historical date: {}", value))?;
+        let end = match end {
+            Some(end) => {
+                Endpoint::parse(end).ok_or_else(|| format!("Invalid historical date: {}", value))?
+            }
+            None => start,
+        };
+
+        let (earliest, _) = unit_bounds(start.date, start.precision);
+        let (_, latest) = unit_bounds(end.date, end.precision);
+        if earliest > latest {
+            return Err(format!("Historical date ends before it starts: {}", value));
+        }
+
+        Ok(Self {
+            earliest,
+            latest,
+            precision: start.precision.max(end.precision),
+            approximate: circa || start.approximate || end.approximate,
+            uncertain: start.uncertain || end.uncertain,
+        })
+    }
+}
+
+impl Serialize for HistoricalDate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for HistoricalDate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Endpoint {
+    date: NaiveDate,
+    precision: DatePrecision,
+    approximate: bool,
+    uncertain: bool,
+}
+
+impl Endpoint {
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (value, approximate, uncertain) = match value.chars().last()? {
+            '~' => (&value[..value.len() - 1], true, false),
+            '?' => (&value[..value.len() - 1], false, true),
+            '%' => (&value[..value.len() - 1], true, true),
+            _ => (value, false, false),
+        };
+
+        let mut fields = value.split('-');
+        let year = parse_number(fields.next()?, 4)?;
+        let month = match fields.next() {
+            Some(month) => Some(parse_number(month, 2)?),
+            None => None,
+        };
+        let day = match fields.next() {
+            Some(day) => Some(parse_number(day, 2)?),
+            None => None,
+        };
+        if fields.next().is_some() {
+            return None;
+        }
+
+        let (date, precision) = match (month, day) {
+            (None, _) => (
+                NaiveDate::from_ymd_opt(year as i32, 1, 1)?,
+                DatePrecision::Year,
+            ),
+            (Some(month), None) => (
+                NaiveDate::from_ymd_opt(year as i32, month, 1)?,
+                DatePrecision::Month,
+            ),
+            (Some(month), Some(day)) => (
+                NaiveDate::from_ymd_opt(year as i32, month, day)?,
+                DatePrecision::Day,
+            ),
+        };
+
+        Some(Self {
+            date,
+            precision,
+            approximate,
+            uncertain,
+        })
+    }
+}
+
+fn parse_number(value: &str, digits: usize) -> Option<u32> {
+    if value.len() != digits || !value.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    value.parse().ok()
+}
+
+fn strip_circa(value: &str) -> (bool, &str) {
+    for prefix in ["circa ", "ca. ", "ca ", "c. "] {
+        if let Some(head) = value.get(..prefix.len())
+            && head.eq_ignore_ascii_case(prefix)
+        {
+            return (true, value[prefix.len()..].trim_start());
+        }
+    }
+    (false, value)
+}
+
+/**
+ * First and last day of the year, month or day that `date` falls in.
+ */
+fn unit_bounds(date: NaiveDate, precision: DatePrecision) -> (NaiveDate, NaiveDate) {
+    match precision {
+        DatePrecision::Day => (date, date),
+        DatePrecision::Month => {
+            let first = date.with_day(1).unwrap();
+            let next = first
+                .checked_add_months(chrono::Months::new(1))
+                .unwrap_or(NaiveDate::MAX);
+            (first, next.pred_opt().unwrap_or(next))
+        }
+        DatePrecision::Year => (
+            NaiveDate::from_ymd_opt(date.year(), 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(date.year(), 12, 31).unwrap(),
+        ),
+    }
+}
+
+fn format_at(date: NaiveDate, precision: DatePrecision) -> String {
+    match precision {
+        DatePrecision::Year => format!("{:04}", date.year()),
+        DatePrecision::Month => format!("{:04}-{:02}", date.year(), date.month()),
+        DatePrecision::Day => format!("{:04}-{:02}-{:02}", date.year(), date.month(), date.day()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn parse(value: &str) -> HistoricalDate {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_partial_dates() {
+        let year = parse("1743");
+        assert_eq!(year.precision, DatePrecision::Year);
+        assert_eq!(
+            (year.earliest, year.latest),
+            (ymd(1743, 1, 1), ymd(1743, 12, 31))
+        );
+
+        let month = parse("1744-02");
+        assert_eq!(month.precision, DatePrecision::Month);
+        assert_eq!(
+            (month.earliest, month.latest),
+            (ymd(1744, 2, 1), ymd(1744, 2, 29))
+        );
+
+        let day = parse("1743-05-12");
+        assert_eq!(day.precision, DatePrecision::Day);
+        assert_eq!(
+            (day.earliest, day.latest),
+            (ymd(1743, 5, 12), ymd(1743, 5, 12))
+        );
+        assert!(!day.approximate && !day.uncertain);
+    }
+
+    #[test]
+    fn parses_ranges_and_qualifiers() {
+        let range = parse("1743–1745");
+        assert_eq!(
+            (range.earliest, range.latest),
+            (ymd(1743, 1, 1), ymd(1745, 12, 31))
+        );
+        assert_eq!(range.precision, DatePrecision::Year);
+
+        let mixed = parse("1743-05/1745");
+        assert_eq!(
+            (mixed.earliest, mixed.latest),
+            (ymd(1743, 5, 1), ymd(1745, 12, 31))
+        );
+        assert_eq!(mixed.precision, DatePrecision::Month);
+
+        let circa = parse("circa 1690");
+        assert!(circa.approximate && !circa.uncertain);
+        assert!(parse("1690?").uncertain);
+        assert!(parse("1690%").approximate && parse("1690%").uncertain);
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        for value in [
+            "",
+            "17",
+            "1743-13",
+            "1743-02-30",
+            "1745/1743",
+            "1743/1744/1745",
+            "May 1743",
+        ] {
+            assert!(value.parse::<HistoricalDate>().is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn formats_as_edtf() {
+        for (input, output) in [
+            ("1743", "1743"),
+            ("1743-05", "1743-05"),
+            ("1743-05-12", "1743-05-12"),
+            ("1743–1745", "1743/1745"),
+            ("1743-05/1745", "1743-05/1745-12"),
+            ("circa 1690", "1690~"),
+            ("1690~/1692~", "1690~/1692~"),
+            ("1690-01-01?", "1690-01-01?"),
+        ] {
+            assert_eq!(parse(input).to_string(), output, "{}", input);
+            assert_eq!(parse(output).to_string(), output, "{}", output);
+        }
+    }
+
+    #[test]
+    fn sorts_on_bounds() {
+        let mut dates = vec![
+            parse("1745"),
+            parse("1743-05-12"),
+            parse("1743/1744"),
+            parse("1743"),
+        ];
+        dates.sort();
+        assert_eq!(
+            dates,
+            vec![
+                parse("1743"),
+                parse("1743/1744"),
+                parse("1743-05-12"),
+                parse("1745")
+            ]
+        );
+    }
+}
@@ -1,4 +1,14 @@
+pub mod api_keys;
 pub mod archives;
+pub mod audit;
 pub mod documents;
+pub mod historical_date;
 pub mod institutes;
+pub mod persons;
 pub mod places;
+pub mod relationships;
+pub mod scans;
+pub mod search;
+pub mod transcriptions;
+pub mod users;
+pub mod vocabularies;
@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row, postgres::PgRow};
+
+use super::historical_date::HistoricalDate;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "sex", rename_all = "lowercase")]
+pub enum Sex {
+    Male,
+    Female,
+    Unknown,
+}
+
+/**
+ * The part a person plays in a document.
+ * `subject` is the person the record is about, e.g. the baptized child or the orphan.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "person_role", rename_all = "lowercase")]
+pub enum PersonRole {
+    Subject,
+    Father,
+    Mother,
+    Witness,
+    Official,
+    Spouse,
+}
+
+/**
+ * A person as named in the sources. All name parts are optional, because records often
+ * give only a given name and a patronymic, e.g. "Jan Pietersz".
+ */
+#[derive(Serialize, Deserialize)]
+pub struct Person {
+    pub id: i32,
+    pub given_names: Option<String>,
+    pub patronymic: Option<String>,
+    pub surname_prefix: Option<String>,
+    pub surname: Option<String>,
+    pub sex: Sex,
+    pub birth_date: Option<HistoricalDate>,
+    pub birth_place_id: Option<i32>,
+    pub death_date: Option<HistoricalDate>,
+    pub death_place_id: Option<i32>,
+}
+
+// Written out, because the optional dates span several prefixed columns each
+impl<'r> FromRow<'r, PgRow> for Person {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            given_names: row.try_get("given_names")?,
+            patronymic: row.try_get("patronymic")?,
+            surname_prefix: row.try_get("surname_prefix")?,
+            surname: row.try_get("surname")?,
+            sex: row.try_get("sex")?,
+            birth_date: HistoricalDate::from_prefixed_row(row, "birth")?,
+            birth_place_id: row.try_get("birth_place_id")?,
+            death_date: HistoricalDate::from_prefixed_row(row, "death")?,
+            death_place_id: row.try_get("death_place_id")?,
+        })
+    }
+}
+
+/**
+ * A mention of a person in a document.
+ */
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct DocumentPerson {
+    pub id: i32,
+    pub document_id: i32,
+    pub person_id: i32,
+    pub role: PersonRole,
+}
+
+/**
+ * A mention of a person in a document, with the person itself.
+ */
+#[derive(Serialize, FromRow)]
+pub struct PersonMention {
+    #[sqlx(rename = "mention_id")]
+    pub id: i32,
+    pub role: PersonRole,
+    #[sqlx(flatten)]
+    pub person: Person,
+}
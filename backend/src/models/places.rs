@@ -1,10 +1,31 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use crate::export::{Cell, Tabular};
+use crate::pagination::Embed;
+
 #[derive(Serialize, Deserialize, FromRow)]
 pub struct Place {
     pub id: i32,
     pub name: String,
     pub latitude: Option<f64>,
     pub longitude: Option<f64>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub deleted_by: Option<i32>,
+}
+
+impl Tabular for Place {
+    fn headers(_embeds: &[&Embed]) -> Vec<&'static str> {
+        vec!["id", "name", "latitude", "longitude"]
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            self.id.into(),
+            self.name.clone().into(),
+            self.latitude.into(),
+            self.longitude.into(),
+        ]
+    }
 }
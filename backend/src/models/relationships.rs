@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "relationship_kind", rename_all = "lowercase")]
+pub enum RelationshipKind {
+    Parent,
+    Spouse,
+}
+
+/**
+ * A family relationship with the documents that support it.
+ * For `parent` the person is a parent of the relative, `spouse` relationships are symmetric
+ * and stored with the lower person id first.
+ */
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct Relationship {
+    pub id: i32,
+    pub kind: RelationshipKind,
+    pub person_id: i32,
+    pub relative_id: i32,
+    pub document_ids: Vec<i32>,
+}
+
+/**
+ * A parent relationship reached while walking a lineage,
+ * `generation` generations away from the person the walk started at.
+ */
+#[derive(Serialize, FromRow, Debug, Clone)]
+pub struct LineageEdge {
+    pub id: i32,
+    pub parent_id: i32,
+    pub child_id: i32,
+    pub generation: i32,
+}
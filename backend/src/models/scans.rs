@@ -0,0 +1,73 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/**
+ * Progress of making the derivatives of a scan.
+ * Scans without a file and PDFs are `skipped`, the error of a `failed` scan is kept with it.
+ */
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "derivatives_status", rename_all = "lowercase")]
+pub enum DerivativesStatus {
+    Pending,
+    Processing,
+    Done,
+    Failed,
+    Skipped,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "derivative_format", rename_all = "lowercase")]
+pub enum DerivativeFormat {
+    Jpeg,
+    Webp,
+}
+
+impl DerivativeFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+        }
+    }
+}
+
+/**
+ * A scan of a document page.
+ * The file metadata is empty for scans that were catalogued by number without uploading a file.
+ */
+#[derive(Serialize, Deserialize, FromRow, Debug)]
+pub struct DocumentScan {
+    pub id: i32,
+    pub document_id: i32,
+    pub scan_number: Option<String>,
+    pub page_number: Option<String>,
+    pub sha256: Option<String>,
+    pub file_name: Option<String>,
+    pub mime_type: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub derivatives_status: DerivativesStatus,
+    pub derivatives_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/**
+ * A scaled down version of a scan, e.g. its thumbnail as WebP.
+ * `size` is the name of the size in the `DerivativeSettings` it was made with.
+ */
+#[derive(Serialize, Deserialize, FromRow, Debug)]
+pub struct ScanDerivative {
+    pub id: i32,
+    pub scan_id: i32,
+    pub size: String,
+    pub format: DerivativeFormat,
+    pub sha256: String,
+    pub size_bytes: i64,
+    pub width: i32,
+    pub height: i32,
+    pub created_at: DateTime<Utc>,
+}
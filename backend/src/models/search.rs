@@ -0,0 +1,27 @@
+use serde::Serialize;
+use sqlx::FromRow;
+
+use super::documents::Document;
+
+/**
+ * A document matching a search, with its relevance and a fragment of its text
+ * in which the matching words are wrapped in `<mark>` tags.
+ */
+#[derive(Serialize, FromRow)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub document: Document,
+    pub rank: f32,
+    pub snippet: String,
+}
+
+/**
+ * The number of matching documents for one archive, institute or place.
+ */
+#[derive(Serialize, FromRow)]
+pub struct Facet {
+    pub id: i32,
+    pub name: String,
+    pub count: i64,
+}
@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "transcription_status", rename_all = "lowercase")]
+pub enum TranscriptionStatus {
+    Draft,
+    Reviewed,
+    Final,
+}
+
+/**
+ * The current version of the transcribed text of a document.
+ * `scan_id` is the scan of the page it transcribes, if it covers a single page.
+ * `transcriber_id` is the user who made the current revision, `transcriber` their name.
+ */
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct Transcription {
+    pub id: i32,
+    pub document_id: i32,
+    pub scan_id: Option<i32>,
+    pub transcriber_id: Option<i32>,
+    pub transcriber: String,
+    pub language: String,
+    pub status: TranscriptionStatus,
+    pub text: String,
+    pub revision: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/**
+ * A version of a transcription, numbered from 1.
+ * Every change of the text, language or status adds a revision.
+ */
+#[derive(Serialize, Deserialize, FromRow, Debug)]
+pub struct TranscriptionRevision {
+    pub id: i32,
+    pub transcription_id: i32,
+    pub revision: i32,
+    pub transcriber_id: Option<i32>,
+    pub transcriber: String,
+    pub language: String,
+    pub status: TranscriptionStatus,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+}
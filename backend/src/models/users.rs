@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::str::FromStr;
+
+/**
+ * What a user may do is decided by their roles, see `Role::permits`.
+ */
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "user_role", rename_all = "lowercase")]
+pub enum Role {
+    Admin,
+    Editor,
+    Volunteer,
+    Reader,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [Role::Admin, Role::Editor, Role::Volunteer, Role::Reader];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Admin => "admin",
+            Self::Editor => "editor",
+            Self::Volunteer => "volunteer",
+            Self::Reader => "reader",
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.name() == value)
+            .ok_or_else(|| {
+                format!(
+                    "Invalid role: {}, expected admin, editor, volunteer or reader",
+                    value
+                )
+            })
+    }
+}
+
+/**
+ * A person who signs in to the archive. The password hash is never part of it.
+ */
+#[derive(Serialize, Deserialize, FromRow, Debug, Clone)]
+pub struct User {
+    pub id: i32,
+    pub email: String,
+    pub name: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, FromRow, Debug, Clone)]
+pub struct UserWithRoles {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub user: User,
+    pub roles: Vec<Role>,
+}
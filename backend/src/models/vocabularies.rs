@@ -0,0 +1,44 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, types::Json};
+
+use crate::export::{Cell, Tabular};
+use crate::pagination::Embed;
+
+/**
+ * A controlled vocabulary, e.g. "Document type" or "Topic".
+ */
+#[derive(Serialize, Deserialize, FromRow)]
+pub struct Vocabulary {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+}
+
+impl Tabular for Vocabulary {
+    fn headers(_embeds: &[&Embed]) -> Vec<&'static str> {
+        vec!["id", "name", "description"]
+    }
+
+    fn cells(&self) -> Vec<Cell> {
+        vec![
+            self.id.into(),
+            self.name.clone().into(),
+            self.description.clone().into(),
+        ]
+    }
+}
+
+/**
+ * A term of a vocabulary. `parent_id` is the broader term it is nested under, `code`
+ * identifies it within the vocabulary and `labels` are its names by language tag.
+ */
+#[derive(Serialize, Deserialize, FromRow, Debug)]
+pub struct VocabularyTerm {
+    pub id: i32,
+    pub vocabulary_id: i32,
+    pub parent_id: Option<i32>,
+    pub code: String,
+    pub labels: Json<BTreeMap<String, String>>,
+}
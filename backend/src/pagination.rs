@@ -0,0 +1,558 @@
+use axum::{
+    Json,
+    http::{HeaderMap, HeaderValue, Uri, header::LINK},
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder, Row, postgres::PgRow};
+
+use crate::errors::AppError;
+use crate::schemas::PageParams;
+
+pub const DEFAULT_LIMIT: i64 = 100;
+pub const MAX_LIMIT: i64 = 1000;
+
+/**
+ * A field of a resource that may be used in `?sort=`.
+ * Only NOT NULL columns are sortable, so that keyset pagination stays correct.
+ */
+pub struct SortField {
+    pub name: &'static str,
+    pub column: &'static str,
+    pub sql_type: &'static str,
+}
+
+/**
+ * Everything `fetch_page` needs to know about a resource.
+ * `default_sort` uses the same `field,-field` syntax as the `?sort=` parameter.
+ */
+pub struct Listing {
+    pub table: &'static str,
+    pub sort_fields: &'static [SortField],
+    pub default_sort: &'static str,
+}
+
+/**
+ * Extra conditions on a list query.
+ * Implementations push ` AND ...` fragments and must bind every value they use.
+ */
+pub trait Filter {
+    fn push_conditions(&self, query: &mut QueryBuilder<'_, Postgres>);
+}
+
+impl Filter for () {
+    fn push_conditions(&self, _query: &mut QueryBuilder<'_, Postgres>) {}
+}
+
+/**
+ * A row of another table that may be embedded in each item with `?expand={name}`.
+ * It is joined through `foreign_key` and returned as a JSON object in the column `name`.
+ */
+pub struct Embed {
+    pub name: &'static str,
+    pub table: &'static str,
+    pub foreign_key: &'static str,
+}
+
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: Option<i64>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort: String,
+    values: Vec<String>,
+}
+
+struct SortKey {
+    field: &'static SortField,
+    descending: bool,
+}
+
+impl SortKey {
+    /**
+     * The sort column qualified with the table, so that it stays unambiguous with embeds.
+     */
+    fn column(&self, listing: &Listing) -> String {
+        format!("{}.{}", listing.table, self.field.column)
+    }
+}
+
+/**
+ * The page size to use, after checking `limit` and `offset` against their bounds.
+ */
+pub fn validate_limit(limit: Option<i64>, offset: Option<i64>) -> Result<i64, AppError> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(AppError::Validation(format!(
+            "limit must be between 1 and {}",
+            MAX_LIMIT
+        )));
+    }
+    if offset.is_some_and(|offset| offset < 0) {
+        return Err(AppError::Validation(
+            "offset must not be negative".to_string(),
+        ));
+    }
+
+    Ok(limit)
+}
+
+/**
+ * Fetch one page of `listing`, filtered by `filter` and ordered and paginated by `params`.
+ */
+pub async fn fetch_page<T>(
+    pool: &PgPool,
+    listing: &Listing,
+    params: &PageParams,
+    filter: &impl Filter,
+) -> Result<Page<T>, AppError>
+where
+    T: for<'r> FromRow<'r, PgRow>,
+{
+    fetch_page_with_embeds(pool, listing, params, filter, &[]).await
+}
+
+/**
+ * `fetch_page` that also joins in the rows of `embeds`, see `embed_columns` and `embed_joins`.
+ */
+pub async fn fetch_page_with_embeds<T>(
+    pool: &PgPool,
+    listing: &Listing,
+    params: &PageParams,
+    filter: &impl Filter,
+    embeds: &[&Embed],
+) -> Result<Page<T>, AppError>
+where
+    T: for<'r> FromRow<'r, PgRow>,
+{
+    let limit = validate_limit(params.limit, params.offset)?;
+    if params.offset.is_some() && params.cursor.is_some() {
+        return Err(AppError::Validation(
+            "offset and cursor cannot be combined".to_string(),
+        ));
+    }
+
+    let sort = params.sort.as_deref().unwrap_or(listing.default_sort);
+    let keys = parse_sort(listing, sort)?;
+    let sort = normalize_sort(&keys);
+    let cursor = params
+        .cursor
+        .as_deref()
+        .map(|cursor| decode_cursor(cursor, &sort, keys.len()))
+        .transpose()?;
+
+    let mut query = QueryBuilder::new(format!("SELECT COUNT(*) FROM {} WHERE TRUE", listing.table));
+    filter.push_conditions(&mut query);
+    let total: i64 = query.build_query_scalar().fetch_one(pool).await?;
+
+    let cursor_columns: Vec<String> = keys
+        .iter()
+        .map(|key| format!("({})::text", key.column(listing)))
+        .collect();
+    let mut query = QueryBuilder::new(format!(
+        "SELECT {}.*{}, ARRAY[{}] AS page_cursor FROM {}{} WHERE TRUE",
+        listing.table,
+        embed_columns(embeds),
+        cursor_columns.join(", "),
+        listing.table,
+        embed_joins(listing.table, embeds)
+    ));
+    filter.push_conditions(&mut query);
+    if let Some(cursor) = &cursor {
+        push_keyset_condition(&mut query, listing, &keys, cursor);
+    }
+
+    query.push(order_clause(listing, &keys));
+    // One extra row tells whether there is a next page
+    query.push(" LIMIT ").push_bind(limit + 1);
+    if let Some(offset) = params.offset {
+        query.push(" OFFSET ").push_bind(offset);
+    }
+
+    let mut rows = query.build().fetch_all(pool).await?;
+    let has_more = rows.len() as i64 > limit;
+    rows.truncate(limit as usize);
+
+    let next_cursor = match rows.last() {
+        Some(row) if has_more => Some(encode_cursor(&Cursor {
+            sort,
+            values: row.try_get("page_cursor")?,
+        })),
+        _ => None,
+    };
+    let items = rows
+        .iter()
+        .map(T::from_row)
+        .collect::<Result<Vec<T>, _>>()?;
+
+    Ok(Page {
+        items,
+        total,
+        limit,
+        offset: params.offset,
+        next_cursor,
+    })
+}
+
+/**
+ * The ` ORDER BY` clause for `sort`, or for the default sort of `listing`.
+ */
+pub fn order_by(listing: &Listing, sort: Option<&str>) -> Result<String, AppError> {
+    let keys = parse_sort(listing, sort.unwrap_or(listing.default_sort))?;
+
+    Ok(order_clause(listing, &keys))
+}
+
+fn order_clause(listing: &Listing, keys: &[SortKey]) -> String {
+    let order: Vec<String> = keys
+        .iter()
+        .map(|key| {
+            let direction = if key.descending { "DESC" } else { "ASC" };
+            format!("{} {}", key.column(listing), direction)
+        })
+        .collect();
+
+    format!(" ORDER BY {}", order.join(", "))
+}
+
+/**
+ * Select list entries for `embeds`, to follow `{table}.*`.
+ */
+pub fn embed_columns(embeds: &[&Embed]) -> String {
+    embeds
+        .iter()
+        .map(|embed| format!(", to_jsonb({}.*) AS {}", embed.name, embed.name))
+        .collect()
+}
+
+/**
+ * Joins for `embeds`, to follow `FROM {table}`.
+ * The joined tables are aliased to the embed names, so columns of `table` must be qualified
+ * where the names clash, e.g. `id`.
+ */
+pub fn embed_joins(table: &str, embeds: &[&Embed]) -> String {
+    embeds
+        .iter()
+        .map(|embed| {
+            format!(
+                " JOIN {} AS {} ON {}.id = {}.{}",
+                embed.table, embed.name, embed.name, table, embed.foreign_key
+            )
+        })
+        .collect()
+}
+
+/**
+ * The embeds named in `expand`, a list of comma separated names that may be repeated.
+ */
+pub fn parse_expand(
+    embeds: &'static [Embed],
+    expand: &[String],
+) -> Result<Vec<&'static Embed>, AppError> {
+    let mut selected: Vec<&'static Embed> = Vec::new();
+
+    for name in expand
+        .iter()
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+    {
+        let embed = embeds
+            .iter()
+            .find(|embed| embed.name == name)
+            .ok_or_else(|| {
+                let allowed: Vec<&str> = embeds.iter().map(|embed| embed.name).collect();
+                AppError::Validation(format!(
+                    "Cannot expand {}, allowed fields are: {}",
+                    name,
+                    allowed.join(", ")
+                ))
+            })?;
+
+        if !selected.iter().any(|selected| selected.name == embed.name) {
+            selected.push(embed);
+        }
+    }
+
+    Ok(selected)
+}
+
+impl<T: Serialize> Page<T> {
+    /**
+     * The list envelope plus an RFC 8288 `Link` header pointing at the neighbouring pages.
+     */
+    pub fn into_list_response(self, uri: &Uri) -> Response {
+        let mut headers = HeaderMap::new();
+        if let Ok(value) = HeaderValue::from_str(&self.links(uri).join(", ")) {
+            headers.insert(LINK, value);
+        }
+
+        let json_response = json!({
+            "status": "success",
+            "results": self.items.len(),
+            "total": self.total,
+            "limit": self.limit,
+            "offset": self.offset,
+            "next_cursor": self.next_cursor,
+            "items": self.items,
+        });
+
+        (headers, Json(json_response)).into_response()
+    }
+
+    fn links(&self, uri: &Uri) -> Vec<String> {
+        let link = |page: &str, rel: &str| {
+            format!(
+                "<{}?{}>; rel=\"{}\"",
+                uri.path(),
+                page_query(uri, self.limit, page),
+                rel
+            )
+        };
+
+        let mut links = vec![link("", "first")];
+        match self.offset {
+            Some(offset) => {
+                if offset > 0 {
+                    let prev = (offset - self.limit).max(0);
+                    links.push(link(&format!("offset={}", prev), "prev"));
+                }
+                if offset + self.limit < self.total {
+                    let next = offset + self.limit;
+                    links.push(link(&format!("offset={}", next), "next"));
+                }
+                let last = ((self.total - 1).max(0) / self.limit) * self.limit;
+                links.push(link(&format!("offset={}", last), "last"));
+            }
+            None => {
+                if let Some(cursor) = &self.next_cursor {
+                    links.push(link(&format!("cursor={}", cursor), "next"));
+                }
+            }
+        }
+
+        links
+    }
+}
+
+/**
+ * The query string of `uri` with its pagination parameters replaced by `limit` and `page`.
+ */
+fn page_query(uri: &Uri, limit: i64, page: &str) -> String {
+    let mut pairs: Vec<&str> = uri
+        .query()
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| {
+            let key = pair.split('=').next().unwrap_or_default();
+            !pair.is_empty() && !matches!(key, "limit" | "offset" | "cursor")
+        })
+        .collect();
+
+    let limit = format!("limit={}", limit);
+    pairs.push(&limit);
+    if !page.is_empty() {
+        pairs.push(page);
+    }
+
+    pairs.join("&")
+}
+
+fn parse_sort(listing: &Listing, sort: &str) -> Result<Vec<SortKey>, AppError> {
+    let mut keys: Vec<SortKey> = Vec::new();
+
+    for part in sort
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let (name, descending) = match part.strip_prefix('-') {
+            Some(name) => (name, true),
+            None => (part.strip_prefix('+').unwrap_or(part), false),
+        };
+
+        let field = listing
+            .sort_fields
+            .iter()
+            .find(|field| field.name == name)
+            .ok_or_else(|| {
+                let allowed: Vec<&str> = listing.sort_fields.iter().map(|f| f.name).collect();
+                AppError::Validation(format!(
+                    "Cannot sort by {}, allowed fields are: {}",
+                    name,
+                    allowed.join(", ")
+                ))
+            })?;
+
+        if !keys.iter().any(|key| key.field.column == field.column) {
+            keys.push(SortKey { field, descending });
+        }
+    }
+
+    // `id` makes the order total, which keyset pagination depends on
+    if !keys.iter().any(|key| key.field.name == "id") {
+        let id = listing
+            .sort_fields
+            .iter()
+            .find(|field| field.name == "id")
+            .expect("every listing must be sortable by id");
+        keys.push(SortKey {
+            field: id,
+            descending: false,
+        });
+    }
+
+    Ok(keys)
+}
+
+fn normalize_sort(keys: &[SortKey]) -> String {
+    keys.iter()
+        .map(|key| {
+            let prefix = if key.descending { "-" } else { "" };
+            format!("{}{}", prefix, key.field.name)
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/**
+ * `(a, b, id) > (x, y, z)` for mixed sort directions, written out as
+ * `a > x OR (a = x AND b > y) OR (a = x AND b = y AND id > z)`.
+ */
+fn push_keyset_condition(
+    query: &mut QueryBuilder<'_, Postgres>,
+    listing: &Listing,
+    keys: &[SortKey],
+    cursor: &Cursor,
+) {
+    query.push(" AND (");
+    for (i, key) in keys.iter().enumerate() {
+        if i > 0 {
+            query.push(" OR ");
+        }
+        query.push("(");
+        for (previous, value) in keys.iter().zip(&cursor.values).take(i) {
+            query.push(format!("{} = CAST(", previous.column(listing)));
+            query.push_bind(value.clone());
+            query.push(format!(" AS {}) AND ", previous.field.sql_type));
+        }
+        let operator = if key.descending { "<" } else { ">" };
+        query.push(format!("{} {} CAST(", key.column(listing), operator));
+        query.push_bind(cursor.values[i].clone());
+        query.push(format!(" AS {}))", key.field.sql_type));
+    }
+    query.push(")");
+}
+
+fn encode_cursor(cursor: &Cursor) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor).unwrap_or_default())
+}
+
+fn decode_cursor(cursor: &str, sort: &str, keys: usize) -> Result<Cursor, AppError> {
+    let invalid = || AppError::Validation("Invalid cursor".to_string());
+
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+    let cursor: Cursor = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+    if cursor.values.len() != keys {
+        return Err(invalid());
+    }
+    if cursor.sort != sort {
+        return Err(AppError::Validation(
+            "Cursor was created for a different sort order".to_string(),
+        ));
+    }
+
+    Ok(cursor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LISTING: Listing = Listing {
+        table: "archives",
+        sort_fields: &[
+            SortField {
+                name: "name",
+                column: "name",
+                sql_type: "text",
+            },
+            SortField {
+                name: "id",
+                column: "id",
+                sql_type: "integer",
+            },
+        ],
+        default_sort: "name",
+    };
+
+    #[test]
+    fn sort_appends_id_and_rejects_unknown_fields() {
+        let keys = parse_sort(&LISTING, "-name").unwrap();
+        assert_eq!(normalize_sort(&keys), "-name,id");
+
+        let keys = parse_sort(&LISTING, "-id, name,-name").unwrap();
+        assert_eq!(normalize_sort(&keys), "-id,name");
+
+        assert!(parse_sort(&LISTING, "name;DROP TABLE archives").is_err());
+    }
+
+    #[test]
+    fn cursor_round_trips_only_for_the_same_sort() {
+        let cursor = encode_cursor(&Cursor {
+            sort: "name,id".to_string(),
+            values: vec!["Haarlem".to_string(), "3".to_string()],
+        });
+
+        let decoded = decode_cursor(&cursor, "name,id", 2).unwrap();
+        assert_eq!(decoded.values, vec!["Haarlem", "3"]);
+        assert!(decode_cursor(&cursor, "-name,id", 2).is_err());
+        assert!(decode_cursor("not a cursor", "name,id", 2).is_err());
+    }
+
+    #[test]
+    fn expand_accepts_lists_and_rejects_unknown_names() {
+        const EMBEDS: &[Embed] = &[
+            Embed {
+                name: "archive",
+                table: "archives",
+                foreign_key: "archive_id",
+            },
+            Embed {
+                name: "place",
+                table: "places",
+                foreign_key: "place_id",
+            },
+        ];
+
+        let expand = vec!["place, archive".to_string(), "place".to_string()];
+        let names: Vec<&str> = parse_expand(EMBEDS, &expand)
+            .unwrap()
+            .iter()
+            .map(|embed| embed.name)
+            .collect();
+        assert_eq!(names, vec!["place", "archive"]);
+
+        assert!(parse_expand(EMBEDS, &["archive,notes".to_string()]).is_err());
+    }
+
+    #[test]
+    fn page_query_replaces_pagination_parameters() {
+        let uri: Uri = "/api/v1/archives?sort=-name&limit=5&cursor=abc&offset=3"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            page_query(&uri, 5, "offset=10"),
+            "sort=-name&limit=5&offset=10"
+        );
+        assert_eq!(page_query(&uri, 5, ""), "sort=-name&limit=5");
+    }
+}
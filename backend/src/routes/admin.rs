@@ -0,0 +1,44 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    routing::{get, post, put},
+};
+
+use crate::auth::permissions::{Access, Action, Authorize, Resource};
+use crate::handlers::api_keys::{
+    api_keys_list_handler, create_api_key_handler, get_api_key_handler, revoke_api_key_handler,
+};
+use crate::handlers::users::{
+    create_user_handler, get_user_handler, roles_list_handler, update_user_roles_handler,
+    users_list_handler,
+};
+
+use crate::AppState;
+
+pub fn get_routes(app_state: Arc<AppState>) -> Router {
+    let users = Router::new()
+        .route("/roles", get(roles_list_handler))
+        .route("/users", get(users_list_handler).post(create_user_handler))
+        .route("/users/{id}", get(get_user_handler))
+        .route("/users/{id}/roles", put(update_user_roles_handler))
+        .authorize(&app_state, Access::Crud(Resource::Users));
+
+    let api_keys = Router::new()
+        .route(
+            "/api-keys",
+            get(api_keys_list_handler).post(create_api_key_handler),
+        )
+        .route("/api-keys/{id}", get(get_api_key_handler))
+        .authorize(&app_state, Access::Crud(Resource::ApiKeys));
+
+    let revoke = Router::new()
+        .route("/api-keys/{id}/revoke", post(revoke_api_key_handler))
+        .authorize(&app_state, Access::Only(Resource::ApiKeys, Action::Update));
+
+    Router::new()
+        .merge(users)
+        .merge(api_keys)
+        .merge(revoke)
+        .with_state(app_state)
+}
@@ -0,0 +1,15 @@
+use std::sync::Arc;
+
+use axum::{Router, routing::get};
+
+use crate::auth::permissions::{Access, Authorize, Resource};
+use crate::handlers::audit::audit_list_handler;
+
+use crate::AppState;
+
+pub fn get_routes(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(audit_list_handler))
+        .authorize(&app_state, Access::Crud(Resource::Audit))
+        .with_state(app_state)
+}
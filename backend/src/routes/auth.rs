@@ -0,0 +1,19 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    routing::{get, post},
+};
+
+use crate::handlers::auth::{login_handler, logout_handler, me_handler, refresh_handler};
+
+use crate::AppState;
+
+pub fn get_routes(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/login", post(login_handler))
+        .route("/refresh", post(refresh_handler))
+        .route("/logout", post(logout_handler))
+        .route("/me", get(me_handler))
+        .with_state(app_state)
+}
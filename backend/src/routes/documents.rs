@@ -2,18 +2,38 @@ use std::sync::Arc;
 
 use axum::{
     Router,
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    routing::{delete, get, post},
 };
 
+use crate::auth::permissions::{Access, Action, Authorize, Resource};
+use crate::handlers::document_import::import_handler;
 use crate::handlers::documents::{
     create_item_handler, delete_item_handler, edit_item_handler, get_item_handler,
-    items_list_handler,
+    items_list_handler, restore_item_handler,
 };
+use crate::handlers::persons::{
+    add_document_person_handler, document_persons_list_handler, remove_document_person_handler,
+};
+use crate::handlers::scans::{
+    delete_scan_handler, document_scans_list_handler, download_scan_handler, edit_scan_handler,
+    get_scan_handler, scan_thumbnail_handler, upload_scan_handler,
+};
+use crate::handlers::transcriptions::{
+    create_transcription_handler, delete_transcription_handler,
+    document_transcriptions_list_handler, edit_transcription_handler, get_revision_handler,
+    get_transcription_handler, restore_revision_handler, transcription_diff_handler,
+    transcription_revisions_list_handler,
+};
+use crate::handlers::vocabularies::{
+    add_document_term_handler, document_terms_list_handler, remove_document_term_handler,
+};
+use crate::scans::MAX_SCAN_SIZE;
 
 use crate::AppState;
 
 pub fn get_routes(app_state: Arc<AppState>) -> Router {
-    Router::new()
+    let documents = Router::new()
         .route("/", post(create_item_handler))
         .route("/", get(items_list_handler))
         .route(
@@ -22,5 +42,108 @@ pub fn get_routes(app_state: Arc<AppState>) -> Router {
                 .patch(edit_item_handler)
                 .delete(delete_item_handler),
         )
+        .authorize(&app_state, Access::Crud(Resource::Documents));
+
+    let restore_document = Router::new()
+        .route("/{id}/restore", post(restore_item_handler))
+        .authorize(
+            &app_state,
+            Access::Only(Resource::Documents, Action::Delete),
+        );
+
+    let import = Router::new()
+        .route("/import", post(import_handler))
+        .authorize(
+            &app_state,
+            Access::Only(Resource::Documents, Action::Import),
+        );
+
+    // Mentioning persons and assigning terms changes the document itself
+    let links = Router::new()
+        .route(
+            "/{id}/persons",
+            get(document_persons_list_handler).post(add_document_person_handler),
+        )
+        .route(
+            "/{id}/persons/{mention_id}",
+            delete(remove_document_person_handler),
+        )
+        .route(
+            "/{id}/terms",
+            get(document_terms_list_handler).post(add_document_term_handler),
+        )
+        .route(
+            "/{id}/terms/{term_id}",
+            delete(remove_document_term_handler),
+        )
+        .authorize(
+            &app_state,
+            Access::Changes(Resource::Documents, Action::Update),
+        );
+
+    let scans = Router::new()
+        .route(
+            "/{id}/scans",
+            get(document_scans_list_handler)
+                .post(upload_scan_handler)
+                .layer(DefaultBodyLimit::max(MAX_SCAN_SIZE)),
+        )
+        .route(
+            "/{id}/scans/{scan_id}",
+            get(get_scan_handler)
+                .patch(edit_scan_handler)
+                .delete(delete_scan_handler),
+        )
+        .route("/{id}/scans/{scan_id}/file", get(download_scan_handler))
+        .route(
+            "/{id}/scans/{scan_id}/thumbnail",
+            get(scan_thumbnail_handler),
+        )
+        .authorize(&app_state, Access::Crud(Resource::Scans));
+
+    let transcriptions = Router::new()
+        .route(
+            "/{id}/transcriptions",
+            get(document_transcriptions_list_handler).post(create_transcription_handler),
+        )
+        .route(
+            "/{id}/transcriptions/{transcription_id}",
+            get(get_transcription_handler)
+                .patch(edit_transcription_handler)
+                .delete(delete_transcription_handler),
+        )
+        .route(
+            "/{id}/transcriptions/{transcription_id}/diff",
+            get(transcription_diff_handler),
+        )
+        .route(
+            "/{id}/transcriptions/{transcription_id}/revisions",
+            get(transcription_revisions_list_handler),
+        )
+        .route(
+            "/{id}/transcriptions/{transcription_id}/revisions/{revision}",
+            get(get_revision_handler),
+        )
+        .authorize(&app_state, Access::Crud(Resource::Transcriptions));
+
+    // Restoring adds a revision to an existing transcription
+    let restore = Router::new()
+        .route(
+            "/{id}/transcriptions/{transcription_id}/revisions/{revision}/restore",
+            post(restore_revision_handler),
+        )
+        .authorize(
+            &app_state,
+            Access::Only(Resource::Transcriptions, Action::Update),
+        );
+
+    Router::new()
+        .merge(documents)
+        .merge(restore_document)
+        .merge(import)
+        .merge(links)
+        .merge(scans)
+        .merge(transcriptions)
+        .merge(restore)
         .with_state(app_state)
 }
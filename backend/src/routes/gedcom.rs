@@ -0,0 +1,26 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    routing::{get, post},
+};
+
+use crate::auth::permissions::{Access, Action, Authorize, Resource};
+use crate::handlers::gedcom::{export_handler, import_handler};
+
+use crate::AppState;
+
+pub fn get_routes(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .merge(
+            Router::new()
+                .route("/import", post(import_handler))
+                .authorize(&app_state, Access::Only(Resource::Persons, Action::Import)),
+        )
+        .merge(
+            Router::new()
+                .route("/export", get(export_handler))
+                .authorize(&app_state, Access::Only(Resource::Persons, Action::Export)),
+        )
+        .with_state(app_state)
+}
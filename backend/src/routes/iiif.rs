@@ -0,0 +1,23 @@
+use std::sync::Arc;
+
+use axum::{Router, routing::get};
+
+use crate::auth::permissions::{Access, Authorize, Resource};
+use crate::handlers::iiif::{
+    image_handler, image_info_handler, image_service_handler, manifest_handler,
+};
+
+use crate::AppState;
+
+pub fn get_routes(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/image/{id}", get(image_service_handler))
+        .route("/image/{id}/info.json", get(image_info_handler))
+        .route(
+            "/image/{id}/{region}/{size}/{rotation}/{file}",
+            get(image_handler),
+        )
+        .route("/manifests/{id}", get(manifest_handler))
+        .authorize(&app_state, Access::Crud(Resource::Scans))
+        .with_state(app_state)
+}
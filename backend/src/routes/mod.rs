@@ -1,5 +1,14 @@
+pub mod admin;
 pub mod archives;
+pub mod audit;
+pub mod auth;
 pub mod documents;
+pub mod gedcom;
 pub mod health_check;
+pub mod iiif;
 pub mod institutes;
+pub mod persons;
 pub mod places;
+pub mod relationships;
+pub mod search;
+pub mod vocabularies;
@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    routing::{get, post},
+};
+
+use crate::auth::permissions::{Access, Authorize, Resource};
+use crate::handlers::lineage::{ancestors_handler, descendants_handler};
+use crate::handlers::persons::{
+    create_item_handler, delete_item_handler, edit_item_handler, get_item_handler,
+    items_list_handler,
+};
+use crate::handlers::relationships::person_relationships_list_handler;
+
+use crate::AppState;
+
+pub fn get_routes(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", post(create_item_handler))
+        .route("/", get(items_list_handler))
+        .route(
+            "/{id}",
+            get(get_item_handler)
+                .patch(edit_item_handler)
+                .delete(delete_item_handler),
+        )
+        .route(
+            "/{id}/relationships",
+            get(person_relationships_list_handler),
+        )
+        .route("/{id}/ancestors", get(ancestors_handler))
+        .route("/{id}/descendants", get(descendants_handler))
+        .authorize(&app_state, Access::Crud(Resource::Persons))
+        .with_state(app_state)
+}
@@ -5,15 +5,16 @@ use axum::{
     routing::{get, post},
 };
 
+use crate::auth::permissions::{Access, Action, Authorize, Resource};
 use crate::handlers::places::{
     create_item_handler, delete_item_handler, edit_item_handler, get_item_handler,
-    items_list_handler,
+    items_list_handler, restore_item_handler,
 };
 
 use crate::AppState;
 
 pub fn get_routes(app_state: Arc<AppState>) -> Router {
-    Router::new()
+    let places = Router::new()
         .route("/", post(create_item_handler))
         .route("/", get(items_list_handler))
         .route(
@@ -22,5 +23,14 @@ pub fn get_routes(app_state: Arc<AppState>) -> Router {
                 .patch(edit_item_handler)
                 .delete(delete_item_handler),
         )
+        .authorize(&app_state, Access::Crud(Resource::Places));
+
+    let restore = Router::new()
+        .route("/{id}/restore", post(restore_item_handler))
+        .authorize(&app_state, Access::Only(Resource::Places, Action::Delete));
+
+    Router::new()
+        .merge(places)
+        .merge(restore)
         .with_state(app_state)
 }
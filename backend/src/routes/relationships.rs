@@ -0,0 +1,26 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    routing::{get, post},
+};
+
+use crate::auth::permissions::{Access, Authorize, Resource};
+use crate::handlers::relationships::{
+    create_item_handler, delete_item_handler, edit_item_handler, get_item_handler,
+};
+
+use crate::AppState;
+
+pub fn get_routes(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", post(create_item_handler))
+        .route(
+            "/{id}",
+            get(get_item_handler)
+                .patch(edit_item_handler)
+                .delete(delete_item_handler),
+        )
+        .authorize(&app_state, Access::Crud(Resource::Relationships))
+        .with_state(app_state)
+}
@@ -0,0 +1,15 @@
+use std::sync::Arc;
+
+use axum::{Router, routing::get};
+
+use crate::auth::permissions::{Access, Authorize, Resource};
+use crate::handlers::search::search_handler;
+
+use crate::AppState;
+
+pub fn get_routes(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", get(search_handler))
+        .authorize(&app_state, Access::Crud(Resource::Documents))
+        .with_state(app_state)
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    routing::{get, post},
+};
+
+use crate::auth::permissions::{Access, Authorize, Resource};
+use crate::handlers::vocabularies::{
+    create_item_handler, create_term_handler, delete_item_handler, delete_term_handler,
+    edit_item_handler, edit_term_handler, get_item_handler, get_term_handler, items_list_handler,
+    vocabulary_terms_list_handler,
+};
+
+use crate::AppState;
+
+pub fn get_routes(app_state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/", post(create_item_handler))
+        .route("/", get(items_list_handler))
+        .route(
+            "/{id}",
+            get(get_item_handler)
+                .patch(edit_item_handler)
+                .delete(delete_item_handler),
+        )
+        .route(
+            "/{id}/terms",
+            get(vocabulary_terms_list_handler).post(create_term_handler),
+        )
+        .route(
+            "/{id}/terms/{term_id}",
+            get(get_term_handler)
+                .patch(edit_term_handler)
+                .delete(delete_term_handler),
+        )
+        .authorize(&app_state, Access::Crud(Resource::Vocabularies))
+        .with_state(app_state)
+}
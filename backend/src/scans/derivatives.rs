@@ -0,0 +1,484 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use image::{DynamicImage, ImageReader, codecs::jpeg::JpegEncoder, codecs::webp::WebPEncoder};
+use sqlx::PgPool;
+use tokio::sync::Notify;
+
+use super::ScanStore;
+use crate::errors::AppError;
+use crate::models::scans::DerivativeFormat;
+
+/**
+ * How long the worker sleeps when nobody wakes it, so that scans of other backend
+ * instances or of a crashed run are picked up eventually.
+ */
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/**
+ * How long a worker may take to make the derivatives of a scan. A scan that was claimed
+ * longer ago is taken to be left by a crashed worker and is queued again.
+ */
+const CLAIM_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/**
+ * Bumped when the way derivatives are made changes, to make them again with the new code.
+ */
+const DERIVATIVES_VERSION: u32 = 1;
+
+const FORMATS: [DerivativeFormat; 2] = [DerivativeFormat::Webp, DerivativeFormat::Jpeg];
+
+/**
+ * A named derivative size, scaled to fit in a square of `max_dimension` pixels.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DerivativeSize {
+    pub name: String,
+    pub max_dimension: u32,
+}
+
+/**
+ * Which derivatives are made of each scan image, in every one of the formats.
+ * Set with `DERIVATIVE_SIZES=thumbnail:256,medium:800,large:1600` and
+ * `DERIVATIVE_JPEG_QUALITY=80`, which are the defaults. The first size is the default
+ * of the thumbnail endpoint.
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DerivativeSettings {
+    pub sizes: Vec<DerivativeSize>,
+    pub jpeg_quality: u8,
+}
+
+impl Default for DerivativeSettings {
+    fn default() -> Self {
+        Self::parse("thumbnail:256,medium:800,large:1600", "80").unwrap()
+    }
+}
+
+impl DerivativeSettings {
+    pub fn from_env() -> Result<Self, String> {
+        let default = Self::default();
+        match (
+            std::env::var("DERIVATIVE_SIZES"),
+            std::env::var("DERIVATIVE_JPEG_QUALITY"),
+        ) {
+            (Err(_), Err(_)) => Ok(default),
+            (sizes, quality) => Self::parse(
+                &sizes.unwrap_or_else(|_| default.sizes_spec()),
+                &quality.unwrap_or_else(|_| default.jpeg_quality.to_string()),
+            ),
+        }
+    }
+
+    fn parse(sizes: &str, jpeg_quality: &str) -> Result<Self, String> {
+        let sizes = sizes
+            .split(',')
+            .map(|size| {
+                let (name, max_dimension) = size.trim().split_once(':').unwrap_or_default();
+                match max_dimension.trim().parse::<u32>() {
+                    Ok(max_dimension) if !name.trim().is_empty() && max_dimension > 0 => {
+                        Ok(DerivativeSize {
+                            name: name.trim().to_string(),
+                            max_dimension,
+                        })
+                    }
+                    _ => Err(format!(
+                        "Invalid derivative size: {}, expected name:pixels",
+                        size
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let jpeg_quality = match jpeg_quality.trim().parse::<u8>() {
+            Ok(quality) if (1..=100).contains(&quality) => quality,
+            _ => {
+                return Err(format!(
+                    "Invalid JPEG quality: {}, expected 1 to 100",
+                    jpeg_quality
+                ));
+            }
+        };
+
+        Ok(Self {
+            sizes,
+            jpeg_quality,
+        })
+    }
+
+    fn sizes_spec(&self) -> String {
+        let sizes: Vec<String> = self
+            .sizes
+            .iter()
+            .map(|size| format!("{}:{}", size.name, size.max_dimension))
+            .collect();
+        sizes.join(",")
+    }
+
+    /**
+     * Identifies the settings, it is stored with the derivatives that were made with them.
+     */
+    pub fn fingerprint(&self) -> String {
+        format!(
+            "v{};sizes={};jpeg_quality={}",
+            DERIVATIVES_VERSION,
+            self.sizes_spec(),
+            self.jpeg_quality
+        )
+    }
+
+    pub fn size(&self, name: &str) -> Option<&DerivativeSize> {
+        self.sizes.iter().find(|size| size.name == name)
+    }
+}
+
+/**
+ * Makes the derivatives of uploaded scans in the background.
+ * Scans are queued through their `derivatives_status`, so the queue survives restarts
+ * and several backend instances can share it.
+ */
+#[derive(Clone)]
+pub struct DerivativeWorker {
+    pool: PgPool,
+    store: ScanStore,
+    settings: Arc<DerivativeSettings>,
+    wake: Arc<Notify>,
+}
+
+/**
+ * A derivative made in memory, before it is stored.
+ */
+struct Rendition {
+    size: String,
+    format: DerivativeFormat,
+    width: u32,
+    height: u32,
+    bytes: Vec<u8>,
+}
+
+impl DerivativeWorker {
+    pub fn new(pool: PgPool, store: ScanStore, settings: DerivativeSettings) -> Self {
+        Self {
+            pool,
+            store,
+            settings: Arc::new(settings),
+            wake: Arc::new(Notify::new()),
+        }
+    }
+
+    pub fn settings(&self) -> &DerivativeSettings {
+        &self.settings
+    }
+
+    /**
+     * Tell the worker that there are new scans to process.
+     */
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /**
+     * Process scans until the backend stops.
+     * Before every round stale scans are queued again, see `requeue_stale`.
+     */
+    pub async fn run(self) {
+        loop {
+            match self.requeue_stale().await {
+                Ok(0) => {}
+                Ok(count) => println!("✅ Queued {} scan(s) to remake their derivatives", count),
+                Err(err) => eprintln!("🔥 Failed to queue scans for derivatives: {:?}", err),
+            }
+            if let Err(err) = self.process_pending().await {
+                eprintln!("🔥 Failed to make scan derivatives: {:?}", err);
+            }
+            tokio::select! {
+                _ = self.wake.notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            }
+        }
+    }
+
+    /**
+     * Queue the scans whose derivatives were made, or failed to be made, with other settings,
+     * and the ones that were left half-done by a crash: those claimed longer than
+     * `CLAIM_TIMEOUT` ago. Scans that other workers are still processing are left alone.
+     * Returns the number of queued scans.
+     */
+    pub async fn requeue_stale(&self) -> Result<u64, AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE document_scans SET derivatives_status = 'pending', derivatives_claimed_at = NULL
+            WHERE CASE derivatives_status
+                WHEN 'pending' THEN false
+                WHEN 'processing' THEN derivatives_claimed_at IS NULL
+                    OR derivatives_claimed_at < now() - make_interval(secs => $2)
+                ELSE derivatives_settings IS DISTINCT FROM $1
+            END
+        "#,
+        )
+        .bind(self.settings.fingerprint())
+        .bind(CLAIM_TIMEOUT.as_secs_f64())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /**
+     * Process queued scans one by one until there are none left.
+     * Returns the number of processed scans.
+     */
+    pub async fn process_pending(&self) -> Result<usize, AppError> {
+        let mut processed = 0;
+        while self.process_next().await? {
+            processed += 1;
+        }
+        Ok(processed)
+    }
+
+    /**
+     * Claim and process the next queued scan, `false` when there is none.
+     * A scan that cannot be processed is marked as failed, so that it is not claimed
+     * again and again.
+     */
+    async fn process_next(&self) -> Result<bool, AppError> {
+        // SKIP LOCKED lets several workers claim different scans
+        let claimed: Option<(i32, Option<String>, Option<String>)> = sqlx::query_as(
+            r#"
+            UPDATE document_scans
+            SET derivatives_status = 'processing', derivatives_claimed_at = now()
+            WHERE id = (
+                SELECT id FROM document_scans WHERE derivatives_status = 'pending'
+                ORDER BY id LIMIT 1 FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, sha256, mime_type
+        "#,
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some((scan_id, sha256, mime_type)) = claimed else {
+            return Ok(false);
+        };
+
+        if let Err(err) = self.process(scan_id, sha256, mime_type).await {
+            self.finish(scan_id, "failed", Some(err.message().to_string()))
+                .await?;
+            return Err(err);
+        }
+
+        Ok(true)
+    }
+
+    /**
+     * Make the derivatives of a claimed scan, or record why there are none.
+     */
+    async fn process(
+        &self,
+        scan_id: i32,
+        sha256: Option<String>,
+        mime_type: Option<String>,
+    ) -> Result<(), AppError> {
+        let sha256 = match (sha256, mime_type) {
+            (Some(sha256), Some(mime_type)) if mime_type.starts_with("image/") => sha256,
+            _ => return self.finish(scan_id, "skipped", None).await,
+        };
+
+        let path = self.store.path(&sha256);
+        let settings = self.settings.clone();
+        let result = tokio::task::spawn_blocking(move || render(path, &settings))
+            .await
+            .map_err(|err| AppError::Internal(err.to_string()))?;
+
+        match result {
+            Ok(renditions) => self.store_renditions(scan_id, renditions).await,
+            Err(error) => self.finish(scan_id, "failed", Some(error)).await,
+        }
+    }
+
+    /**
+     * Replace the derivatives of a scan with the new ones and remove the old files.
+     */
+    async fn store_renditions(
+        &self,
+        scan_id: i32,
+        renditions: Vec<Rendition>,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        // The scan may have been deleted in the meantime
+        let exists = sqlx::query("SELECT id FROM document_scans WHERE id = $1 FOR UPDATE")
+            .bind(scan_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .is_some();
+        if !exists {
+            return Ok(tx.rollback().await?);
+        }
+
+        let mut hashes = Vec::new();
+        for rendition in &renditions {
+            hashes.push(self.store.save_bytes(&mut tx, &rendition.bytes).await?);
+        }
+
+        let old_hashes: Vec<String> =
+            sqlx::query_scalar("DELETE FROM scan_derivatives WHERE scan_id = $1 RETURNING sha256")
+                .bind(scan_id)
+                .fetch_all(&mut *tx)
+                .await?;
+
+        for (rendition, sha256) in renditions.iter().zip(&hashes) {
+            sqlx::query(
+                r#"
+                INSERT INTO scan_derivatives (scan_id, size, format, sha256, size_bytes, width, height)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            )
+            .bind(scan_id)
+            .bind(&rendition.size)
+            .bind(rendition.format)
+            .bind(sha256)
+            .bind(rendition.bytes.len() as i64)
+            .bind(rendition.width as i32)
+            .bind(rendition.height as i32)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            r#"
+            UPDATE document_scans
+            SET derivatives_status = 'done', derivatives_error = NULL, derivatives_settings = $1,
+                derivatives_claimed_at = NULL
+            WHERE id = $2
+        "#,
+        )
+        .bind(self.settings.fingerprint())
+        .bind(scan_id)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        self.store
+            .remove_unreferenced(&self.pool, &old_hashes)
+            .await
+    }
+
+    async fn finish(
+        &self,
+        scan_id: i32,
+        status: &str,
+        error: Option<String>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE document_scans
+            SET derivatives_status = $1::derivatives_status, derivatives_error = $2,
+                derivatives_settings = $3, derivatives_claimed_at = NULL
+            WHERE id = $4
+        "#,
+        )
+        .bind(status)
+        .bind(error)
+        .bind(self.settings.fingerprint())
+        .bind(scan_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+/**
+ * Decode a scan image and encode every size in every format.
+ * Images are only scaled down, never up. WebP is encoded lossless, JPEG with the
+ * configured quality.
+ */
+fn render(path: PathBuf, settings: &DerivativeSettings) -> Result<Vec<Rendition>, String> {
+    let image = ImageReader::open(&path)
+        .map_err(|err| format!("Cannot open scan: {}", err))?
+        .with_guessed_format()
+        .map_err(|err| format!("Cannot read scan: {}", err))?
+        .decode()
+        .map_err(|err| format!("Cannot decode scan: {}", err))?;
+
+    let mut renditions = Vec::new();
+    for size in &settings.sizes {
+        let scaled = if image.width().max(image.height()) > size.max_dimension {
+            image.resize(
+                size.max_dimension,
+                size.max_dimension,
+                image::imageops::FilterType::CatmullRom,
+            )
+        } else {
+            image.clone()
+        };
+        let scaled = DynamicImage::ImageRgb8(scaled.to_rgb8());
+
+        for format in FORMATS {
+            let mut bytes = Vec::new();
+            let result = match format {
+                DerivativeFormat::Jpeg => scaled.write_with_encoder(JpegEncoder::new_with_quality(
+                    &mut Cursor::new(&mut bytes),
+                    settings.jpeg_quality,
+                )),
+                DerivativeFormat::Webp => scaled
+                    .write_with_encoder(WebPEncoder::new_lossless(&mut Cursor::new(&mut bytes))),
+            };
+            result.map_err(|err| format!("Cannot encode {} {:?}: {}", size.name, format, err))?;
+
+            renditions.push(Rendition {
+                size: size.name.clone(),
+                format,
+                width: scaled.width(),
+                height: scaled.height(),
+                bytes,
+            });
+        }
+    }
+
+    Ok(renditions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_settings() {
+        let settings = DerivativeSettings::parse("thumb:128, web:2000", "90").unwrap();
+        assert_eq!(
+            settings.sizes,
+            vec![
+                DerivativeSize {
+                    name: "thumb".to_string(),
+                    max_dimension: 128
+                },
+                DerivativeSize {
+                    name: "web".to_string(),
+                    max_dimension: 2000
+                },
+            ]
+        );
+        assert_eq!(settings.jpeg_quality, 90);
+        assert_ne!(
+            settings.fingerprint(),
+            DerivativeSettings::default().fingerprint()
+        );
+
+        for (sizes, quality) in [
+            ("thumb", "80"),
+            ("thumb:0", "80"),
+            (":100", "80"),
+            ("a:1", "0"),
+        ] {
+            assert!(
+                DerivativeSettings::parse(sizes, quality).is_err(),
+                "{}",
+                sizes
+            );
+        }
+    }
+}
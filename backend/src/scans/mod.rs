@@ -0,0 +1,312 @@
+pub mod derivatives;
+pub mod range;
+
+use std::path::{Path, PathBuf};
+
+use axum::{body::Bytes, http::StatusCode};
+use futures::{Stream, TryStreamExt};
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::errors::AppError;
+
+/**
+ * Where uploaded scans are kept when `SCAN_DIR` is not set.
+ */
+const DEFAULT_SCAN_DIR: &str = "scans";
+
+/**
+ * Largest scan upload in bytes, large enough for an uncompressed TIFF of a folio page.
+ */
+pub const MAX_SCAN_SIZE: usize = 512 * 1024 * 1024;
+
+/**
+ * File types accepted as scans, recognized by their first bytes, see `sniff_mime_type`.
+ */
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\xFF\xD8\xFF", "image/jpeg"),
+    (b"\x89PNG\r\n\x1A\n", "image/png"),
+    (b"II*\x00", "image/tiff"),
+    (b"MM\x00*", "image/tiff"),
+    (b"%PDF-", "application/pdf"),
+];
+
+/**
+ * Scan files on the local disk, content-addressed by their SHA-256.
+ * A file with hash `ab12...` is stored as `{root}/ab/ab12...`, so uploading the same
+ * file twice, e.g. for two documents on one page, stores it only once.
+ *
+ * Files are put in place and removed while holding a transaction-level advisory lock on
+ * their hash. A file is put in place in the transaction that adds the row referring to it,
+ * so a concurrent `remove_unreferenced` cannot remove it between the two.
+ */
+#[derive(Clone, Debug)]
+pub struct ScanStore {
+    root: PathBuf,
+}
+
+/**
+ * Metadata of a file that was written to the store, under a temporary name until it is
+ * kept with `ScanStore::keep`. `width` and `height` are in pixels and only known for images.
+ */
+#[derive(Debug)]
+pub struct StoredFile {
+    pub sha256: String,
+    pub mime_type: &'static str,
+    pub size_bytes: i64,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    temp_path: PathBuf,
+}
+
+impl ScanStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /**
+     * The store in the `SCAN_DIR` directory, `./scans` by default.
+     */
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("SCAN_DIR").unwrap_or_else(|_| DEFAULT_SCAN_DIR.to_string()))
+    }
+
+    /**
+     * Path of the file with the given hash, whether it exists or not.
+     */
+    pub fn path(&self, sha256: &str) -> PathBuf {
+        self.root.join(&sha256[..2]).join(sha256)
+    }
+
+    /**
+     * Write the streamed file to the store under a temporary name while it is hashed and
+     * return its metadata. Files that are not a JPEG, PNG, TIFF or PDF are rejected with
+     * a 415 and not kept.
+     */
+    pub async fn save<S, E>(&self, stream: S) -> Result<StoredFile, AppError>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+        AppError: From<E>,
+    {
+        let temp_path = self.temp_path().await?;
+
+        let result = self.write_and_describe(stream, &temp_path).await;
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path).await;
+        }
+        result
+    }
+
+    /**
+     * Move a saved file to its content address, in the transaction of `conn` that adds
+     * the row referring to it.
+     */
+    pub async fn keep(&self, conn: &mut PgConnection, file: &StoredFile) -> Result<(), AppError> {
+        lock_files(conn, std::slice::from_ref(&file.sha256)).await?;
+        self.move_into_place(&file.temp_path, &file.sha256).await
+    }
+
+    /**
+     * Remove a saved file that was not kept.
+     */
+    pub async fn discard(&self, file: &StoredFile) {
+        let _ = fs::remove_file(&file.temp_path).await;
+    }
+
+    /**
+     * Write a file that was made by the backend, e.g. a thumbnail, in the transaction of
+     * `conn` that adds the row referring to it, and return its SHA-256.
+     * Unlike `save`, the type of the file is not checked.
+     */
+    pub async fn save_bytes(
+        &self,
+        conn: &mut PgConnection,
+        bytes: &[u8],
+    ) -> Result<String, AppError> {
+        let sha256 = format!("{:x}", Sha256::digest(bytes));
+        lock_files(conn, std::slice::from_ref(&sha256)).await?;
+
+        let temp_path = self.temp_path().await?;
+        fs::write(&temp_path, bytes).await.map_err(io_error)?;
+        self.move_into_place(&temp_path, &sha256).await?;
+
+        Ok(sha256)
+    }
+
+    async fn temp_path(&self) -> Result<PathBuf, AppError> {
+        let temp_dir = self.root.join("tmp");
+        fs::create_dir_all(&temp_dir).await.map_err(io_error)?;
+
+        Ok(temp_dir.join(uuid::Uuid::new_v4().to_string()))
+    }
+
+    async fn move_into_place(&self, temp_path: &Path, sha256: &str) -> Result<(), AppError> {
+        let path = self.path(sha256);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await.map_err(io_error)?;
+        }
+        // An existing file with the same hash has the same content, replacing it is harmless
+        fs::rename(temp_path, &path).await.map_err(io_error)
+    }
+
+    async fn write_and_describe<S, E>(&self, stream: S, path: &Path) -> Result<StoredFile, AppError>
+    where
+        S: Stream<Item = Result<Bytes, E>>,
+        AppError: From<E>,
+    {
+        let mut file = fs::File::create(path).await.map_err(io_error)?;
+        let mut hasher = Sha256::new();
+        let mut header = Vec::new();
+        let mut size_bytes = 0;
+
+        let mut stream = std::pin::pin!(stream);
+        while let Some(chunk) = stream.try_next().await? {
+            if header.len() < 16 {
+                let missing = (16 - header.len()).min(chunk.len());
+                header.extend_from_slice(&chunk[..missing]);
+            }
+            hasher.update(&chunk);
+            size_bytes += chunk.len() as i64;
+            file.write_all(&chunk).await.map_err(io_error)?;
+        }
+        file.flush().await.map_err(io_error)?;
+
+        let mime_type = sniff_mime_type(&header).ok_or_else(|| {
+            AppError::InvalidRequest(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "Scans must be JPEG, PNG, TIFF or PDF files".to_string(),
+            )
+        })?;
+
+        let (width, height) = if mime_type.starts_with("image/") {
+            let path = path.to_path_buf();
+            let size = tokio::task::spawn_blocking(move || imagesize::size(path))
+                .await
+                .map_err(|err| AppError::Internal(err.to_string()))?
+                .map_err(|_| {
+                    AppError::Validation(format!("Scan is not a readable {} image", mime_type))
+                })?;
+            (Some(size.width as i32), Some(size.height as i32))
+        } else {
+            (None, None)
+        };
+
+        Ok(StoredFile {
+            sha256: format!("{:x}", hasher.finalize()),
+            mime_type,
+            size_bytes,
+            width,
+            height,
+            temp_path: path.to_path_buf(),
+        })
+    }
+
+    /**
+     * Remove the files with the given hashes that no scan or derivative refers to anymore.
+     * Call this after deleting scans, it is a no-op for files that are still in use.
+     */
+    pub async fn remove_unreferenced(
+        &self,
+        pool: &PgPool,
+        hashes: &[String],
+    ) -> Result<(), AppError> {
+        let mut tx = pool.begin().await?;
+        lock_files(&mut tx, hashes).await?;
+
+        let unreferenced: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT hash FROM unnest($1::TEXT[]) AS hash
+            WHERE NOT EXISTS (SELECT 1 FROM document_scans WHERE sha256 = hash)
+                AND NOT EXISTS (SELECT 1 FROM scan_derivatives WHERE sha256 = hash)
+        "#,
+        )
+        .bind(hashes)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        for hash in unreferenced {
+            if let Err(err) = fs::remove_file(self.path(&hash)).await
+                && err.kind() != std::io::ErrorKind::NotFound
+            {
+                return Err(io_error(err));
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/**
+ * Lock the files with the given hashes until the transaction of `conn` ends.
+ * They are locked in order, so that two transactions cannot wait for each other.
+ */
+async fn lock_files(conn: &mut PgConnection, hashes: &[String]) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        SELECT pg_advisory_xact_lock(hashtext(hash))
+        FROM (SELECT DISTINCT unnest($1::TEXT[]) AS hash ORDER BY hash) AS hashes
+    "#,
+    )
+    .bind(hashes)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/**
+ * SQL for an array of the hashes of all files of the scans that match `condition`, including
+ * their derivatives. Select it before deleting scans, to remove their files afterwards with
+ * `ScanStore::remove_unreferenced`.
+ */
+pub fn file_hashes_sql(condition: &str) -> String {
+    format!(
+        r#"ARRAY(
+            SELECT sha256 FROM document_scans WHERE {condition} AND sha256 IS NOT NULL
+            UNION
+            SELECT scan_derivatives.sha256 FROM scan_derivatives
+            JOIN document_scans ON document_scans.id = scan_derivatives.scan_id
+            WHERE {condition}
+        )"#
+    )
+}
+
+/**
+ * MIME type of a scan file from its first bytes, `None` when it is not a supported type.
+ * The type the client claims for the upload is not trusted.
+ */
+pub fn sniff_mime_type(header: &[u8]) -> Option<&'static str> {
+    SIGNATURES
+        .iter()
+        .find(|(signature, _)| header.starts_with(signature))
+        .map(|(_, mime_type)| *mime_type)
+}
+
+fn io_error(err: std::io::Error) -> AppError {
+    AppError::Internal(format!("Scan storage: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_supported_types_only() {
+        assert_eq!(
+            sniff_mime_type(b"\xFF\xD8\xFF\xE0\x00\x10JFIF"),
+            Some("image/jpeg")
+        );
+        assert_eq!(sniff_mime_type(b"\x89PNG\r\n\x1A\n\x00"), Some("image/png"));
+        assert_eq!(sniff_mime_type(b"II*\x00\x08\x00"), Some("image/tiff"));
+        assert_eq!(sniff_mime_type(b"MM\x00*\x00\x00"), Some("image/tiff"));
+        assert_eq!(sniff_mime_type(b"%PDF-1.7\n"), Some("application/pdf"));
+
+        assert_eq!(sniff_mime_type(b"GIF89a"), None);
+        assert_eq!(sniff_mime_type(b"<html>"), None);
+        assert_eq!(sniff_mime_type(b"\xFF\xD8"), None);
+        assert_eq!(sniff_mime_type(b""), None);
+    }
+}
@@ -0,0 +1,123 @@
+/**
+ * The part of a file to send for a `Range` request header.
+ */
+#[derive(Debug, PartialEq, Eq)]
+pub enum ByteRange {
+    /** No usable range: send the whole file with a 200 */
+    Full,
+    /** Send bytes `start..=end` with a 206 */
+    Partial { start: u64, end: u64 },
+    /** The range lies outside of the file: respond with a 416 */
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    /**
+     * Interpret a `Range` header for a file of `size` bytes, see RFC 9110, section 14.
+     * Only a single `bytes` range is served. Malformed headers, other units and
+     * multiple ranges are ignored, which the RFC allows, and get the whole file.
+     */
+    pub fn parse(header: Option<&str>, size: u64) -> Self {
+        let Some(spec) = header.and_then(|header| header.trim().strip_prefix("bytes=")) else {
+            return Self::Full;
+        };
+        if spec.contains(',') {
+            return Self::Full;
+        }
+        let Some((start, end)) = spec.trim().split_once('-') else {
+            return Self::Full;
+        };
+
+        let parse = |value: &str| value.trim().parse::<u64>().ok();
+        match (start.trim(), end.trim()) {
+            // `bytes=-500`: the last 500 bytes
+            ("", suffix) => match parse(suffix) {
+                Some(0) => Self::Unsatisfiable,
+                Some(_) if size == 0 => Self::Unsatisfiable,
+                Some(length) => Self::Partial {
+                    start: size.saturating_sub(length),
+                    end: size - 1,
+                },
+                None => Self::Full,
+            },
+            // `bytes=500-` or `bytes=500-999`
+            (start, end) => {
+                let Some(start) = parse(start) else {
+                    return Self::Full;
+                };
+                let end = match end {
+                    "" => u64::MAX,
+                    end => match parse(end) {
+                        Some(end) if end >= start => end,
+                        _ => return Self::Full,
+                    },
+                };
+                if start >= size {
+                    return Self::Unsatisfiable;
+                }
+                Self::Partial {
+                    start,
+                    end: end.min(size - 1),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_byte_ranges() {
+        let parse = |header| ByteRange::parse(Some(header), 1000);
+
+        assert_eq!(
+            parse("bytes=0-99"),
+            ByteRange::Partial { start: 0, end: 99 }
+        );
+        assert_eq!(
+            parse("bytes=900-"),
+            ByteRange::Partial {
+                start: 900,
+                end: 999
+            }
+        );
+        assert_eq!(
+            parse("bytes=900-5000"),
+            ByteRange::Partial {
+                start: 900,
+                end: 999
+            }
+        );
+        assert_eq!(
+            parse("bytes=-100"),
+            ByteRange::Partial {
+                start: 900,
+                end: 999
+            }
+        );
+        assert_eq!(
+            parse("bytes=-5000"),
+            ByteRange::Partial { start: 0, end: 999 }
+        );
+    }
+
+    #[test]
+    fn ignores_unusable_ranges_and_rejects_unsatisfiable_ones() {
+        let parse = |header| ByteRange::parse(Some(header), 1000);
+
+        assert_eq!(ByteRange::parse(None, 1000), ByteRange::Full);
+        assert_eq!(parse("items=0-9"), ByteRange::Full);
+        assert_eq!(parse("bytes=0-9,20-29"), ByteRange::Full);
+        assert_eq!(parse("bytes=9-0"), ByteRange::Full);
+        assert_eq!(parse("bytes=a-b"), ByteRange::Full);
+
+        assert_eq!(parse("bytes=1000-"), ByteRange::Unsatisfiable);
+        assert_eq!(parse("bytes=-0"), ByteRange::Unsatisfiable);
+        assert_eq!(
+            ByteRange::parse(Some("bytes=-10"), 0),
+            ByteRange::Unsatisfiable
+        );
+    }
+}
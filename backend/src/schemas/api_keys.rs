@@ -0,0 +1,41 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::permissions::Permission;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateApiKey {
+    pub name: String,
+    pub scopes: Vec<Permission>,
+    /** 90 days from now by default */
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl CreateApiKey {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name must not be empty".to_string());
+        }
+        if self.scopes.is_empty() {
+            return Err("An API key needs at least one scope".to_string());
+        }
+        if let Some(scope) = self
+            .scopes
+            .iter()
+            .find(|scope| scope.resource.is_access_control())
+        {
+            return Err(format!(
+                "API keys cannot manage users or API keys, remove the {} scope",
+                scope
+            ));
+        }
+        if self
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+        {
+            return Err("expires_at must be in the future".to_string());
+        }
+
+        Ok(())
+    }
+}
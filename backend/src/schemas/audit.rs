@@ -0,0 +1,22 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::models::audit::AuditAction;
+
+/**
+ * Filters of the audit log, all optional.
+ * Different filters combine with AND, repeated values of one filter
+ * (`?resource_type=archives&resource_type=places`) combine with OR.
+ * `from` and `to` bound the time of the change, e.g. `?from=2026-10-01T00:00:00Z`.
+ */
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct AuditFilters {
+    pub actor_user_id: Vec<i32>,
+    pub actor_api_key_id: Vec<i32>,
+    pub resource_type: Vec<String>,
+    pub resource_id: Vec<i32>,
+    pub action: Vec<AuditAction>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
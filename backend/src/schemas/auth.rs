@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+/**
+ * Body of `/auth/refresh` and `/auth/logout`. Browsers leave it out and send the
+ * `refresh_token` cookie instead.
+ */
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct RefreshRequest {
+    pub refresh_token: Option<String>,
+}
@@ -1,12 +1,13 @@
-use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use super::deserialize_some;
+use crate::models::historical_date::HistoricalDate;
+use crate::models::persons::PersonRole;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CreateDocument {
-    pub date: DateTime<Utc>,
+    pub date: HistoricalDate,
     pub inventory_number: String,
-    pub scan_number: Option<String>,
-    pub page_number: Option<String>,
     pub notes: Option<String>,
     pub archive_id: i32,
     pub institute_id: i32,
@@ -15,12 +16,72 @@ pub struct CreateDocument {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UpdateDocument {
-    pub date: Option<DateTime<Utc>>,
+    pub date: Option<HistoricalDate>,
     pub inventory_number: Option<String>,
-    pub scan_number: Option<String>,
-    pub page_number: Option<String>,
-    pub notes: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub notes: Option<Option<String>>,
     pub archive_id: Option<i32>,
     pub institute_id: Option<i32>,
     pub place_id: Option<i32>,
 }
+
+/**
+ * Filters of the documents list.
+ * Different filters combine with AND, repeated values of one filter (`?archive_id=1&archive_id=2`)
+ * combine with OR. `date_from` and `date_to` accept partial dates such as `1743` or `1743-05`.
+ * `person_id` and `role` apply to the same mention: `?person_id=7&role=father` finds the
+ * documents in which person 7 is mentioned as the father. `term_id` also finds the documents
+ * classified with a narrower term, e.g. baptisms for the term "sacraments".
+ * Deleted documents are left out, unless `?include_deleted=true` is given.
+ */
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct DocumentFilters {
+    pub archive_id: Vec<i32>,
+    pub institute_id: Vec<i32>,
+    pub place_id: Vec<i32>,
+    pub date_from: Vec<HistoricalDate>,
+    pub date_to: Vec<HistoricalDate>,
+    pub inventory_number: Vec<String>,
+    pub has_scan: Vec<bool>,
+    pub notes: Vec<String>,
+    pub person_id: Vec<i32>,
+    pub role: Vec<PersonRole>,
+    pub term_id: Vec<i32>,
+    pub include_deleted: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /** Import nothing when any row is invalid */
+    #[default]
+    AllOrNothing,
+    /** Import the valid rows and report the invalid ones */
+    SkipInvalid,
+}
+
+/**
+ * Options of a CSV import of documents.
+ * The `*_column` parameters name the CSV column to read a field from, by default the column
+ * with the name of the field, e.g. `?inventory_number_column=Inv.%20nr.`.
+ * Archives, institutes and places are given by name. A scan or page number adds a scan
+ * without a file to the document, see `DocumentScan`.
+ */
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct DocumentImportParams {
+    pub mode: ImportMode,
+    /** Validate and report without importing anything */
+    pub dry_run: bool,
+    /** Field delimiter, `,` by default */
+    pub delimiter: Option<char>,
+    pub date_column: Option<String>,
+    pub inventory_number_column: Option<String>,
+    pub scan_number_column: Option<String>,
+    pub page_number_column: Option<String>,
+    pub notes_column: Option<String>,
+    pub archive_column: Option<String>,
+    pub institute_column: Option<String>,
+    pub place_column: Option<String>,
+}
@@ -0,0 +1,19 @@
+use serde::Deserialize;
+
+use crate::gedcom::Version;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ImportParams {
+    /** Report what the import would do without changing anything */
+    pub dry_run: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ExportParams {
+    pub version: Version,
+    /** Export the tree of this person instead of the whole database */
+    pub person_id: Option<i32>,
+    pub generations: Option<i32>,
+}
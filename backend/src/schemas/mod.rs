@@ -1,4 +1,109 @@
+pub mod api_keys;
 pub mod archives;
+pub mod audit;
+pub mod auth;
 pub mod documents;
+pub mod gedcom;
 pub mod institutes;
+pub mod persons;
 pub mod places;
+pub mod relationships;
+pub mod scans;
+pub mod search;
+pub mod transcriptions;
+pub mod users;
+pub mod vocabularies;
+
+use serde::{Deserialize, Deserializer};
+
+use crate::export::ExportFormat;
+
+/**
+ * Deserialize a field that is present in the request body into `Some`, even when it is `null`.
+ * Combined with `#[serde(default)]` on an `Option<Option<T>>` this tells a missing field
+ * (`None`) apart from an explicit `null` (`Some(None)`).
+ */
+pub fn deserialize_some<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Deserialize::deserialize(deserializer).map(Some)
+}
+
+/**
+ * A BCP 47 language tag in its common form: a two or three letter language,
+ * followed by subtags of one to eight letters or digits, e.g. `nl`, `la` or `nl-BE`.
+ */
+pub fn is_language_tag(tag: &str) -> bool {
+    let mut subtags = tag.split('-');
+    let language = subtags.next().unwrap_or_default();
+
+    (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+/**
+ * Deleting an item only marks it as deleted, unless `?purge=true` is given.
+ */
+#[derive(Deserialize, Debug)]
+pub struct DeleteParams {
+    /** Move the documents that refer to the deleted item to this item first */
+    pub reassign_to: Option<i32>,
+    #[serde(default)]
+    pub purge: bool,
+}
+
+/**
+ * `?purge=true` removes a document for good, instead of marking it as deleted.
+ */
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct PurgeParams {
+    pub purge: bool,
+}
+
+/**
+ * Lists leave out deleted items, unless `?include_deleted=true` is given.
+ */
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct DeletedParams {
+    pub include_deleted: bool,
+}
+
+/**
+ * Pagination and sorting of list endpoints.
+ * Either `offset` or `cursor` may be given, `sort` is a comma separated list of fields,
+ * each optionally prefixed with `-` for descending order.
+ */
+#[derive(Deserialize, Debug, Default)]
+pub struct PageParams {
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub cursor: Option<String>,
+    pub sort: Option<String>,
+}
+
+/**
+ * `?format=csv|xlsx|json` exports the whole filtered list as a file instead of returning a page,
+ * see `ExportFormat::negotiate`. Pagination parameters other than `sort` are ignored then.
+ */
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ExportParams {
+    pub format: Option<ExportFormat>,
+}
+
+/**
+ * `?expand=archive,place` embeds referenced items in the response instead of only their ids.
+ * The names may also be given as repeated parameters: `?expand=archive&expand=place`.
+ */
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ExpandParams {
+    pub expand: Vec<String>,
+}
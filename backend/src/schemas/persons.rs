@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+use super::deserialize_some;
+use crate::models::historical_date::HistoricalDate;
+use crate::models::persons::{Person, PersonRole, Sex};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreatePerson {
+    pub given_names: Option<String>,
+    pub patronymic: Option<String>,
+    pub surname_prefix: Option<String>,
+    pub surname: Option<String>,
+    pub sex: Option<Sex>,
+    pub birth_date: Option<HistoricalDate>,
+    pub birth_place_id: Option<i32>,
+    pub death_date: Option<HistoricalDate>,
+    pub death_place_id: Option<i32>,
+}
+
+impl CreatePerson {
+    pub fn validate(&self) -> Result<(), String> {
+        let named = [&self.given_names, &self.patronymic, &self.surname]
+            .iter()
+            .any(|part| part.as_deref().is_some_and(|part| !part.trim().is_empty()));
+        if !named {
+            return Err("A person needs a given name, patronymic or surname".to_string());
+        }
+
+        if let (Some(birth), Some(death)) = (self.birth_date, self.death_date)
+            && death.latest < birth.earliest
+        {
+            return Err(format!(
+                "Death date {} is before birth date {}",
+                death, birth
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * Every field but `sex` distinguishes between a missing field (`None`, keep the stored value)
+ * and an explicit `null` (`Some(None)`, clear the stored value).
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdatePerson {
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub given_names: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub patronymic: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub surname_prefix: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub surname: Option<Option<String>>,
+    pub sex: Option<Sex>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub birth_date: Option<Option<HistoricalDate>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub birth_place_id: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub death_date: Option<Option<HistoricalDate>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub death_place_id: Option<Option<i32>>,
+}
+
+impl UpdatePerson {
+    /**
+     * The stored `person` with the changes of this request applied.
+     */
+    pub fn apply_to(self, person: Person) -> CreatePerson {
+        CreatePerson {
+            given_names: self.given_names.unwrap_or(person.given_names),
+            patronymic: self.patronymic.unwrap_or(person.patronymic),
+            surname_prefix: self.surname_prefix.unwrap_or(person.surname_prefix),
+            surname: self.surname.unwrap_or(person.surname),
+            sex: Some(self.sex.unwrap_or(person.sex)),
+            birth_date: self.birth_date.unwrap_or(person.birth_date),
+            birth_place_id: self.birth_place_id.unwrap_or(person.birth_place_id),
+            death_date: self.death_date.unwrap_or(person.death_date),
+            death_place_id: self.death_place_id.unwrap_or(person.death_place_id),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateDocumentPerson {
+    pub person_id: i32,
+    pub role: PersonRole,
+}
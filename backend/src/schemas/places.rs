@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use super::deserialize_some;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CreatePlace {
     pub name: String,
@@ -9,9 +11,49 @@ pub struct CreatePlace {
     pub longitude: Option<f64>,
 }
 
+impl CreatePlace {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_coordinates(self.latitude, self.longitude)
+    }
+}
+
+/**
+ * Coordinates distinguish between a missing field (`None`, keep the stored value)
+ * and an explicit `null` (`Some(None)`, clear the stored value).
+ */
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UpdatePlace {
     pub name: Option<String>,
-    pub latitude: Option<f64>,
-    pub longitude: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub latitude: Option<Option<f64>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub longitude: Option<Option<f64>>,
+}
+
+impl UpdatePlace {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_coordinates(self.latitude.flatten(), self.longitude.flatten())
+    }
+}
+
+fn validate_coordinates(latitude: Option<f64>, longitude: Option<f64>) -> Result<(), String> {
+    if let Some(latitude) = latitude
+        && !(-90.0..=90.0).contains(&latitude)
+    {
+        return Err(format!(
+            "Latitude must be between -90 and 90, got {}",
+            latitude
+        ));
+    }
+
+    if let Some(longitude) = longitude
+        && !(-180.0..=180.0).contains(&longitude)
+    {
+        return Err(format!(
+            "Longitude must be between -180 and 180, got {}",
+            longitude
+        ));
+    }
+
+    Ok(())
 }
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::relationships::RelationshipKind;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateRelationship {
+    pub kind: RelationshipKind,
+    pub person_id: i32,
+    pub relative_id: i32,
+    pub document_ids: Vec<i32>,
+}
+
+impl CreateRelationship {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.person_id == self.relative_id {
+            return Err("A person cannot be related to themselves".to_string());
+        }
+        validate_document_ids(&self.document_ids)
+    }
+}
+
+/**
+ * `document_ids` replaces the documents that support the relationship.
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateRelationship {
+    pub document_ids: Option<Vec<i32>>,
+}
+
+impl UpdateRelationship {
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.document_ids {
+            Some(document_ids) => validate_document_ids(document_ids),
+            None => Ok(()),
+        }
+    }
+}
+
+fn validate_document_ids(document_ids: &[i32]) -> Result<(), String> {
+    if document_ids.is_empty() {
+        return Err("A relationship needs at least one supporting document".to_string());
+    }
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineageShape {
+    /** Every person once, with the parent relationships between them */
+    #[default]
+    Graph,
+    /** Nested from the starting person, repeated persons are only expanded the first time */
+    Tree,
+}
+
+/**
+ * Parameters of the ancestors and descendants endpoints.
+ */
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct LineageParams {
+    pub generations: Option<i32>,
+    pub shape: LineageShape,
+}
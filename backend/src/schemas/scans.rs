@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+use super::deserialize_some;
+use crate::models::scans::DerivativeFormat;
+
+/**
+ * Renumber a scan. `null` clears a number, a missing field keeps it.
+ * The file of a scan cannot be replaced, upload a new scan instead.
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateScan {
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub scan_number: Option<Option<String>>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub page_number: Option<Option<String>>,
+}
+
+/**
+ * `?size=` names one of the configured derivative sizes, see `DerivativeSettings`.
+ * Without `?format=` WebP is sent to clients that accept it and JPEG to others.
+ */
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ThumbnailParams {
+    pub size: Option<String>,
+    pub format: Option<DerivativeFormat>,
+}
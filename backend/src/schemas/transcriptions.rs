@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+use super::{deserialize_some, is_language_tag};
+use crate::models::transcriptions::{Transcription, TranscriptionStatus};
+
+/**
+ * The transcriber is the user who makes the request.
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateTranscription {
+    pub scan_id: Option<i32>,
+    pub language: String,
+    pub status: Option<TranscriptionStatus>,
+    pub text: String,
+}
+
+impl CreateTranscription {
+    pub fn validate(&self) -> Result<(), String> {
+        if !is_language_tag(&self.language) {
+            return Err(format!(
+                "Invalid language: {}, expected a language tag such as nl, la or nl-BE",
+                self.language
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * Fields that are left out keep their stored value. `scan_id: null` unties the
+ * transcription from its scan.
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateTranscription {
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub scan_id: Option<Option<i32>>,
+    pub language: Option<String>,
+    pub status: Option<TranscriptionStatus>,
+    pub text: Option<String>,
+}
+
+impl UpdateTranscription {
+    /**
+     * The stored `transcription` with the changes of this request applied.
+     */
+    pub fn apply_to(self, transcription: Transcription) -> CreateTranscription {
+        CreateTranscription {
+            scan_id: self.scan_id.unwrap_or(transcription.scan_id),
+            language: self.language.unwrap_or(transcription.language),
+            status: Some(self.status.unwrap_or(transcription.status)),
+            text: self.text.unwrap_or(transcription.text),
+        }
+    }
+}
+
+/**
+ * The revisions to compare. `to` is the current revision by default, `from` the one before `to`.
+ */
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct DiffParams {
+    pub from: Option<i32>,
+    pub to: Option<i32>,
+}
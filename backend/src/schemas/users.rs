@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::users::Role;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateUser {
+    pub email: String,
+    pub name: String,
+    pub password: String,
+    #[serde(default)]
+    pub roles: Vec<Role>,
+}
+
+/**
+ * The new roles of a user, replacing all current ones.
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateRoles {
+    pub roles: Vec<Role>,
+}
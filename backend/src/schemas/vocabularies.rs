@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{deserialize_some, is_language_tag};
+use crate::models::vocabularies::VocabularyTerm;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateVocabulary {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+/**
+ * A missing field keeps the stored value, `description: null` clears it.
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateVocabulary {
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub description: Option<Option<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateTerm {
+    pub parent_id: Option<i32>,
+    pub code: String,
+    pub labels: BTreeMap<String, String>,
+}
+
+impl CreateTerm {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.code.trim().is_empty() {
+            return Err("code must not be empty".to_string());
+        }
+        if self.labels.is_empty() {
+            return Err("A term needs a label in at least one language".to_string());
+        }
+        for (language, label) in &self.labels {
+            if !is_language_tag(language) {
+                return Err(format!(
+                    "Invalid label language: {}, expected a language tag such as en or nl",
+                    language
+                ));
+            }
+            if label.trim().is_empty() {
+                return Err(format!("The {} label must not be empty", language));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/**
+ * A missing field keeps the stored value, `parent_id: null` makes the term a top term.
+ * `labels` replaces all labels of the term.
+ */
+#[derive(Serialize, Deserialize, Debug)]
+pub struct UpdateTerm {
+    #[serde(default, deserialize_with = "deserialize_some")]
+    pub parent_id: Option<Option<i32>>,
+    pub code: Option<String>,
+    pub labels: Option<BTreeMap<String, String>>,
+}
+
+impl UpdateTerm {
+    /**
+     * The stored `term` with the changes of this request applied.
+     */
+    pub fn apply_to(self, term: VocabularyTerm) -> CreateTerm {
+        CreateTerm {
+            parent_id: self.parent_id.unwrap_or(term.parent_id),
+            code: self.code.unwrap_or(term.code),
+            labels: self.labels.unwrap_or(term.labels.0),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CreateDocumentTerm {
+    pub term_id: i32,
+}
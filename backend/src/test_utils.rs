@@ -0,0 +1,278 @@
+use std::sync::Arc;
+
+use axum::{
+    Router,
+    body::{Body, Bytes, to_bytes},
+    http::{
+        HeaderMap, Method, Request, StatusCode,
+        header::{AUTHORIZATION, CONTENT_TYPE},
+    },
+    middleware::{Next, from_fn},
+};
+use serde_json::Value;
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+use crate::auth::{AuthSettings, tokens::issue_access_token};
+use crate::iiif::IiifSettings;
+use crate::models::users::Role;
+use crate::scans::{ScanStore, derivatives::DerivativeSettings};
+use crate::{create_app, db::AppState};
+
+/**
+ * The app, signed in as a test user with the admin role: requests without an
+ * `Authorization` header get the test user's access token.
+ */
+pub fn app(pool: PgPool) -> Router {
+    signed_in(app_state(pool))
+}
+
+/**
+ * Like `app`, for tests that need the state, e.g. to run the derivative worker.
+ */
+pub fn signed_in(state: Arc<AppState>) -> Router {
+    signed_in_as(state, Role::Admin)
+}
+
+/**
+ * The app, signed in as a test user with only the given role.
+ */
+pub fn signed_in_as(state: Arc<AppState>, role: Role) -> Router {
+    create_app(state.clone()).layer(from_fn(move |mut request: Request<Body>, next: Next| {
+        let state = state.clone();
+        async move {
+            if !request.headers().contains_key(AUTHORIZATION) {
+                let user_id = test_user(state.pool(), role).await;
+                let token = issue_access_token(state.auth(), user_id).unwrap();
+                request
+                    .headers_mut()
+                    .insert(AUTHORIZATION, format!("Bearer {}", token).parse().unwrap());
+            }
+            next.run(request).await
+        }
+    }))
+}
+
+/**
+ * The id of the test user with `role`, e.g. `admin@golijath.test`, who is added on first use.
+ * It has no password to sign in with.
+ */
+pub async fn test_user(pool: &PgPool, role: Role) -> i32 {
+    let user_id = sqlx::query_scalar(
+        r#"
+        INSERT INTO users (email, name, password_hash)
+        VALUES ($1, $2, '!')
+        ON CONFLICT (email) DO UPDATE SET email = EXCLUDED.email
+        RETURNING id
+    "#,
+    )
+    .bind(format!("{}@golijath.test", role.name()))
+    .bind(format!("Test {}", role.name()))
+    .fetch_one(pool)
+    .await
+    .unwrap();
+
+    sqlx::query("INSERT INTO user_roles (user_id, role) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+        .bind(user_id)
+        .bind(role)
+        .execute(pool)
+        .await
+        .unwrap();
+
+    user_id
+}
+
+/**
+ * State with the default derivative settings and a new, empty scan directory.
+ * The derivative worker does not run, tests call `process_pending` themselves.
+ */
+pub fn app_state(pool: PgPool) -> Arc<AppState> {
+    let scan_dir = std::env::temp_dir().join(format!("golijath-scans-{}", uuid::Uuid::new_v4()));
+    Arc::new(AppState::new(
+        pool,
+        ScanStore::new(scan_dir),
+        DerivativeSettings::default(),
+        AuthSettings::new(b"golijath test secret of thirty-two bytes".to_vec()),
+        IiifSettings::default(),
+    ))
+}
+
+/**
+ * Send a single request through the router and decode the JSON response body.
+ * An empty body (e.g. 204 No Content) decodes to `Value::Null`.
+ */
+pub async fn send(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    send_json(app, Request::builder().method(method).uri(uri), body).await
+}
+
+/**
+ * Like `send`, with `token` as bearer token, e.g. an API key.
+ */
+pub async fn send_as(
+    app: &Router,
+    token: &str,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(AUTHORIZATION, format!("Bearer {}", token));
+    send_json(app, request, body).await
+}
+
+async fn send_json(
+    app: &Router,
+    request: axum::http::request::Builder,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let request = match body {
+        Some(body) => request
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string())),
+        None => request.body(Body::empty()),
+    }
+    .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+
+    (status, json)
+}
+
+/**
+ * Send a request as is, e.g. with a file as body, and return the raw response.
+ */
+pub async fn send_request(app: &Router, request: Request<Body>) -> (StatusCode, HeaderMap, Bytes) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+
+    (status, headers, bytes)
+}
+
+/**
+ * A `multipart/form-data` request with the given `(name, file name, content)` parts.
+ */
+pub fn multipart_request(
+    method: Method,
+    uri: &str,
+    parts: &[(&str, Option<&str>, &[u8])],
+) -> Request<Body> {
+    const BOUNDARY: &str = "golijath-test-boundary";
+
+    let mut body = Vec::new();
+    for (name, file_name, content) in parts {
+        body.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+        match file_name {
+            Some(file_name) => body.extend_from_slice(
+                format!(
+                    "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n\
+                     Content-Type: application/octet-stream\r\n\r\n",
+                    name, file_name
+                )
+                .as_bytes(),
+            ),
+            None => body.extend_from_slice(
+                format!("Content-Disposition: form-data; name=\"{}\"\r\n\r\n", name).as_bytes(),
+            ),
+        }
+        body.extend_from_slice(content);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(
+            CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", BOUNDARY),
+        )
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/**
+ * The start of a PNG of `width` by `height` pixels, enough to sniff its type and size.
+ */
+pub fn png_header(width: u32, height: u32) -> Vec<u8> {
+    let mut png = b"\x89PNG\r\n\x1A\n\x00\x00\x00\x0DIHDR".to_vec();
+    png.extend_from_slice(&width.to_be_bytes());
+    png.extend_from_slice(&height.to_be_bytes());
+    png.extend_from_slice(&[8, 2, 0, 0, 0, 0, 0, 0, 0]);
+    png
+}
+
+/**
+ * A complete PNG of `width` by `height` pixels with a gradient, which can be decoded.
+ */
+pub fn png_image(width: u32, height: u32) -> Vec<u8> {
+    let image = image::RgbImage::from_fn(width, height, |x, y| {
+        image::Rgb([(x % 256) as u8, (y % 256) as u8, 128])
+    });
+    let mut png = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    png
+}
+
+/**
+ * Insert a row with only a `name` into one of the lookup tables and return its id.
+ */
+pub async fn insert_named(pool: &PgPool, table: &str, name: &str) -> i32 {
+    let query = format!("INSERT INTO {} (name) VALUES ($1) RETURNING id", table);
+    sqlx::query_scalar(&query)
+        .bind(name)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+/**
+ * Insert a document dated 1743-05-12 and return its id.
+ */
+pub async fn insert_document(
+    pool: &PgPool,
+    inventory_number: &str,
+    archive_id: i32,
+    institute_id: i32,
+    place_id: i32,
+) -> i32 {
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO documents
+            (date_earliest, date_latest, date_precision, inventory_number, archive_id, institute_id, place_id)
+        VALUES ('1743-05-12', '1743-05-12', 'day', $1, $2, $3, $4)
+        RETURNING id
+    "#,
+    )
+    .bind(inventory_number)
+    .bind(archive_id)
+    .bind(institute_id)
+    .bind(place_id)
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+/**
+ * Insert a person with only given names and return its id.
+ */
+pub async fn insert_person(pool: &PgPool, given_names: &str) -> i32 {
+    sqlx::query_scalar("INSERT INTO persons (given_names) VALUES ($1) RETURNING id")
+        .bind(given_names)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}